@@ -1,18 +1,17 @@
-#![feature(try_blocks)]
-#![feature(proc_macro_span)]
-#![feature(iter_collect_into)]
-
 extern crate proc_macro;
 
-use proc_macro::{TokenStream};
-use std::collections::{HashMap, HashSet};
-use quote::{format_ident, quote, ToTokens};
-use syn::{Attribute, Field, Fields, FnArg, Ident, ImplItem, ImplItemFn, Item, ItemEnum, ItemFn, ItemMod, ItemStruct, Lit, LitInt, LitStr, Meta, parse_quote, Pat, Path, PathArguments, ReturnType, Signature, Token, Type, TypePath, TypeTuple, Visibility};
-use syn::parse::Parser;
+use proc_macro::TokenStream;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::{Attribute, Expr, ExprLit, Field, Fields, FnArg, GenericArgument, Generics, Ident, ImplItem, ImplItemFn, Item, ItemEnum, ItemFn, ItemImpl, ItemMod, ItemStruct, Lit, LitInt, LitStr, Meta, MetaList, parse_quote, parse_quote_spanned, Pat, Path, PathArguments, PathSegment, ReturnType, Signature, StaticMutability, Token, Type, TypeParamBound, TypePath, TypeReference, TypeTuple, Visibility};
+use syn::parse::{Parse, ParseStream, Parser};
 use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::token::Paren;
 
+#[cfg(feature = "preview")]
+mod preview;
+
 /// Verify that the given string is a valid java identifier
 fn verify_java_identifier(identifier: &str) -> Result<(), String> {
     if identifier.len() == 0 {
@@ -137,6 +136,526 @@ fn verify_package_identifier(decl: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Mangle a Java name for use in JNI export symbols
+///
+/// Package separators (`.` or `/`) become `_`, and `_`/`$` are escaped as `_1`/`_00024`
+fn mangle_jni_name(name: &str) -> String {
+    let mut mangled = String::with_capacity(name.len());
+    for char in name.chars() {
+        match char {
+            '.' | '/' => mangled.push('_'),
+            '_' => mangled.push_str("_1"),
+            '$' => mangled.push_str("_00024"),
+            char => mangled.push(char),
+        }
+    }
+    mangled
+}
+
+/// Options read from `#[java(...)]` helper attributes
+#[derive(Default)]
+struct JavaAttributes {
+    /// `nested_in = "Outer"`; Emit this class as a static nested class of another generated class in the same package
+    nested_in: Option<LitStr>,
+    /// `visibility = "package"`; Access modifier of the generated class, one of "public" (default), "package", and for nested classes "protected" or "private"
+    visibility: Option<LitStr>,
+    /// `factory`; Emit a public static `of(...)` factory method mirroring the private constructor, structs only
+    factory: bool,
+    /// `with_methods`; Emit `withFieldName(value)` copy methods for each field, structs only
+    with_methods: bool,
+    /// `handle`; Generate a handle-backed class, where the rust value is owned by the Java object rather than converted, structs only
+    handle: bool,
+    /// `bean`; Emit a public no-arg constructor and setters for frameworks that instantiate classes reflectively, structs only. Null fields convert to their `Default` value
+    bean: bool,
+    /// `default_constructor`; Emit a public no-arg constructor initializing fields to the values of the rust `Default` implementation, structs only
+    default_constructor: bool,
+    /// `to_string`; Export the rust `Display` implementation as the Java `toString()` method
+    to_string: bool,
+    /// `from_str`; Export the rust `FromStr` implementation as a static Java `fromString(String)` factory, throwing IllegalArgumentException for strings that do not parse
+    from_str: bool,
+    /// `hash`; Export the rust `Hash` and `PartialEq` implementations as the Java `hashCode()` and `equals(Object)` methods
+    hash: bool,
+    /// `copy`; Export the rust `Clone` implementation as a Java `copy()` method returning a new, independent handle, handle-backed classes only
+    copy: bool,
+    /// `subclassable`; Emit a non-final class which Java may extend, overriding its `#[java(overridable)]` methods, handle-backed classes only
+    subclassable: bool,
+    /// `external = "com.example.Color"`; Convert to an existing Java class rather than generating one, fieldless enums only
+    external: Option<LitStr>,
+    /// `annotate = "@com.example.Audited"`; Java annotation written verbatim on the generated class, may be repeated
+    annotations: Vec<LitStr>,
+    /// `implements = "java.util.function.IntSupplier"`; Java interface implemented by the generated class through its exported methods, structs only, may be repeated
+    interfaces: Vec<LitStr>,
+    /// `flags` or `flags = "int"`; Map a `bitflags` type to a `java.util.EnumSet` of a generated enum, or to its bits with a generated class of constants, tuple structs of an integer only
+    flags: Option<FlagsTransport>,
+    /// `import = "java.util.List"`; Import of the generated class file, so annotations, interfaces and bodies may use short names, may be repeated
+    imports: Vec<LitStr>,
+    /// `body = "public int doubled() { return value * 2; }"`; Java source written verbatim in the class body, such as non-native convenience methods, may be repeated
+    body: Vec<LitStr>,
+}
+
+impl JavaAttributes {
+    /// Parse all `#[java(...)]` attributes in the given attribute list
+    fn parse(attributes: &[Attribute]) -> Result<JavaAttributes, syn::Error> {
+        let mut java_attributes = JavaAttributes::default();
+        for attribute in attributes.iter().filter(|attribute| attribute.path().is_ident("java")) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("nested_in") {
+                    let outer = meta.value()?.parse::<LitStr>()?;
+                    for name in outer.value().split('.') {
+                        verify_type_identifier(name).map_err(|e| syn::Error::new(outer.span(), e))?;
+                    }
+                    java_attributes.nested_in = Some(outer);
+                    Ok(())
+                } else if meta.path.is_ident("visibility") {
+                    let visibility = meta.value()?.parse::<LitStr>()?;
+                    if !matches!(&*visibility.value(), "public" | "package" | "protected" | "private") {
+                        Err(syn::Error::new(visibility.span(), "unknown visibility, expected \"public\", \"package\", \"protected\" or \"private\""))?;
+                    }
+                    java_attributes.visibility = Some(visibility);
+                    Ok(())
+                } else if meta.path.is_ident("factory") {
+                    java_attributes.factory = true;
+                    Ok(())
+                } else if meta.path.is_ident("with_methods") {
+                    java_attributes.with_methods = true;
+                    Ok(())
+                } else if meta.path.is_ident("handle") {
+                    java_attributes.handle = true;
+                    Ok(())
+                } else if meta.path.is_ident("bean") {
+                    java_attributes.bean = true;
+                    Ok(())
+                } else if meta.path.is_ident("default_constructor") {
+                    java_attributes.default_constructor = true;
+                    Ok(())
+                } else if meta.path.is_ident("to_string") {
+                    java_attributes.to_string = true;
+                    Ok(())
+                } else if meta.path.is_ident("from_str") {
+                    java_attributes.from_str = true;
+                    Ok(())
+                } else if meta.path.is_ident("hash") {
+                    java_attributes.hash = true;
+                    Ok(())
+                } else if meta.path.is_ident("copy") {
+                    java_attributes.copy = true;
+                    Ok(())
+                } else if meta.path.is_ident("subclassable") {
+                    java_attributes.subclassable = true;
+                    Ok(())
+                } else if meta.path.is_ident("external") {
+                    let class = meta.value()?.parse::<LitStr>()?;
+                    verify_package_identifier(&class.value()).map_err(|e| syn::Error::new(class.span(), e))?;
+                    if !class.value().contains('.') {
+                        Err(syn::Error::new(class.span(), "external classes must be fully qualified"))?;
+                    }
+                    java_attributes.external = Some(class);
+                    Ok(())
+                } else if meta.path.is_ident("annotate") {
+                    java_attributes.annotations.push(parse_annotation(meta.value()?)?);
+                    Ok(())
+                } else if meta.path.is_ident("implements") {
+                    let interface = meta.value()?.parse::<LitStr>()?;
+                    // Type arguments, as in `java.util.Comparator<com.example.Point>`, are written verbatim
+                    let value = interface.value();
+                    let name = match value.split_once('<') {
+                        Some((name, arguments)) if arguments.ends_with('>') => name,
+                        Some(_) => Err(syn::Error::new(interface.span(), "unclosed interface type arguments"))?,
+                        None => &*value
+                    };
+                    verify_package_identifier(name.trim_end()).map_err(|e| syn::Error::new(interface.span(), e))?;
+                    if value.contains('\n') {
+                        Err(syn::Error::new(interface.span(), "interfaces must be a single line"))?;
+                    }
+                    java_attributes.interfaces.push(interface);
+                    Ok(())
+                } else if meta.path.is_ident("flags") {
+                    let transport = if meta.input.peek(Token![=]) {
+                        let transport = meta.value()?.parse::<LitStr>()?;
+                        match &*transport.value() {
+                            "enum_set" => FlagsTransport::EnumSet,
+                            "int" => FlagsTransport::Int,
+                            _ => Err(syn::Error::new(transport.span(), "unknown flags transport, expected \"enum_set\" or \"int\""))?
+                        }
+                    } else {
+                        FlagsTransport::EnumSet
+                    };
+                    java_attributes.flags = Some(transport);
+                    Ok(())
+                } else if meta.path.is_ident("import") {
+                    let import = meta.value()?.parse::<LitStr>()?;
+                    let value = import.value();
+                    verify_package_identifier(value.strip_suffix(".*").unwrap_or(&value)).map_err(|e| syn::Error::new(import.span(), e))?;
+                    java_attributes.imports.push(import);
+                    Ok(())
+                } else if meta.path.is_ident("body") {
+                    java_attributes.body.push(meta.value()?.parse::<LitStr>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown java attribute"))
+                }
+            })?;
+        }
+        // Java only allows nested classes to be protected or private
+        if let Some(visibility) = &java_attributes.visibility {
+            if java_attributes.nested_in.is_none() && matches!(&*visibility.value(), "protected" | "private") {
+                Err(syn::Error::new(visibility.span(), "only nested classes may be protected or private, top-level classes are \"public\" or \"package\""))?;
+            }
+        }
+        Ok(java_attributes)
+    }
+
+    /// Dot-separated path of a class within its package, accounting for `nested_in`
+    fn class_path(&self, class_name: &str) -> String {
+        match &self.nested_in {
+            Some(outer) => format!("{}.{}", outer.value(), class_name),
+            None => class_name.to_string()
+        }
+    }
+
+    /// Impl block exporting the rust trait implementations requested by these attributes as methods of `java.lang.Object`, if any
+    ///
+    /// Added to the module by `jmodule`, which exports its `extern "jni"` functions as any other
+    fn object_methods_impl(&self, type_ident: &Ident) -> Option<Item> {
+        let mut methods = Vec::<ImplItemFn>::new();
+        if self.to_string {
+            methods.push(parse_quote_spanned! {type_ident.span()=>
+                #[allow(non_snake_case)]
+                #[java(annotate = "@Override")]
+                extern "jni" fn toString(&self) -> String {
+                    std::string::ToString::to_string(self)
+                }
+            });
+        }
+        if self.from_str {
+            let type_name = type_ident.to_string();
+            methods.push(parse_quote_spanned! {type_ident.span()=>
+                #[allow(non_snake_case)]
+                extern "jni" fn fromString(value: String, env: &mut jni::JNIEnv) -> Option<Self> {
+                    match <Self as std::str::FromStr>::from_str(&value) {
+                        Ok(parsed) => Some(parsed),
+                        Err(_) => {
+                            // Thrown once the null result is returned
+                            let _ = env.throw_new("java/lang/IllegalArgumentException", format!("cannot parse {:?} as {}", value, #type_name));
+                            None
+                        }
+                    }
+                }
+            });
+        }
+        if self.hash {
+            methods.push(parse_quote_spanned! {type_ident.span()=>
+                #[allow(non_snake_case)]
+                #[java(annotate = "@Override")]
+                extern "jni" fn hashCode(&self) -> i32 {
+                    instant_coffee::interop::java_hash_code(self)
+                }
+            });
+            methods.push(parse_quote_spanned! {type_ident.span()=>
+                #[java(annotate = "@Override")]
+                extern "jni" fn equals(&self, other: instant_coffee::interop::InstanceOf<Self>) -> bool {
+                    other.get().is_some_and(|other| self == other)
+                }
+            });
+        }
+        if self.copy {
+            methods.push(parse_quote_spanned! {type_ident.span()=>
+                extern "jni" fn copy(&self) -> Self {
+                    std::clone::Clone::clone(self)
+                }
+            });
+        }
+
+        if methods.is_empty() {
+            None
+        } else {
+            Some(parse_quote!(impl #type_ident { #(#methods)* }))
+        }
+    }
+
+    /// `annotations` value for `JClassDecl`
+    fn quote_annotations(&self) -> proc_macro2::TokenStream {
+        let annotations = &self.annotations;
+        quote!(vec![#(#annotations),*])
+    }
+
+    /// `interfaces` value for `JClassDecl`
+    fn quote_interfaces(&self) -> proc_macro2::TokenStream {
+        let interfaces = &self.interfaces;
+        quote!(vec![#(#interfaces),*])
+    }
+
+    /// `imports` value for `JClassDecl`
+    fn quote_imports(&self) -> proc_macro2::TokenStream {
+        let imports = &self.imports;
+        quote!(vec![#(#imports),*])
+    }
+
+    /// `body` value for `JClassDecl`
+    fn quote_body(&self) -> proc_macro2::TokenStream {
+        let body = &self.body;
+        quote!(vec![#(#body),*])
+    }
+
+    /// `nested_in` value for `JClassDecl`
+    fn quote_nested_in(&self) -> proc_macro2::TokenStream {
+        match &self.nested_in {
+            Some(outer) => quote!(Some(#outer)),
+            None => quote!(None)
+        }
+    }
+
+    /// `access` value for `JClassDecl`
+    fn quote_access(&self) -> proc_macro2::TokenStream {
+        match self.visibility.as_ref().map(LitStr::value).as_deref() {
+            Some("package") => quote!(instant_coffee::codegen::JAccessModifier::PackagePrivate),
+            Some("protected") => quote!(instant_coffee::codegen::JAccessModifier::Protected),
+            Some("private") => quote!(instant_coffee::codegen::JAccessModifier::Private),
+            _ => quote!(instant_coffee::codegen::JAccessModifier::Public)
+        }
+    }
+}
+
+/// Java representation of `#[java(flags)]` types
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum FlagsTransport {
+    /// `java.util.EnumSet` of a generated enum, with a constant for each named flag
+    EnumSet,
+    /// The flag bits as `int` (or `long` for 64-bit flags), with a generated class of `public static final` constants
+    Int,
+}
+
+/// Options read from `#[java(...)]` attributes on exported `extern "jni"` functions
+#[derive(Default)]
+struct MethodAttributes {
+    /// `annotate = "@com.example.Audited"`; Java annotation written verbatim on the generated method, may be repeated
+    annotations: Vec<LitStr>,
+    /// `overridable`; Leave the method non-final, so that Java subclasses may override it, `&self` methods of subclassable classes only
+    overridable: bool,
+    /// `init` or `shutdown`; Module lifecycle hook, exported as `initialize()` or `shutdown()`. See [`Lifecycle`]
+    lifecycle: Option<Lifecycle>,
+    /// `throws = "java.io.IOException"`; Checked exception declared in the `throws` clause of the generated method, may be repeated. Functions returning `Result<T, E>` with `E: JavaThrowable` also declare the exception class of `E`
+    throws: Vec<LitStr>,
+}
+
+/// Module lifecycle hooks, for runtime setup such as installing a logger or starting a thread pool
+///
+/// Hooks are static functions without Java parameters, that may take `&mut JNIEnv`. Each hook runs at most once per library load; Later calls return without calling it again
+///
+/// An `on_class_load` hook runs right after `System.loadLibrary`, before any exported method is usable. A module may have one; It requires the `load_library` option, and should not use the module's Java classes, as their initializers call it in turn.
+/// Application servers may never unload a library whose class loader is still reachable, shutdown hooks can call `jni_util::release_global_state` to release it early
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Lifecycle {
+    /// `init`, or `init, on_load` to also run it from `jmodule_on_load`; `init, on_class_load` to also run it from the static initializers of the generated classes
+    Init { on_load: bool, on_class_load: bool },
+    /// `shutdown`, or `shutdown, on_unload` to also run it from `jmodule_on_unload`
+    Shutdown { on_unload: bool },
+}
+
+impl MethodAttributes {
+    /// Parse all `#[java(...)]` attributes in the given function attribute list
+    fn parse(attributes: &[Attribute]) -> Result<MethodAttributes, syn::Error> {
+        let mut method_attributes = MethodAttributes::default();
+        for attribute in attributes.iter().filter(|attribute| attribute.path().is_ident("java")) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("annotate") {
+                    method_attributes.annotations.push(parse_annotation(meta.value()?)?);
+                    Ok(())
+                } else if meta.path.is_ident("overridable") {
+                    method_attributes.overridable = true;
+                    Ok(())
+                } else if meta.path.is_ident("throws") {
+                    let exception = meta.value()?.parse::<LitStr>()?;
+                    verify_package_identifier(&exception.value()).map_err(|e| syn::Error::new(exception.span(), e))?;
+                    method_attributes.throws.push(exception);
+                    Ok(())
+                } else if meta.path.is_ident("init") || meta.path.is_ident("shutdown") {
+                    let lifecycle = if meta.path.is_ident("init") { Lifecycle::Init { on_load: false, on_class_load: false } } else { Lifecycle::Shutdown { on_unload: false } };
+                    match method_attributes.lifecycle {
+                        None => method_attributes.lifecycle = Some(lifecycle),
+                        Some(_) => Err(meta.error("a function may be a single lifecycle hook, either `init` or `shutdown`"))?
+                    }
+                    Ok(())
+                } else if meta.path.is_ident("on_load") || meta.path.is_ident("on_unload") {
+                    match (&mut method_attributes.lifecycle, meta.path.is_ident("on_load")) {
+                        (Some(Lifecycle::Init { on_load, .. }), true) => *on_load = true,
+                        (Some(Lifecycle::Shutdown { on_unload }), false) => *on_unload = true,
+                        (_, true) => Err(meta.error("`on_load` must follow `init`, e.g. `#[java(init, on_load)]`"))?,
+                        (_, false) => Err(meta.error("`on_unload` must follow `shutdown`, e.g. `#[java(shutdown, on_unload)]`"))?,
+                    }
+                    Ok(())
+                } else if meta.path.is_ident("on_class_load") {
+                    match &mut method_attributes.lifecycle {
+                        Some(Lifecycle::Init { on_class_load, .. }) => *on_class_load = true,
+                        _ => Err(meta.error("`on_class_load` must follow `init`, e.g. `#[java(init, on_class_load)]`"))?,
+                    }
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown java method attribute"))
+                }
+            })?;
+        }
+        Ok(method_attributes)
+    }
+
+    /// Java name of the method exported from function `ident`; Lifecycle hooks are named `initialize` and `shutdown`, other methods are named by the naming policy
+    fn java_name(&self, ident: &Ident, naming: Naming) -> String {
+        match self.lifecycle {
+            Some(Lifecycle::Init { .. }) => "initialize".to_string(),
+            Some(Lifecycle::Shutdown { .. }) => "shutdown".to_string(),
+            None => naming.apply(&ident.to_string())
+        }
+    }
+}
+
+/// Options read from `#[java(...)]` attributes on parameters of exported `extern "jni"` functions
+#[derive(Default)]
+struct ParamAttributes {
+    /// `raw`; Pass the parameter as its `JniType` rather than converting it, such as a `JString` for a `String` parameter
+    raw: bool,
+}
+
+impl ParamAttributes {
+    /// Parse all `#[java(...)]` attributes in the given parameter attribute list
+    fn parse(attributes: &[Attribute]) -> Result<ParamAttributes, syn::Error> {
+        let mut param_attributes = ParamAttributes::default();
+        for attribute in attributes.iter().filter(|attribute| attribute.path().is_ident("java")) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("raw") {
+                    param_attributes.raw = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown java parameter attribute"))
+                }
+            })?;
+        }
+        Ok(param_attributes)
+    }
+}
+
+/// Exported function as transferred from `jmodule` to the derive macro by `jmodule_methods`: `#[cfg(...)] #[java(...)] fn name(...) -> T`
+struct ExportedMethod {
+    /// `#[cfg(...)]` attributes of the function and its impl block; The method is only declared if the function is compiled
+    cfgs: Vec<Attribute>,
+    /// `#[java(...)]` attributes of the function, see [`MethodAttributes`]
+    attributes: Vec<Attribute>,
+    signature: Signature,
+}
+
+impl Parse for ExportedMethod {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let (cfgs, attributes) = input.call(Attribute::parse_outer)?.into_iter().partition(is_cfg_attr);
+        Ok(ExportedMethod { cfgs, attributes, signature: input.parse()? })
+    }
+}
+
+impl ToTokens for ExportedMethod {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        tokens.extend(self.cfgs.iter().map(ToTokens::to_token_stream));
+        tokens.extend(self.attributes.iter().map(ToTokens::to_token_stream));
+        self.signature.to_tokens(tokens);
+    }
+}
+
+/// Parse an `annotate = "..."` value; A Java annotation such as `@com.example.Audited` or `@Tag(name = "x")`
+///
+/// Only the annotation name is verified, any arguments are written verbatim
+fn parse_annotation(input: ParseStream) -> Result<LitStr, syn::Error> {
+    let literal = input.parse::<LitStr>()?;
+    let annotation = literal.value();
+    let name = annotation.strip_prefix('@')
+        .ok_or_else(|| syn::Error::new(literal.span(), "annotations must start with `@`"))?;
+    let name = match name.split_once('(') {
+        Some((name, arguments)) if arguments.ends_with(')') => name,
+        Some(_) => Err(syn::Error::new(literal.span(), "unclosed annotation arguments"))?,
+        None => name
+    };
+    if annotation.contains('\n') {
+        Err(syn::Error::new(literal.span(), "annotations must be a single line"))?;
+    }
+    verify_package_identifier(name.trim_end()).map_err(|e| syn::Error::new(literal.span(), e))?;
+    Ok(literal)
+}
+
+/// Options read from `#[java(...)]` helper attributes on fields
+#[derive(Default)]
+struct FieldAttributes {
+    /// `bitset`; Map a `Box<[bool]>` field to `java.util.BitSet` rather than `boolean[]`
+    bitset: bool,
+    /// `json_name = "name"`; Name of the field in JSON annotations, defaults to the rust field name
+    json_name: Option<LitStr>,
+    /// `default = "expression"`; Java initializer of the field, the value of bean-mode fields until they are set
+    default: Option<LitStr>,
+}
+
+impl FieldAttributes {
+    /// Parse all `#[java(...)]` attributes in the given field attribute list
+    fn parse(attributes: &[Attribute]) -> Result<FieldAttributes, syn::Error> {
+        let mut field_attributes = FieldAttributes::default();
+        for attribute in attributes.iter().filter(|attribute| attribute.path().is_ident("java")) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("bitset") {
+                    field_attributes.bitset = true;
+                    Ok(())
+                } else if meta.path.is_ident("json_name") {
+                    field_attributes.json_name = Some(meta.value()?.parse::<LitStr>()?);
+                    Ok(())
+                } else if meta.path.is_ident("default") {
+                    let default = meta.value()?.parse::<LitStr>()?;
+                    if default.value().trim().is_empty() || default.value().contains(['\n', ';']) {
+                        Err(syn::Error::new(default.span(), "default must be a single-line Java expression"))?;
+                    }
+                    field_attributes.default = Some(default);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown java field attribute"))
+                }
+            })?;
+        }
+        Ok(field_attributes)
+    }
+}
+
+/// Options read from `#[java(...)]` helper attributes on enum variants
+#[derive(Default)]
+struct VariantAttributes {
+    /// `fallback`; Variant that unrecognized Java values convert to, `#[non_exhaustive]` enums only
+    fallback: bool,
+    /// `name = "IN_PROGRESS"`; Name of the Java enum constant or tagged union subclass of this variant, defaults to the rust variant name
+    name: Option<LitStr>,
+}
+
+impl VariantAttributes {
+    /// Parse all `#[java(...)]` attributes in the given variant attribute list
+    fn parse(attributes: &[Attribute]) -> Result<VariantAttributes, syn::Error> {
+        let mut variant_attributes = VariantAttributes::default();
+        for attribute in attributes.iter().filter(|attribute| attribute.path().is_ident("java")) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("fallback") {
+                    variant_attributes.fallback = true;
+                    Ok(())
+                } else if meta.path.is_ident("name") {
+                    let name = meta.value()?.parse::<LitStr>()?;
+                    verify_type_identifier(&name.value()).map_err(|e| syn::Error::new(name.span(), e))?;
+                    variant_attributes.name = Some(name);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown java variant attribute"))
+                }
+            })?;
+        }
+        Ok(variant_attributes)
+    }
+
+    /// Java name of the variant `ident`, see `name`
+    fn java_name(&self, ident: &Ident) -> String {
+        match &self.name {
+            Some(name) => name.value(),
+            None => ident.to_string()
+        }
+    }
+}
+
 enum ClassKind {
     /// Rust struct
     Struct(ItemStruct),
@@ -168,9 +687,10 @@ enum StructKind {
 /// * `ident_span`: Span to attach errors to; Should be the Ident of the struct attributes are attached to
 /// * `attributes`: Attribute macros to scan
 ///
-/// returns: If Ok, (Package path, methods)
-fn read_jmodule_info(ident_span: proc_macro2::Span, attributes: Vec<Attribute>) -> Result<(String, Vec<Signature>), syn::Error> {
+/// returns: If Ok, (Package path, methods, naming policy)
+fn read_jmodule_info(ident_span: proc_macro2::Span, attributes: Vec<Attribute>) -> Result<(String, Vec<ExportedMethod>, Naming), syn::Error> {
     let mut package_name = None;
+    let mut naming = Naming::default();
     let mut method_list = None;
     for attribute in attributes {
         if let Meta::List(ref list) = attribute.meta {
@@ -179,9 +699,25 @@ fn read_jmodule_info(ident_span: proc_macro2::Span, attributes: Vec<Attribute>)
                     Err(syn::Error::new(attribute.span(), "duplicate jmodule_package"))?;
                 }
 
-                let package_literal = syn::parse::<LitStr>(list.tokens.to_token_stream().into())?;
+                // jmodule_package("com.example"[, naming = "..."])
+                let (package_literal, naming_literal) = (|input: ParseStream| {
+                    let package_literal = input.parse::<LitStr>()?;
+                    if input.is_empty() {
+                        return Ok((package_literal, None));
+                    }
+                    input.parse::<Token![,]>()?;
+                    let name = input.parse::<Ident>()?;
+                    if name != "naming" {
+                        return Err(syn::Error::new(name.span(), "invalid jmodule_package macro"));
+                    }
+                    input.parse::<Token![=]>()?;
+                    Ok((package_literal, Some(input.parse::<LitStr>()?)))
+                }).parse(list.tokens.to_token_stream().into())?;
                 let name = package_literal.value();
                 verify_package_identifier(&name).map_err(|e| syn::Error::new(package_literal.span(), e))?;
+                if let Some(naming_literal) = naming_literal {
+                    naming = Naming::parse(&naming_literal)?;
+                }
 
                 package_name = Some(name)
             } else if list.path.segments.last().is_some_and(|segment| segment.ident == "jmodule_methods") {
@@ -189,25 +725,33 @@ fn read_jmodule_info(ident_span: proc_macro2::Span, attributes: Vec<Attribute>)
                     Err(syn::Error::new(attribute.span(), "duplicate jmodule_methods"))?;
                 }
 
-                let signatures = Punctuated::<Signature, Token![,]>::parse_terminated.parse(list.tokens.to_token_stream().into())?;
+                let methods = Punctuated::<ExportedMethod, Token![,]>::parse_terminated.parse(list.tokens.to_token_stream().into())?;
 
-                method_list = Some(signatures.into_iter().collect::<Vec<_>>());
+                method_list = Some(methods.into_iter().collect::<Vec<_>>());
             }
         }
     }
 
     if let (Some(package), Some(methods)) = (package_name, method_list) {
-        Ok((package, methods))
+        Ok((package, methods, naming))
     } else {
         Err(syn::Error::new(ident_span.into(), "Missing jmodule context!"))
     }
 }
 
-/// Turn syn function signatures into `JMethod` declarations
-fn quote_method_decls(signatures: Vec<Signature>) -> Result<Vec<proc_macro2::TokenStream>, syn::Error> {
+/// Turn exported functions into an expression of the `Vec` of their `JMethod` declarations; Methods gated by `#[cfg]` are only declared if their function is compiled
+///
+/// Overridable methods are only permitted if the class is `subclassable`
+fn quote_method_decls(methods: Vec<ExportedMethod>, naming: Naming, subclassable: bool) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let mut method_cfgs = Vec::new();
     let mut method_decls = Vec::new();
-    for signature in signatures {
-        let method_name = signature.ident.to_string();
+    for ExportedMethod { cfgs, attributes, signature } in methods {
+        let method_attributes = MethodAttributes::parse(&attributes)?;
+        let method_name = method_attributes.java_name(&signature.ident, naming);
+        let MethodAttributes { annotations, overridable, throws, .. } = method_attributes;
+        if overridable && !subclassable {
+            Err(syn::Error::new(signature.ident.span(), "overridable methods require a subclassable class, `#[java(handle, subclassable)]`"))?;
+        }
         verify_java_identifier(&method_name).map_err(|e| syn::Error::new(signature.ident.span(), e))?;
 
         let mut is_static = true;
@@ -219,11 +763,15 @@ fn quote_method_decls(signatures: Vec<Signature>) -> Result<Vec<proc_macro2::Tok
                 }
                 FnArg::Typed(input_type) => {
                     let param_name = match *input_type.pat {
-                        Pat::Ident(ident) => ident.ident.to_string(),
+                        Pat::Ident(ident) => naming.apply(&ident.ident.to_string()),
                         _ => unreachable!("invalid jmodule_methods macro")
                     };
 
-                    let i_ty = *input_type.ty;
+                    // Reference parameters are borrowed, and have the same Java type as their referent
+                    let i_ty = match *input_type.ty {
+                        Type::Reference(reference) => referent_type(&reference),
+                        ty => ty
+                    };
                     Some(quote!((#param_name, <#i_ty as instant_coffee::JavaType>::QUALIFIED_NAME())))
                 }
             }
@@ -233,35 +781,181 @@ fn quote_method_decls(signatures: Vec<Signature>) -> Result<Vec<proc_macro2::Tok
             ReturnType::Type(_, return_type) => *return_type
         };
         let output = quote!(<#o_ty as instant_coffee::JavaReturn>::QUALIFIED_NAME());
+        // Exceptions thrown in place of returning, such as the errors of `Result` returns, are declared along with those of `#[java(throws)]`
+        let throws = quote!({
+            let mut throws: Vec<&'static str> = vec![#(#throws),*];
+            if let Some(exception) = <#o_ty as instant_coffee::JavaReturn>::THROWS() {
+                if !throws.contains(&exception) {
+                    throws.push(exception);
+                }
+            }
+            throws
+        });
 
+        method_cfgs.push(cfgs);
         method_decls.push(
             quote!(instant_coffee::codegen::JMethod {
                 is_static: #is_static,
                 name: #method_name,
                 inputs: vec![#(#inputs),*],
-                output: #output
+                output: #output,
+                annotations: vec![#(#annotations),*],
+                overridable: #overridable,
+                throws: #throws,
+                body: None
             })
         );
     }
 
-    Ok(method_decls)
+    if method_cfgs.iter().all(Vec::is_empty) {
+        Ok(quote!(vec![#(#method_decls),*]))
+    } else {
+        Ok(quote!({
+            let mut methods = Vec::new();
+            #(#(#method_cfgs)* methods.push(#method_decls);)*
+            methods
+        }))
+    }
+}
+
+/// Schema checks of exported functions, verifying that the native methods are declared on `CLASS` with matching signatures; See `JavaClass::verify_schema`
+fn quote_method_checks(methods: &[ExportedMethod], naming: Naming) -> Result<Vec<proc_macro2::TokenStream>, syn::Error> {
+    let mut checks = Vec::new();
+    for ExportedMethod { cfgs, attributes, signature } in methods {
+        let method_name = MethodAttributes::parse(attributes)?.java_name(&signature.ident, naming);
+        let is_static = !signature.inputs.iter().any(|input| matches!(input, FnArg::Receiver(_)));
+        let param_sigs = signature.inputs.iter().filter_map(|input| match input {
+            FnArg::Receiver(_) => None,
+            FnArg::Typed(input_type) => {
+                let i_ty = match &*input_type.ty {
+                    Type::Reference(reference) => referent_type(reference),
+                    ty => ty.clone()
+                };
+                Some(quote!(<#i_ty as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE()))
+            }
+        }).collect::<Vec<_>>();
+        let o_ty: Type = match &signature.output {
+            ReturnType::Default => parse_quote!(()),
+            ReturnType::Type(_, return_type) => (**return_type).clone()
+        };
+
+        checks.push(quote! {
+            #(#cfgs)*
+            errors.method(&CLASS, #method_name, &["(", #(#param_sigs,)* ")", <#o_ty as instant_coffee::JavaReturn>::JVM_PARAM_SIGNATURE()].join(""), #is_static, env);
+        });
+    }
+    Ok(checks)
+}
+
+/// Functions calling the overridable methods of a subclassable class on its Java object, dispatching to Java overrides: `virtual_name(this: &Virtual<Self>, env, ...)`
+///
+/// Parameters are converted into Java by value, as in `jimport!`
+fn quote_virtual_methods(methods: &[ExportedMethod], naming: Naming, qualified_name_str: &str) -> Result<Vec<proc_macro2::TokenStream>, syn::Error> {
+    let mut functions = Vec::new();
+    for ExportedMethod { cfgs, attributes, signature } in methods {
+        if !MethodAttributes::parse(attributes)?.overridable {
+            continue;
+        }
+
+        let mut param_idents = Vec::new();
+        let mut param_types = Vec::new();
+        for input in &signature.inputs {
+            let FnArg::Typed(pat_type) = input else { continue };
+            let Pat::Ident(param) = &*pat_type.pat else { unreachable!("invalid jmodule_methods macro") };
+            if let Type::Reference(reference) = &*pat_type.ty {
+                Err(syn::Error::new(reference.span(), "parameters of overridable methods are passed to Java by value"))?;
+            }
+            param_idents.push(param.ident.clone());
+            param_types.push((*pat_type.ty).clone());
+        }
+
+        let method_ident = &signature.ident;
+        let java_name = naming.apply(&method_ident.to_string());
+        let virtual_ident = Ident::new(&format!("virtual_{}", method_ident), method_ident.span());
+        let return_type = match &signature.output {
+            ReturnType::Type(_, return_type) if **return_type != parse_quote!(()) => Some(&**return_type),
+            _ => None
+        };
+        let (output_type, return_sig, convert_result) = match return_type {
+            Some(return_type) => (
+                quote!(#return_type),
+                quote!(<#return_type as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE()),
+                quote!(<#return_type as instant_coffee::JavaType>::from_jni(<#return_type as instant_coffee::JavaType>::from_jvalue(result, env)?, env))
+            ),
+            None => (quote!(()), quote!("V"), quote!({ let _ = result; Ok(()) }))
+        };
+        let local_capacity = param_idents.len() + 2;    // Local references for each argument, the class of the object, and the result
+
+        functions.push(quote_spanned! {method_ident.span()=>
+            #(#cfgs)*
+            #[allow(non_snake_case)]
+            pub fn #virtual_ident<'local>(this: &instant_coffee::interop::Virtual<Self>, env: &mut jni::JNIEnv<'local>, #(#param_idents: #param_types),*) -> Result<#output_type, Option<jni::errors::Exception>> {
+                static METHOD: instant_coffee::interop::VirtualMethod = instant_coffee::interop::VirtualMethod::new(#java_name);
+
+                instant_coffee::jni_util::ensure_local_capacity(#local_capacity, #qualified_name_str, env)?;
+                #(let #param_idents = jni::objects::JValueOwned::from(<#param_types as instant_coffee::JavaType>::into_jni(#param_idents, env)?);)*
+                let args = &[#(jni::objects::JValue::from(&#param_idents)),*];
+                let signature = [
+                    "(",
+                    #(<#param_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE(),)*
+                    ")",
+                    #return_sig
+                ].join("");
+
+                let result = METHOD.call(this.as_obj(), &signature, args, env)?;
+                // Release argument references; Only the result is still referenced
+                #(if let jni::objects::JValueOwned::Object(argument) = #param_idents {
+                    env.delete_local_ref(argument).map_err(instant_coffee::jni_util::map_jni_error)?;
+                })*
+                #convert_result
+            }
+        });
+    }
+    Ok(functions)
+}
+
+/// Fields of a struct or variant as quoted by [`quote_fields`], one entry per field in declaration order
+struct QuotedFields {
+    /// Local variable names; The rust field names, or `field_N` for tuple fields
+    field_names: Vec<Ident>,
+    /// Tokens accessing the field; The field name, or the index of tuple fields
+    field_idents: Vec<proc_macro2::TokenStream>,
+    /// Types used for conversion
+    field_types: Vec<Type>,
+    /// `JField` declarations
+    field_decls: Vec<proc_macro2::TokenStream>,
+    /// Java field names
+    java_field_names: Vec<String>,
 }
 
-// Turn syn fields into `JField` declarations
-fn quote_fields<T: IntoIterator<Item=Field>>(fields: T) -> Result<(Vec<Ident>, Vec<proc_macro2::TokenStream>, Vec<Type>, Vec<proc_macro2::TokenStream>), syn::Error> {
+/// Turn syn fields into `JField` declarations, and the names, accessors and conversion types of the fields, see [`QuotedFields`]
+///
+/// If `defaults` names a local holding the `Default` value of the type, fields without a `default` Java initializer are initialized to their value in it, see `JavaAttributes::default_constructor`
+fn quote_fields<T: IntoIterator<Item=Field>>(fields: T, naming: Naming, defaults: Option<&Ident>) -> Result<QuotedFields, syn::Error> {
     let mut field_names = Vec::new();
+    let mut java_field_names = Vec::new();
     let mut field_idents = Vec::new();
     let mut field_types = Vec::new();
     let mut field_decls = Vec::new();
     for (idx, field) in fields.into_iter().enumerate() {
-        let r_ty = field.ty;
+        let field_attributes = FieldAttributes::parse(&field.attrs)?;
+        // Type used for conversion, which may differ from the field type for fields with an alternate Java mapping
+        let r_ty = match field_attributes {
+            FieldAttributes { bitset: true, .. } => parse_quote_spanned!(field.ty.span()=> instant_coffee::interop::BitSet),
+            _ => field.ty
+        };
         let j_ty = quote!(<#r_ty as instant_coffee::JavaType>::QUALIFIED_NAME());
         let vis = match field.vis {
             Visibility::Public(_) => quote!(instant_coffee::codegen::JAccessModifier::Public),
             Visibility::Inherited => quote!(instant_coffee::codegen::JAccessModifier::Private),
             vis @ Visibility::Restricted(_) => Err(syn::Error::new(vis.span(), "only pub or private visibility is supported"))?,
         };
-        let name_string = field.ident.as_ref().map(Ident::to_string).unwrap_or(format!("field_{}", idx));
+        // Tuple fields have generated names, to which the naming policy does not apply
+        let name_string = field.ident.as_ref().map(|ident| naming.apply(&ident.to_string())).unwrap_or(format!("field_{}", idx));
+        let serialized_name = match &field_attributes.json_name {
+            Some(json_name) => json_name.value(),
+            None => field.ident.as_ref().map(|ident| ident.to_string()).unwrap_or(format!("field_{}", idx))
+        };
 
         // Tuple 'fields' are accessed by integer literal, not an ident token
         field_idents.push(
@@ -273,16 +967,46 @@ fn quote_fields<T: IntoIterator<Item=Field>>(fields: T) -> Result<(Vec<Ident>, V
         let name_ident = field.ident.map(|ident| format_ident!("{}", ident)).unwrap_or_else(|| format_ident!("field_{}", idx));
         verify_java_identifier(&name_string).map_err(|e| syn::Error::new(name_ident.span(), e))?;
 
+        let initializer = match (&field_attributes.default, defaults) {
+            (Some(default), _) => quote!(Some(#default)),
+            // The rust value of bitset fields is not of the conversion type
+            (None, Some(_)) if field_attributes.bitset => Err(syn::Error::new(name_ident.span(), "bitset fields of classes with a default constructor require a `default` Java initializer"))?,
+            (None, Some(defaults)) => {
+                let field_ident = field_idents.last();
+                quote!(Some(&*instant_coffee::codegen::JavaLiteral::java_literal(&#defaults.#field_ident).leak()))
+            }
+            (None, None) => quote!(None)
+        };
         field_names.push(name_ident);
         field_types.push(r_ty.clone());
-        field_decls.push(quote!(instant_coffee::codegen::JField { access: #vis, jtype: #j_ty, name: #name_string }));
+        field_decls.push(quote!(instant_coffee::codegen::JField { access: #vis, jtype: #j_ty, name: #name_string, serialized_name: #serialized_name, initializer: #initializer }));
+        java_field_names.push(name_string);
     }
-    Ok((
+    Ok(QuotedFields {
         field_names,
         field_idents,
         field_types,
         field_decls,
-    ))
+        java_field_names,
+    })
+}
+
+/// Assert that field types implement `JavaType`, with each assertion spanned to its field's type so that errors point at the offending field rather than into the generated impl
+///
+/// As with the assertions `jmodule` adds for method signatures, these must come first in the generated code for the best errors
+fn quote_field_type_assertions(name_ident: &Ident, generics: &Generics, field_types: &[Type]) -> proc_macro2::TokenStream {
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let assertions = field_types.iter().enumerate().map(|(idx, field_type)| {
+        let ident = Ident::new(&format!("__ASSERT_FIELD_TYPE_IMPL_JAVATYPE_{}", idx), proc_macro2::Span::call_site());
+        quote_spanned!(field_type.span()=> const #ident: fn() -> &'static str = <#field_type as instant_coffee::JavaType>::QUALIFIED_NAME;)
+    });
+
+    quote! {
+        #[allow(dead_code)]
+        impl #impl_generics #name_ident #type_generics #where_clause {
+            #(#assertions)*
+        }
+    }
 }
 
 fn impl_struct_gen(item_struct: ItemStruct) -> Result<TokenStream, syn::Error> {
@@ -292,177 +1016,735 @@ fn impl_struct_gen(item_struct: ItemStruct) -> Result<TokenStream, syn::Error> {
         Fields::Unit => StructKind::Unit
     };
 
-    let (package_name_str, method_signatures) = read_jmodule_info(item_struct.ident.span(), item_struct.attrs)?;    // read jmodule info verifies that the package name is a valid java name
+    let java_attributes = JavaAttributes::parse(&item_struct.attrs)?;
+    if let Some(external) = &java_attributes.external {
+        Err(syn::Error::new(external.span(), "external classes are only supported on fieldless enums"))?;
+    }
+    if java_attributes.handle {
+        return impl_handle_struct_gen(item_struct, java_attributes);
+    }
+    if let Some(transport) = java_attributes.flags {
+        return impl_flags_struct_gen(item_struct, java_attributes, transport);
+    }
+    let (package_name_str, exported_methods, naming) = read_jmodule_info(item_struct.ident.span(), item_struct.attrs)?;    // read jmodule info verifies that the package name is a valid java name
     let struct_name_str = item_struct.ident.to_string();
     let name_ident = item_struct.ident;
-    let qualified_name_str = format!("{}.{}", package_name_str, struct_name_str);
-    let jvm_class_name_str = format!("{}/{}", package_name_str.replace('.', "/"), struct_name_str);
-    let jvm_param_sig_str = format!("L{}/{};", package_name_str.replace('.', "/"), struct_name_str);
+    let class_path_str = java_attributes.class_path(&struct_name_str);
+    let qualified_name_str = format!("{}.{}", package_name_str, class_path_str);
+    let jvm_class_name_str = format!("{}/{}", package_name_str.replace('.', "/"), class_path_str.replace('.', "$"));
+    let jvm_param_sig_str = format!("L{};", jvm_class_name_str);
+    let nested_in = java_attributes.quote_nested_in();
+    let access = java_attributes.quote_access();
+    let annotations = java_attributes.quote_annotations();
+    let interfaces = java_attributes.quote_interfaces();
+    let imports = java_attributes.quote_imports();
+    let body = java_attributes.quote_body();
+    let factory = java_attributes.factory;
+    let with_methods = java_attributes.with_methods;
+    let bean = java_attributes.bean;
+    let default_constructor = java_attributes.default_constructor;
+    // Field initializers of the default constructor are read from the `Default` value when the class is declared
+    let defaults = format_ident!("defaults");
+    let read_defaults = if default_constructor {
+        quote!(let #defaults = <Self as std::default::Default>::default();)
+    } else {
+        proc_macro2::TokenStream::new()
+    };
     let (impl_generics, type_generics, where_clause) = item_struct.generics.split_for_impl();
-    let method_decls = quote_method_decls(method_signatures)?;   // quote method decls verifies method names are valid java names
+    let method_checks = quote_method_checks(&exported_methods, naming)?;
+    let method_decls = quote_method_decls(exported_methods, naming, false)?;   // quote method decls verifies method names are valid java names
 
     verify_type_identifier(&struct_name_str).map_err(|e| syn::Error::new(name_ident.span(), e))?;
+    if java_attributes.copy {
+        Err(syn::Error::new(name_ident.span(), "value classes are copied on conversion; copy is only supported on handle-backed classes"))?;
+    }
+    if java_attributes.subclassable {
+        Err(syn::Error::new(name_ident.span(), "value classes are converted to the exact class; subclassable is only supported on handle-backed classes"))?;
+    }
 
-    let (
+    let QuotedFields {
         field_names,
         field_idents,
         field_types,
         field_decls,
-    ) = quote_fields(item_struct.fields)?;  // quote fields verifies that field names are valid java names
+        java_field_names,
+    } = quote_fields(item_struct.fields, naming, default_constructor.then_some(&defaults))?;  // quote fields verifies that field names are valid java names
+    let local_capacity = field_names.len() + 1;     // Local references for each field, and the object itself
+    let field_type_assertions = quote_field_type_assertions(&name_ident, &item_struct.generics, &field_types);
+
+    // Fields of bean classes may be left null by the no-arg constructor
+    let field_reads = field_types.iter().zip(&java_field_names).map(|(field_type, java_field_name)| {
+        let field_value = quote!(
+            env.get_field(&jni_value, #java_field_name, <#field_type as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE())
+                .map_err(instant_coffee::jni_util::map_jni_error)?
+        );
+        if bean {
+            quote!(instant_coffee::jni_util::from_field_or_default::<#field_type>(#field_value, env)?)
+        } else {
+            quote!(<#field_type as instant_coffee::JavaType>::from_jni(<#field_type as instant_coffee::JavaType>::from_jvalue(#field_value, env)?, env)?)
+        }
+    }).collect::<Vec<_>>();
 
     let from_jni_impl = match struct_kind {
         StructKind::Named => quote! {
             fn from_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
-                Ok(Self {#(
-                    #field_idents: <#field_types as instant_coffee::JavaType>::from_jni(
-                        <#field_types as instant_coffee::JavaType>::from_jvalue(
-                            env.get_field(&jni_value, stringify!(#field_names), <#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE())
-                                .map_err(instant_coffee::jni_util::map_jni_error)?,
-                            env
-                        )?,
-                        env
-                    )?
-                ),*})
+                instant_coffee::jni_util::non_null(&jni_value, #qualified_name_str)?;
+                instant_coffee::jni_util::with_local_frame(#local_capacity, #qualified_name_str, env, |env| Ok(Self {#(
+                    #field_idents: #field_reads.into()
+                ),*}))
             }
         },
         StructKind::Tuple => quote! {
             fn from_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
-                Ok(Self (#(
-                    <#field_types as instant_coffee::JavaType>::from_jni(
-                        <#field_types as instant_coffee::JavaType>::from_jvalue(
-                            env.get_field(&jni_value, stringify!(#field_names), <#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE())
-                                .map_err(instant_coffee::jni_util::map_jni_error)?,
-                            env
-                        )?,
-                        env
-                    )?
-                ),*))
+                instant_coffee::jni_util::non_null(&jni_value, #qualified_name_str)?;
+                instant_coffee::jni_util::with_local_frame(#local_capacity, #qualified_name_str, env, |env| Ok(Self (#(
+                    #field_reads.into()
+                ),*)))
             }
         },
         StructKind::Unit => quote! {
             fn from_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
-                Ok(Self)
+                // Without fields to read, the class is checked explicitly
+                instant_coffee::jni_util::non_null(&jni_value, #qualified_name_str)?;
+                let class = &CLASS.get(env)?;
+                if env.is_instance_of(&jni_value, class).map_err(instant_coffee::jni_util::map_jni_error)? {
+                    Ok(Self)
+                } else {
+                    let class_name = instant_coffee::jni_util::obj_classname(&jni_value, env)?;
+                    Err(Some(jni::errors::Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", class_name, #qualified_name_str) }))
+                }
             }
         }
     };
 
 
     let exp = quote! {
-        impl #impl_generics instant_coffee::codegen::JavaClass for #name_ident #type_generics #where_clause {
-            fn declaration() -> instant_coffee::codegen::JClassDecl {
-                instant_coffee::codegen::JClassDecl::Class {
-                    name: #struct_name_str,
-                    package: #package_name_str,
-                    fields: vec![#(#field_decls),*],
-                    methods: vec![#(#method_decls),*]
+        const _: () = {
+            #field_type_assertions
+
+            static CLASS: instant_coffee::jni_util::CachedClass = instant_coffee::jni_util::CachedClass::new(#jvm_class_name_str);
+
+            impl #impl_generics instant_coffee::codegen::JavaClass for #name_ident #type_generics #where_clause {
+                fn declaration() -> instant_coffee::codegen::JClassDecl {
+                    #read_defaults
+                    instant_coffee::codegen::JClassDecl::Class {
+                        name: #struct_name_str,
+                        package: #package_name_str,
+                        nested_in: #nested_in,
+                        access: #access,
+                        annotations: #annotations,
+                        imports: #imports,
+                        interfaces: #interfaces,
+                        body: #body,
+                        fields: vec![#(#field_decls),*],
+                        methods: #method_decls,
+                        factory: #factory,
+                        with_methods: #with_methods,
+                        bean: #bean,
+                        default_constructor: #default_constructor
+                    }
+                }
+
+                fn resolve_classes(env: &mut jni::JNIEnv) {
+                    CLASS.resolve(env);
+                }
+
+                fn verify_schema(env: &mut jni::JNIEnv, errors: &mut instant_coffee::jni_util::SchemaErrors) {
+                    #(errors.field(&CLASS, #java_field_names, <#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE(), false, env);)*
+                    errors.method(&CLASS, "<init>", &["(", #(<#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE(),)* ")V"].join(""), false, env);
+                    #(#method_checks)*
                 }
             }
-        }
 
-        impl #impl_generics instant_coffee::JavaType for #name_ident #type_generics #where_clause {
-            type JniType<'local> = jni::objects::JObject<'local>;
-            type ArrayType<'local> = jni::objects::JObjectArray<'local>;
+            impl #impl_generics instant_coffee::JavaType for #name_ident #type_generics #where_clause {
+                type JniType<'local> = jni::objects::JObject<'local>;
+                type ArrayType<'local> = jni::objects::JObjectArray<'local>;
 
-            fn QUALIFIED_NAME() -> &'static str { #qualified_name_str }
+                fn QUALIFIED_NAME() -> &'static str { #qualified_name_str }
 
-            fn JVM_PARAM_SIGNATURE() -> &'static str {#jvm_param_sig_str }
+                fn JVM_PARAM_SIGNATURE() -> &'static str {#jvm_param_sig_str }
 
-            fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { jni::objects::JObject::null() }
+                fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { jni::objects::JObject::null() }
 
-            fn from_jvalue<'local>(jvalue: jni::objects::JValueOwned<'local>, _env: &mut jni::JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<jni::errors::Exception>> {
-                match jvalue {
-                    jni::objects::JValueOwned::Object(obj) => Ok(obj),
-                    _ => Err(Some(jni::errors::Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as instant_coffee::JavaType>::QUALIFIED_NAME()) }))
+                fn from_jvalue<'local>(jvalue: jni::objects::JValueOwned<'local>, _env: &mut jni::JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<jni::errors::Exception>> {
+                    match jvalue {
+                        jni::objects::JValueOwned::Object(obj) => Ok(obj),
+                        _ => Err(Some(jni::errors::Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as instant_coffee::JavaType>::QUALIFIED_NAME()) }))
+                    }
                 }
-            }
 
-            fn into_jni<'local>(self, env: &mut jni::JNIEnv<'local>) -> Result<jni::objects::JObject<'local>, Option<jni::errors::Exception>> {
-                #(let #field_names = jni::objects::JValueOwned::from(<#field_types as instant_coffee::JavaType>::into_jni(self.#field_idents, env)?);)*
+                fn into_jni<'local>(self, env: &mut jni::JNIEnv<'local>) -> Result<jni::objects::JObject<'local>, Option<jni::errors::Exception>> {
+                    instant_coffee::jni_util::ensure_local_capacity(#local_capacity, #qualified_name_str, env)?;
+                    #(let #field_names = jni::objects::JValueOwned::from(<#field_types as instant_coffee::JavaType>::into_jni(self.#field_idents.into(), env)?);)*
 
-                let args = &[
-                    #(jni::objects::JValue::from(&#field_names)),*
-                ];
+                    let args = &[
+                        #(jni::objects::JValue::from(&#field_names)),*
+                    ];
 
-                env.new_object(
-                    #jvm_class_name_str,
-                    [
-                        "(",
-                        #(<#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE(),)*
-                        ")V"
-                    ].join(""), // Micro-optimization candidate: Use const-cat
-                    args
-                )
-                .map_err(instant_coffee::jni_util::map_jni_error)
+                    let class = &CLASS.get(env)?;
+                    let object = env.new_object(
+                        class,
+                        [
+                            "(",
+                            #(<#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE(),)*
+                            ")V"
+                        ].join(""), // Micro-optimization candidate: Use const-cat
+                        args
+                    )
+                    .map_err(instant_coffee::jni_util::map_jni_error)?;
+
+                    // Release field references, leaving only the object itself
+                    #(if let jni::objects::JValueOwned::Object(field) = #field_names {
+                        env.delete_local_ref(field).map_err(instant_coffee::jni_util::map_jni_error)?;
+                    })*
+                    Ok(object)
+                }
+
+                #from_jni_impl
             }
 
-            #from_jni_impl
-        }
+            impl #impl_generics instant_coffee::JavaBorrow for #name_ident #type_generics #where_clause {
+                type Guard = Box<Self>;
+
+                fn borrow_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Box<Self>, Option<jni::errors::Exception>> {
+                    <Self as instant_coffee::JavaType>::from_jni(jni_value, env).map(Box::new)
+                }
+            }
+        };
     };
 
     Ok(exp.into())
 }
 
-fn impl_enum_gen(item_enum: ItemEnum) -> Result<TokenStream, syn::Error> {
-    let (package_name_str, method_signatures) = read_jmodule_info(item_enum.ident.span(), item_enum.attrs)?;
-    let enum_name_str = item_enum.ident.to_string();
-    let name_ident = item_enum.ident;
-    let qualified_name_str = format!("{}.{}", package_name_str, enum_name_str);
-    let jvm_class_name_str = format!("{}/{}", package_name_str.replace('.', "/"), enum_name_str);
-    let jvm_param_sig_str = format!("L{}/{};", package_name_str.replace('.', "/"), enum_name_str);
-    let (impl_generics, type_generics, where_clause) = item_enum.generics.split_for_impl();
-    let method_decls = quote_method_decls(method_signatures)?;   // quote method decls verifies method names are valid java names
+fn impl_handle_struct_gen(item_struct: ItemStruct, java_attributes: JavaAttributes) -> Result<TokenStream, syn::Error> {
+    let (package_name_str, exported_methods, naming) = read_jmodule_info(item_struct.ident.span(), item_struct.attrs)?;    // read jmodule info verifies that the package name is a valid java name
+    let struct_name_str = item_struct.ident.to_string();
+    let name_ident = item_struct.ident;
+    let class_path_str = java_attributes.class_path(&struct_name_str);
+    let qualified_name_str = format!("{}.{}", package_name_str, class_path_str);
+    let jvm_class_name_str = format!("{}/{}", package_name_str.replace('.', "/"), class_path_str.replace('.', "$"));
+    let jvm_param_sig_str = format!("L{};", jvm_class_name_str);
+    let nested_in = java_attributes.quote_nested_in();
+    let access = java_attributes.quote_access();
+    let annotations = java_attributes.quote_annotations();
+    let interfaces = java_attributes.quote_interfaces();
+    let imports = java_attributes.quote_imports();
+    let body = java_attributes.quote_body();
+    let subclassable = java_attributes.subclassable;
+    let virtual_methods = quote_virtual_methods(&exported_methods, naming, &qualified_name_str)?;
+    let method_checks = quote_method_checks(&exported_methods, naming)?;
+    let method_decls = quote_method_decls(exported_methods, naming, subclassable)?;   // quote method decls verifies method names are valid java names
 
-    verify_type_identifier(&enum_name_str).map_err(|e| syn::Error::new(name_ident.span(), e))?;
+    verify_type_identifier(&struct_name_str).map_err(|e| syn::Error::new(name_ident.span(), e))?;
+    if !item_struct.generics.params.is_empty() {
+        Err(syn::Error::new(item_struct.generics.span(), "handle-backed classes cannot be generic"))?;
+    }
+    if java_attributes.factory || java_attributes.with_methods || java_attributes.bean || java_attributes.default_constructor {
+        Err(syn::Error::new(name_ident.span(), "handle-backed classes have no Java fields, and do not support factory, with methods, bean mode or default constructors"))?;
+    }
+    if java_attributes.flags.is_some() {
+        Err(syn::Error::new(name_ident.span(), "flags types are converted by value, and cannot be handle-backed"))?;
+    }
 
-    let is_tagged_union = item_enum.variants.iter().any(|variant| variant.fields != Fields::Unit);
+    // Fields are not exported; The rust value is opaque to Java
+    let binary_name_str = format!("{}.{}", package_name_str, class_path_str.replace('.', "$"));
+    let release_ident = Ident::new(
+        &format!("Java_{}_{}_release", mangle_jni_name(&package_name_str), mangle_jni_name(&class_path_str.replace('.', "$"))),
+        name_ident.span()
+    );
+    let release_symbol = release_ident.to_string();
+    // Subclasses take over the handle of an existing object through the `transfer` native method
+    let (transfer_fn, transfer_export) = if subclassable {
+        let transfer_ident = Ident::new(
+            &format!("Java_{}_{}_transfer", mangle_jni_name(&package_name_str), mangle_jni_name(&class_path_str.replace('.', "$"))),
+            name_ident.span()
+        );
+        let transfer_symbol = transfer_ident.to_string();
+        let transfer_signature = format!("({})J", jvm_param_sig_str);
+        let transfer_export = quote! {
+            exports.push(instant_coffee::codegen::ExportInfo { symbol: #transfer_symbol, class: #binary_name_str, method: "transfer", signature: String::from(#transfer_signature), is_static: true });
+        };
+        (quote! {
+            #[no_mangle]
+            pub unsafe extern "system" fn #transfer_ident<'local>(mut env: jni::JNIEnv<'local>, _class: jni::objects::JClass<'local>, base: jni::objects::JObject<'local>) -> jni::sys::jlong {
+                match instant_coffee::interop::handle_transfer::<#name_ident>(&base, &mut env) {
+                    Ok(handle) => handle,
+                    Err(None) => 0,
+                    Err(Some(exception)) => {
+                        env.throw_new(exception.class, exception.msg)
+                            .expect("could not throw exception!");
+                        0
+                    }
+                }
+            }
+        }, transfer_export)
+    } else {
+        (proc_macro2::TokenStream::new(), proc_macro2::TokenStream::new())
+    };
 
-    let class_decl_impl;
-    let into_jni_impl;
-    let from_jni_impl;
+    let exp = quote! {
+        const _: () = {
+            static CLASS: instant_coffee::jni_util::CachedClass = instant_coffee::jni_util::CachedClass::new(#jvm_class_name_str);
 
-    if is_tagged_union {
-        let mut variant_decls = Vec::new();
-        let mut variant_into_jni_expressions = Vec::new();
-        let mut variant_from_jni_expressions = Vec::new();
-        for variant in item_enum.variants {
-            let variant_ident = variant.ident;
-            let variant_name = variant_ident.to_string();
-            let jvm_variant_name_str = format!("{}${}", jvm_class_name_str, variant_name);
+            impl instant_coffee::codegen::JavaClass for #name_ident {
+                fn declaration() -> instant_coffee::codegen::JClassDecl {
+                    instant_coffee::codegen::JClassDecl::Handle {
+                        name: #struct_name_str,
+                        package: #package_name_str,
+                        nested_in: #nested_in,
+                        access: #access,
+                        annotations: #annotations,
+                        imports: #imports,
+                        interfaces: #interfaces,
+                        body: #body,
+                        methods: #method_decls,
+                        subclassable: #subclassable
+                    }
+                }
 
-            verify_type_identifier(&variant_name).map_err(|e| syn::Error::new(name_ident.span(), e))?;
+                fn resolve_classes(env: &mut jni::JNIEnv) {
+                    CLASS.resolve(env);
+                }
 
-            let variant_kind = match &variant.fields {
-                Fields::Named(_) => StructKind::Named,
-                Fields::Unnamed(_) => StructKind::Tuple,
-                Fields::Unit => StructKind::Unit
-            };
+                fn verify_schema(env: &mut jni::JNIEnv, errors: &mut instant_coffee::jni_util::SchemaErrors) {
+                    errors.field(&CLASS, "handle", "J", false, env);
+                    errors.method(&CLASS, "<init>", "(J)V", false, env);
+                    #(#method_checks)*
+                }
 
-            let (
-                field_names,
-                field_idents,
-                field_types,
-                field_decls,
-            ) = quote_fields(variant.fields)?;  // quote fields verifies that field names are valid java names
+                fn exports(exports: &mut Vec<instant_coffee::codegen::ExportInfo>) {
+                    exports.push(instant_coffee::codegen::ExportInfo { symbol: #release_symbol, class: #binary_name_str, method: "release", signature: String::from("(J)V"), is_static: true });
+                    #transfer_export
+                }
+            }
 
-            variant_decls.push(quote! {
+            impl #name_ident {
+                #(#virtual_methods)*
+            }
+
+            impl instant_coffee::interop::HandleClass for #name_ident {
+                fn handles() -> &'static std::sync::Mutex<instant_coffee::interop::HandleMap<std::sync::Arc<Self>>> {
+                    static HANDLES: std::sync::Mutex<instant_coffee::interop::HandleMap<std::sync::Arc<#name_ident>>> = std::sync::Mutex::new(instant_coffee::interop::HandleMap::new());
+                    &HANDLES
+                }
+            }
+
+            impl instant_coffee::JavaType for #name_ident {
+                type JniType<'local> = jni::objects::JObject<'local>;
+                type ArrayType<'local> = jni::objects::JObjectArray<'local>;
+
+                fn QUALIFIED_NAME() -> &'static str { #qualified_name_str }
+
+                fn JVM_PARAM_SIGNATURE() -> &'static str { #jvm_param_sig_str }
+
+                fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { jni::objects::JObject::null() }
+
+                fn from_jvalue<'local>(jvalue: jni::objects::JValueOwned<'local>, _env: &mut jni::JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<jni::errors::Exception>> {
+                    match jvalue {
+                        jni::objects::JValueOwned::Object(obj) => Ok(obj),
+                        _ => Err(Some(jni::errors::Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as instant_coffee::JavaType>::QUALIFIED_NAME()) }))
+                    }
+                }
+
+                fn into_jni<'local>(self, env: &mut jni::JNIEnv<'local>) -> Result<jni::objects::JObject<'local>, Option<jni::errors::Exception>> {
+                    instant_coffee::interop::handle_into_jni(self, &CLASS, env)
+                }
+
+                // Passing by value moves the rust value out of the Java object, which is left closed
+                fn from_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
+                    instant_coffee::interop::handle_take(&jni_value, env)
+                }
+            }
+
+            impl instant_coffee::JavaBorrow for #name_ident {
+                type Guard = std::sync::Arc<Self>;
+
+                fn borrow_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<std::sync::Arc<Self>, Option<jni::errors::Exception>> {
+                    instant_coffee::interop::handle_borrow(&jni_value, env)
+                }
+            }
+        };
+
+        #[no_mangle]
+        pub unsafe extern "system" fn #release_ident<'local>(_env: jni::JNIEnv<'local>, _class: jni::objects::JClass<'local>, handle: jni::sys::jlong) {
+            instant_coffee::interop::handle_release::<#name_ident>(handle)
+        }
+
+        #transfer_fn
+    };
+
+    Ok(exp.into())
+}
+
+/// Generate a `JavaType` implementation for a `bitflags` type, declared with "externally defined flags": `struct Flags(u32);` and `bitflags! { impl Flags: u32 { ... } }`
+///
+/// Flag names and values are only known to the `bitflags` implementation, and are read from [`bitflags::Flags::FLAGS`] when declaring the Java class
+fn impl_flags_struct_gen(item_struct: ItemStruct, java_attributes: JavaAttributes, transport: FlagsTransport) -> Result<TokenStream, syn::Error> {
+    let (package_name_str, exported_methods, _) = read_jmodule_info(item_struct.ident.span(), item_struct.attrs)?;    // read jmodule info verifies that the package name is a valid java name
+    let struct_name_str = item_struct.ident.to_string();
+    let name_ident = item_struct.ident;
+    let class_path_str = java_attributes.class_path(&struct_name_str);
+    let qualified_name_str = format!("{}.{}", package_name_str, class_path_str);
+    let jvm_class_name_str = format!("{}/{}", package_name_str.replace('.', "/"), class_path_str.replace('.', "$"));
+    let jvm_param_sig_str = format!("L{};", jvm_class_name_str);
+    let nested_in = java_attributes.quote_nested_in();
+    let access = java_attributes.quote_access();
+    let annotations = java_attributes.quote_annotations();
+    let imports = java_attributes.quote_imports();
+    let body = &java_attributes.body;
+
+    verify_type_identifier(&struct_name_str).map_err(|e| syn::Error::new(name_ident.span(), e))?;
+    if !item_struct.generics.params.is_empty() {
+        Err(syn::Error::new(item_struct.generics.span(), "flags types cannot be generic"))?;
+    }
+    if java_attributes.factory || java_attributes.with_methods || java_attributes.bean || java_attributes.default_constructor || java_attributes.copy || java_attributes.subclassable {
+        Err(syn::Error::new(name_ident.span(), "flags types have no Java fields, and do not support factory, with methods, bean mode, default constructors, copy or subclassing"))?;
+    }
+    if java_attributes.to_string || java_attributes.from_str || java_attributes.hash || !java_attributes.interfaces.is_empty() || !exported_methods.is_empty() {
+        Err(syn::Error::new(name_ident.span(), "flags types are converted to a Java set or integer, and cannot declare methods or interfaces"))?;
+    }
+
+    // The bits field; Integers of up to 32 bits are an `int`, 64-bit integers a `long`
+    let bits_type = match &item_struct.fields {
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0].ty,
+        fields => Err(syn::Error::new(fields.span(), "flags types must be a tuple struct of their bits, `struct Flags(u32);`, with the flags declared as `bitflags! { impl Flags: u32 { ... } }`"))?
+    };
+    let bits_name = match bits_type {
+        Type::Path(type_path) => type_path.path.get_ident().map(Ident::to_string),
+        _ => None
+    };
+    let (java_bits_type, java_bits_literal_suffix, jni_bits_type, jni_bits_array_type) = match bits_name.as_deref() {
+        Some("u8" | "i8" | "u16" | "i16" | "u32" | "i32") => ("int", "", quote!(jni::sys::jint), quote!(jni::objects::JIntArray)),
+        Some("u64" | "i64") => ("long", "L", quote!(jni::sys::jlong), quote!(jni::objects::JLongArray)),
+        _ => Err(syn::Error::new(bits_type.span(), "the bits of flags types must be a primitive integer"))?
+    };
+
+    let exp = match transport {
+        FlagsTransport::EnumSet => {
+            let qualified_set_name_str = format!("java.util.EnumSet<{}>", qualified_name_str);
+            quote! {
+                const _: () = {
+                    static CLASS: instant_coffee::jni_util::CachedClass = instant_coffee::jni_util::CachedClass::new(#jvm_class_name_str);
+
+                    impl instant_coffee::codegen::JavaClass for #name_ident {
+                        fn declaration() -> instant_coffee::codegen::JClassDecl {
+                            instant_coffee::codegen::JClassDecl::Enum {
+                                name: #struct_name_str,
+                                package: #package_name_str,
+                                nested_in: #nested_in,
+                                access: #access,
+                                annotations: #annotations,
+                                imports: #imports,
+                                variants: <Self as instant_coffee::interop::bitflags::Flags>::FLAGS.iter()
+                                    .map(instant_coffee::interop::bitflags::Flag::name)
+                                    .filter(|name| !name.is_empty())
+                                    .collect(),
+                                methods: vec![],
+                                body: vec![#(#body),*]
+                            }
+                        }
+
+                        fn resolve_classes(env: &mut jni::JNIEnv) {
+                            CLASS.resolve(env);
+                        }
+
+                        fn verify_schema(env: &mut jni::JNIEnv, errors: &mut instant_coffee::jni_util::SchemaErrors) {
+                            for flag in <Self as instant_coffee::interop::bitflags::Flags>::FLAGS.iter().filter(|flag| !flag.name().is_empty()) {
+                                errors.field(&CLASS, flag.name(), #jvm_param_sig_str, true, env);
+                            }
+                        }
+                    }
+
+                    impl instant_coffee::JavaType for #name_ident {
+                        type JniType<'local> = jni::objects::JObject<'local>;
+                        type ArrayType<'local> = jni::objects::JObjectArray<'local>;
+
+                        fn QUALIFIED_NAME() -> &'static str { #qualified_set_name_str }
+
+                        fn JVM_PARAM_SIGNATURE() -> &'static str { "Ljava/util/EnumSet;" }
+
+                        fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { jni::objects::JObject::null() }
+
+                        fn from_jvalue<'local>(jvalue: jni::objects::JValueOwned<'local>, _env: &mut jni::JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<jni::errors::Exception>> {
+                            match jvalue {
+                                jni::objects::JValueOwned::Object(obj) => Ok(obj),
+                                _ => Err(Some(jni::errors::Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as instant_coffee::JavaType>::QUALIFIED_NAME()) }))
+                            }
+                        }
+
+                        fn into_jni<'local>(self, env: &mut jni::JNIEnv<'local>) -> Result<jni::objects::JObject<'local>, Option<jni::errors::Exception>> {
+                            let class = &CLASS.get(env)?;
+                            instant_coffee::interop::enum_set_into_jni(&self, class, #jvm_param_sig_str, env)
+                        }
+
+                        fn from_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
+                            instant_coffee::interop::enum_set_from_jni(jni_value, #qualified_set_name_str, env)
+                        }
+                    }
+
+                    impl instant_coffee::JavaBorrow for #name_ident {
+                        type Guard = Box<Self>;
+
+                        fn borrow_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Box<Self>, Option<jni::errors::Exception>> {
+                            <Self as instant_coffee::JavaType>::from_jni(jni_value, env).map(Box::new)
+                        }
+                    }
+                };
+            }
+        }
+        FlagsTransport::Int => quote! {
+            const _: () = {
+                impl instant_coffee::codegen::JavaClass for #name_ident {
+                    fn declaration() -> instant_coffee::codegen::JClassDecl {
+                        // The constants are only known at runtime; Declarations are built once for code generation, their source is leaked as `JClassDecl` holds static strings
+                        let constants = <Self as instant_coffee::interop::bitflags::Flags>::FLAGS.iter()
+                            .filter(|flag| !flag.name().is_empty())
+                            .map(|flag| format!(
+                                "public static final {} {} = {}{};",
+                                #java_bits_type,
+                                flag.name(),
+                                instant_coffee::interop::bitflags::Flags::bits(flag.value()) as #jni_bits_type,
+                                #java_bits_literal_suffix
+                            ))
+                            .collect::<Vec<_>>();
+                        let constants = (!constants.is_empty()).then(|| &*constants.join("\n").leak());
+                        instant_coffee::codegen::JClassDecl::Class {
+                            name: #struct_name_str,
+                            package: #package_name_str,
+                            nested_in: #nested_in,
+                            access: #access,
+                            annotations: #annotations,
+                            imports: #imports,
+                            interfaces: vec![],
+                            body: constants.into_iter().chain([#(#body),*]).collect(),
+                            fields: vec![],
+                            methods: vec![],
+                            factory: false,
+                            with_methods: false,
+                            bean: false,
+                            default_constructor: false
+                        }
+                    }
+                }
+
+                impl instant_coffee::JavaType for #name_ident {
+                    type JniType<'local> = #jni_bits_type;
+                    type ArrayType<'local> = #jni_bits_array_type<'local>;
+
+                    fn QUALIFIED_NAME() -> &'static str { <#jni_bits_type as instant_coffee::JavaType>::QUALIFIED_NAME() }
+
+                    fn JVM_PARAM_SIGNATURE() -> &'static str { <#jni_bits_type as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE() }
+
+                    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { 0 }
+
+                    fn from_jvalue<'local>(jvalue: jni::objects::JValueOwned<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<jni::errors::Exception>> {
+                        <#jni_bits_type as instant_coffee::JavaType>::from_jvalue(jvalue, env)
+                    }
+
+                    // Unknown bits are retained, as flags declared by newer bindings
+                    fn from_jni<'local>(jni_value: Self::JniType<'local>, _env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
+                        Ok(<Self as instant_coffee::interop::bitflags::Flags>::from_bits_retain(jni_value as #bits_type))
+                    }
+
+                    fn into_jni<'local>(self, _env: &mut jni::JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<jni::errors::Exception>> {
+                        Ok(instant_coffee::interop::bitflags::Flags::bits(&self) as #jni_bits_type)
+                    }
+                }
+
+                impl<'local> instant_coffee::JniArray<'local, #name_ident> for #jni_bits_array_type<'local> {
+                    fn from_jni(jni_value: Self, env: &mut jni::JNIEnv<'local>) -> Result<Box<[#name_ident]>, Option<jni::errors::Exception>> {
+                        let bits = <Self as instant_coffee::JniArray<'local, #jni_bits_type>>::from_jni(jni_value, env)?;
+                        Ok(bits.iter().map(|bits| <#name_ident as instant_coffee::interop::bitflags::Flags>::from_bits_retain(*bits as #bits_type)).collect())
+                    }
+
+                    fn into_jni(input: Box<[#name_ident]>, env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
+                        let bits = input.iter().map(|flags| instant_coffee::interop::bitflags::Flags::bits(flags) as #jni_bits_type).collect();
+                        <Self as instant_coffee::JniArray<'local, #jni_bits_type>>::into_jni(bits, env)
+                    }
+                }
+
+                impl instant_coffee::JavaBorrow for #name_ident {
+                    type Guard = Box<Self>;
+
+                    fn borrow_jni<'local>(jni_value: #jni_bits_type, env: &mut jni::JNIEnv<'local>) -> Result<Box<Self>, Option<jni::errors::Exception>> {
+                        <Self as instant_coffee::JavaType>::from_jni(jni_value, env).map(Box::new)
+                    }
+                }
+            };
+        }
+    };
+
+    Ok(exp.into())
+}
+
+fn impl_enum_gen(item_enum: ItemEnum) -> Result<TokenStream, syn::Error> {
+    let java_attributes = JavaAttributes::parse(&item_enum.attrs)?;
+    let non_exhaustive = item_enum.attrs.iter().any(|attribute| attribute.path().is_ident("non_exhaustive"));
+    let (package_name_str, exported_methods, naming) = read_jmodule_info(item_enum.ident.span(), item_enum.attrs)?;
+    let enum_name_str = item_enum.ident.to_string();
+    let name_ident = item_enum.ident;
+    // External enums convert to an existing Java enum, and are not generated
+    let external = java_attributes.external.as_ref().map(LitStr::value);
+    let class_path_str = java_attributes.class_path(&enum_name_str);
+    let (qualified_name_str, jvm_class_name_str) = match &external {
+        Some(class) => (class.clone(), class.replace('.', "/")),
+        None => (
+            format!("{}.{}", package_name_str, class_path_str),
+            format!("{}/{}", package_name_str.replace('.', "/"), class_path_str.replace('.', "$"))
+        )
+    };
+    let (decl_package_str, decl_name_str) = match &external {
+        Some(class) => class.rsplit_once('.').map(|(package, name)| (package.to_string(), name.to_string())).unwrap_or_default(),
+        None => (package_name_str.clone(), enum_name_str.clone())
+    };
+    let jvm_param_sig_str = format!("L{};", jvm_class_name_str);
+    let nested_in = java_attributes.quote_nested_in();
+    let access = java_attributes.quote_access();
+    let annotations = java_attributes.quote_annotations();
+    let imports = java_attributes.quote_imports();
+    let body = java_attributes.quote_body();
+    let (impl_generics, type_generics, where_clause) = item_enum.generics.split_for_impl();
+    let has_methods = !exported_methods.is_empty();
+    let method_checks = quote_method_checks(&exported_methods, naming)?;
+    let method_decls = quote_method_decls(exported_methods, naming, false)?;   // quote method decls verifies method names are valid java names
+
+    verify_type_identifier(&enum_name_str).map_err(|e| syn::Error::new(name_ident.span(), e))?;
+    if java_attributes.factory {
+        Err(syn::Error::new(name_ident.span(), "factory methods are only supported on structs"))?;
+    }
+    if java_attributes.with_methods {
+        Err(syn::Error::new(name_ident.span(), "with methods are only supported on structs"))?;
+    }
+    if java_attributes.handle {
+        Err(syn::Error::new(name_ident.span(), "handle-backed classes are only supported on structs"))?;
+    }
+    if java_attributes.bean {
+        Err(syn::Error::new(name_ident.span(), "bean mode is only supported on structs"))?;
+    }
+    if java_attributes.default_constructor {
+        Err(syn::Error::new(name_ident.span(), "default constructors are only supported on structs"))?;
+    }
+    if java_attributes.flags.is_some() {
+        Err(syn::Error::new(name_ident.span(), "flags are declared with bitflags on a struct, `struct Flags(u32);`"))?;
+    }
+    if java_attributes.copy {
+        Err(syn::Error::new(name_ident.span(), "copy is only supported on handle-backed classes"))?;
+    }
+    if java_attributes.subclassable {
+        Err(syn::Error::new(name_ident.span(), "subclassable is only supported on handle-backed classes"))?;
+    }
+    if !java_attributes.interfaces.is_empty() {
+        Err(syn::Error::new(name_ident.span(), "interfaces are only supported on structs"))?;
+    }
+
+    // #[non_exhaustive] enums convert unrecognized Java values, such as variants added by newer bindings, to a fallback variant rather than failing
+    let mut fallback_variant = None;
+    for variant in &item_enum.variants {
+        if VariantAttributes::parse(&variant.attrs)?.fallback {
+            if !non_exhaustive {
+                Err(syn::Error::new(variant.ident.span(), "fallback variants are only supported on #[non_exhaustive] enums"))?;
+            }
+            if variant.fields != Fields::Unit {
+                Err(syn::Error::new(variant.ident.span(), "fallback variants must be unit variants"))?;
+            }
+            if fallback_variant.is_some() {
+                Err(syn::Error::new(variant.ident.span(), "only one variant may be the fallback"))?;
+            }
+            fallback_variant = Some(variant.ident.clone());
+        }
+    }
+    if non_exhaustive && fallback_variant.is_none() {
+        Err(syn::Error::new(name_ident.span(), "#[non_exhaustive] enums require a #[java(fallback)] variant, which unrecognized Java values convert to"))?;
+    }
+
+    let is_tagged_union = item_enum.variants.iter().any(|variant| variant.fields != Fields::Unit);
+    if java_attributes.hash && !is_tagged_union {
+        Err(syn::Error::new(name_ident.span(), "Java enums declare equals and hashCode final; hash is only supported on structs and tagged unions"))?;
+    }
+    if external.is_some() {
+        if is_tagged_union {
+            Err(syn::Error::new(name_ident.span(), "external classes are only supported on fieldless enums"))?;
+        }
+        if java_attributes.nested_in.is_some() || !java_attributes.annotations.is_empty() || !java_attributes.imports.is_empty() || !java_attributes.body.is_empty() {
+            Err(syn::Error::new(name_ident.span(), "external classes are not generated, and do not support nested_in, annotations, imports, or body"))?;
+        }
+        if has_methods {
+            Err(syn::Error::new(name_ident.span(), "external classes are not generated, and cannot declare exported methods"))?;
+        }
+    }
+    // Renamed variants may not collide with each other, or with the name of another variant
+    let mut java_variant_names = HashSet::new();
+    for variant in &item_enum.variants {
+        let variant_attributes = VariantAttributes::parse(&variant.attrs)?;
+        if !java_variant_names.insert(variant_attributes.java_name(&variant.ident)) {
+            let span = variant_attributes.name.as_ref().map_or(variant.ident.span(), LitStr::span);
+            Err(syn::Error::new(span, "duplicate Java variant name"))?;
+        }
+    }
+    let mut jvm_variant_names = Vec::new();     // Classes of tagged union variants, cached by index
+    let mut variant_field_types = Vec::new();
+
+    let class_decl_impl;
+    let into_jni_impl;
+    let from_jni_impl;
+    // Rust helpers mapping variants to the names of their Java enum constants, fieldless enums only
+    let mut java_names_impl = proc_macro2::TokenStream::new();
+    // Schema checks of the variants, see `JavaClass::verify_schema`
+    let mut variant_checks = Vec::new();
+
+    if is_tagged_union {
+        let mut variant_decls = Vec::new();
+        let mut variant_into_jni_expressions = Vec::new();
+        let mut variant_from_jni_expressions = Vec::new();
+        for (variant_index, variant) in item_enum.variants.into_iter().enumerate() {
+            let variant_name = VariantAttributes::parse(&variant.attrs)?.java_name(&variant.ident);
+            let variant_ident = variant.ident;
+            jvm_variant_names.push(format!("{}${}", jvm_class_name_str, variant_name));
+
+            verify_type_identifier(&variant_name).map_err(|e| syn::Error::new(name_ident.span(), e))?;
+
+            let variant_kind = match &variant.fields {
+                Fields::Named(_) => StructKind::Named,
+                Fields::Unnamed(_) => StructKind::Tuple,
+                Fields::Unit => StructKind::Unit
+            };
+
+            let QuotedFields {
+                field_names,
+                field_idents,
+                field_types,
+                field_decls,
+                java_field_names,
+            } = quote_fields(variant.fields, naming, None)?;  // quote fields verifies that field names are valid java names
+            variant_field_types.extend(field_types.iter().cloned());
+            let local_capacity = field_names.len() + 1;
+            let qualified_variant_name_str = format!("{}.{}", qualified_name_str, variant_name);
+
+            variant_decls.push(quote! {
                 instant_coffee::codegen::JUnionVariant {
                     name: #variant_name,
                     fields: vec![#(#field_decls),*]
                 }
             });
+            variant_checks.push(quote! {
+                #(errors.field(&VARIANT_CLASSES[#variant_index], #java_field_names, <#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE(), false, env);)*
+                errors.method(&VARIANT_CLASSES[#variant_index], "<init>", &["(", #(<#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE(),)* ")V"].join(""), false, env);
+            });
 
             match variant_kind {
                 StructKind::Named => {
                     variant_into_jni_expressions.push(quote! {
                         #name_ident::#variant_ident { #(#field_idents),* } => {
-                            #(let #field_names = jni::objects::JValueOwned::from(<#field_types as instant_coffee::JavaType>::into_jni(#field_idents, env)?);)*
+                            instant_coffee::jni_util::ensure_local_capacity(#local_capacity, #qualified_variant_name_str, env)?;
+                            #(let #field_names = jni::objects::JValueOwned::from(<#field_types as instant_coffee::JavaType>::into_jni(#field_idents.into(), env)?);)*
 
                             let args = &[
                                 #(jni::objects::JValue::from(&#field_names)),*
                             ];
 
-                            env.new_object(
-                                #jvm_variant_name_str,
+                            let class = &VARIANT_CLASSES[#variant_index].get(env)?;
+                            let object = env.new_object(
+                                class,
                                 [
                                     "(",
                                     #(<#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE(),)*
@@ -470,36 +1752,45 @@ fn impl_enum_gen(item_enum: ItemEnum) -> Result<TokenStream, syn::Error> {
                                 ].join(""), // Micro-optimization candidate: Use const-cat
                                 args
                             )
-                            .map_err(instant_coffee::jni_util::map_jni_error)
+                            .map_err(instant_coffee::jni_util::map_jni_error)?;
+
+                            // Release field references, leaving only the object itself
+                            #(if let jni::objects::JValueOwned::Object(field) = #field_names {
+                                env.delete_local_ref(field).map_err(instant_coffee::jni_util::map_jni_error)?;
+                            })*
+                            Ok(object)
                         }
                     });
 
                     variant_from_jni_expressions.push(quote! {
-                        if env.is_instance_of(&jni_value, #jvm_variant_name_str).map_err(instant_coffee::jni_util::map_jni_error)? {
-                            return Ok(#name_ident::#variant_ident {#(
+                        let class = &VARIANT_CLASSES[#variant_index].get(env)?;
+                        if env.is_instance_of(&jni_value, class).map_err(instant_coffee::jni_util::map_jni_error)? {
+                            return instant_coffee::jni_util::with_local_frame(#local_capacity, #qualified_variant_name_str, env, |env| Ok(#name_ident::#variant_ident {#(
                                 #field_idents: <#field_types as instant_coffee::JavaType>::from_jni(
                                     <#field_types as instant_coffee::JavaType>::from_jvalue(
-                                        env.get_field(&jni_value, stringify!(#field_names), <#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE())
+                                        env.get_field(&jni_value, #java_field_names, <#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE())
                                             .map_err(instant_coffee::jni_util::map_jni_error)?,
                                         env
                                     )?,
                                     env
-                                )?
-                            ),*});
+                                )?.into()
+                            ),*}));
                         }
                     })
                 },
                 StructKind::Tuple => {
                     variant_into_jni_expressions.push(quote! {
                         #name_ident::#variant_ident ( #(#field_names),* ) => {
-                            #(let #field_names = jni::objects::JValueOwned::from(<#field_types as instant_coffee::JavaType>::into_jni(#field_idents, env)?);)*
+                            instant_coffee::jni_util::ensure_local_capacity(#local_capacity, #qualified_variant_name_str, env)?;
+                            #(let #field_names = jni::objects::JValueOwned::from(<#field_types as instant_coffee::JavaType>::into_jni(#field_names.into(), env)?);)*
 
                             let args = &[
                                 #(jni::objects::JValue::from(&#field_names)),*
                             ];
 
-                            env.new_object(
-                                #jvm_variant_name_str,
+                            let class = &VARIANT_CLASSES[#variant_index].get(env)?;
+                            let object = env.new_object(
+                                class,
                                 [
                                     "(",
                                     #(<#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE(),)*
@@ -507,34 +1798,43 @@ fn impl_enum_gen(item_enum: ItemEnum) -> Result<TokenStream, syn::Error> {
                                 ].join(""), // Micro-optimization candidate: Use const-cat
                                 args
                             )
-                            .map_err(instant_coffee::jni_util::map_jni_error)
+                            .map_err(instant_coffee::jni_util::map_jni_error)?;
+
+                            // Release field references, leaving only the object itself
+                            #(if let jni::objects::JValueOwned::Object(field) = #field_names {
+                                env.delete_local_ref(field).map_err(instant_coffee::jni_util::map_jni_error)?;
+                            })*
+                            Ok(object)
                         }
                     });
 
                     variant_from_jni_expressions.push(quote! {
-                        if env.is_instance_of(&jni_value, #jvm_variant_name_str).map_err(instant_coffee::jni_util::map_jni_error)? {
-                            return Ok(#name_ident::#variant_ident (#(
+                        let class = &VARIANT_CLASSES[#variant_index].get(env)?;
+                        if env.is_instance_of(&jni_value, class).map_err(instant_coffee::jni_util::map_jni_error)? {
+                            return instant_coffee::jni_util::with_local_frame(#local_capacity, #qualified_variant_name_str, env, |env| Ok(#name_ident::#variant_ident (#(
                                 <#field_types as instant_coffee::JavaType>::from_jni(
                                     <#field_types as instant_coffee::JavaType>::from_jvalue(
-                                        env.get_field(&jni_value, stringify!(#field_names), <#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE())
+                                        env.get_field(&jni_value, #java_field_names, <#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE())
                                             .map_err(instant_coffee::jni_util::map_jni_error)?,
                                         env
                                     )?,
                                     env
-                                )?
-                            ),*));
+                                )?.into()
+                            ),*)));
                         }
                     })
                 },
                 StructKind::Unit => {
                     variant_into_jni_expressions.push(quote! {
                         #name_ident::#variant_ident => {
-                            env.new_object(#jvm_variant_name_str,"()V",&[]).map_err(instant_coffee::jni_util::map_jni_error)
+                            let class = &VARIANT_CLASSES[#variant_index].get(env)?;
+                            env.new_object(class, "()V", &[]).map_err(instant_coffee::jni_util::map_jni_error)
                         }
                     });
 
                     variant_from_jni_expressions.push(quote! {
-                        if env.is_instance_of(&jni_value, #jvm_variant_name_str).map_err(instant_coffee::jni_util::map_jni_error)? {
+                        let class = &VARIANT_CLASSES[#variant_index].get(env)?;
+                        if env.is_instance_of(&jni_value, class).map_err(instant_coffee::jni_util::map_jni_error)? {
                             return Ok(#name_ident::#variant_ident);
                         }
                     })
@@ -547,8 +1847,13 @@ fn impl_enum_gen(item_enum: ItemEnum) -> Result<TokenStream, syn::Error> {
                 instant_coffee::codegen::JClassDecl::EnumTaggedUnion {
                     name: #enum_name_str,
                     package: #package_name_str,
+                    nested_in: #nested_in,
+                    access: #access,
+                    annotations: #annotations,
+                    imports: #imports,
                     variants: vec![#(#variant_decls),*],
-                    methods: vec![#(#method_decls),*]
+                    methods: #method_decls,
+                    body: #body
                 }
             }
         };
@@ -561,370 +1866,1929 @@ fn impl_enum_gen(item_enum: ItemEnum) -> Result<TokenStream, syn::Error> {
             }
         };
 
+        let unmatched_variant = match &fallback_variant {
+            // Variant subclasses unknown to this library, declared by newer bindings
+            Some(fallback) => quote!(Ok(#name_ident::#fallback)),
+            // If none of the above blocks match and return, somehow none of the variant subclasses match
+            None => quote! {
+                let class_name = instant_coffee::jni_util::obj_classname(&jni_value, env).unwrap_or_else(|_| "[UNKNOWN]".to_string());
+
+                Err(Some(jni::errors::Exception { class: "java/lang/RuntimeException".to_string(), msg: format!("JNI: Could not match {} as Rust Enum: {}", #enum_name_str, class_name)}))
+            }
+        };
         from_jni_impl = quote! {
             fn from_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
+                // IsInstanceOf accepts null for every class, which would match the first variant
+                instant_coffee::jni_util::non_null(&jni_value, #qualified_name_str)?;
                 #(#variant_from_jni_expressions)*
-                // If none of the above blocks match and return, somehow none of the variant subclasses match
-                let class_name = instant_coffee::jni_util::obj_classname(&jni_value, env).unwrap_or("[UNKNOWN]".to_string());
-
-                Err(Some(jni::errors::Exception { class: "java/lang/RuntimeException".to_string(), msg: format!("JNI: Could not match {} as Rust Enum: {}", #enum_name_str, class_name)}))
+                #unmatched_variant
             }
         };
     } else {
+        // The Java enum of non-exhaustive enums has an additional UNKNOWN variant; External enums are declared as-is
+        let has_unknown_variant = non_exhaustive && external.is_none();
         let mut variant_names = Vec::new();
         for variant in &item_enum.variants {
-            let name = variant.ident.to_string();
+            let name = VariantAttributes::parse(&variant.attrs)?.java_name(&variant.ident);
             verify_type_identifier(&name).map_err(|e| syn::Error::new(variant.ident.span(), e))?;
+            if has_unknown_variant && name == "UNKNOWN" {
+                Err(syn::Error::new(variant.ident.span(), "UNKNOWN is reserved for the Java variant of unrecognized values of #[non_exhaustive] enums"))?;
+            }
             variant_names.push(name);
         }
 
         let variant_idents = item_enum.variants.into_iter().map(|variant| variant.ident).collect::<Vec<_>>();
         let ordinals = (0..variant_idents.len()).into_iter().map(|ord| LitInt::new(&format!("{}", ord), proc_macro2::Span::call_site())).collect::<Vec<_>>();
+        let unknown_variant = if has_unknown_variant { quote!("UNKNOWN") } else { proc_macro2::TokenStream::new() };
+        // Variants missing from older bindings convert to UNKNOWN, or are never received from Java if there is a fallback variant
+        if has_unknown_variant {
+            variant_checks.push(quote!(errors.field(&CLASS, "UNKNOWN", #jvm_param_sig_str, true, env);));
+        } else if fallback_variant.is_none() {
+            variant_checks.push(quote!(#(errors.field(&CLASS, #variant_names, #jvm_param_sig_str, true, env);)*));
+        }
+
+        java_names_impl = quote! {
+            impl #impl_generics #name_ident #type_generics #where_clause {
+                /// Name of the Java enum constant of this variant, as returned by its `name()` method
+                pub fn java_name(&self) -> &'static str {
+                    match self {
+                        #(#name_ident::#variant_idents => #variant_names,)*
+                    }
+                }
+
+                /// Variant of the Java enum constant with the given name, as accepted by its `valueOf(String)` method; None for unrecognized names
+                pub fn from_java_name(name: &str) -> Option<Self> {
+                    match name {
+                        #(#variant_names => Some(#name_ident::#variant_idents),)*
+                        _ => None
+                    }
+                }
+            }
+
+            impl #impl_generics instant_coffee::codegen::JavaLiteral for #name_ident #type_generics #where_clause {
+                fn java_literal(&self) -> String {
+                    format!("{}.{}", #qualified_name_str, self.java_name())
+                }
+            }
+        };
 
         class_decl_impl = quote! {
             fn declaration() -> instant_coffee::codegen::JClassDecl {
                 instant_coffee::codegen::JClassDecl::Enum {
-                    name: #enum_name_str,
-                    package: #package_name_str,
-                    variants: vec![#(#variant_names),*],
-                    methods: vec![#(#method_decls),*]
+                    name: #decl_name_str,
+                    package: #decl_package_str,
+                    nested_in: #nested_in,
+                    access: #access,
+                    annotations: #annotations,
+                    imports: #imports,
+                    variants: vec![#(#variant_names,)* #unknown_variant],
+                    methods: #method_decls,
+                    body: #body
                 }
             }
         };
 
-        into_jni_impl = quote! {
-            fn into_jni<'local>(self, env: &mut jni::JNIEnv<'local>) -> Result<jni::objects::JObject<'local>, Option<jni::errors::Exception>> {
-                match self {
-                    #(#name_ident::#variant_idents => {
-                        env.get_static_field(#jvm_class_name_str, #variant_names, #jvm_param_sig_str)
-                            .map_err(instant_coffee::jni_util::map_jni_error)?
-                            .l().map_err(instant_coffee::jni_util::map_jni_error)   // This should never error; All Enum variants are objects
-                    })*
+        into_jni_impl = if has_unknown_variant {
+            // Older bindings may lack variants added since; These convert to UNKNOWN instead
+            quote! {
+                fn into_jni<'local>(self, env: &mut jni::JNIEnv<'local>) -> Result<jni::objects::JObject<'local>, Option<jni::errors::Exception>> {
+                    let class = &CLASS.get(env)?;
+                    let variant = match env.get_static_field(class, self.java_name(), #jvm_param_sig_str) {
+                        Ok(variant) => variant,
+                        Err(_) => {
+                            env.exception_clear().map_err(instant_coffee::jni_util::map_jni_error)?;
+                            env.get_static_field(class, "UNKNOWN", #jvm_param_sig_str)
+                                .map_err(instant_coffee::jni_util::map_jni_error)?
+                        }
+                    };
+                    variant.l().map_err(instant_coffee::jni_util::map_jni_error)   // This should never error; All Enum variants are objects
+                }
+            }
+        } else {
+            quote! {
+                fn into_jni<'local>(self, env: &mut jni::JNIEnv<'local>) -> Result<jni::objects::JObject<'local>, Option<jni::errors::Exception>> {
+                    let class = &CLASS.get(env)?;
+                    match self {
+                        #(#name_ident::#variant_idents => {
+                            env.get_static_field(class, #variant_names, #jvm_param_sig_str)
+                                .map_err(instant_coffee::jni_util::map_jni_error)?
+                                .l().map_err(instant_coffee::jni_util::map_jni_error)   // This should never error; All Enum variants are objects
+                        })*
+                    }
                 }
             }
         };
 
-        from_jni_impl = quote! {
-            fn from_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
-                let ordinal = env.call_method(jni_value, "ordinal", "()I", &[])
-                    .map_err(instant_coffee::jni_util::map_jni_error)?
-                    .i().map_err(instant_coffee::jni_util::map_jni_error)?;   // This shouldn't error; ordinal must return an int
+        from_jni_impl = if fallback_variant.is_some() || external.is_some() {
+            // Variants are matched by name, as ordinals differ between versions of the bindings, or from the rust declaration order for external enums
+            let unmatched_variant = match &fallback_variant {
+                // Unrecognized variants and UNKNOWN
+                Some(fallback) => quote!(Ok(#name_ident::#fallback)),
+                None => quote!(Err(Some(jni::errors::Exception { class: "java/lang/RuntimeException".to_string(), msg: format!("JNI: Could not match {} as Rust Enum: {}", #enum_name_str, name)})))
+            };
+            quote! {
+                fn from_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
+                    instant_coffee::jni_util::non_null(&jni_value, #qualified_name_str)?;
+                    let name = env.call_method(jni_value, "name", "()Ljava/lang/String;", &[])
+                        .map_err(instant_coffee::jni_util::map_jni_error)?
+                        .l().map_err(instant_coffee::jni_util::map_jni_error)?;   // This shouldn't error; name must return a String
+                    let name = <String as instant_coffee::JavaType>::from_jni(jni::objects::JString::from(name), env)?;
+
+                    match #name_ident::from_java_name(&name) {
+                        Some(variant) => Ok(variant),
+                        None => #unmatched_variant
+                    }
+                }
+            }
+        } else {
+            quote! {
+                fn from_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
+                    instant_coffee::jni_util::non_null(&jni_value, #qualified_name_str)?;
+                    let ordinal = env.call_method(jni_value, "ordinal", "()I", &[])
+                        .map_err(instant_coffee::jni_util::map_jni_error)?
+                        .i().map_err(instant_coffee::jni_util::map_jni_error)?;   // This shouldn't error; ordinal must return an int
 
-                match ordinal {
-                    #(#ordinals => Ok(#name_ident::#variant_idents),)*
-                    _ => Err(Some(jni::errors::Exception { class: "java/lang/RuntimeException".to_string(), msg: format!("enum ordinal out of range: {}", ordinal)}))
+                    match ordinal {
+                        #(#ordinals => Ok(#name_ident::#variant_idents),)*
+                        _ => Err(Some(jni::errors::Exception { class: "java/lang/RuntimeException".to_string(), msg: format!("enum ordinal out of range: {}", ordinal)}))
+                    }
                 }
             }
         };
     };
 
+    let variant_count = jvm_variant_names.len();
+    let field_type_assertions = quote_field_type_assertions(&name_ident, &item_enum.generics, &variant_field_types);
     let exp = quote! {
-        impl #impl_generics instant_coffee::codegen::JavaClass for #name_ident #type_generics #where_clause {
-            #class_decl_impl
-        }
+        const _: () = {
+            #field_type_assertions
 
-        impl #impl_generics instant_coffee::JavaType for #name_ident #type_generics #where_clause {
-            type JniType<'local> = jni::objects::JObject<'local>;
-            type ArrayType<'local> = jni::objects::JObjectArray<'local>;
+            static CLASS: instant_coffee::jni_util::CachedClass = instant_coffee::jni_util::CachedClass::new(#jvm_class_name_str);
+            static VARIANT_CLASSES: [instant_coffee::jni_util::CachedClass; #variant_count] = [#(instant_coffee::jni_util::CachedClass::new(#jvm_variant_names)),*];
 
-            fn QUALIFIED_NAME() -> &'static str {#qualified_name_str }
+            #java_names_impl
 
-            fn JVM_PARAM_SIGNATURE() -> &'static str { #jvm_param_sig_str }
+            impl #impl_generics instant_coffee::codegen::JavaClass for #name_ident #type_generics #where_clause {
+                #class_decl_impl
 
-            fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { jni::objects::JObject::null() }
+                fn resolve_classes(env: &mut jni::JNIEnv) {
+                    CLASS.resolve(env);
+                    for class in &VARIANT_CLASSES {
+                        class.resolve(env);
+                    }
+                }
 
-            fn from_jvalue<'local>(jvalue: jni::objects::JValueOwned<'local>, _env: &mut jni::JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<jni::errors::Exception>> {
-                match jvalue {
-                    jni::objects::JValueOwned::Object(obj) => Ok(obj),
-                    _ => Err(Some(jni::errors::Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as instant_coffee::JavaType>::QUALIFIED_NAME()) }))
+                fn verify_schema(env: &mut jni::JNIEnv, errors: &mut instant_coffee::jni_util::SchemaErrors) {
+                    errors.class(&CLASS, env);
+                    #(#variant_checks)*
+                    #(#method_checks)*
                 }
             }
 
-            #into_jni_impl
+            impl #impl_generics instant_coffee::JavaType for #name_ident #type_generics #where_clause {
+                type JniType<'local> = jni::objects::JObject<'local>;
+                type ArrayType<'local> = jni::objects::JObjectArray<'local>;
 
-            #from_jni_impl
-        }
+                fn QUALIFIED_NAME() -> &'static str {#qualified_name_str }
+
+                fn JVM_PARAM_SIGNATURE() -> &'static str { #jvm_param_sig_str }
+
+                fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { jni::objects::JObject::null() }
+
+                fn from_jvalue<'local>(jvalue: jni::objects::JValueOwned<'local>, _env: &mut jni::JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<jni::errors::Exception>> {
+                    match jvalue {
+                        jni::objects::JValueOwned::Object(obj) => Ok(obj),
+                        _ => Err(Some(jni::errors::Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as instant_coffee::JavaType>::QUALIFIED_NAME()) }))
+                    }
+                }
+
+                #into_jni_impl
+
+                #from_jni_impl
+            }
+
+            impl #impl_generics instant_coffee::JavaBorrow for #name_ident #type_generics #where_clause {
+                type Guard = Box<Self>;
+
+                fn borrow_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Box<Self>, Option<jni::errors::Exception>> {
+                    <Self as instant_coffee::JavaType>::from_jni(jni_value, env).map(Box::new)
+                }
+            }
+        };
     };
 
     Ok(exp.into())
 }
 
-#[proc_macro_derive(JavaType)]
+#[proc_macro_derive(JavaType, attributes(java))]
 pub fn java_type(item: TokenStream) -> TokenStream {
-    let class_item = match syn::parse::<ItemStruct>(item.clone()) {
-        Ok(item_struct) => Ok(ClassKind::Struct(item_struct)),
-        Err(struct_err) => match syn::parse::<ItemEnum>(item) {
-            Ok(item_enum) => Ok(ClassKind::Enum(item_enum)),
-            Err(enum_err) => {
-                // Yield whichever error happens further into the file, as that's the variant we probably should be parsing
-                if enum_err.span().unwrap().byte_range().start > struct_err.span().unwrap().byte_range().start {
-                    Err(enum_err)
-                } else {
-                    Err(struct_err)
-                }
-            }
-        }
+    // Parsed as any item, which picks struct or enum by its keyword, and reports errors of that kind of item
+    let class_item = match syn::parse::<Item>(item) {
+        Ok(Item::Struct(item_struct)) => Ok(ClassKind::Struct(item_struct)),
+        Ok(Item::Enum(item_enum)) => Ok(ClassKind::Enum(item_enum)),
+        Ok(other) => Err(syn::Error::new(other.span(), "JavaType can only be derived for structs and enums")),
+        Err(err) => Err(err)
     };
 
     class_item.and_then(ClassKind::generate)
         .unwrap_or_else(|err| err.to_compile_error().into())
 }
 
-fn is_java_attr(attribute: &Attribute) -> bool {
-    match &attribute.meta {
-        Meta::List(list) => {
-            if list.path.is_ident("derive") {
-                list.tokens.to_string() == "JavaType"
-            } else {
-                false
-            }
-        }
-        _ => false,
-    }
+/// How `jmodule` reports types used by exported functions that lack `#[derive(JavaType)]`
+#[derive(Copy, Clone, Eq, PartialEq, Default)]
+enum MissingDerive {
+    Allow,
+    #[default]
+    Warn,
+    Deny,
 }
 
+/// Naming policy for Java method, parameter and field names
+#[derive(Copy, Clone, Eq, PartialEq, Default)]
+enum Naming {
+    /// Names are used as declared in rust
+    #[default]
+    AsDeclared,
+    /// snake_case names are converted to camelCase
+    CamelCase,
+}
 
-#[proc_macro_attribute]
-pub fn jmodule(attribute: TokenStream, item: TokenStream) -> TokenStream {
-    let result: Result<TokenStream, syn::Error> = try {
-        let package_literal = syn::parse::<Lit>(attribute)?;
-        let package_name = if let Lit::Str(str) = &package_literal {
-            let package_name = str.value();
-            verify_package_identifier(&package_name).map_err(|e| syn::Error::new(str.span(), e))?;
-            package_name
-        } else {
-            Err(syn::Error::new(package_literal.span(), "Package name must be a string literal"))?
-        };
+impl Naming {
+    fn parse(value: &LitStr) -> Result<Naming, syn::Error> {
+        Naming::from_name(&value.value()).ok_or_else(|| syn::Error::new(value.span(), "expected \"snake_case\" or \"camelCase\""))
+    }
 
-        let mut item_mod = syn::parse::<ItemMod>(item)?;
+    fn from_name(name: &str) -> Option<Naming> {
+        match name {
+            "snake_case" => Some(Naming::AsDeclared),
+            "camelCase" => Some(Naming::CamelCase),
+            _ => None
+        }
+    }
 
-        if let Some((_, content)) = &mut item_mod.content {
-            let mut classes = Vec::new();
-            let mut method_map = HashMap::new();
-
-            for item in &mut *content {
-                if let Item::Impl(item_impl) = item {
-                    if let Type::Path(type_path) = &*item_impl.self_ty {
-                        for segment in &type_path.path.segments {
-                            match &segment.arguments {
-                                PathArguments::None => {}
-                                PathArguments::AngleBracketed(arg) => {
-                                    Err(syn::Error::new(arg.span(), "generic type impls are not supported"))?;
-                                }
-                                PathArguments::Parenthesized(arg) => {
-                                    Err(syn::Error::new(arg.span(), "function type impls are not supported"))?;
-                                }
-                            }
-                        }
+    /// Java name for a rust name
+    fn apply(self, name: &str) -> String {
+        match self {
+            Naming::AsDeclared => name.to_string(),
+            Naming::CamelCase => {
+                // Leading underscores are kept, as they typically mark the name as unused/internal
+                let trimmed = name.trim_start_matches('_');
+                let mut camel = name[..name.len() - trimmed.len()].to_string();
+                for (idx, word) in trimmed.split('_').filter(|word| !word.is_empty()).enumerate() {
+                    if idx == 0 {
+                        camel.push_str(word);
                     } else {
-                        Err(syn::Error::new(item_impl.self_ty.span(), "unsupported type for impl block"))?;
+                        let mut chars = word.chars();
+                        camel.extend(chars.next().into_iter().flat_map(char::to_uppercase).chain(chars));
                     }
+                }
+                camel
+            }
+        }
+    }
+}
 
-                    let self_type_name = item_impl.self_ty.to_token_stream().to_string();
+/// JSON library whose annotations mark the serialized names of generated fields
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum JsonAnnotations {
+    Jackson,
+    Gson,
+}
 
-                    if item_impl.trait_.is_none() {
-                        let mut used_types = HashSet::new();
-                        let mut used_returns = HashSet::new();
-                        let mut exported_functions = Vec::new();
-                        for item in &mut item_impl.items {
-                            if let ImplItem::Fn(ref mut func) = item {
-                                let is_jni_func = func.sig.abi.as_ref()
-                                    .and_then(|abi| abi.name.as_ref())
-                                    .map(|str| str.value())
-                                    .is_some_and(|abi| abi == "jni");
+impl JsonAnnotations {
+    fn from_name(name: &str) -> Option<JsonAnnotations> {
+        match name {
+            "jackson" => Some(JsonAnnotations::Jackson),
+            "gson" => Some(JsonAnnotations::Gson),
+            _ => None
+        }
+    }
+}
 
-                                if is_jni_func {
-                                    if func.sig.asyncness.is_some() {
-                                        Err(syn::Error::new(func.sig.asyncness.span(), "async functions are unsupported"))?
-                                    }
-                                    if func.sig.constness.is_some() {
-                                        Err(syn::Error::new(func.sig.constness.span(), "const functions are unsupported"))?
-                                    }
-                                    if !func.sig.generics.params.is_empty() {
-                                        Err(syn::Error::new(func.sig.generics.span(), "generic functions are unsupported"))?
+/// Java language version of the generated sources if not configured, matching `instant_coffee::codegen::DEFAULT_JAVA_VERSION`
+const JAVA_VERSION_DEFAULT: u32 = 17;
+
+/// File name of the project configuration, read from the directory of the crate's `Cargo.toml`
+const PROJECT_CONFIG_FILE: &str = "instant-coffee.toml";
+
+/// Project-level configuration, shared by all `jmodule`s of a crate; Attribute options take precedence over it
+///
+/// ```toml
+/// # Package of jmodules which do not specify one, followed by the module name: `com.example.my_module`
+/// package_prefix = "com.example"
+/// # Default naming policy, "snake_case" or "camelCase"
+/// naming = "camelCase"
+/// # Java language version of the generated sources
+/// java_version = 17
+/// # Output directory of the generated sources, relative to the crate root; See `JModuleDecl::write_to_out_dir`
+/// out_dir = "target/java"
+/// # JSON library to annotate fields for, "jackson" or "gson"
+/// json_annotations = "jackson"
+/// ```
+#[derive(Default)]
+struct ProjectConfig {
+    /// Path of the configuration file, if present
+    path: Option<std::path::PathBuf>,
+    package_prefix: Option<String>,
+    naming: Option<Naming>,
+    java_version: Option<u32>,
+    out_dir: Option<String>,
+    generated: Option<Option<String>>,
+    json_annotations: Option<JsonAnnotations>,
+}
+
+impl ProjectConfig {
+    /// Read the configuration of the crate being compiled; Errors are reported at `span`
+    fn read(span: proc_macro2::Span) -> Result<ProjectConfig, syn::Error> {
+        let Some(manifest_dir) = std::env::var_os("CARGO_MANIFEST_DIR") else {
+            return Ok(ProjectConfig::default());
+        };
+        let manifest_dir = std::path::PathBuf::from(manifest_dir);
+        let path = manifest_dir.join(PROJECT_CONFIG_FILE);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(ProjectConfig::default()),
+            Err(e) => Err(syn::Error::new(span, format!("could not read {}: {}", path.display(), e)))?
+        };
+        let error = |message: String| syn::Error::new(span, format!("{}: {}", PROJECT_CONFIG_FILE, message));
+
+        let table = text.parse::<toml::Table>().map_err(|e| error(e.message().to_string()))?;
+        let mut config = ProjectConfig::default();
+        for (key, value) in table {
+            match (key.as_str(), value) {
+                ("package_prefix", toml::Value::String(prefix)) => {
+                    verify_package_identifier(&prefix).map_err(&error)?;
+                    config.package_prefix = Some(prefix);
+                }
+                ("naming", toml::Value::String(naming)) => {
+                    config.naming = Some(Naming::from_name(&naming).ok_or_else(|| error("naming must be \"snake_case\" or \"camelCase\"".to_string()))?);
+                }
+                ("java_version", toml::Value::Integer(version)) => {
+                    config.java_version = Some(u32::try_from(version).ok().filter(|version| *version >= 8).ok_or_else(|| error("java_version must be 8 or higher".to_string()))?);
+                }
+                ("out_dir", toml::Value::String(out_dir)) => {
+                    config.out_dir = Some(manifest_dir.join(out_dir).to_string_lossy().into_owned());
+                }
+                ("generated", toml::Value::Boolean(generated)) => config.generated = generated.then_some(None),
+                ("generated", toml::Value::String(date)) => config.generated = Some(Some(date)),
+                ("generated", _) => Err(error(format!("{} must be a boolean or a date string", key)))?,
+                ("json_annotations", toml::Value::String(library)) => {
+                    config.json_annotations = Some(JsonAnnotations::from_name(&library).ok_or_else(|| error("json_annotations must be \"jackson\" or \"gson\"".to_string()))?);
+                }
+                ("package_prefix" | "naming" | "out_dir" | "json_annotations", _) => Err(error(format!("{} must be a string", key)))?,
+                ("java_version", _) => Err(error(format!("{} must be an integer", key)))?,
+                _ => Err(error(format!("unknown key `{}`", key)))?
+            }
+        }
+        config.path = Some(path);
+        Ok(config)
+    }
+
+    /// Item making the configuration file a dependency of the compilation, so that changes to it cause a rebuild
+    fn track(&self) -> proc_macro2::TokenStream {
+        match &self.path {
+            Some(path) => {
+                let path = path.to_string_lossy();
+                quote!(const _: &[u8] = include_bytes!(#path);)
+            }
+            None => quote!()
+        }
+    }
+}
+
+/// Options of the `jmodule` attribute: `#[jmodule(package = "com.example", option = value, ...)]`
+///
+/// The package may also be given as a leading string literal: `#[jmodule("com.example", option = value, ...)]`
+struct ModuleOptions {
+    /// `package = "com.example"`; Java package of the module, required unless `package_prefix` is configured in `instant-coffee.toml`
+    package: LitStr,
+    /// `load_library = "mylib"`; Load the native library with `System.loadLibrary` from the generated classes
+    load_library: Option<LitStr>,
+    /// `records = true`; Generate Java records for structs
+    records: bool,
+    /// `naming = "snake_case" | "camelCase"`; Naming policy for Java method, parameter and field names, defaults to the configured policy or "snake_case" (as declared)
+    naming: Naming,
+    /// Java language version of the generated sources, from `instant-coffee.toml`
+    java_version: u32,
+    /// Output directory of the generated sources, from `instant-coffee.toml`
+    out_dir: Option<String>,
+    /// `missing_derive = "allow" | "warn" | "deny"`; Report pub types used by exported functions which lack `#[derive(JavaType)]`, defaults to "warn"
+    missing_derive: MissingDerive,
+    /// `generated = true | "2024-05-01"`; Annotate generated classes with `@Generated`, optionally dated. Defaults to the configured value, or no annotation
+    generated: Option<Option<String>>,
+    /// `json_annotations = "jackson" | "gson"`; Annotate fields with the serialized name of the given JSON library, defaults to the configured value, or no annotations
+    json_annotations: Option<JsonAnnotations>,
+    /// `facade = "MyLibrary"`; Write a facade class of that name in the module package, forwarding to every static method of the module
+    facade: Option<LitStr>,
+    /// `on_load = true`; Define the library's `JNI_OnLoad` and `JNI_OnUnload`, calling `jmodule_on_load` and `jmodule_on_unload`. Only one module per library may do so
+    on_load: bool,
+    /// `verify_schema = true`; Verify the loaded Java classes against the native library from `jmodule_on_load`, failing the load with a `LinkageError` on mismatches
+    verify_schema: bool,
+    /// `max_array_length = 100000`; Maximum number of elements of arrays and maps converted from Java by the exported functions of the module. Unlimited by default
+    max_array_length: Option<usize>,
+    /// `max_string_bytes = 1048576`; Maximum length in bytes of strings converted from Java by the exported functions of the module. Unlimited by default
+    max_string_bytes: Option<usize>,
+    /// `max_depth = 64`; Maximum nesting depth of conversions from Java by the exported functions of the module. Defaults to `jni_util::DEFAULT_MAX_CONVERSION_DEPTH`
+    max_depth: Option<usize>,
+}
+
+impl ModuleOptions {
+    /// Parse the `jmodule` attribute of module `module_ident`, with defaults from the project configuration
+    fn parse(input: ParseStream, config: &ProjectConfig, module_ident: &Ident) -> Result<ModuleOptions, syn::Error> {
+        let mut package = None;
+        let mut load_library = None;
+        let mut records = false;
+        let mut naming = config.naming.unwrap_or_default();
+        let mut missing_derive = MissingDerive::default();
+        let mut generated = config.generated.clone();
+        let mut json_annotations = config.json_annotations;
+        let mut facade = None;
+        let mut on_load = false;
+        let mut verify_schema = false;
+        let mut max_array_length = None;
+        let mut max_string_bytes = None;
+        let mut max_depth = None;
+
+        if input.peek(LitStr) {
+            package = Some(input.parse::<LitStr>()?);
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        let options = Punctuated::<Meta, Token![,]>::parse_terminated(input)?;
+        for option in options {
+            let Meta::NameValue(name_value) = &option else {
+                Err(syn::Error::new(option.span(), "expected `option = value`"))?
+            };
+            let Some(name) = name_value.path.get_ident() else {
+                Err(syn::Error::new(name_value.path.span(), "unknown jmodule option"))?
+            };
+            let value = match &name_value.value {
+                Expr::Lit(ExprLit { lit, .. }) => lit,
+                value => Err(syn::Error::new(value.span(), "expected a literal"))?
+            };
+
+            match (name.to_string().as_str(), value) {
+                ("package", Lit::Str(value)) if package.is_none() => package = Some(value.clone()),
+                ("package", Lit::Str(value)) => Err(syn::Error::new(value.span(), "duplicate package"))?,
+                ("load_library", Lit::Str(value)) => load_library = Some(value.clone()),
+                ("facade", Lit::Str(value)) => {
+                    verify_type_identifier(&value.value()).map_err(|e| syn::Error::new(value.span(), e))?;
+                    facade = Some(value.clone());
+                }
+                ("records", Lit::Bool(value)) => records = value.value,
+                ("on_load", Lit::Bool(value)) => on_load = value.value,
+                ("verify_schema", Lit::Bool(value)) => verify_schema = value.value,
+                ("max_array_length" | "max_string_bytes" | "max_depth", Lit::Int(value)) => {
+                    let limit = Some(value.base10_parse::<usize>()?);
+                    match name.to_string().as_str() {
+                        "max_array_length" => max_array_length = limit,
+                        "max_string_bytes" => max_string_bytes = limit,
+                        _ => max_depth = limit,
+                    }
+                }
+                ("generated", Lit::Bool(value)) => generated = value.value.then_some(None),
+                ("generated", Lit::Str(value)) => generated = Some(Some(value.value())),
+                ("naming", Lit::Str(value)) => naming = Naming::parse(value)?,
+                ("json_annotations", Lit::Str(value)) => {
+                    json_annotations = Some(JsonAnnotations::from_name(&value.value()).ok_or_else(|| syn::Error::new(value.span(), "expected \"jackson\" or \"gson\""))?);
+                }
+                ("missing_derive", Lit::Str(value)) => {
+                    missing_derive = match value.value().as_str() {
+                        "allow" => MissingDerive::Allow,
+                        "warn" => MissingDerive::Warn,
+                        "deny" => MissingDerive::Deny,
+                        _ => Err(syn::Error::new(value.span(), "expected \"allow\", \"warn\" or \"deny\""))?
+                    };
+                }
+                ("package" | "load_library" | "naming" | "missing_derive" | "json_annotations" | "facade", value) => Err(syn::Error::new(value.span(), "expected a string literal"))?,
+                ("records" | "on_load" | "verify_schema", value) => Err(syn::Error::new(value.span(), "expected `true` or `false`"))?,
+                ("generated", value) => Err(syn::Error::new(value.span(), "expected `true`, `false` or a date string"))?,
+                ("max_array_length" | "max_string_bytes" | "max_depth", value) => Err(syn::Error::new(value.span(), "expected an integer literal"))?,
+                _ => Err(syn::Error::new(name.span(), "unknown jmodule option"))?
+            }
+        }
+
+        let package = match (package, &config.package_prefix) {
+            (Some(package), _) => package,
+            (None, Some(prefix)) => LitStr::new(&format!("{}.{}", prefix, module_ident), module_ident.span()),
+            (None, None) => return Err(syn::Error::new(proc_macro2::Span::call_site(), "missing package; Expected `#[jmodule(package = \"com.example\")]`, or `package_prefix` in instant-coffee.toml"))
+        };
+        verify_package_identifier(&package.value()).map_err(|e| syn::Error::new(package.span(), e))?;
+
+        let java_version = config.java_version.unwrap_or(JAVA_VERSION_DEFAULT);
+        if records && java_version < 16 {
+            return Err(syn::Error::new(proc_macro2::Span::call_site(), format!("records require Java 16 or higher, but java_version is {} in instant-coffee.toml", java_version)));
+        }
+
+        Ok(ModuleOptions { package, load_library, records, naming, missing_derive, java_version, out_dir: config.out_dir.clone(), generated, json_annotations, facade, on_load, verify_schema, max_array_length, max_string_bytes, max_depth })
+    }
+}
+
+/// Single-identifier type paths within a type, such as `Foo` in `Box<[Foo]>`
+fn collect_type_idents<'t>(ty: &'t Type, idents: &mut Vec<&'t Ident>) {
+    match ty {
+        Type::Path(type_path) if type_path.qself.is_none() => {
+            if let Some(ident) = type_path.path.get_ident() {
+                idents.push(ident);
+            }
+            for segment in &type_path.path.segments {
+                if let PathArguments::AngleBracketed(arguments) = &segment.arguments {
+                    for argument in &arguments.args {
+                        if let GenericArgument::Type(ty) = argument {
+                            collect_type_idents(ty, idents);
+                        }
+                    }
+                }
+            }
+        }
+        Type::Reference(reference) => collect_type_idents(&reference.elem, idents),
+        Type::Slice(slice) => collect_type_idents(&slice.elem, idents),
+        Type::Array(array) => collect_type_idents(&array.elem, idents),
+        Type::Paren(paren) => collect_type_idents(&paren.elem, idents),
+        Type::Group(group) => collect_type_idents(&group.elem, idents),
+        Type::Tuple(tuple) => tuple.elems.iter().for_each(|ty| collect_type_idents(ty, idents)),
+        _ => {}
+    }
+}
+
+/// Non-generic type aliases declared in a module, such as `type UserId = i64;`, keyed by name; Aliases of aliases are resolved to their final target
+fn collect_type_aliases(content: &[Item]) -> HashMap<String, Type> {
+    let mut aliases = content.iter()
+        .filter_map(|item| match item {
+            Item::Type(alias) if alias.generics.params.is_empty() => Some((alias.ident.to_string(), (*alias.ty).clone())),
+            _ => None
+        })
+        .collect::<HashMap<_, _>>();
+
+    // Each pass resolves one level of aliasing; Cyclic aliases are reported by rustc, and are left partially resolved here
+    for _ in 0..aliases.len() {
+        let previous = aliases.clone();
+        let mut replaced = false;
+        for target in aliases.values_mut() {
+            replaced |= resolve_type_aliases(target, &previous);
+        }
+        if !replaced {
+            break;
+        }
+    }
+    aliases
+}
+
+/// Replace uses of type aliases within a type by the aliased types, spanned at the use; Returns whether any alias was replaced
+///
+/// Exports match types by their tokens, such as the self type of an impl block to its derived struct, or `Option<T>` parameters; Aliases would otherwise not be recognized as the type they name
+fn resolve_type_aliases(ty: &mut Type, aliases: &HashMap<String, Type>) -> bool {
+    match ty {
+        Type::Path(type_path) if type_path.qself.is_none() => {
+            if let Some(target) = type_path.path.get_ident().and_then(|ident| aliases.get(&ident.to_string())) {
+                *ty = respan_type(target, ty.span());
+                return true;
+            }
+
+            let mut replaced = false;
+            for segment in &mut type_path.path.segments {
+                if let PathArguments::AngleBracketed(arguments) = &mut segment.arguments {
+                    for argument in &mut arguments.args {
+                        if let GenericArgument::Type(ty) = argument {
+                            replaced |= resolve_type_aliases(ty, aliases);
+                        }
+                    }
+                }
+            }
+            replaced
+        }
+        Type::Reference(reference) => resolve_type_aliases(&mut reference.elem, aliases),
+        Type::Slice(slice) => resolve_type_aliases(&mut slice.elem, aliases),
+        Type::Array(array) => resolve_type_aliases(&mut array.elem, aliases),
+        Type::Paren(paren) => resolve_type_aliases(&mut paren.elem, aliases),
+        Type::Group(group) => resolve_type_aliases(&mut group.elem, aliases),
+        Type::Tuple(tuple) => tuple.elems.iter_mut().fold(false, |replaced, ty| resolve_type_aliases(ty, aliases) | replaced),
+        _ => false
+    }
+}
+
+/// Copy of a type with every token spanned at `span`, so that errors about an aliased type point at the use of the alias
+fn respan_type(ty: &Type, span: proc_macro2::Span) -> Type {
+    fn respan(tokens: proc_macro2::TokenStream, span: proc_macro2::Span) -> proc_macro2::TokenStream {
+        tokens.into_iter()
+            .map(|tree| match tree {
+                proc_macro2::TokenTree::Group(group) => {
+                    let mut respanned = proc_macro2::Group::new(group.delimiter(), respan(group.stream(), span));
+                    respanned.set_span(span);
+                    proc_macro2::TokenTree::Group(respanned)
+                }
+                mut tree => {
+                    tree.set_span(span);
+                    tree
+                }
+            })
+            .collect()
+    }
+
+    syn::parse2(respan(ty.to_token_stream(), span)).unwrap_or_else(|_| ty.clone())
+}
+
+/// Resolve the type aliases declared in a module within its impl blocks, exported function signatures and derived types, see `resolve_type_aliases`
+///
+/// Aliases are transparent to rustc, so the resolved items are unchanged in meaning. Aliases of parent modules are not resolved, as they are not in scope of sub-modules
+fn resolve_module_aliases(content: &mut [Item]) {
+    let aliases = collect_type_aliases(content);
+    if aliases.is_empty() {
+        return;
+    }
+
+    for item in content {
+        match item {
+            Item::Impl(item_impl) => {
+                resolve_type_aliases(&mut item_impl.self_ty, &aliases);
+                for item in &mut item_impl.items {
+                    let ImplItem::Fn(func) = item else { continue };
+                    if !is_jni_fn(&func.sig) {
+                        continue;
+                    }
+                    for input in &mut func.sig.inputs {
+                        if let FnArg::Typed(pat_type) = input {
+                            resolve_type_aliases(&mut pat_type.ty, &aliases);
+                        }
+                    }
+                    if let ReturnType::Type(_, ty) = &mut func.sig.output {
+                        resolve_type_aliases(ty, &aliases);
+                    }
+                }
+            }
+            Item::Struct(s) if s.attrs.iter().any(|attribute| is_java_attr(attribute) || is_java_external_attr(attribute)) => {
+                s.fields.iter_mut().for_each(|field| { resolve_type_aliases(&mut field.ty, &aliases); });
+            }
+            Item::Enum(e) if e.attrs.iter().any(is_java_attr) => {
+                e.variants.iter_mut()
+                    .flat_map(|variant| variant.fields.iter_mut())
+                    .for_each(|field| { resolve_type_aliases(&mut field.ty, &aliases); });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Report pub structs & enums of a module that are used by its exported functions, but do not `#[derive(JavaType)]`
+///
+/// Catches a forgotten derive before the trait-bound errors it causes in the generated code. Warnings are reported through the deprecation lint of marker items added to the module,
+/// as proc macros cannot emit warnings on stable
+fn check_missing_derives(content: &mut Vec<Item>, level: MissingDerive) -> Result<(), syn::Error> {
+    if level == MissingDerive::Allow {
+        return Ok(());
+    }
+
+    let underived = content.iter()
+        .filter_map(|item| match item {
+            Item::Struct(s) if matches!(s.vis, Visibility::Public(_)) && !s.attrs.iter().any(|attribute| is_java_attr(attribute) || is_java_external_attr(attribute)) => Some(&s.ident),
+            Item::Enum(e) if matches!(e.vis, Visibility::Public(_)) && !e.attrs.iter().any(is_java_attr) => Some(&e.ident),
+            _ => None
+        })
+        .map(|ident| (ident.to_string(), ident))
+        .collect::<HashMap<_, _>>();
+
+    let mut reported = HashSet::new();
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    for item in &*content {
+        let Item::Impl(item_impl) = item else { continue };
+        for item in &item_impl.items {
+            let ImplItem::Fn(func) = item else { continue };
+            if !is_jni_fn(&func.sig) {
+                continue;
+            }
+
+            let mut used = Vec::new();
+            collect_type_idents(&item_impl.self_ty, &mut used);
+            for input in &func.sig.inputs {
+                if let FnArg::Typed(pat_type) = input {
+                    collect_type_idents(&pat_type.ty, &mut used);
+                }
+            }
+            if let ReturnType::Type(_, ty) = &func.sig.output {
+                collect_type_idents(ty, &mut used);
+            }
+
+            for used_ident in used {
+                let Some(declared_ident) = underived.get(&used_ident.to_string()) else { continue };
+                if !reported.insert(used_ident.to_string()) {
+                    continue;
+                }
+
+                let message = format!("`{}` is used by exported function `{}`, but does not #[derive(JavaType)]", used_ident, func.sig.ident);
+                match level {
+                    MissingDerive::Deny => errors.push(syn::Error::new(used_ident.span(), message)),
+                    _ => {
+                        let note = format!("{}; Add #[derive(JavaType)] to `{}`, or set `missing_derive = \"allow\"` on the jmodule", message, declared_ident);
+                        let marker = format_ident!("__INSTANT_COFFEE_MISSING_DERIVE_{}", used_ident, span = used_ident.span());
+                        warnings.push(parse_quote_spanned! {used_ident.span()=>
+                            const _: () = {
+                                #[deprecated(note = #note)]
+                                #[allow(non_camel_case_types)]
+                                struct #marker;
+                                let _ = #marker;
+                            };
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    content.extend(warnings);
+    match errors.into_iter().reduce(|mut error, next| { error.combine(next); error }) {
+        Some(error) => Err(error),
+        None => Ok(())
+    }
+}
+
+/// True if the function is declared `extern "jni"`
+fn is_jni_fn(signature: &Signature) -> bool {
+    signature.abi.as_ref()
+        .and_then(|abi| abi.name.as_ref())
+        .is_some_and(|abi| abi.value() == "jni")
+}
+
+/// True for the `jmodule_impl` marker attribute, which `jmodule` removes from the impl blocks it expands
+fn is_jmodule_impl_attr(attribute: &Attribute) -> bool {
+    attribute.path().segments.last().is_some_and(|segment| segment.ident == "jmodule_impl")
+}
+
+/// True if the attribute is `#[java_external(...)]`, which implements [`JavaType`] for a type without generating its class
+fn is_java_external_attr(attribute: &Attribute) -> bool {
+    attribute.path().segments.last().is_some_and(|segment| segment.ident == "java_external")
+}
+
+/// True for `#[cfg(...)]` attributes; `jmodule` copies them onto the items it generates for gated items, as attribute macros receive their input before it is configured
+///
+/// `#[cfg_attr]` is only evaluated for derives of [`JavaType`], see [`java_derive_cfgs`]; Other attributes, such as `#[java(...)]`, should not be gated through it
+fn is_cfg_attr(attribute: &Attribute) -> bool {
+    attribute.path().is_ident("cfg")
+}
+
+/// True if the attribute derives [`JavaType`], as `#[derive(JavaType)]` or conditionally as `#[cfg_attr(predicate, derive(JavaType))]`
+fn is_java_attr(attribute: &Attribute) -> bool {
+    match &attribute.meta {
+        Meta::List(list) if list.path.is_ident("derive") => derives_java_type(list),
+        Meta::List(list) if list.path.is_ident("cfg_attr") => java_derive_predicate(list).is_some(),
+        _ => false,
+    }
+}
+
+/// True if a `derive(...)` list includes [`JavaType`], alone or alongside other derives, by name or by path
+fn derives_java_type(list: &MetaList) -> bool {
+    list.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)
+        .is_ok_and(|paths| paths.iter().any(|path| path.segments.last().is_some_and(|segment| segment.ident == "JavaType")))
+}
+
+/// Configuration predicate under which a `cfg_attr(predicate, ...)` attribute derives [`JavaType`]; Predicates of nested `cfg_attr`s are combined with `all(...)`
+fn java_derive_predicate(list: &MetaList) -> Option<proc_macro2::TokenStream> {
+    let mut metas = list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated).ok()?.into_iter();
+    let predicate = metas.next()?;
+    metas.find_map(|meta| match meta {
+        Meta::List(list) if list.path.is_ident("derive") && derives_java_type(&list) => Some(quote!(#predicate)),
+        Meta::List(list) if list.path.is_ident("cfg_attr") => java_derive_predicate(&list).map(|nested| quote!(all(#predicate, #nested))),
+        _ => None
+    })
+}
+
+/// `#[cfg]` attributes equivalent to the `cfg_attr` derives of [`JavaType`] on an item, gating the items `jmodule` generates for it
+///
+/// Empty if the item derives [`JavaType`] unconditionally
+fn java_derive_cfgs(attrs: &[Attribute]) -> Vec<Attribute> {
+    if attrs.iter().any(|attribute| matches!(&attribute.meta, Meta::List(list) if list.path.is_ident("derive") && derives_java_type(list))) {
+        return Vec::new();
+    }
+    attrs.iter()
+        .filter_map(|attribute| match &attribute.meta {
+            Meta::List(list) if list.path.is_ident("cfg_attr") => java_derive_predicate(list),
+            _ => None
+        })
+        .map(|predicate| parse_quote!(#[cfg(#predicate)]))
+        .collect()
+}
+
+/// Trailing parameters of exported functions which are passed from the JNI call as-is, bypassing conversion; For mixing direct jni-rs calls with converted parameters
+///
+/// They are not part of the Java method. `this` is consumed by the conversion of `&self`, so a `JObject` parameter receives a second local reference to it
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum ContextParam {
+    /// `env: &mut JNIEnv`; The JNI environment of the call
+    Env,
+    /// `this: JObject`; The object a method is called on
+    This,
+    /// `class: JClass`; The class declaring a static native method
+    Class,
+}
+
+impl ContextParam {
+    /// Context parameter of the given type, recognized by the name of the jni-rs type; `&mut JNIEnv`, `JObject`, or `JClass`
+    fn of_type(ty: &Type) -> Option<ContextParam> {
+        // None if by value, otherwise whether the reference is mutable
+        let (ty, reference) = match ty {
+            Type::Reference(reference) => (&*reference.elem, Some(reference.mutability.is_some())),
+            ty => (ty, None)
+        };
+        let Type::Path(type_path) = ty else { return None };
+        match (type_path.path.segments.last()?.ident.to_string().as_str(), reference) {
+            ("JNIEnv", Some(true)) => Some(ContextParam::Env),
+            ("JObject", None) => Some(ContextParam::This),
+            ("JClass", None) => Some(ContextParam::Class),
+            _ => None
+        }
+    }
+
+    /// True if the function argument is a context parameter
+    fn is_context_arg(input: &FnArg) -> bool {
+        matches!(input, FnArg::Typed(input_type) if ContextParam::of_type(&input_type.ty).is_some())
+    }
+}
+
+/// Item type `T` of an `impl Stream<Item = T>` return type
+fn stream_item_type(return_type: &Type) -> Option<&Type> {
+    let Type::ImplTrait(impl_trait) = return_type else { return None };
+    impl_trait.bounds.iter().find_map(|bound| {
+        let TypeParamBound::Trait(trait_bound) = bound else { return None };
+        let segment = trait_bound.path.segments.last()?;
+        if segment.ident != "Stream" {
+            return None;
+        }
+        let PathArguments::AngleBracketed(arguments) = &segment.arguments else { return None };
+        arguments.args.iter().find_map(|argument| match argument {
+            GenericArgument::AssocType(assoc) if assoc.ident == "Item" => Some(&assoc.ty),
+            _ => None
+        })
+    })
+}
+
+/// Type converted for a reference parameter; Its referent, or `Box<[T]>` for unsized slices `&[T]`, which have the same Java array type
+fn referent_type(reference: &TypeReference) -> Type {
+    match &*reference.elem {
+        Type::Slice(slice) => {
+            let elem = &slice.elem;
+            parse_quote_spanned!(reference.elem.span()=> Box<[#elem]>)
+        }
+        elem => elem.clone()
+    }
+}
+
+/// True if the type mentions a type matching `is_match` by its path segment, such as `Array2<f64>` in `Option<Array2<f64>>`
+fn mentions_type(ty: &Type, is_match: &impl Fn(&PathSegment) -> bool) -> bool {
+    match ty {
+        Type::Path(type_path) => type_path.path.segments.iter().any(|segment| {
+            is_match(segment) || match &segment.arguments {
+                PathArguments::AngleBracketed(arguments) => arguments.args.iter().any(|argument| matches!(argument, GenericArgument::Type(ty) if mentions_type(ty, is_match))),
+                _ => false
+            }
+        }),
+        Type::Reference(reference) => mentions_type(&reference.elem, is_match),
+        Type::Slice(slice) => mentions_type(&slice.elem, is_match),
+        Type::Array(array) => mentions_type(&array.elem, is_match),
+        Type::Tuple(tuple) => tuple.elems.iter().any(|elem| mentions_type(elem, is_match)),
+        Type::Paren(paren) => mentions_type(&paren.elem, is_match),
+        Type::Group(group) => mentions_type(&group.elem, is_match),
+        _ => false
+    }
+}
+
+/// Collect the arities of the tuples within a type, such as 2 for `Option<(i32, String)>`; The unit type `()` is not a tuple class
+fn collect_tuple_arities(ty: &Type, arities: &mut BTreeSet<usize>) {
+    match ty {
+        Type::Path(type_path) => type_path.path.segments.iter().for_each(|segment| if let PathArguments::AngleBracketed(arguments) = &segment.arguments {
+            arguments.args.iter().for_each(|argument| if let GenericArgument::Type(ty) = argument { collect_tuple_arities(ty, arities) });
+        }),
+        Type::Tuple(tuple) => {
+            if tuple.elems.len() > 1 {
+                arities.insert(tuple.elems.len());
+            }
+            tuple.elems.iter().for_each(|elem| collect_tuple_arities(elem, arities));
+        }
+        Type::Reference(reference) => collect_tuple_arities(&reference.elem, arities),
+        Type::Slice(slice) => collect_tuple_arities(&slice.elem, arities),
+        Type::Array(array) => collect_tuple_arities(&array.elem, arities),
+        Type::Paren(paren) => collect_tuple_arities(&paren.elem, arities),
+        Type::Group(group) => collect_tuple_arities(&group.elem, arities),
+        _ => {}
+    }
+}
+
+/// Types of the fields of derived types and of the signatures of exported functions among the items; Sub-modules are checked when they are expanded
+fn exported_types(content: &[Item]) -> Vec<&Type> {
+    let mut types = Vec::new();
+    for item in content {
+        match item {
+            Item::Struct(s) if s.attrs.iter().any(is_java_attr) => types.extend(s.fields.iter().map(|field| &field.ty)),
+            Item::Enum(e) if e.attrs.iter().any(is_java_attr) => types.extend(e.variants.iter().flat_map(|variant| &variant.fields).map(|field| &field.ty)),
+            Item::Impl(item_impl) => for item in &item_impl.items {
+                if let ImplItem::Fn(func) = item {
+                    if is_jni_fn(&func.sig) {
+                        types.extend(func.sig.inputs.iter().filter_map(|input| match input {
+                            FnArg::Typed(pat_type) => Some(&*pat_type.ty),
+                            FnArg::Receiver(_) => None
+                        }));
+                        if let ReturnType::Type(_, return_type) = &func.sig.output {
+                            types.push(return_type);
+                        }
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+    types
+}
+
+/// True if fields of derived types or signatures of exported functions among the items mention a type matching `is_match`, see [`mentions_type`]; Sub-modules are checked when they are expanded
+fn items_mention_type(content: &[Item], is_match: &impl Fn(&PathSegment) -> bool) -> bool {
+    exported_types(content).into_iter().any(|ty| mentions_type(ty, is_match))
+}
+
+/// True for `ndarray` arrays mapped to `instantcoffee.NdArray`; `Array<f64, D>`, the `Array0` through `Array6` and `ArrayD` aliases, and `DirectNdArray`
+fn is_nd_array(segment: &PathSegment) -> bool {
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else { return false };
+    let name = segment.ident.to_string();
+    match name.as_str() {
+        "Array" => arguments.args.len() == 2,
+        "ArrayD" | "DirectNdArray" => true,
+        _ => name.strip_prefix("Array").is_some_and(|dimensions| matches!(dimensions, "0" | "1" | "2" | "3" | "4" | "5" | "6")),
+    }
+}
+
+/// True for `image` buffers mapped to `instantcoffee.PixelBuffer`; `RgbaImage`, `ImageBuffer<Rgba<u8>, Vec<u8>>`, and `DirectPixelBuffer`
+fn is_pixel_buffer(segment: &PathSegment) -> bool {
+    match &segment.arguments {
+        PathArguments::None => segment.ident == "RgbaImage" || segment.ident == "DirectPixelBuffer",
+        PathArguments::AngleBracketed(arguments) => segment.ident == "ImageBuffer"
+            && matches!(arguments.args.first(), Some(GenericArgument::Type(Type::Path(pixel))) if pixel.path.segments.last().is_some_and(|segment| segment.ident == "Rgba")),
+        PathArguments::Parenthesized(_) => false
+    }
+}
+
+/// True for boxed closures implemented by `instantcoffee.NativeClosure`; `Box<dyn Fn() -> R>` and `Box<dyn Fn(T) -> R>`, with any further bounds
+fn is_boxed_closure(segment: &PathSegment) -> bool {
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else { return false };
+    segment.ident == "Box" && matches!(arguments.args.first(), Some(GenericArgument::Type(Type::TraitObject(trait_object))) if trait_object.bounds.iter().any(|bound| {
+        matches!(bound, TypeParamBound::Trait(trait_bound) if trait_bound.path.segments.last().is_some_and(|segment| segment.ident == "Fn" && matches!(segment.arguments, PathArguments::Parenthesized(_))))
+    }))
+}
+
+/// True for shared memory regions mapped to `instantcoffee.SharedMemory`; `SharedMemory`
+fn is_shared_memory(segment: &PathSegment) -> bool {
+    matches!(segment.arguments, PathArguments::None) && segment.ident == "SharedMemory"
+}
+
+/// True for arrays transferred in chunks through `instantcoffee.ChunkedArray`; `ChunkedArray<T>`
+fn is_chunked_array(segment: &PathSegment) -> bool {
+    segment.ident == "ChunkedArray" && matches!(&segment.arguments, PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1)
+}
+
+/// True for buffers shared through `instantcoffee.DirectBytes`; `DirectBytes`
+fn is_direct_bytes(segment: &PathSegment) -> bool {
+    matches!(segment.arguments, PathArguments::None) && segment.ident == "DirectBytes"
+}
+
+/// True for results mapped to `instantcoffee.Result`; `JavaResult<T, E>`, which hands errors to Java as values where `Result<T, E>` throws them
+fn is_java_result(segment: &PathSegment) -> bool {
+    segment.ident == "JavaResult" && matches!(&segment.arguments, PathArguments::AngleBracketed(arguments) if arguments.args.len() == 2)
+}
+
+/// `#[java(class = "Config")]` on a `static`, or on a `static ref` of a `lazy_static!` block; Exports the static as a static getter of the class of rust type `Config`
+///
+/// The getter returns a clone of the value, named `get_` followed by the lowercased name of the static before the naming policy is applied. `LazyLock<T>` and `Lazy<T>` statics are returned as `T`
+struct StaticAttributes {
+    /// Rust type in the same module, whose class declares the getter
+    class: Ident,
+}
+
+impl StaticAttributes {
+    /// Parse all `#[java(...)]` attributes of a static; None if it has none, and is not exported
+    fn parse(attributes: &[Attribute]) -> Result<Option<StaticAttributes>, syn::Error> {
+        let mut java_attributes = attributes.iter().filter(|attribute| attribute.path().is_ident("java")).peekable();
+        let Some(first) = java_attributes.peek().copied() else { return Ok(None) };
+
+        let mut class = None;
+        for attribute in java_attributes {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("class") {
+                    class = Some(meta.value()?.parse::<LitStr>()?.parse::<Ident>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown java attribute for statics; expected `class = \"Type\"`"))
+                }
+            })?;
+        }
+        match class {
+            Some(class) => Ok(Some(StaticAttributes { class })),
+            None => Err(syn::Error::new(first.span(), "exported statics require `class = \"Type\"`, naming the type whose class declares the getter")),
+        }
+    }
+
+    /// Inherent impl block of the class type, with the getter of static `ident`
+    ///
+    /// # Arguments
+    ///
+    /// * `item_attrs`: Attributes of the static, whose `#[cfg]` attributes gate the getter alike
+    /// * `ident`: Name of the static
+    /// * `ty`: Declared type of the static
+    /// * `is_lazy_static`: Whether the static is a `lazy_static!` reference, dereferencing to its declared type
+    fn getter_impl(&self, item_attrs: &[Attribute], ident: &Ident, ty: &Type, is_lazy_static: bool) -> Item {
+        let class = &self.class;
+        let getter = Ident::new(&format!("get_{}", ident.to_string().to_lowercase()), ident.span());
+        let cfgs = item_attrs.iter().filter(|attribute| is_cfg_attr(attribute));
+        let (value_type, value) = if is_lazy_static {
+            (ty, quote!(&*#ident))
+        } else if let Some(value_type) = lazy_value_type(ty) {
+            (value_type, quote!(&*#ident))
+        } else {
+            (ty, quote!(&#ident))
+        };
+        parse_quote_spanned! {ident.span()=>
+            #(#cfgs)*
+            impl #class {
+                extern "jni" fn #getter() -> #value_type {
+                    ::std::clone::Clone::clone(#value)
+                }
+            }
+        }
+    }
+}
+
+/// Value type `T` of a lazily initialized `LazyLock<T>` or `Lazy<T>` static
+fn lazy_value_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(type_path) = ty else { return None };
+    let segment = type_path.path.segments.last()?;
+    if segment.ident != "LazyLock" && segment.ident != "Lazy" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else { return None };
+    match arguments.args.first() {
+        Some(GenericArgument::Type(value_type)) => Some(value_type),
+        _ => None
+    }
+}
+
+/// `static ref` item of a `lazy_static!` block
+struct LazyStaticItem {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    ty: Type,
+    expr: Expr,
+}
+
+impl Parse for LazyStaticItem {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![static]>()?;
+        input.parse::<Token![ref]>()?;
+        let ident = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let ty = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let expr = input.parse()?;
+        input.parse::<Token![;]>()?;
+        Ok(LazyStaticItem { attrs, vis, ident, ty, expr })
+    }
+}
+
+impl ToTokens for LazyStaticItem {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        let LazyStaticItem { attrs, vis, ident, ty, expr } = self;
+        tokens.extend(quote!(#(#attrs)* #vis static ref #ident: #ty = #expr;));
+    }
+}
+
+/// Export the `#[java(class = "...")]` statics of a module as static getters, see [`StaticAttributes`]; The getters are added to the module as impl blocks
+fn export_statics(content: &mut Vec<Item>) -> Result<(), syn::Error> {
+    let mut getter_impls = Vec::new();
+    for item in content.iter_mut() {
+        match item {
+            Item::Static(item_static) => {
+                let Some(static_attributes) = StaticAttributes::parse(&item_static.attrs)? else { continue };
+                if let StaticMutability::Mut(mutability) = &item_static.mutability {
+                    Err(syn::Error::new(mutability.span(), "mutable statics cannot be exported"))?
+                }
+                // `#[java(...)]` is not an attribute outside of derives
+                item_static.attrs.retain(|attribute| !attribute.path().is_ident("java"));
+                getter_impls.push(static_attributes.getter_impl(&item_static.attrs, &item_static.ident, &item_static.ty, false));
+            }
+            Item::Macro(item_macro) if item_macro.mac.path.segments.last().is_some_and(|segment| segment.ident == "lazy_static") => {
+                // Blocks that do not parse are left to `lazy_static!` to report
+                let Ok(mut statics) = item_macro.mac.parse_body_with(|input: ParseStream| {
+                    let mut statics = Vec::new();
+                    while !input.is_empty() {
+                        statics.push(input.parse::<LazyStaticItem>()?);
+                    }
+                    Ok(statics)
+                }) else { continue };
+                if !statics.iter().any(|item| item.attrs.iter().any(|attribute| attribute.path().is_ident("java"))) {
+                    continue;
+                }
+
+                for lazy_static in &mut statics {
+                    let Some(static_attributes) = StaticAttributes::parse(&lazy_static.attrs)? else { continue };
+                    lazy_static.attrs.retain(|attribute| !attribute.path().is_ident("java"));
+                    getter_impls.push(static_attributes.getter_impl(&lazy_static.attrs, &lazy_static.ident, &lazy_static.ty, true));
+                }
+                item_macro.mac.tokens = quote!(#(#statics)*);
+            }
+            _ => {}
+        }
+    }
+    content.extend(getter_impls);
+    Ok(())
+}
+
+/// Lifecycle hook calls collected from a jmodule, see [`Lifecycle`]
+#[derive(Default)]
+struct LifecycleHooks {
+    /// Calls of `init, on_load` hooks, run from `jmodule_on_load` in declaration order
+    on_load: Vec<proc_macro2::TokenStream>,
+    /// Calls of `shutdown, on_unload` hooks, run from `jmodule_on_unload` in declaration order
+    on_unload: Vec<proc_macro2::TokenStream>,
+    /// Qualified Java class name of the `init, on_class_load` hook, at most one per module, as a `&'static str` expression with its cfgs
+    on_class_load: Option<(Vec<Attribute>, proc_macro2::TokenStream)>,
+}
+
+/// Verify that a lifecycle hook is a static function without Java parameters or return value, which may only take `&mut JNIEnv`
+fn verify_lifecycle_hook(signature: &Signature) -> Result<(), syn::Error> {
+    for input in &signature.inputs {
+        match input {
+            FnArg::Receiver(receiver) => Err(syn::Error::new(receiver.span(), "lifecycle hooks must be static functions"))?,
+            FnArg::Typed(input_type) if matches!(ContextParam::of_type(&input_type.ty), Some(ContextParam::Env)) => {}
+            FnArg::Typed(input_type) => Err(syn::Error::new(input_type.span(), "lifecycle hooks may only take a `&mut JNIEnv` parameter"))?,
+        }
+    }
+    if let ReturnType::Type(_, return_type) = &signature.output {
+        Err(syn::Error::new(return_type.span(), "lifecycle hooks may not return a value"))?
+    }
+    Ok(())
+}
+
+/// Expand the contents of a jmodule, generating exports for `extern "jni"` functions and attaching context to [`JavaType`] derives
+///
+/// Functions are exported from inherent impl blocks and from impls of non-generic traits; Trait functions are declared on the class of the implementing type, and called through the trait.
+/// Inline sub-modules are expanded recursively, with each sub-module mapping to a sub-package of the same name
+///
+/// # Arguments
+///
+/// * `package_name`: Java package for this module
+/// * `module_path`: Path of this module relative to the jmodule root
+/// * `content`: Items in this module
+/// * `module_cfgs`: `#[cfg]` attributes of this module and its parent modules, for the items generated at the jmodule root
+/// * `declarations`: Output list of `JClassDecl` expressions for the module, such as those of (derived) [`JavaType`] classes, with the `#[cfg]` attributes gating them
+/// * `classes`: Output list of paths to (derived) [`JavaType`] classes, relative to the jmodule root, with the `#[cfg]` attributes gating them
+/// * `hooks`: Output lifecycle hooks run from `jmodule_on_load` and `jmodule_on_unload`
+/// * `options`: Options of the jmodule
+#[allow(clippy::too_many_arguments)] // Module state is threaded through the recursion into submodules
+fn expand_module_content(package_name: &str, module_path: &[Ident], module_cfgs: &[Attribute], content: &mut Vec<Item>, declarations: &mut Vec<(Vec<Attribute>, proc_macro2::TokenStream)>, classes: &mut Vec<(Vec<Attribute>, proc_macro2::TokenStream)>, hooks: &mut LifecycleHooks, exports: &mut Vec<proc_macro2::TokenStream>, options: &ModuleOptions) -> Result<(), syn::Error> {
+    export_statics(content)?;
+    resolve_module_aliases(content);
+    check_missing_derives(content, options.missing_derive)?;
+
+    // Limits of the exports, declared at the jmodule root
+    let root_path = module_path.iter().map(|_| quote!(super::));
+    let module_limits = quote!(self::#(#root_path)*JMODULE_LIMITS);
+
+    // Interop types passed as classes of the `instantcoffee` package, declared once per jmodule
+    let interop_classes = [
+        (is_nd_array as fn(&PathSegment) -> bool, quote!(instant_coffee::interop::nd_array_declaration())),
+        (is_pixel_buffer, quote!(instant_coffee::interop::pixel_buffer_declaration())),
+        (is_java_result, quote!(instant_coffee::interop::java_result_declaration())),
+        (is_direct_bytes, quote!(instant_coffee::interop::direct_bytes_declaration())),
+        (is_chunked_array, quote!(instant_coffee::interop::chunked_array_declaration())),
+        (is_shared_memory, quote!(instant_coffee::interop::shared_memory_declaration())),
+        (is_boxed_closure, quote!(instant_coffee::interop::native_closure_declaration())),
+    ];
+    for (is_match, interop_decl) in interop_classes {
+        if items_mention_type(content, &is_match) && !declarations.iter().any(|(_, decl)| decl.to_string() == interop_decl.to_string()) {
+            declarations.push((Vec::new(), interop_decl));
+        }
+    }
+    // Tuples are passed as `instantcoffee.Tuple2` through `Tuple4`; Other arities are left to fail the `JavaType` bound
+    let mut tuple_arities = BTreeSet::new();
+    exported_types(content).into_iter().for_each(|ty| collect_tuple_arities(ty, &mut tuple_arities));
+    for arity in tuple_arities.into_iter().filter(|arity| (2..=4).contains(arity)) {
+        let tuple_decl = quote!(instant_coffee::interop::tuple_declaration(#arity));
+        if !declarations.iter().any(|(_, decl)| decl.to_string() == tuple_decl.to_string()) {
+            declarations.push((Vec::new(), tuple_decl));
+        }
+    }
+
+    let mut method_map = HashMap::new();
+
+    // JVM binary class names (relative to the package) of derived types, which differ from the rust name for nested classes
+    // Invalid java attributes are ignored here, and reported by the derive macro instead
+    let mut binary_class_names = HashMap::new();
+    // Java records may not declare native methods; Their methods are exported from a nested `Native` class instead
+    // Bean classes are mutable, and are never written as records
+    let mut record_classes = HashSet::new();
+    // Impl blocks exporting trait implementations as Java methods, see `JavaAttributes::object_methods_impl`
+    let mut object_method_impls = Vec::new();
+    // Types deriving `JavaType` through `cfg_attr`; Their exported functions are only generated where the derive is
+    let mut derive_cfgs = HashMap::new();
+    for item in &*content {
+        let (ident, attrs) = match item {
+            Item::Struct(s) if s.attrs.iter().any(is_java_attr) => (&s.ident, &s.attrs),
+            Item::Enum(e) if e.attrs.iter().any(is_java_attr) => (&e.ident, &e.attrs),
+            _ => continue
+        };
+        derive_cfgs.insert(ident.to_string(), java_derive_cfgs(attrs));
+        if let Ok(java_attributes) = JavaAttributes::parse(attrs) {
+            binary_class_names.insert(ident.to_string(), java_attributes.class_path(&ident.to_string()).replace('.', "$"));
+            if options.records && matches!(item, Item::Struct(_)) && !java_attributes.handle && !java_attributes.bean {
+                record_classes.insert(ident.to_string());
+            }
+            object_method_impls.extend(java_attributes.object_methods_impl(ident));
+        }
+    }
+    content.extend(object_method_impls);
+
+    // Inherent impl blocks exporting the functions of trait impls
+    let mut trait_export_impls = Vec::new();
+    // Items are indexed to keep the type assertions of multiple impl blocks for the same type distinct
+    for (item_idx, item) in content.iter_mut().enumerate() {
+        if let Item::Fn(func) = item {
+            if is_jni_fn(&func.sig) {
+                Err(syn::Error::new(func.sig.abi.span(), "extern \"jni\" functions must be associated functions of an impl block"))?;
+            }
+        }
+
+        if let Item::Impl(item_impl) = item {
+            item_impl.attrs.retain(|attribute| !is_jmodule_impl_attr(attribute));
+
+            // Functions of trait impls are exported through an inherent impl block of the same type, as trait impls may not declare other items
+            let exports_functions = item_impl.items.iter().any(|item| matches!(item, ImplItem::Fn(func) if is_jni_fn(&func.sig)));
+            if let (Some((_, trait_path, _)), true) = (&item_impl.trait_, exports_functions) {
+                if !item_impl.generics.params.is_empty() {
+                    Err(syn::Error::new(item_impl.generics.span(), "extern \"jni\" functions are not exported from generic trait impls"))?;
+                }
+                if let Some(segment) = trait_path.segments.iter().find(|segment| !segment.arguments.is_none()) {
+                    Err(syn::Error::new(segment.span(), "extern \"jni\" functions are not exported from impls of generic traits, as Java methods cannot be generic over the trait's parameters"))?;
+                }
+            }
+
+            if let Type::Path(type_path) = &*item_impl.self_ty {
+                for segment in &type_path.path.segments {
+                    match &segment.arguments {
+                        PathArguments::None => {}
+                        PathArguments::AngleBracketed(arg) => {
+                            Err(syn::Error::new(arg.span(), "generic type impls are not supported"))?;
+                        }
+                        PathArguments::Parenthesized(arg) => {
+                            Err(syn::Error::new(arg.span(), "function type impls are not supported"))?;
+                        }
+                    }
+                }
+            } else {
+                Err(syn::Error::new(item_impl.self_ty.span(), "unsupported type for impl block"))?;
+            }
+
+            let self_type_name = item_impl.self_ty.to_token_stream().to_string();
+
+            if item_impl.trait_.is_none() || exports_functions {
+                let impl_cfgs = item_impl.attrs.iter().filter(|attribute| is_cfg_attr(attribute)).cloned().collect::<Vec<_>>();
+                // Functions of trait impls are called through the trait, which inherent functions of the same name would otherwise shadow
+                let fn_owner = match &item_impl.trait_ {
+                    Some((_, trait_path, _)) => quote!(<Self as #trait_path>),
+                    None => quote!(Self),
+                };
+                let mut used_types = HashSet::new();
+                let mut used_returns = HashSet::new();
+                let mut exported_functions = Vec::new();
+                // Export infos of the impl block's functions, see `jmodule_exports`
+                let mut impl_exports = Vec::new();
+                for item in &mut item_impl.items {
+                    if let ImplItem::Fn(ref mut func) = item {
+                        if is_jni_fn(&func.sig) {
+                            if func.sig.asyncness.is_some() {
+                                Err(syn::Error::new(func.sig.asyncness.span(), "async functions are unsupported"))?
+                            }
+                            if func.sig.constness.is_some() {
+                                Err(syn::Error::new(func.sig.constness.span(), "const functions are unsupported"))?
+                            }
+                            if !func.sig.generics.params.is_empty() {
+                                Err(syn::Error::new(func.sig.generics.span(), "generic functions are unsupported"))?
+                            }
+
+                            func.sig.abi.take();
+                            // `#[java(...)]` is not an attribute outside of derives; It is moved to the method list of the derived type
+                            let method_attributes = MethodAttributes::parse(&func.attrs)?;
+                            if method_attributes.overridable && func.sig.receiver().is_none_or(|receiver| receiver.reference.is_none()) {
+                                Err(syn::Error::new(func.sig.ident.span(), "overridable methods must take `&self`"))?
+                            }
+                            if method_attributes.lifecycle.is_some() {
+                                verify_lifecycle_hook(&func.sig)?;
+                            }
+                            let (java_attrs, other_attrs) = std::mem::take(&mut func.attrs).into_iter().partition(|attribute| attribute.path().is_ident("java"));
+                            func.attrs = other_attrs;
+                            // Items generated for a gated function are gated alike; Its types may not exist when it is configured out
+                            let fn_cfgs = func.attrs.iter().filter(|attribute| is_cfg_attr(attribute))
+                                .chain(derive_cfgs.get(&self_type_name).into_iter().flatten())
+                                .cloned()
+                                .collect::<Vec<_>>();
+                            let method_cfgs = impl_cfgs.iter().chain(&fn_cfgs).cloned().collect::<Vec<_>>();
+                            // if none, this function is static
+                            // if some, this function is a non-static method, with the self type and whether self is borrowed
+                            let mut self_type: Option<(Type, bool)> = None;
+
+                            verify_java_identifier(&func.sig.ident.to_string()).map_err(|e| syn::Error::new(func.sig.ident.span(), e))?;
+
+                            let mut inputs = Vec::new();
+                            let mut input_mappers = Vec::new();
+                            // Trailing parameters passed without conversion, see `ContextParam`
+                            let mut context_params = Vec::new();
+                            let mut context_mappers = Vec::new();
+                            for input in &func.sig.inputs {
+                                match input {
+                                    FnArg::Receiver(receiver) => {
+                                        debug_assert!(self_type.is_none(), "duplicate receiver (self) argument?!");
+                                        match (&receiver.reference, &*receiver.ty) {
+                                            (Some(_), Type::Reference(reference)) => {
+                                                if reference.mutability.is_some() {
+                                                    Err(syn::Error::new(receiver.span(), "`&mut self` receivers are unsupported"))?
+                                                }
+                                                self_type = Some(((*reference.elem).clone(), true));
+                                                used_types.insert((fn_cfgs.clone(), (*reference.elem).clone()));
+                                            }
+                                            _ => {
+                                                self_type = Some(((*receiver.ty).clone(), false));
+                                                used_types.insert((fn_cfgs.clone(), (*receiver.ty).clone()));
+                                            }
+                                        }
+                                    }
+                                    FnArg::Typed(input_type) if ContextParam::is_context_arg(input) => {
+                                        if ParamAttributes::parse(&input_type.attrs)?.raw {
+                                            Err(syn::Error::new(input_type.span(), "JNI context parameters are always passed without conversion"))?
+                                        }
+                                        let context_param = ContextParam::of_type(&input_type.ty).unwrap();
+                                        if context_params.contains(&context_param) {
+                                            Err(syn::Error::new(input_type.span(), "duplicate JNI context parameter"))?
+                                        }
+                                        context_params.push(context_param);
+                                        context_mappers.push(match context_param {
+                                            ContextParam::Env => quote!(&mut env),
+                                            ContextParam::This if self_type.is_some() => quote!(raw_self),
+                                            ContextParam::This => Err(syn::Error::new(input_type.ty.span(), "`JObject` parameters receive `this`, static functions may take a `JClass` parameter instead"))?,
+                                            ContextParam::Class if self_type.is_none() => quote!(class),
+                                            ContextParam::Class => Err(syn::Error::new(input_type.ty.span(), "`JClass` parameters receive the class of static functions, methods may take a `JObject` parameter instead"))?,
+                                        });
                                     }
+                                    FnArg::Typed(input_type) => {
+                                        if !context_params.is_empty() {
+                                            Err(syn::Error::new(input_type.span(), "JNI context parameters (`&mut JNIEnv`, `JObject`, `JClass`) must follow all other parameters"))?
+                                        }
+                                        let param_name = match &*input_type.pat {
+                                            Pat::Ident(ident) => {
+                                                verify_java_identifier(&ident.ident.to_string()).map_err(|e| syn::Error::new(ident.span(), e))?;
+
+                                                // Create new ident to redirect Trait-not-implemented errors onto the type's span
+                                                // This de-duplicates trait-not-implemented errors
+                                                Ident::new(&ident.ident.to_string(), input_type.ty.span())
+                                            },
+                                            pattern => {
+                                                Err(syn::Error::new(pattern.span(), "patterns in functions are unsupported"))?
+                                            }
+                                        };
 
-                                    func.sig.abi.take();
-                                    // if none, this function is static
-                                    // if some, this function is a non-static method
-                                    let mut self_type: Option<Type> = None;
-
-                                    verify_java_identifier(&func.sig.ident.to_string()).map_err(|e| syn::Error::new(func.sig.ident.span(), e))?;
-
-                                    let mut inputs = Vec::new();
-                                    let mut input_mappers = Vec::new();
-                                    for input in &func.sig.inputs {
-                                        match input {
-                                            FnArg::Receiver(receiver) => {
-                                                debug_assert!(self_type.is_none(), "duplicate receiver (self) argument?!");
-                                                self_type = Some((*receiver.ty).clone());
-                                                used_types.insert((*receiver.ty).clone());
+                                        // Reference parameters are borrowed, rather than converted by value
+                                        let (i_ty, borrowed) = match &*input_type.ty {
+                                            Type::Reference(reference) => {
+                                                if reference.mutability.is_some() {
+                                                    Err(syn::Error::new(reference.span(), "mutable reference parameters are unsupported"))?
+                                                }
+                                                (referent_type(reference), true)
                                             }
-                                            FnArg::Typed(input_type) => {
-                                                let param_name = match &*input_type.pat {
-                                                    Pat::Ident(ident) => {
-                                                        verify_java_identifier(&ident.ident.to_string()).map_err(|e| syn::Error::new(ident.span(), e))?;
-
-                                                        // Create new ident to redirect Trait-not-implemented errors onto the type's span
-                                                        // This de-duplicates trait-not-implemented errors
-                                                        Ident::new(&ident.ident.to_string(), input_type.ty.span())
-                                                    },
-                                                    pattern => {
-                                                        Err(syn::Error::new(pattern.span(), "patterns in functions are unsupported"))?
-                                                    }
-                                                };
-
-                                                used_types.insert((*input_type.ty).clone());
-                                                let i_ty = &input_type.ty;
-                                                inputs.push(quote!(#param_name: <#i_ty as instant_coffee::JavaType>::JniType<'local>));
-                                                input_mappers.push(quote!(<#i_ty as instant_coffee::JavaType>::from_jni(#param_name, &mut env)?));
+                                            ty => (ty.clone(), false)
+                                        };
+
+                                        used_types.insert((fn_cfgs.clone(), i_ty.clone()));
+                                        inputs.push(quote!(#param_name: <#i_ty as instant_coffee::JavaType>::JniType<'local>));
+                                        if ParamAttributes::parse(&input_type.attrs)?.raw {
+                                            if borrowed {
+                                                Err(syn::Error::new(input_type.ty.span(), "raw parameters are passed by value, as their `JniType`"))?
                                             }
+                                            input_mappers.push(quote!(#param_name));
+                                        } else if borrowed {
+                                            input_mappers.push(quote!(&*<#i_ty as instant_coffee::JavaBorrow>::borrow_jni(#param_name, &mut env)?));
+                                        } else {
+                                            input_mappers.push(quote!(<#i_ty as instant_coffee::JavaType>::from_jni(#param_name, &mut env)?));
                                         }
                                     }
+                                }
+                            }
 
-                                    let output_type = match &func.sig.output {
-                                        ReturnType::Default => {
-                                            let unit_type_with_span: Type = Type::Tuple(TypeTuple { paren_token: Paren(func.sig.span()), elems: Punctuated::new() });
-                                            used_returns.insert(unit_type_with_span.clone());
-                                            unit_type_with_span
-                                        }
-                                        ReturnType::Type(_, return_type) => {
-                                            used_returns.insert((**return_type).clone());
-                                            (**return_type).clone()
-                                        }
+                            // `impl Stream<Item = T>` returns are published to Java as a Flow.Publisher
+                            let stream_item = match &func.sig.output {
+                                ReturnType::Type(_, return_type) => stream_item_type(return_type),
+                                ReturnType::Default => None
+                            };
+
+                            let output_type = match (&func.sig.output, stream_item) {
+                                (_, Some(item_type)) => {
+                                    let publisher_decl = quote!(instant_coffee::interop::stream_publisher_declaration());
+                                    if !declarations.iter().any(|(_, decl)| decl.to_string() == publisher_decl.to_string()) {
+                                        declarations.push((Vec::new(), publisher_decl));
+                                    }
+
+                                    let publisher_type: Type = parse_quote_spanned!(item_type.span()=> instant_coffee::interop::StreamPublisher<#item_type>);
+                                    used_returns.insert((fn_cfgs.clone(), publisher_type.clone()));
+                                    publisher_type
+                                }
+                                (ReturnType::Default, None) => {
+                                    let unit_type_with_span: Type = Type::Tuple(TypeTuple { paren_token: Paren(func.sig.span()), elems: Punctuated::new() });
+                                    used_returns.insert((fn_cfgs.clone(), unit_type_with_span.clone()));
+                                    unit_type_with_span
+                                }
+                                (ReturnType::Type(_, return_type), None) => {
+                                    used_returns.insert((fn_cfgs.clone(), (**return_type).clone()));
+                                    (**return_type).clone()
+                                }
+                            };
+
+                            // Context parameters are not part of the Java method
+                            let mut declared_sig = func.sig.clone();
+                            declared_sig.inputs = declared_sig.inputs.into_iter().filter(|input| !ContextParam::is_context_arg(input)).collect();
+                            // Raw parameters keep their declared type for the Java method, but are received by the function as their `JniType`
+                            for (declared_input, input) in declared_sig.inputs.iter_mut().zip(func.sig.inputs.iter_mut()) {
+                                if let (FnArg::Typed(declared_type), FnArg::Typed(input_type)) = (declared_input, input) {
+                                    declared_type.attrs.retain(|attribute| !attribute.path().is_ident("java"));
+                                    if ParamAttributes::parse(&input_type.attrs)?.raw {
+                                        let i_ty = &input_type.ty;
+                                        input_type.ty = parse_quote_spanned!(i_ty.span()=> <#i_ty as instant_coffee::JavaType>::JniType<'_>);
+                                    }
+                                    input_type.attrs.retain(|attribute| !attribute.path().is_ident("java"));
+                                }
+                            }
+                            if stream_item.is_some() {
+                                declared_sig.output = parse_quote!(-> #output_type);
+                            }
+                            let param_sigs = declared_sig.inputs.iter().filter_map(|input| match input {
+                                FnArg::Receiver(_) => None,
+                                FnArg::Typed(input_type) => {
+                                    let i_ty = match &*input_type.ty {
+                                        Type::Reference(reference) => referent_type(reference),
+                                        ty => ty.clone()
                                     };
+                                    Some(quote!(<#i_ty as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE()))
+                                }
+                            }).collect::<Vec<_>>();
+                            method_map.entry(item_impl.self_ty.clone())
+                                .or_insert(Vec::new())
+                                .push(ExportedMethod { cfgs: method_cfgs.clone(), attributes: java_attrs, signature: declared_sig });
+
+                            let is_record = record_classes.contains(&self_type_name);
+                            let mut binary_class_name = binary_class_names.get(&self_type_name).unwrap_or(&self_type_name).clone();
+                            if is_record {
+                                binary_class_name.push_str("$Native");
+                            }
+                            let export_name = format!(
+                                "Java_{}_{}_{}",
+                                mangle_jni_name(package_name),
+                                mangle_jni_name(&binary_class_name),
+                                mangle_jni_name(&method_attributes.java_name(&func.sig.ident, options.naming))
+                            );
+                            let export_ident = Ident::new(&export_name, func.sig.ident.span());
 
-                                    method_map.entry(item_impl.self_ty.clone())
-                                        .or_insert(Vec::new())
-                                        .push(func.sig.clone());
-
-                                    let export_name = format!(
-                                        "Java_{}_{}_{}",
-                                        package_name.replace('_', "_1").replace('.', "_"),
-                                        self_type_name.replace('_', "_1"),
-                                        func.sig.ident.to_string().replace('_', "_1")
-                                    );
-                                    let export_ident = Ident::new(&export_name, func.sig.ident.span());
-
-                                    let func_ident = func.sig.ident.clone();
-
-                                    let (self_param, self_mapper) = if let Some(self_type) = self_type {
-                                        (
-                                            quote!(obj_self: jni::objects::JObject<'local>),
-                                            quote!(<#self_type as instant_coffee::JavaType>::from_jni(obj_self, &mut env)?,)
-                                        )
+                            // Record methods are static natives of the nested class, receiving `this` as their first parameter
+                            let is_static = self_type.is_none() || is_record;
+                            let this_sig = if self_type.is_some() && is_record {
+                                quote!(<Self as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE(),)
+                            } else {
+                                proc_macro2::TokenStream::new()
+                            };
+                            let export_class = format!("{}.{}", package_name, binary_class_name);
+                            let export_method = method_attributes.java_name(&func.sig.ident, options.naming);
+                            impl_exports.push(quote! {
+                                #(#fn_cfgs)*
+                                exports.push(instant_coffee::codegen::ExportInfo {
+                                    symbol: #export_name,
+                                    class: #export_class,
+                                    method: #export_method,
+                                    signature: ["(", #this_sig #(#param_sigs,)* ")", <#output_type as instant_coffee::JavaReturn>::JVM_PARAM_SIGNATURE()].join(""),
+                                    is_static: #is_static
+                                });
+                            });
+
+                            let func_ident = func.sig.ident.clone();
+
+                            let wrap_output = if stream_item.is_some() {
+                                quote!(let out = instant_coffee::interop::StreamPublisher::new(out);)
+                            } else {
+                                proc_macro2::TokenStream::new()
+                            };
+
+                            let (self_param, self_mapper) = if let Some((self_type, borrowed)) = self_type {
+                                (
+                                    if is_record {
+                                        // Record methods forward to a static native method, which receives `this` as its first parameter
+                                        quote!(_class: jni::objects::JClass<'local>, obj_self: jni::objects::JObject<'local>)
                                     } else {
-                                        (quote!(class: jni::objects::JClass<'local>), TokenStream::new().into())
-                                    };
+                                        quote!(obj_self: jni::objects::JObject<'local>)
+                                    },
+                                    if borrowed {
+                                        quote!(&*<#self_type as instant_coffee::JavaBorrow>::borrow_jni(obj_self, &mut env)?,)
+                                    } else {
+                                        quote!(<#self_type as instant_coffee::JavaType>::from_jni(obj_self, &mut env)?,)
+                                    }
+                                )
+                            } else {
+                                (quote!(class: jni::objects::JClass<'local>), TokenStream::new().into())
+                            };
+
+                            // `this` is consumed by the self conversion, raw `JObject` parameters receive a second local reference to it
+                            let raw_self = if context_params.contains(&ContextParam::This) {
+                                quote!(let raw_self = jni::objects::JObject::from_raw(obj_self.as_raw());)
+                            } else {
+                                proc_macro2::TokenStream::new()
+                            };
+
+                            // Lifecycle hooks run at most once per library load, whether called from Java or from `jmodule_on_load`/`jmodule_on_unload`
+                            let call = match method_attributes.lifecycle {
+                                Some(lifecycle) => {
+                                    let hook_ident = Ident::new(&format!("__jmodule_lifecycle_{}", func_ident), func_ident.span());
+                                    let hook_args = context_params.iter().map(|_| quote!(env));
+                                    exported_functions.push(parse_quote! {
+                                        #(#fn_cfgs)*
+                                        #[doc(hidden)]
+                                        pub fn #hook_ident(env: &mut jni::JNIEnv) {
+                                            static ONCE: instant_coffee::jni_util::LoadOnce = instant_coffee::jni_util::LoadOnce::new();
+                                            ONCE.call_once(|| #fn_owner::#func_ident(#(#hook_args),*));
+                                        }
+                                    });
 
-                                    let export_fn: ImplItemFn = parse_quote! {
-                                        #[no_mangle]
-                                        pub unsafe extern "system" fn #export_ident<'local>(
-                                            mut env: jni::JNIEnv<'local>,
-                                            #self_param,
-                                            #(#inputs,)*
-                                        ) -> <#output_type as instant_coffee::JavaReturn>::JniType<'local> {
-                                            let res: Result<<#output_type as instant_coffee::JavaReturn>::JniType<'local>, Option<jni::errors::Exception>> = try {
-                                                let out = Self::#func_ident(
-                                                    #self_mapper
-                                                    #(#input_mappers),*
-                                                );
-
-                                                <#output_type as instant_coffee::JavaReturn>::into_jni(out, &mut env)?
-                                            };
-                                            match res {
-                                                Ok(out) => out,
-                                                Err(None) => <#output_type as instant_coffee::JavaReturn>::EXCEPTION_NULL(),
-                                                Err(Some(exception)) => {
-                                                    env.throw_new(exception.class, exception.msg)
-                                                        .expect("could not throw exception!");
-                                                    <#output_type as instant_coffee::JavaReturn>::EXCEPTION_NULL()
+                                    let self_ty = &item_impl.self_ty;
+                                    match lifecycle {
+                                        Lifecycle::Init { on_load, on_class_load } => {
+                                            if on_load {
+                                                hooks.on_load.push(quote!(#(#module_cfgs)* #(#method_cfgs)* #(#module_path::)*#self_ty::#hook_ident(&mut env);));
+                                            }
+                                            if on_class_load {
+                                                if options.load_library.is_none() {
+                                                    Err(syn::Error::new(func_ident.span(), "`on_class_load` hooks are called after `System.loadLibrary`, and require the `load_library` module option"))?
                                                 }
+                                                if hooks.on_class_load.is_some() {
+                                                    Err(syn::Error::new(func_ident.span(), "a module may have a single `on_class_load` hook"))?
+                                                }
+                                                let cfgs = module_cfgs.iter().chain(&method_cfgs).cloned().collect();
+                                                hooks.on_class_load = Some((cfgs, quote!(<#(#module_path::)*#self_ty as instant_coffee::JavaType>::QUALIFIED_NAME())));
                                             }
                                         }
-                                    };
+                                        Lifecycle::Shutdown { on_unload: true } => hooks.on_unload.push(quote!(#(#module_cfgs)* #(#method_cfgs)* #(#module_path::)*#self_ty::#hook_ident(&mut env);)),
+                                        Lifecycle::Shutdown { on_unload: false } => {}
+                                    }
+                                    quote!(Self::#hook_ident(&mut env))
+                                }
+                                None => quote! {
+                                    #fn_owner::#func_ident(
+                                        #self_mapper
+                                        #(#input_mappers,)*
+                                        #(#context_mappers),*
+                                    )
+                                }
+                            };
+
+                            let export_fn: ImplItemFn = parse_quote! {
+                                #(#fn_cfgs)*
+                                #[no_mangle]
+                                pub unsafe extern "system" fn #export_ident<'local>(
+                                    mut env: jni::JNIEnv<'local>,
+                                    #self_param,
+                                    #(#inputs,)*
+                                ) -> <#output_type as instant_coffee::JavaReturn>::JniType<'local> {
+                                    #raw_self
+                                    let res: Result<<#output_type as instant_coffee::JavaReturn>::JniType<'local>, Option<jni::errors::Exception>> = instant_coffee::jni_util::with_limits(#module_limits, || {
+                                        let out = #call;
+                                        #wrap_output
 
-                                    exported_functions.push(ImplItem::Fn(export_fn));
+                                        <#output_type as instant_coffee::JavaReturn>::into_jni(out, &mut env)
+                                    });
+                                    match res {
+                                        Ok(out) => out,
+                                        Err(None) => <#output_type as instant_coffee::JavaReturn>::EXCEPTION_NULL(),
+                                        Err(Some(exception)) => {
+                                            env.throw_new(exception.class, exception.msg)
+                                                .expect("could not throw exception!");
+                                            <#output_type as instant_coffee::JavaReturn>::EXCEPTION_NULL()
+                                        }
+                                    }
                                 }
-                            }
+                            };
+
+                            exported_functions.push(ImplItem::Fn(export_fn));
                         }
+                    }
+                }
 
-                        used_returns.retain(|ret_type| !used_types.contains(ret_type));
+                if !impl_exports.is_empty() {
+                    let exports_ident = Ident::new(&format!("__jmodule_exports_{}", item_idx), proc_macro2::Span::call_site());
+                    exported_functions.push(parse_quote! {
+                        #[doc(hidden)]
+                        pub fn #exports_ident(exports: &mut Vec<instant_coffee::codegen::ExportInfo>) {
+                            #(#impl_exports)*
+                        }
+                    });
+                    let self_ty = &item_impl.self_ty;
+                    exports.push(quote!(#(#module_cfgs)* #(#impl_cfgs)* #(#module_path::)*#self_ty::#exports_ident(&mut exports);));
+                }
 
-                        let new = Vec::with_capacity(item_impl.items.len() + exported_functions.len() + used_types.len() + used_returns.len());
-                        let old_items = std::mem::replace(&mut item_impl.items, new);
+                used_returns.retain(|ret_type| !used_types.contains(ret_type));
 
-                        // Bit of a hacky mess, but our type assertions need to be at the top/start of the item list for best errors
-                        // RustC generates less helpful errors for the mangled functions
-                        std::iter::empty::<ImplItem>()
-                            .chain(
-                                used_types.into_iter().enumerate().map(|(idx, used_type)| {
-                                    let ident = Ident::new(&format!("__ASSERT_TYPE_IMPL_JAVATYPE_{}", idx), proc_macro2::Span::call_site());
+                // Generated items of trait impls go into a new inherent impl block, following the trait impl
+                let (target_impl, old_items) = if item_impl.trait_.is_some() {
+                    let self_ty = &item_impl.self_ty;
+                    trait_export_impls.push(parse_quote!(#(#impl_cfgs)* impl #self_ty {}));
+                    let Some(Item::Impl(export_impl)) = trait_export_impls.last_mut() else { unreachable!() };
+                    (export_impl, Vec::new())
+                } else {
+                    let new = Vec::with_capacity(item_impl.items.len() + exported_functions.len() + used_types.len() + used_returns.len());
+                    let old_items = std::mem::replace(&mut item_impl.items, new);
+                    (item_impl, old_items)
+                };
 
-                                    parse_quote!(const #ident: fn() -> &'static str = <#used_type as instant_coffee::JavaType>::QUALIFIED_NAME;)
-                                })
-                            )
-                            .chain(
-                                used_returns.into_iter().enumerate().map(|(idx, used_return)| {
-                                    let ident = Ident::new(&format!("__ASSERT_TYPE_IMPL_JAVARETURN_{}", idx), proc_macro2::Span::call_site());
+                // Bit of a hacky mess, but our type assertions need to be at the top/start of the item list for best errors
+                // RustC generates less helpful errors for the mangled functions
+                target_impl.items.extend(std::iter::empty::<ImplItem>()
+                    .chain(
+                        used_types.into_iter().enumerate().map(|(idx, (cfgs, used_type))| {
+                            let ident = Ident::new(&format!("__ASSERT_TYPE_IMPL_JAVATYPE_{}_{}", item_idx, idx), proc_macro2::Span::call_site());
 
-                                    parse_quote!(const #ident: fn() -> &'static str = <#used_return as instant_coffee::JavaReturn>::QUALIFIED_NAME;)
-                                })
-                            )
-                            .chain(old_items)
-                            .chain(exported_functions)
-                            .collect_into(&mut item_impl.items);
-                    }
-                }
+                            parse_quote!(#(#cfgs)* const #ident: fn() -> &'static str = <#used_type as instant_coffee::JavaType>::QUALIFIED_NAME;)
+                        })
+                    )
+                    .chain(
+                        used_returns.into_iter().enumerate().map(|(idx, (cfgs, used_return))| {
+                            let ident = Ident::new(&format!("__ASSERT_TYPE_IMPL_JAVARETURN_{}_{}", item_idx, idx), proc_macro2::Span::call_site());
+
+                            parse_quote!(#(#cfgs)* const #ident: fn() -> &'static str = <#used_return as instant_coffee::JavaReturn>::QUALIFIED_NAME;)
+                        })
+                    )
+                    .chain(old_items)
+                    .chain(exported_functions));
             }
+        }
+    }
+    content.extend(trait_export_impls);
 
-            let empty_method_vec = Vec::new();
+    let empty_method_vec = Vec::new();
+    let naming_option = match options.naming {
+        Naming::AsDeclared => quote!(),
+        Naming::CamelCase => quote!(, naming = "camelCase"),
+    };
 
-            // Loop again; We need to have all methods collected first, so cannot do a single pass
-            for item in &mut *content {
-                match item {
-                    Item::Struct(s) if s.attrs.iter().any(is_java_attr) => {
-                        let path = Type::Path(TypePath { qself: None, path: Path::from(s.ident.clone()) });
-                        let methods = method_map.get(&path).unwrap_or(&empty_method_vec);
+    // Loop again; We need to have all methods collected first, so cannot do a single pass
+    for item in &mut *content {
+        match item {
+            Item::Struct(s) if s.attrs.iter().any(is_java_attr) => {
+                let path = Type::Path(TypePath { qself: None, path: Path::from(s.ident.clone()) });
+                let methods = method_map.get(&path).unwrap_or(&empty_method_vec);
 
-                        let package_attr: Attribute = parse_quote!(#[instant_coffee::proc_macro::jmodule_package(#package_name)]);
-                        let method_attr: Attribute = parse_quote!(#[instant_coffee::proc_macro::jmodule_methods(#(#methods),*)]);
-                        s.attrs.push(package_attr);
-                        s.attrs.push(method_attr);
-                        classes.push(s.ident.clone());
-                    }
-                    Item::Enum(e) if e.attrs.iter().any(is_java_attr) => {
-                        let path = Type::Path(TypePath { qself: None, path: Path::from(e.ident.clone()) });
-                        let methods = method_map.get(&path).unwrap_or(&empty_method_vec);
+                let package_attr: Attribute = parse_quote!(#[instant_coffee::proc_macro::jmodule_package(#package_name #naming_option)]);
+                let method_attr: Attribute = parse_quote!(#[instant_coffee::proc_macro::jmodule_methods(#(#methods),*)]);
+                s.attrs.push(package_attr);
+                s.attrs.push(method_attr);
+                let ident = &s.ident;
+                let cfgs = module_cfgs.iter().chain(s.attrs.iter().filter(|attribute| is_cfg_attr(attribute))).cloned().chain(java_derive_cfgs(&s.attrs)).collect::<Vec<_>>();
+                declarations.push((cfgs.clone(), quote!(<#(#module_path::)*#ident as instant_coffee::codegen::JavaClass>::declaration())));
+                classes.push((cfgs, quote!(#(#module_path::)*#ident)));
+            }
+            Item::Enum(e) if e.attrs.iter().any(is_java_attr) => {
+                let path = Type::Path(TypePath { qself: None, path: Path::from(e.ident.clone()) });
+                let methods = method_map.get(&path).unwrap_or(&empty_method_vec);
 
-                        let package_attr: Attribute = parse_quote!(#[instant_coffee::proc_macro::jmodule_package(#package_name)]);
-                        let method_attr: Attribute = parse_quote!(#[instant_coffee::proc_macro::jmodule_methods(#(#methods),*)]);
-                        e.attrs.push(package_attr);
-                        e.attrs.push(method_attr);
-                        classes.push(e.ident.clone());
-                    }
-                    _ => {}
+                let package_attr: Attribute = parse_quote!(#[instant_coffee::proc_macro::jmodule_package(#package_name #naming_option)]);
+                let method_attr: Attribute = parse_quote!(#[instant_coffee::proc_macro::jmodule_methods(#(#methods),*)]);
+                e.attrs.push(package_attr);
+                e.attrs.push(method_attr);
+                let ident = &e.ident;
+                let cfgs = module_cfgs.iter().chain(e.attrs.iter().filter(|attribute| is_cfg_attr(attribute))).cloned().chain(java_derive_cfgs(&e.attrs)).collect::<Vec<_>>();
+                // External enums are declared in Java already, and are only resolved
+                if !JavaAttributes::parse(&e.attrs).is_ok_and(|java_attributes| java_attributes.external.is_some()) {
+                    declarations.push((cfgs.clone(), quote!(<#(#module_path::)*#ident as instant_coffee::codegen::JavaClass>::declaration())));
+                }
+                classes.push((cfgs, quote!(#(#module_path::)*#ident)));
+            }
+            Item::Mod(m) => {
+                // Out-of-line modules (`mod foo;`) cannot be inspected by the macro, and are skipped
+                if let Some((_, sub_content)) = &mut m.content {
+                    verify_java_identifier(&m.ident.to_string()).map_err(|e| syn::Error::new(m.ident.span(), e))?;
+
+                    let sub_package = format!("{}.{}", package_name, m.ident);
+                    let sub_path = module_path.iter().cloned().chain(std::iter::once(m.ident.clone())).collect::<Vec<_>>();
+                    let sub_cfgs = module_cfgs.iter().chain(m.attrs.iter().filter(|attribute| is_cfg_attr(attribute))).cloned().collect::<Vec<_>>();
+                    expand_module_content(&sub_package, &sub_path, &sub_cfgs, sub_content, declarations, classes, hooks, exports, options)?;
                 }
             }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Attribute to export a module as a Java package
+///
+/// Exports the `extern "jni"` functions of the module's impl blocks as native methods of its Java classes, which are declared by `#[derive(JavaType)]`; Inline sub-modules map to sub-packages of the same name.
+/// Also generates the module items `jmodule_decl`, `jmodule_on_load`, `jmodule_on_unload`, `jmodule_verify_schema`, `jmodule_exports` and `JMODULE_LIMITS`, each documented where it is generated
+///
+/// Options are given as `name = "value"` pairs, such as `#[jmodule(package = "com.example", load_library = "mylib", records = true, naming = "camelCase")]`;
+/// The package may also be given as a leading string literal, `#[jmodule("com.example")]`. See `ModuleOptions` for the options, and `MethodAttributes`, `ParamAttributes`, `StaticAttributes` and `Lifecycle` for the `#[java(...)]` attributes of items in the module
+///
+/// Type aliases and `#[cfg]` attributes in the module are honored, see `resolve_module_aliases` and `is_cfg_attr`. With the `preview` feature, each expansion also writes a preview of the module's Java API, see the `preview` module
+#[proc_macro_attribute]
+pub fn jmodule(attribute: TokenStream, item: TokenStream) -> TokenStream {
+    let result: Result<TokenStream, syn::Error> = (|| {
+        let mut item_mod = syn::parse::<ItemMod>(item)?;
+
+        let config = ProjectConfig::read(proc_macro2::Span::call_site())?;
+        let options = (|input: ParseStream| ModuleOptions::parse(input, &config, &item_mod.ident)).parse(attribute)?;
+        let package_literal = &options.package;
+        let package_name = package_literal.value();
+
+        if let Some((_, content)) = &mut item_mod.content {
+            #[cfg(feature = "preview")]
+            preview::write_preview(&options, content);
 
+            let mut declarations = Vec::new();
+            let mut classes = Vec::new();
+            let mut hooks = LifecycleHooks::default();
+            let mut exports = Vec::new();
+            expand_module_content(&package_name, &[], &[], content, &mut declarations, &mut classes, &mut hooks, &mut exports, &options)?;
+            // Classes gated by `#[cfg]` are only declared and resolved if they are compiled
+            let (declaration_cfgs, declarations): (Vec<_>, Vec<_>) = declarations.into_iter().unzip();
+            let (class_cfgs, classes): (Vec<_>, Vec<_>) = classes.into_iter().unzip();
+
+            let load_library = match &options.load_library {
+                Some(library) => quote!(Some(#library)),
+                None => quote!(None)
+            };
+            // Gated hooks are only called if they are compiled
+            let class_load_hook = match &hooks.on_class_load {
+                Some((cfgs, class_name)) => quote!({
+                    #[allow(unused_variables)]
+                    let hook: Option<&'static str> = None;
+                    #(#cfgs)* let hook = Some(#class_name);
+                    hook
+                }),
+                None => quote!(None)
+            };
+            let records = options.records;
+            let java_version = options.java_version;
+            let out_dir = match &options.out_dir {
+                Some(out_dir) => quote!(Some(#out_dir)),
+                None => quote!(None)
+            };
+            let generated = match &options.generated {
+                Some(Some(date)) => quote!(Some(instant_coffee::codegen::GeneratedAnnotation { date: Some(String::from(#date)) })),
+                Some(None) => quote!(Some(instant_coffee::codegen::GeneratedAnnotation { date: None })),
+                None => quote!(None)
+            };
+            let json_annotations = match options.json_annotations {
+                Some(JsonAnnotations::Jackson) => quote!(Some(instant_coffee::codegen::JsonAnnotations::Jackson)),
+                Some(JsonAnnotations::Gson) => quote!(Some(instant_coffee::codegen::JsonAnnotations::Gson)),
+                None => quote!(None)
+            };
+            let facade = match &options.facade {
+                Some(facade) => quote!(Some(#facade)),
+                None => quote!(None)
+            };
             let module_decl: ItemFn = parse_quote! {
                 pub fn jmodule_decl() -> instant_coffee::codegen::JModuleDecl {
                     instant_coffee::codegen::JModuleDecl {
                         name: #package_name,
-                        classes: vec![
-                            #(<#classes as instant_coffee::codegen::JavaClass>::declaration()),*
-                        ]
+                        classes: {
+                            let mut classes = Vec::new();
+                            #(#(#declaration_cfgs)* classes.push(#declarations);)*
+                            classes
+                        },
+                        load_library: #load_library,
+                        class_load_hook: #class_load_hook,
+                        records: #records,
+                        java_version: #java_version,
+                        out_dir: #out_dir,
+                        generated: #generated,
+                        json_annotations: #json_annotations,
+                        facade: #facade
                     }
                 }
             };
             content.push(Item::Fn(module_decl));
 
+            // Classes are resolved while loading the library, as the class loader of the library is then in context; FindClass from other threads may not see it
+            let resolve_classes: ItemFn = parse_quote! {
+                pub fn jmodule_resolve_classes(env: &mut jni::JNIEnv) {
+                    #(#(#class_cfgs)* <#classes as instant_coffee::codegen::JavaClass>::resolve_classes(env);)*
+                }
+            };
+            content.push(Item::Fn(resolve_classes));
+
+            let verify_schema: ItemFn = parse_quote! {
+                /// Check that the loaded Java classes of this module match the native library, listing every missing class, field, constructor or method
+                pub fn jmodule_verify_schema(env: &mut jni::JNIEnv) -> Result<(), String> {
+                    let mut errors = instant_coffee::jni_util::SchemaErrors::new();
+                    #(#(#class_cfgs)* <#classes as instant_coffee::codegen::JavaClass>::verify_schema(env, &mut errors);)*
+                    errors.into_result()
+                }
+            };
+            content.push(Item::Fn(verify_schema));
+
+            let module_exports: ItemFn = parse_quote! {
+                /// Native functions exported by this module, with the JNI symbol and Java method of each; For diagnosing an `UnsatisfiedLinkError`, or checking the library against a jar
+                pub fn jmodule_exports() -> Vec<instant_coffee::codegen::ExportInfo> {
+                    let mut exports = Vec::new();
+                    #(#exports)*
+                    #(#(#class_cfgs)* <#classes as instant_coffee::codegen::JavaClass>::exports(&mut exports);)*
+                    exports
+                }
+            };
+            content.push(Item::Fn(module_exports));
+
+            let max_array_length = options.max_array_length.iter();
+            let max_string_bytes = options.max_string_bytes.iter();
+            let max_depth = options.max_depth.iter();
+            content.push(Item::Const(parse_quote! {
+                /// Limits on the size of inputs converted from Java by the exported functions of this module
+                pub const JMODULE_LIMITS: instant_coffee::jni_util::Limits = instant_coffee::jni_util::Limits {
+                    #(max_array_length: #max_array_length,)*
+                    #(max_string_bytes: #max_string_bytes,)*
+                    #(max_depth: #max_depth,)*
+                    ..instant_coffee::jni_util::Limits::DEFAULT
+                };
+            }));
+
+            // A mismatched schema fails `System.loadLibrary` with the pending LinkageError
+            let verify_on_load = if options.verify_schema {
+                quote! {
+                    if let Err(message) = jmodule_verify_schema(&mut env) {
+                        let _ = env.throw_new("java/lang/LinkageError", message);
+                        return jni::sys::JNI_ERR;
+                    }
+                }
+            } else {
+                proc_macro2::TokenStream::new()
+            };
+            let on_load_hooks = &hooks.on_load;
+            let module_on_load: ItemFn = parse_quote! {
+                /// Load-time setup of this module; Caches the module's classes, verifies the schema if enabled, and runs the `on_load` hooks
+                ///
+                /// Called from the generated `JNI_OnLoad` with `on_load = true`, otherwise to be called from the library's own `JNI_OnLoad`, returning its result on errors
+                pub fn jmodule_on_load(vm: &jni::JavaVM) -> jni::sys::jint {
+                    if let Ok(mut env) = vm.get_env() {
+                        jmodule_resolve_classes(&mut env);
+                        #verify_on_load
+                        #(#on_load_hooks)*
+                    }
+                    jni::sys::JNI_VERSION_1_8
+                }
+            };
+            content.push(Item::Fn(module_on_load));
+
+            // Global references must be released before the library is unloaded, so that a reloaded library starts from a clean state
+            let on_unload_hooks = &hooks.on_unload;
+            let module_on_unload: ItemFn = parse_quote! {
+                /// Unload-time teardown of this module; Runs the `on_unload` hooks, then releases the global state of the library
+                ///
+                /// Called from the generated `JNI_OnUnload` with `on_load = true`, otherwise to be called from the library's own `JNI_OnUnload`
+                pub fn jmodule_on_unload(vm: &jni::JavaVM) {
+                    if let Ok(mut env) = vm.get_env() {
+                        #(#on_unload_hooks)*
+                    }
+                    instant_coffee::jni_util::release_global_state();
+                }
+            };
+            content.push(Item::Fn(module_on_unload));
+
+            // A library has a single `JNI_OnLoad`; It is only defined on request, so that libraries may hold several modules, or define their own
+            if options.on_load {
+                let on_load: ItemFn = parse_quote! {
+                    #[no_mangle]
+                    pub extern "system" fn JNI_OnLoad(vm: jni::JavaVM, _reserved: *mut std::ffi::c_void) -> jni::sys::jint {
+                        jmodule_on_load(&vm)
+                    }
+                };
+                content.push(Item::Fn(on_load));
+
+                let on_unload: ItemFn = parse_quote! {
+                    #[no_mangle]
+                    pub extern "system" fn JNI_OnUnload(vm: jni::JavaVM, _reserved: *mut std::ffi::c_void) {
+                        jmodule_on_unload(&vm)
+                    }
+                };
+                content.push(Item::Fn(on_unload));
+            }
+
+            let track_config = config.track();
+            content.push(Item::Verbatim(track_config));
+
             #[cfg(feature = "codegen-ffi")]
             {
-                let module_decl_ident = Ident::new(&format!("jmodule_export_{}", package_name.replace('.', "_")), package_literal.span());
+                // Namespaced by crate, see `instant_coffee::codegen::ffi_export_symbol`
                 let module_decl_ffi: ItemFn = parse_quote! {
-                    #[no_mangle]
-                    pub extern "system" fn #module_decl_ident() -> instant_coffee::codegen::FFIJarBlob {
+                    #[export_name = instant_coffee::ffi_export_symbol!(#package_literal)]
+                    pub extern "system" fn jmodule_export() -> instant_coffee::codegen::FFIJarBlob {
                         jmodule_decl().as_ffi_blob()
                     }
                 };
@@ -934,9 +3798,429 @@ pub fn jmodule(attribute: TokenStream, item: TokenStream) -> TokenStream {
             Err(syn::Error::new(item_mod.span(), "jmodule attribute must be used on an inline module"))?;
         }
 
-        item_mod.into_token_stream().into()
+        Ok(item_mod.into_token_stream().into())
+    })();
+
+    result.unwrap_or_else(|err| err.into_compile_error().into())
+}
+
+/// Marker for impl blocks exporting `extern "jni"` functions
+///
+/// `jmodule` removes this attribute from the impl blocks it expands, so it is only expanded on impl blocks outside of a `jmodule`, such as those in out-of-line modules (`mod foo;`), where it reports each `extern "jni"` function as not exported
+#[proc_macro_attribute]
+pub fn jmodule_impl(_attribute: TokenStream, item: TokenStream) -> TokenStream {
+    let mut item_impl = match syn::parse::<ItemImpl>(item) {
+        Ok(item_impl) => item_impl,
+        Err(err) => return err.into_compile_error().into()
     };
 
+    let mut errors = Vec::new();
+    for item in &mut item_impl.items {
+        if let ImplItem::Fn(func) = item {
+            if is_jni_fn(&func.sig) {
+                errors.push(syn::Error::new(func.sig.abi.span(), "extern \"jni\" functions are only exported from impl blocks within a #[jmodule] module; Out-of-line modules (`mod foo;`) are not expanded by jmodule").into_compile_error());
+                func.sig.abi.take();    // Avoids an additional 'invalid ABI' error
+            }
+        }
+    }
+
+    quote!(#(#errors)* #item_impl).into()
+}
+
+/// Implement [`JavaType`] for a struct whose Java class already exists, such as a class of a third-party Java library: `#[java_external("com.example.Point")]`
+///
+/// No Java source is generated. Fields are mapped by name and type signature, and may be renamed with a naming policy: `#[java_external("com.example.Point", naming = "camelCase")]`.
+/// Conversions into Java allocate the object without running a constructor, and set each field
+///
+/// Unlike derived classes, the class is looked up when first used, rather than while loading the library
+#[proc_macro_attribute]
+pub fn java_external(attribute: TokenStream, item: TokenStream) -> TokenStream {
+    let result: Result<TokenStream, syn::Error> = (|| {
+        let mut item_struct = syn::parse::<ItemStruct>(item)?;
+        let (class, naming) = Parser::parse(|input: ParseStream| {
+            let class = input.parse::<LitStr>()?;
+            let mut naming = Naming::default();
+            if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+                let option = input.parse::<Ident>()?;
+                if option != "naming" {
+                    Err(syn::Error::new(option.span(), "unknown java_external option, expected `naming`"))?;
+                }
+                input.parse::<Token![=]>()?;
+                naming = Naming::parse(&input.parse::<LitStr>()?)?;
+                input.parse::<Option<Token![,]>>()?;
+            }
+            Ok((class, naming))
+        }, attribute)?;
+
+        let qualified_name_str = class.value();
+        verify_package_identifier(&qualified_name_str).map_err(|e| syn::Error::new(class.span(), e))?;
+        if !qualified_name_str.contains('.') {
+            Err(syn::Error::new(class.span(), "external classes must be fully qualified"))?;
+        }
+        let jvm_class_name_str = qualified_name_str.replace('.', "/");
+        let jvm_param_sig_str = format!("L{};", jvm_class_name_str);
+
+        let Fields::Named(fields) = &mut item_struct.fields else {
+            Err(syn::Error::new(item_struct.ident.span(), "fields of external classes are mapped by name; only structs with named fields are supported"))?
+        };
+        let mut field_idents = Vec::new();
+        let mut field_types = Vec::new();
+        let mut java_field_names = Vec::new();
+        for field in &mut fields.named {
+            let field_attributes = FieldAttributes::parse(&field.attrs)?;
+            // There is no derive macro to accept `java` helper attributes
+            field.attrs.retain(|attribute| !attribute.path().is_ident("java"));
+            if field_attributes.json_name.is_some() {
+                Err(syn::Error::new(field.span(), "external classes are not generated, and do not support json_name"))?;
+            }
+            if field_attributes.default.is_some() {
+                Err(syn::Error::new(field.span(), "external classes are not generated, and do not support default"))?;
+            }
+            let r_ty: Type = if field_attributes.bitset {
+                parse_quote_spanned!(field.ty.span()=> instant_coffee::interop::BitSet)
+            } else {
+                field.ty.clone()
+            };
+            let ident = field.ident.clone().expect("named fields have idents");
+            let java_name = naming.apply(&ident.to_string());
+            verify_java_identifier(&java_name).map_err(|e| syn::Error::new(ident.span(), e))?;
+
+            field_idents.push(ident);
+            field_types.push(r_ty);
+            java_field_names.push(java_name);
+        }
+        let local_capacity = field_idents.len() + 1;     // Local references for each field, and the object itself
+
+        let name_ident = &item_struct.ident;
+        let (impl_generics, type_generics, where_clause) = item_struct.generics.split_for_impl();
+        let field_type_assertions = quote_field_type_assertions(name_ident, &item_struct.generics, &field_types);
+
+        Ok(quote! {
+            #item_struct
+
+            const _: () = {
+                #field_type_assertions
+
+                static CLASS: instant_coffee::jni_util::CachedClass = instant_coffee::jni_util::CachedClass::new(#jvm_class_name_str);
+
+                impl #impl_generics instant_coffee::JavaType for #name_ident #type_generics #where_clause {
+                    type JniType<'local> = jni::objects::JObject<'local>;
+                    type ArrayType<'local> = jni::objects::JObjectArray<'local>;
+
+                    fn QUALIFIED_NAME() -> &'static str { #qualified_name_str }
+
+                    fn JVM_PARAM_SIGNATURE() -> &'static str { #jvm_param_sig_str }
+
+                    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { jni::objects::JObject::null() }
+
+                    fn from_jvalue<'local>(jvalue: jni::objects::JValueOwned<'local>, _env: &mut jni::JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<jni::errors::Exception>> {
+                        match jvalue {
+                            jni::objects::JValueOwned::Object(obj) => Ok(obj),
+                            _ => Err(Some(jni::errors::Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as instant_coffee::JavaType>::QUALIFIED_NAME()) }))
+                        }
+                    }
+
+                    fn into_jni<'local>(self, env: &mut jni::JNIEnv<'local>) -> Result<jni::objects::JObject<'local>, Option<jni::errors::Exception>> {
+                        instant_coffee::jni_util::ensure_local_capacity(#local_capacity, #qualified_name_str, env)?;
+                        let class = &CLASS.get(env)?;
+                        let object = env.alloc_object(class)
+                            .map_err(instant_coffee::jni_util::map_jni_error)?;
+
+                        #(
+                            let field = jni::objects::JValueOwned::from(<#field_types as instant_coffee::JavaType>::into_jni(self.#field_idents.into(), env)?);
+                            env.set_field(&object, #java_field_names, <#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE(), jni::objects::JValue::from(&field))
+                                .map_err(instant_coffee::jni_util::map_jni_error)?;
+                            if let jni::objects::JValueOwned::Object(field) = field {
+                                env.delete_local_ref(field).map_err(instant_coffee::jni_util::map_jni_error)?;
+                            }
+                        )*
+                        Ok(object)
+                    }
+
+                    fn from_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
+                        instant_coffee::jni_util::non_null(&jni_value, #qualified_name_str)?;
+                        instant_coffee::jni_util::with_local_frame(#local_capacity, #qualified_name_str, env, |env| Ok(Self {#(
+                            #field_idents: <#field_types as instant_coffee::JavaType>::from_jni(
+                                <#field_types as instant_coffee::JavaType>::from_jvalue(
+                                    env.get_field(&jni_value, #java_field_names, <#field_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE())
+                                        .map_err(instant_coffee::jni_util::map_jni_error)?,
+                                    env
+                                )?,
+                                env
+                            )?.into()
+                        ),*}))
+                    }
+                }
+
+                impl #impl_generics instant_coffee::JavaBorrow for #name_ident #type_generics #where_clause {
+                    type Guard = Box<Self>;
+
+                    fn borrow_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Box<Self>, Option<jni::errors::Exception>> {
+                        <Self as instant_coffee::JavaType>::from_jni(jni_value, env).map(Box::new)
+                    }
+                }
+            };
+        }.into())
+    })();
+
+    result.unwrap_or_else(|err| err.into_compile_error().into())
+}
+
+/// Class declared in [`jimport!`]: `pub class ArrayList = "java.util.ArrayList" { ... }`
+struct ImportedClass {
+    attributes: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    class: LitStr,
+    members: Vec<ImportedMember>,
+}
+
+/// Constructor or method of an [`ImportedClass`]
+struct ImportedMember {
+    attributes: Vec<Attribute>,
+    kind: ImportedMemberKind,
+    signature: Signature,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum ImportedMemberKind {
+    /// `constructor fn new(...);`
+    Constructor,
+    /// `static fn name(...) -> T;`
+    Static,
+    /// `fn name(&self, ...) -> T;`
+    Instance,
+}
+
+impl Parse for ImportedClass {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attributes = input.call(Attribute::parse_outer)?;
+        let vis = input.parse::<Visibility>()?;
+        let keyword = input.parse::<Ident>()?;
+        if keyword != "class" {
+            Err(syn::Error::new(keyword.span(), "expected `class`"))?;
+        }
+        let ident = input.parse::<Ident>()?;
+        input.parse::<Token![=]>()?;
+        let class = input.parse::<LitStr>()?;
+        verify_package_identifier(&class.value()).map_err(|e| syn::Error::new(class.span(), e))?;
+        if !class.value().contains('.') {
+            Err(syn::Error::new(class.span(), "imported classes must be fully qualified"))?;
+        }
+
+        let content;
+        syn::braced!(content in input);
+        let mut members = Vec::new();
+        while !content.is_empty() {
+            members.push(content.parse::<ImportedMember>()?);
+        }
+        Ok(ImportedClass { attributes, vis, ident, class, members })
+    }
+}
+
+impl Parse for ImportedMember {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attributes = input.call(Attribute::parse_outer)?;
+        let kind = if input.parse::<Option<Token![static]>>()?.is_some() {
+            ImportedMemberKind::Static
+        } else if input.peek(Ident) && !input.peek(Token![fn]) {
+            let keyword = input.parse::<Ident>()?;
+            if keyword != "constructor" {
+                Err(syn::Error::new(keyword.span(), "expected `fn`, `static fn` or `constructor fn`"))?;
+            }
+            ImportedMemberKind::Constructor
+        } else {
+            ImportedMemberKind::Instance
+        };
+        let signature = input.parse::<Signature>()?;
+        input.parse::<Token![;]>()?;
+
+        match (kind, signature.receiver()) {
+            (ImportedMemberKind::Instance, Some(receiver)) if receiver.reference.is_some() && receiver.mutability.is_none() => {}
+            (ImportedMemberKind::Instance, _) => Err(syn::Error::new(signature.ident.span(), "instance methods must take `&self`; Static methods are declared `static fn`"))?,
+            (_, Some(receiver)) => Err(syn::Error::new(receiver.span(), "static methods and constructors cannot take `self`"))?,
+            (_, None) => {}
+        }
+        if kind == ImportedMemberKind::Constructor && signature.output != ReturnType::Default {
+            Err(syn::Error::new(signature.output.span(), "constructors return the imported class, and cannot declare a return type"))?;
+        }
+        if !signature.generics.params.is_empty() || signature.asyncness.is_some() || signature.abi.is_some() {
+            Err(syn::Error::new(signature.span(), "imported methods cannot be generic, async or extern"))?;
+        }
+        if kind != ImportedMemberKind::Constructor {
+            verify_java_identifier(&signature.ident.to_string()).map_err(|e| syn::Error::new(signature.ident.span(), e))?;
+        }
+        Ok(ImportedMember { attributes, kind, signature })
+    }
+}
+
+impl ImportedClass {
+    fn expand(self) -> Result<proc_macro2::TokenStream, syn::Error> {
+        let ImportedClass { attributes, vis, ident, class, members } = self;
+        let qualified_name_str = class.value();
+        let jvm_class_name_str = qualified_name_str.replace('.', "/");
+        let jvm_param_sig_str = format!("L{};", jvm_class_name_str);
+
+        let mut functions = Vec::new();
+        for ImportedMember { attributes, kind, signature } in members {
+            let mut param_idents = Vec::new();
+            let mut param_types = Vec::new();
+            for input in &signature.inputs {
+                let FnArg::Typed(pat_type) = input else { continue };
+                let Pat::Ident(param) = &*pat_type.pat else {
+                    Err(syn::Error::new(pat_type.pat.span(), "parameters of imported methods must be identifiers"))?
+                };
+                if let Type::Reference(reference) = &*pat_type.ty {
+                    Err(syn::Error::new(reference.span(), "parameters of imported methods are passed by value"))?;
+                }
+                param_idents.push(param.ident.clone());
+                param_types.push((*pat_type.ty).clone());
+            }
+
+            let method_ident = &signature.ident;
+            let java_name = method_ident.to_string();
+            let return_type = match &signature.output {
+                ReturnType::Type(_, return_type) if **return_type != parse_quote!(()) => Some(&**return_type),
+                _ => None
+            };
+            let (output_type, return_sig, convert_result) = match (kind, return_type) {
+                (ImportedMemberKind::Constructor, _) => (quote!(Self), quote!("V"), quote!(<Self as instant_coffee::JavaType>::from_jni(result, env))),
+                (_, Some(return_type)) => (
+                    quote!(#return_type),
+                    quote!(<#return_type as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE()),
+                    quote!(<#return_type as instant_coffee::JavaType>::from_jni(<#return_type as instant_coffee::JavaType>::from_jvalue(result, env)?, env))
+                ),
+                (_, None) => (quote!(()), quote!("V"), quote!({ let _ = result; Ok(()) }))
+            };
+            let self_param = match kind {
+                ImportedMemberKind::Instance => quote!(&self,),
+                _ => proc_macro2::TokenStream::new()
+            };
+            let call = match kind {
+                ImportedMemberKind::Constructor => quote!(let class = &CLASS.get(env)?; env.new_object(class, signature, args)),
+                ImportedMemberKind::Static => quote!(let class = &CLASS.get(env)?; env.call_static_method(class, #java_name, signature, args)),
+                ImportedMemberKind::Instance => quote!(env.call_method(self.object.as_obj(), #java_name, signature, args)),
+            };
+            let local_capacity = param_idents.len() + 1;    // Local references for each argument, and the result
+
+            functions.push(quote_spanned! {method_ident.span()=>
+                #(#attributes)*
+                #[allow(non_snake_case)]
+                pub fn #method_ident<'local>(#self_param env: &mut jni::JNIEnv<'local>, #(#param_idents: #param_types),*) -> Result<#output_type, Option<jni::errors::Exception>> {
+                    instant_coffee::jni_util::ensure_local_capacity(#local_capacity, #qualified_name_str, env)?;
+                    #(let #param_idents = jni::objects::JValueOwned::from(<#param_types as instant_coffee::JavaType>::into_jni(#param_idents, env)?);)*
+                    let args = &[#(jni::objects::JValue::from(&#param_idents)),*];
+                    let signature = [
+                        "(",
+                        #(<#param_types as instant_coffee::JavaType>::JVM_PARAM_SIGNATURE(),)*
+                        ")",
+                        #return_sig
+                    ].join("");
+
+                    let result = { #call }.map_err(instant_coffee::jni_util::map_jni_error)?;
+                    // Release argument references; Only the result is still referenced
+                    #(if let jni::objects::JValueOwned::Object(argument) = #param_idents {
+                        env.delete_local_ref(argument).map_err(instant_coffee::jni_util::map_jni_error)?;
+                    })*
+                    #convert_result
+                }
+            });
+        }
+
+        Ok(quote! {
+            #(#attributes)*
+            #[derive(Clone)]
+            #vis struct #ident {
+                object: jni::objects::GlobalRef,
+            }
+
+            const _: () = {
+                static CLASS: instant_coffee::jni_util::CachedClass = instant_coffee::jni_util::CachedClass::new(#jvm_class_name_str);
+
+                impl #ident {
+                    /// The Java object
+                    pub fn as_obj(&self) -> &jni::objects::JObject<'static> {
+                        self.object.as_obj()
+                    }
+
+                    #(#functions)*
+                }
+
+                impl instant_coffee::JavaType for #ident {
+                    type JniType<'local> = jni::objects::JObject<'local>;
+                    type ArrayType<'local> = jni::objects::JObjectArray<'local>;
+
+                    fn QUALIFIED_NAME() -> &'static str { #qualified_name_str }
+
+                    fn JVM_PARAM_SIGNATURE() -> &'static str { #jvm_param_sig_str }
+
+                    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { jni::objects::JObject::null() }
+
+                    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Self, Option<jni::errors::Exception>> {
+                        instant_coffee::jni_util::non_null(&jni_value, #qualified_name_str)?;
+                        Ok(#ident { object: env.new_global_ref(jni_value).map_err(instant_coffee::jni_util::map_jni_error)? })
+                    }
+
+                    fn into_jni<'local>(self, env: &mut jni::JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<jni::errors::Exception>> {
+                        env.new_local_ref(self.object).map_err(instant_coffee::jni_util::map_jni_error)
+                    }
+
+                    fn from_jvalue<'local>(jvalue: jni::objects::JValueOwned<'local>, _env: &mut jni::JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<jni::errors::Exception>> {
+                        match jvalue {
+                            jni::objects::JValueOwned::Object(obj) => Ok(obj),
+                            _ => Err(Some(jni::errors::Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), #qualified_name_str) }))
+                        }
+                    }
+                }
+
+                impl instant_coffee::JavaBorrow for #ident {
+                    type Guard = Box<Self>;
+
+                    fn borrow_jni<'local>(jni_value: jni::objects::JObject<'local>, env: &mut jni::JNIEnv<'local>) -> Result<Box<Self>, Option<jni::errors::Exception>> {
+                        <Self as instant_coffee::JavaType>::from_jni(jni_value, env).map(Box::new)
+                    }
+                }
+            };
+        })
+    }
+}
+
+/// Generate rust wrappers for calling existing Java classes; The inverse of exporting rust functions with [`jmodule`]
+///
+/// Each class is a rust struct holding a global reference to the Java object, which implements [`JavaType`] and may be used in exported functions. Methods take the JNI Env, and convert their arguments and result as exported functions do
+///
+/// ```ignore
+/// jimport! {
+///     pub class ArrayList = "java.util.ArrayList" {
+///         constructor fn new();
+///         fn size(&self) -> i32;
+///         fn add(&self, element: Erased<String>) -> bool;  // Generic parameters are erased to java.lang.Object
+///     }
+///
+///     pub class Integer = "java.lang.Integer" {
+///         static fn toHexString(value: i32) -> String;
+///     }
+/// }
+/// ```
+///
+/// Parameter and result types must match the Java method descriptor; See `instant_coffee::interop::Erased` for generic methods. Java method names are used as declared. Null results convert as they do for exported functions; Declare `Option<T>` results for methods that may return null
+#[proc_macro]
+pub fn jimport(input: TokenStream) -> TokenStream {
+    let result: Result<TokenStream, syn::Error> = (|| {
+        let classes = Parser::parse(|input: ParseStream| {
+            let mut classes = Vec::new();
+            while !input.is_empty() {
+                classes.push(input.parse::<ImportedClass>()?);
+            }
+            Ok(classes)
+        }, input)?;
+
+        let expanded = classes.into_iter()
+            .map(ImportedClass::expand)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(quote!(#(#expanded)*).into())
+    })();
+
     result.unwrap_or_else(|err| err.into_compile_error().into())
 }
 
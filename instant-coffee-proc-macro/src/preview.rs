@@ -0,0 +1,271 @@
+//! Preview of the Java API of a `jmodule`, written while the macro expands; Enabled by the `preview` feature
+//!
+//! The preview is derived from the rust source alone, as Java type names are only resolved through `JavaType` once the crate is compiled.
+//! Derived types are named by their rust name, primitives, strings, arrays, options, maps and tuples by their Java equivalent, and other types by their rust name.
+//! Classes are written as declared, without nesting, conversion code or library loading, and items gated by `#[cfg]` are previewed whether or not they are compiled.
+//! The sources written by `JModuleDecl` remain authoritative
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use syn::{Fields, FnArg, GenericArgument, ImplItem, Item, ItemEnum, ItemImpl, ItemStruct, Pat, PathArguments, ReturnType, Type};
+use crate::{is_java_attr, is_jni_fn, resolve_module_aliases, ContextParam, JavaAttributes, MethodAttributes, ModuleOptions};
+
+/// Directory of the preview, within the target directory of the crate being compiled
+const PREVIEW_DIR: &str = "instant-coffee-preview";
+
+/// Class of the preview, as its declaration line and body lines
+#[derive(Default)]
+struct PreviewClass {
+    /// Declaration up to the opening brace, such as `public final class Point`; None if only the class's exported methods are declared in this module
+    declaration: Option<String>,
+    /// Fields, enum constants and nested classes, one per line
+    members: Vec<String>,
+    /// Exported native methods, one per line
+    methods: Vec<String>,
+}
+
+/// Write the preview of the module's classes to `target/instant-coffee-preview/`, replacing the previous preview of its packages
+///
+/// The preview is a debugging aid; Errors writing it are ignored, and never fail the build
+pub(crate) fn write_preview(options: &ModuleOptions, content: &[Item]) {
+    let Some(root) = preview_root() else {
+        return;
+    };
+    let mut packages = BTreeMap::new();
+    collect_package(&options.package.value(), content, options, &mut packages);
+    for (package, classes) in packages {
+        let _ = write_package(&root, &package, &classes);
+    }
+}
+
+/// Preview directory in the target directory; `CARGO_TARGET_DIR` if set, otherwise the nearest `target` directory of the crate or its workspace
+fn preview_root() -> Option<PathBuf> {
+    let target = match std::env::var_os("CARGO_TARGET_DIR") {
+        Some(target) => PathBuf::from(target),
+        None => {
+            let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR")?);
+            // Workspace members share the target directory of the workspace root
+            manifest_dir.ancestors()
+                .find(|dir| dir.join("target").is_dir())
+                .unwrap_or(&manifest_dir)
+                .join("target")
+        }
+    };
+    Some(target.join(PREVIEW_DIR))
+}
+
+/// Collect the classes of a module and its submodules, by package
+fn collect_package(package: &str, content: &[Item], options: &ModuleOptions, packages: &mut BTreeMap<String, BTreeMap<String, PreviewClass>>) {
+    // Type aliases are previewed as the types they name, as they are exported
+    let mut content = content.to_vec();
+    resolve_module_aliases(&mut content);
+    for item in &content {
+        match item {
+            Item::Struct(item_struct) if item_struct.attrs.iter().any(is_java_attr) => {
+                let class = packages.entry(package.to_string()).or_default().entry(item_struct.ident.to_string()).or_default();
+                preview_struct(item_struct, options, class);
+            }
+            Item::Enum(item_enum) if item_enum.attrs.iter().any(is_java_attr) => {
+                let class = packages.entry(package.to_string()).or_default().entry(item_enum.ident.to_string()).or_default();
+                preview_enum(item_enum, options, class);
+            }
+            Item::Impl(item_impl) => {
+                let Type::Path(self_type) = &*item_impl.self_ty else {
+                    continue;
+                };
+                let Some(self_segment) = self_type.path.segments.last() else {
+                    continue;
+                };
+                let methods = preview_methods(item_impl, options);
+                if !methods.is_empty() {
+                    let class = packages.entry(package.to_string()).or_default().entry(self_segment.ident.to_string()).or_default();
+                    class.methods.extend(methods);
+                }
+            }
+            Item::Mod(item_mod) => {
+                if let Some((_, content)) = &item_mod.content {
+                    collect_package(&format!("{}.{}", package, item_mod.ident), content, options, packages);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn preview_struct(item_struct: &ItemStruct, options: &ModuleOptions, class: &mut PreviewClass) {
+    // Invalid attributes are reported by the derive macro
+    let java_attributes = JavaAttributes::parse(&item_struct.attrs).unwrap_or_default();
+    let name = &item_struct.ident;
+    let fields = preview_fields(&item_struct.fields, options);
+    if java_attributes.handle {
+        class.declaration = Some(format!("public final class {} implements AutoCloseable", name));
+    } else if options.records && !java_attributes.bean {
+        class.declaration = Some(format!("public record {}({})", name, fields.join(", ")));
+    } else {
+        class.declaration = Some(format!("public final class {}", name));
+        class.members.extend(fields.into_iter().map(|field| format!("private {};", field)));
+    }
+}
+
+fn preview_enum(item_enum: &ItemEnum, options: &ModuleOptions, class: &mut PreviewClass) {
+    let name = &item_enum.ident;
+    if item_enum.variants.iter().all(|variant| matches!(variant.fields, Fields::Unit)) {
+        class.declaration = Some(format!("public enum {}", name));
+        let constants = item_enum.variants.iter().map(|variant| variant.ident.to_string()).collect::<Vec<_>>();
+        class.members.push(format!("{};", constants.join(", ")));
+    } else {
+        // Tagged unions are an abstract class, with a nested class per variant
+        class.declaration = Some(format!("public abstract class {}", name));
+        for variant in &item_enum.variants {
+            let fields = preview_fields(&variant.fields, options).into_iter().map(|field| format!(" private {};", field)).collect::<String>();
+            class.members.push(format!("public static final class {} extends {} {{{} }}", variant.ident, name, fields));
+        }
+    }
+}
+
+/// Fields as `type name`; Tuple fields are named by their index
+fn preview_fields(fields: &Fields, options: &ModuleOptions) -> Vec<String> {
+    fields.iter().enumerate().map(|(idx, field)| {
+        let name = match &field.ident {
+            Some(ident) => options.naming.apply(&ident.to_string()),
+            None => format!("_{}", idx),
+        };
+        format!("{} {}", java_type(&field.ty), name)
+    }).collect()
+}
+
+/// Exported functions of an impl block, as native method declarations
+fn preview_methods(item_impl: &ItemImpl, options: &ModuleOptions) -> Vec<String> {
+    item_impl.items.iter()
+        .filter_map(|item| match item {
+            ImplItem::Fn(func) if is_jni_fn(&func.sig) => Some(func),
+            _ => None
+        })
+        .map(|func| {
+            let method_attributes = MethodAttributes::parse(&func.attrs).unwrap_or_default();
+            let static_modifier = if func.sig.receiver().is_some() { "" } else { "static " };
+            let return_type = match &func.sig.output {
+                ReturnType::Default => "void".to_string(),
+                ReturnType::Type(_, ty) => java_type(ty),
+            };
+            let parameters = func.sig.inputs.iter()
+                .filter_map(|input| match input {
+                    FnArg::Typed(input) if ContextParam::of_type(&input.ty).is_none() => Some(input),
+                    _ => None
+                })
+                .enumerate()
+                .map(|(idx, input)| {
+                    let name = match &*input.pat {
+                        Pat::Ident(pat) => options.naming.apply(&pat.ident.to_string()),
+                        _ => format!("arg{}", idx),
+                    };
+                    format!("{} {}", java_type(&input.ty), name)
+                })
+                .collect::<Vec<_>>();
+            let throws = if method_attributes.throws.is_empty() {
+                String::new()
+            } else {
+                format!(" throws {}", method_attributes.throws.iter().map(|exception| exception.value()).collect::<Vec<_>>().join(", "))
+            };
+            format!("public {}native {} {}({}){};", static_modifier, return_type, method_attributes.java_name(&func.sig.ident, options.naming), parameters.join(", "), throws)
+        })
+        .collect()
+}
+
+/// Java name of a rust type, as far as it can be told from the type's syntax
+fn java_type(ty: &Type) -> String {
+    match ty {
+        Type::Reference(reference) => match &*reference.elem {
+            Type::Path(path) if path.path.is_ident("str") => "String".to_string(),
+            Type::Slice(slice) => format!("{}[]", java_type(&slice.elem)),
+            elem => java_type(elem),
+        },
+        Type::Tuple(tuple) if tuple.elems.is_empty() => "void".to_string(),
+        Type::Tuple(tuple) => format!("instantcoffee.Tuple{}<{}>", tuple.elems.len(), tuple.elems.iter().map(|elem| boxed(java_type(elem))).collect::<Vec<_>>().join(", ")),
+        Type::Paren(paren) => java_type(&paren.elem),
+        Type::Group(group) => java_type(&group.elem),
+        Type::Path(path) => {
+            let Some(segment) = path.path.segments.last() else {
+                return "Object".to_string();
+            };
+            let arguments = match &segment.arguments {
+                PathArguments::AngleBracketed(arguments) => arguments.args.iter()
+                    .filter_map(|argument| match argument {
+                        GenericArgument::Type(ty) => Some(ty),
+                        _ => None
+                    })
+                    .collect::<Vec<_>>(),
+                _ => Vec::new(),
+            };
+            match (segment.ident.to_string().as_str(), arguments.as_slice()) {
+                ("bool", []) => "boolean".to_string(),
+                ("i8" | "u8", []) => "byte".to_string(),
+                ("i16" | "u16", []) => "short".to_string(),
+                ("i32" | "u32", []) => "int".to_string(),
+                ("i64" | "u64" | "Handle", _) => "long".to_string(),
+                ("f32", []) => "float".to_string(),
+                ("f64", []) => "double".to_string(),
+                ("JavaChar", []) => "char".to_string(),
+                ("String", []) => "String".to_string(),
+                ("CString" | "CStr" | "Bytes" | "BytesMut", []) => "byte[]".to_string(),
+                ("Box", [Type::Slice(slice)]) => format!("{}[]", java_type(&slice.elem)),
+                ("Vec", [inner]) => format!("{}[]", java_type(inner)),
+                ("Box" | "Rc" | "Arc", [inner]) => java_type(inner),
+                ("Option", [inner]) => boxed(java_type(inner)),
+                // Errors are thrown rather than returned; Their exception class is not known from the syntax
+                ("Result", [inner, ..]) => java_type(inner),
+                ("HashMap", [key, value]) => format!("java.util.Map<{}, {}>", boxed(java_type(key)), boxed(java_type(value))),
+                (name, []) => name.to_string(),
+                (name, arguments) => format!("{}<{}>", name, arguments.iter().map(|argument| boxed(java_type(argument))).collect::<Vec<_>>().join(", ")),
+            }
+        }
+        Type::Slice(slice) => format!("{}[]", java_type(&slice.elem)),
+        Type::Array(array) => format!("{}[]", java_type(&array.elem)),
+        _ => "Object".to_string(),
+    }
+}
+
+/// Boxed class of a primitive type, for nullable values and type arguments; Other types are returned as-is
+fn boxed(java_type: String) -> String {
+    match java_type.as_str() {
+        "boolean" => "Boolean".to_string(),
+        "byte" => "Byte".to_string(),
+        "short" => "Short".to_string(),
+        "char" => "Character".to_string(),
+        "int" => "Integer".to_string(),
+        "long" => "Long".to_string(),
+        "float" => "Float".to_string(),
+        "double" => "Double".to_string(),
+        _ => java_type
+    }
+}
+
+/// Write the preview sources of a package, removing the sources of its previous preview
+fn write_package(root: &Path, package: &str, classes: &BTreeMap<String, PreviewClass>) -> std::io::Result<()> {
+    let dir = package.split('.').fold(root.to_path_buf(), |dir, segment| dir.join(segment));
+    std::fs::create_dir_all(&dir)?;
+    for entry in std::fs::read_dir(&dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "java") {
+            std::fs::remove_file(path)?;
+        }
+    }
+
+    for (name, class) in classes {
+        let mut source = format!("// Preview generated while expanding `jmodule`; Not compiled, see the sources written by `JModuleDecl`\npackage {};\n\n", package);
+        let declaration = class.declaration.clone().unwrap_or_else(|| format!("public final class {}", name));
+        source.push_str(&format!("{} {{\n", declaration));
+        for member in &class.members {
+            source.push_str(&format!("\t{}\n", member));
+        }
+        if !class.members.is_empty() && !class.methods.is_empty() {
+            source.push('\n');
+        }
+        for method in &class.methods {
+            source.push_str(&format!("\t{}\n", method));
+        }
+        source.push_str("}\n");
+        std::fs::write(dir.join(format!("{}.java", name)), source)?;
+    }
+    Ok(())
+}
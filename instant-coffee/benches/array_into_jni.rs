@@ -0,0 +1,76 @@
+//! Benchmarks for converting multi-megabyte primitive arrays to Java
+//!
+//! Each conversion is compared against collecting into an intermediate Vec before copying, which `JniArray::into_jni` used to do for bool and char arrays
+//!
+//! Requires the `fuzzing` feature and a JVM, located through JAVA_HOME
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main, Throughput};
+use jni::JNIEnv;
+use jni::objects::{JBooleanArray, JCharArray};
+use jni::sys::{jboolean, jchar, jsize};
+use instant_coffee::fuzzing::embedded_jvm;
+use instant_coffee::interop::JavaChar;
+use instant_coffee::JniArray;
+
+const SIZES: [usize; 2] = [1 << 20, 8 << 20];
+
+// Previous implementations, taking a box to mirror JniArray::into_jni
+
+#[allow(clippy::boxed_local)]
+fn collect_bool_array<'local>(input: Box<[bool]>, env: &mut JNIEnv<'local>) -> JBooleanArray<'local> {
+    let array = env.new_boolean_array(input.len() as jsize).unwrap();
+    let jslice = input.iter().map(|bool| *bool as jboolean).collect::<Vec<jboolean>>();
+    env.set_boolean_array_region(&array, 0, &jslice).unwrap();
+    array
+}
+
+#[allow(clippy::boxed_local)]
+fn collect_char_array<'local>(input: Box<[JavaChar]>, env: &mut JNIEnv<'local>) -> JCharArray<'local> {
+    let array = env.new_char_array(input.len() as jsize).unwrap();
+    let jslice = input.iter().map(|char| char.0 as jchar).collect::<Vec<jchar>>();
+    env.set_char_array_region(&array, 0, &jslice).unwrap();
+    array
+}
+
+fn bench_into_jni(c: &mut Criterion) {
+    let mut env = embedded_jvm(&[]).attach_current_thread().expect("could not attach to JVM");
+
+    let mut group = c.benchmark_group("boolean[]");
+    for size in SIZES {
+        let input = (0..size).map(|idx| idx % 3 == 0).collect::<Box<[bool]>>();
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("into_jni", size), &input, |b, input| {
+            b.iter(|| env.with_local_frame(4, |env| {
+                <JBooleanArray as JniArray<bool>>::into_jni(input.clone(), env).map(|_| ()).map_err(|_| jni::errors::Error::JavaException)
+            }).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("collect", size), &input, |b, input| {
+            b.iter(|| env.with_local_frame(4, |env| {
+                collect_bool_array(input.clone(), env);
+                Ok::<_, jni::errors::Error>(())
+            }).unwrap())
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("char[]");
+    for size in SIZES {
+        let input = (0..size).map(|idx| JavaChar(idx as u16)).collect::<Box<[JavaChar]>>();
+        group.throughput(Throughput::Bytes(2 * size as u64));
+        group.bench_with_input(BenchmarkId::new("into_jni", size), &input, |b, input| {
+            b.iter(|| env.with_local_frame(4, |env| {
+                <JCharArray as JniArray<JavaChar>>::into_jni(input.clone(), env).map(|_| ()).map_err(|_| jni::errors::Error::JavaException)
+            }).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("collect", size), &input, |b, input| {
+            b.iter(|| env.with_local_frame(4, |env| {
+                collect_char_array(input.clone(), env);
+                Ok::<_, jni::errors::Error>(())
+            }).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_into_jni);
+criterion_main!(benches);
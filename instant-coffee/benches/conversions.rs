@@ -0,0 +1,15 @@
+//! Throughput of the built-in conversions, see [`instant_coffee::bench`]
+//!
+//! Requires a JVM, located through JAVA_HOME
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use instant_coffee::bench::bench_builtin_conversions;
+use instant_coffee::fuzzing::embedded_jvm;
+
+fn bench_conversions(c: &mut Criterion) {
+    let mut env = embedded_jvm(&[]).attach_current_thread().expect("could not attach to JVM");
+    bench_builtin_conversions(c, &mut env);
+}
+
+criterion_group!(benches, bench_conversions);
+criterion_main!(benches);
@@ -0,0 +1,115 @@
+//! Conversion throughput benchmarks, measured with [`criterion`] inside a real JVM
+//!
+//! [`bench_builtin_conversions`] measures the conversions of primitive arrays, strings, and object arrays, which do not need any generated classes.
+//! [`bench_conversion`] measures any other [`JavaType`] in both directions, such as derived structs nesting other structs, whose generated classes must then be on the class path.
+//!
+//! Benchmarks run in a JVM started by [`embedded_jvm`](crate::fuzzing::embedded_jvm), such as from a `harness = false` bench target:
+//!
+//! ```ignore
+//! fn conversions(c: &mut Criterion) {
+//!     let jvm = embedded_jvm(&["-Djava.class.path=target/java-classes"]);
+//!     let mut env = jvm.attach_current_thread().unwrap();
+//!     bench_builtin_conversions(c, &mut env);
+//!
+//!     let line = Line { start: Point { x: 1, y: 2 }, end: Point { x: 3, y: 4 } };
+//!     let mut group = c.benchmark_group("structs");
+//!     bench_conversion(&mut group, "Line", &line, Throughput::Elements(1), &mut env);
+//!     group.finish();
+//! }
+//!
+//! criterion_group!(benches, conversions);
+//! criterion_main!(benches);
+//! ```
+
+use criterion::{BenchmarkGroup, BenchmarkId, Criterion, Throughput};
+use criterion::measurement::WallTime;
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::objects::JObject;
+use crate::JavaType;
+use crate::jni_util::{map_jni_error, with_local_frame};
+
+/// Length of the large primitive arrays and strings of [`bench_builtin_conversions`]
+const LARGE_LENGTH: usize = 1 << 20;
+
+/// Length of the object arrays of [`bench_builtin_conversions`]
+const OBJECT_ARRAY_LENGTH: usize = 10_000;
+
+/// Measure converting `value` to Java, and back from Java, as benchmarks `into_jni/{id}` and `from_jni/{id}` of `group`
+///
+/// Each conversion runs in its own local frame, so that the references it creates are released between iterations
+///
+/// # Arguments
+///
+/// * `group`: Benchmark group to add the benchmarks to
+/// * `id`: Name of the value within the group
+/// * `value`: Value to convert; Cloned for each conversion to Java, as conversions take their value
+/// * `throughput`: Amount of data converted per iteration, such as the number of elements or bytes
+/// * `env`: JNI Env
+///
+/// # Panics
+///
+/// If the value cannot be converted in either direction
+pub fn bench_conversion<T: JavaType + Clone>(group: &mut BenchmarkGroup<'_, WallTime>, id: &str, value: &T, throughput: Throughput, env: &mut JNIEnv) where for<'local> T::JniType<'local>: From<JObject<'local>> + AsRef<JObject<'local>> {
+    let java_value = with_local_frame(4, "benchmark input", env, |env| {
+        let local = value.clone().into_jni(env)?;
+        env.new_global_ref(local.as_ref()).map_err(map_jni_error)
+    }).unwrap_or_else(|exception| conversion_failed::<T>("into_jni", exception, env));
+
+    group.throughput(throughput);
+    group.bench_with_input(BenchmarkId::new("into_jni", id), value, |b, value| {
+        b.iter(|| with_local_frame(4, "benchmark", env, |env| value.clone().into_jni(env).map(|_| ()))
+            .unwrap_or_else(|exception| conversion_failed::<T>("into_jni", exception, env)))
+    });
+    group.bench_with_input(BenchmarkId::new("from_jni", id), &java_value, |b, java_value| {
+        b.iter(|| with_local_frame(4, "benchmark", env, |env| {
+            let local = env.new_local_ref(java_value).map_err(map_jni_error)?;
+            T::from_jni(T::JniType::from(local), env)
+        }).unwrap_or_else(|exception| conversion_failed::<T>("from_jni", exception, env)))
+    });
+}
+
+/// Measure the conversions of primitive arrays, strings, and object arrays in both directions, see [`bench_conversion`]
+///
+/// Adds the benchmark groups `primitive arrays`, `strings`, and `object arrays` to `c`
+pub fn bench_builtin_conversions(c: &mut Criterion, env: &mut JNIEnv) {
+    let mut group = c.benchmark_group("primitive arrays");
+    let ints = (0..LARGE_LENGTH as i32).collect::<Box<[i32]>>();
+    bench_conversion(&mut group, "int[]", &ints, Throughput::Bytes(4 * LARGE_LENGTH as u64), env);
+    let doubles = (0..LARGE_LENGTH).map(|idx| idx as f64 * 0.5).collect::<Box<[f64]>>();
+    bench_conversion(&mut group, "double[]", &doubles, Throughput::Bytes(8 * LARGE_LENGTH as u64), env);
+    let booleans = (0..LARGE_LENGTH).map(|idx| idx % 3 == 0).collect::<Box<[bool]>>();
+    bench_conversion(&mut group, "boolean[]", &booleans, Throughput::Bytes(LARGE_LENGTH as u64), env);
+    group.finish();
+
+    let mut group = c.benchmark_group("strings");
+    let short = "instant-coffee".to_string();
+    bench_conversion(&mut group, "short", &short, Throughput::Bytes(short.len() as u64), env);
+    let ascii = "abcdefghijklmnopqrstuvwxyz012345".repeat(LARGE_LENGTH / 32);
+    bench_conversion(&mut group, "ascii", &ascii, Throughput::Bytes(ascii.len() as u64), env);
+    // Characters outside the BMP are encoded as surrogate pairs, and differ between UTF-8 and modified UTF-8
+    let unicode = "añ€😀".repeat(LARGE_LENGTH / 10);
+    bench_conversion(&mut group, "unicode", &unicode, Throughput::Bytes(unicode.len() as u64), env);
+    group.finish();
+
+    let mut group = c.benchmark_group("object arrays");
+    let strings = (0..OBJECT_ARRAY_LENGTH).map(|idx| format!("element {}", idx)).collect::<Box<[String]>>();
+    bench_conversion(&mut group, "String[]", &strings, Throughput::Elements(OBJECT_ARRAY_LENGTH as u64), env);
+    let boxed = (0..OBJECT_ARRAY_LENGTH as i64).map(|idx| (idx % 7 != 0).then_some(idx)).collect::<Box<[Option<i64>]>>();
+    bench_conversion(&mut group, "Long[]", &boxed, Throughput::Elements(OBJECT_ARRAY_LENGTH as u64), env);
+    let nested = (0..OBJECT_ARRAY_LENGTH / 10).map(|row| (0..100).map(|column| row as i32 * column).collect()).collect::<Box<[Box<[i32]>]>>();
+    bench_conversion(&mut group, "int[][]", &nested, Throughput::Elements(OBJECT_ARRAY_LENGTH as u64 * 10), env);
+    group.finish();
+}
+
+/// Panic with the exception of a failed conversion, describing and clearing a pending Java exception
+fn conversion_failed<T: JavaType>(direction: &str, exception: Option<Exception>, env: &mut JNIEnv) -> ! {
+    match exception {
+        Some(exception) => panic!("bench: {} of {} failed: {}: {}", direction, T::QUALIFIED_NAME(), exception.class, exception.msg),
+        None => {
+            let _ = env.exception_describe();
+            let _ = env.exception_clear();
+            panic!("bench: {} of {} threw an exception", direction, T::QUALIFIED_NAME())
+        }
+    }
+}
@@ -1,11 +1,14 @@
 //! Java code generation
 
 use std::fmt::{Display, Formatter};
-use std::fs::File;
 use std::io;
 use std::path::PathBuf;
 use crate::JavaType;
 
+mod diff;
+
+pub use diff::{diff, ApiChange, ApiDiff, VersionBump};
+
 /// Java field & method access modifier
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum JAccessModifier {
@@ -26,6 +29,18 @@ impl Display for JAccessModifier {
     }
 }
 
+impl JAccessModifier {
+    /// This modifier followed by a space, as written before a declaration; Empty for package-private declarations, which have no modifier
+    fn prefix(&self) -> &'static str {
+        match self {
+            JAccessModifier::Public => "public ",
+            JAccessModifier::Protected => "protected ",
+            JAccessModifier::PackagePrivate => "",
+            JAccessModifier::Private => "private ",
+        }
+    }
+}
+
 /// Java field descriptor
 pub struct JField {
     /// Access modifier
@@ -34,11 +49,18 @@ pub struct JField {
     pub jtype: &'static str,
     /// Name of this field, as verbatim in Java source
     pub name: &'static str,
+    /// Name of this field in serialized forms, usually the rust field name; Written in JSON annotations, see [`JsonAnnotations`]
+    pub serialized_name: &'static str,
+    /// Initializer expression written at the declaration of this field, as verbatim in Java source, e.g. `java.util.List.of()`
+    ///
+    /// The value of the field until it is assigned, such as the default value of bean-mode classes created with the no-arg constructor.
+    /// Record components cannot have initializers, records ignore this
+    pub initializer: Option<&'static str>,
 }
 
 /// Java method descriptor
 ///
-/// Currently only describes `native` methods
+/// Describes `native` methods implemented in rust, or plain Java methods if a [`body`](JMethod::body) is given
 pub struct JMethod {
     /// True if this method is 'static'
     pub is_static: bool,
@@ -48,15 +70,38 @@ pub struct JMethod {
     pub inputs: Vec<(&'static str, &'static str)>,
     /// Return type of this method, as verbatim in Java source
     pub output: &'static str,
+    /// Annotations written before this method, as verbatim in Java source, e.g. `@com.example.Audited`
+    pub annotations: Vec<&'static str>,
+    /// True if Java subclasses may override this method; Other methods of subclassable classes are `final`, see [`JClassDecl::Handle`]
+    pub overridable: bool,
+    /// Exceptions in the `throws` clause of this method, fully qualified, as verbatim in Java source
+    ///
+    /// Native methods may throw checked exceptions without declaring them; Declaring them makes Java callers handle them
+    pub throws: Vec<&'static str>,
+    /// Statements of a plain (non-native) Java method, as verbatim in Java source, e.g. `return of(0, 0);`; None for native methods
+    ///
+    /// Each line of the body is indented into the method
+    pub body: Option<&'static str>,
 }
 
 impl JMethod {
     /// Write this method declaration's Java source to the specified io::Write
     pub fn write_method<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.write_method_indented(out, "", false)
+    }
+
+    /// Write this method declaration's Java source, with all lines prefixed by `indent` (for nested classes)
+    ///
+    /// Instance methods of subclassable classes are written `final` unless they are overridable
+    fn write_method_indented<W: io::Write>(&self, out: &mut W, indent: &str, subclassable: bool) -> io::Result<()> {
+        write_annotations(out, &format!("{}\t", indent), &self.annotations)?;
+        let native_modifier = if self.body.is_none() { "native " } else { "" };
         if self.is_static {
-            write!(out, "\tpublic static native {} {}(", self.output, self.name)?;
+            write!(out, "{}\tpublic static {}{} {}(", indent, native_modifier, self.output, self.name)?;
+        } else if subclassable && !self.overridable {
+            write!(out, "{}\tpublic final {}{} {}(", indent, native_modifier, self.output, self.name)?;
         } else {
-            write!(out, "\tpublic native {} {}(", self.output, self.name)?;
+            write!(out, "{}\tpublic {}{} {}(", indent, native_modifier, self.output, self.name)?;
         }
         let mut first = true;
         for (name, param_type) in &self.inputs {
@@ -67,7 +112,70 @@ impl JMethod {
             }
             write!(out, "{} {}", param_type, name)?;
         }
-        writeln!(out, ");")
+        write!(out, ")")?;
+        self.write_throws(out)?;
+        match self.body {
+            Some(body) => {
+                writeln!(out, " {{")?;
+                for line in body.lines() {
+                    writeln!(out, "{}\t\t{}", indent, line)?;
+                }
+                writeln!(out, "{}\t}}", indent)
+            }
+            None => writeln!(out, ";")
+        }
+    }
+
+    /// Write the ` throws A, B` clause of this method, if it declares any exceptions
+    fn write_throws<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        if !self.throws.is_empty() {
+            write!(out, " throws {}", self.throws.join(", "))?;
+        }
+        Ok(())
+    }
+
+    /// Write a method of a Java record, which forwards to the native method written by [`JMethod::write_record_native`]
+    fn write_record_forwarder<W: io::Write>(&self, out: &mut W, indent: &str) -> io::Result<()> {
+        let static_modifier = if self.is_static { "static " } else { "" };
+        write_annotations(out, &format!("{}\t", indent), &self.annotations)?;
+        write!(out, "{}\tpublic {}{} {}(", indent, static_modifier, self.output, self.name)?;
+        write_parameters(out, self.inputs.iter().copied())?;
+        write!(out, ")")?;
+        self.write_throws(out)?;
+        writeln!(out, " {{")?;
+
+        let return_keyword = if self.output == "void" { "" } else { "return " };
+        write!(out, "{}\t\t{}Native.{}(", indent, return_keyword, self.name)?;
+        let self_argument = if self.is_static { None } else { Some("this") };
+        for (idx, name) in self_argument.into_iter().chain(self.inputs.iter().map(|(name, _)| *name)).enumerate() {
+            if idx != 0 {
+                write!(out, ", ")?;
+            }
+            write!(out, "{}", name)?;
+        }
+        writeln!(out, ");")?;
+        writeln!(out, "{}\t}}", indent)
+    }
+
+    /// Write the static native method backing a method of the Java record `record_name`; Instance methods take the record as their first parameter
+    fn write_record_native<W: io::Write>(&self, out: &mut W, indent: &str, record_name: &str) -> io::Result<()> {
+        write!(out, "{}\t\tstatic native {} {}(", indent, self.output, self.name)?;
+        let self_parameter = if self.is_static { None } else { Some(("self", record_name)) };
+        write_parameters(out, self_parameter.into_iter().chain(self.inputs.iter().copied()))?;
+        write!(out, ")")?;
+        self.write_throws(out)?;
+        writeln!(out, ";")
+    }
+
+    /// Rewrite qualified names in this method's parameter and return types, see [`JModuleDecl::remap_package`]
+    fn remap_package(&mut self, from: &str, to: &str) {
+        for (_, param_type) in &mut self.inputs {
+            *param_type = remap_qualified_names(param_type, from, to);
+        }
+        self.output = remap_qualified_names(self.output, from, to);
+        for exception in &mut self.throws {
+            *exception = remap_qualified_names(exception, from, to);
+        }
     }
 }
 
@@ -83,9 +191,29 @@ pub struct JUnionVariant {
     pub fields: Vec<JField>,
 }
 
+impl JUnionVariant {
+    /// Name of the `int` constant of the outer class holding the tag of this variant, returned by `getTag()`; `TAG_` followed by the classname in upper snake case, e.g. `TAG_IN_PROGRESS`
+    ///
+    /// Tags number the variants in declaration order. Java compilers inline the constants into consumers, reordering variants breaks compiled consumers
+    pub fn tag_constant(&self) -> String {
+        let mut constant = String::from("TAG_");
+        let mut previous = None;
+        for c in self.name.chars() {
+            if c.is_uppercase() && previous.is_some_and(|previous: char| previous.is_lowercase() || previous.is_ascii_digit()) {
+                constant.push('_');
+            }
+            constant.extend(c.to_uppercase());
+            previous = Some(c);
+        }
+        constant
+    }
+}
+
 /// Java class declaration
 ///
-/// All classes are final
+/// All classes are final, other than subclassable [`JClassDecl::Handle`] classes
+///
+/// Classes may be nested in another class of the same package, in which case they are emitted as `static` nested classes in the outer class' source file
 pub enum JClassDecl {
     /// Regular Java class
     Class {
@@ -93,10 +221,57 @@ pub enum JClassDecl {
         name: &'static str,
         /// Fully qualified package, as verbatim in Java source
         package: &'static str,
+        /// Outer class this class is nested in, if any. Dot-separated path of classnames within the package, e.g. "Outer" or "Outer.Inner"
+        nested_in: Option<&'static str>,
+        /// Access modifier of the class; Top-level classes are public or package-private, only nested classes may be protected or private
+        access: JAccessModifier,
+        /// Annotations written before the class declaration, as verbatim in Java source, e.g. `@com.example.Audited`
+        annotations: Vec<&'static str>,
+        /// Imports of the class file, as verbatim in Java source, e.g. `java.util.List` or `java.util.*`; Imports of nested classes are written to the file of their outer class
+        imports: Vec<&'static str>,
+        /// Interfaces implemented by this class, as verbatim in Java source, e.g. `java.util.function.IntSupplier`; Their methods are implemented by the class' (native) methods
+        interfaces: Vec<&'static str>,
         /// Fields for this class
         fields: Vec<JField>,
         /// Methods for this class
         methods: Vec<JMethod>,
+        /// Java source written verbatim at the end of the class body, such as non-native convenience methods
+        body: Vec<&'static str>,
+        /// If true, a public static `of` factory method is emitted with the same parameters as the (private) constructor
+        factory: bool,
+        /// If true, a `withFieldName(value)` method is emitted for each field, returning a copy of this object with that field replaced
+        with_methods: bool,
+        /// If true, a public no-arg constructor and a `setFieldName(value)` method for each field are emitted, for frameworks that instantiate classes reflectively.
+        /// Bean classes are never written as records
+        bean: bool,
+        /// If true, a public no-arg constructor is emitted, initializing each field to its [`initializer`](JField::initializer), or to the zero value of its type if it has none
+        default_constructor: bool,
+    },
+    /// Handle-backed Java class; Holds a handle to a rust value owned by the Java object, in place of fields
+    ///
+    /// Implements AutoCloseable, the rust value is released when the object is closed, or by a Cleaner once the object is unreachable.
+    /// Reserves the method names `close` and `release`, and `transfer` for subclassable classes
+    Handle {
+        /// Classname, as verbatim in Java source
+        name: &'static str,
+        /// Fully qualified package, as verbatim in Java source
+        package: &'static str,
+        /// Outer class this class is nested in, if any. Dot-separated path of classnames within the package, e.g. "Outer" or "Outer.Inner"
+        nested_in: Option<&'static str>,
+        /// Access modifier of the class; Top-level classes are public or package-private, only nested classes may be protected or private
+        access: JAccessModifier,
+        /// Annotations written before the class declaration, as verbatim in Java source, e.g. `@com.example.Audited`
+        annotations: Vec<&'static str>,
+        /// Imports of the class file, as verbatim in Java source, e.g. `java.util.List` or `java.util.*`; Imports of nested classes are written to the file of their outer class
+        imports: Vec<&'static str>,
+        /// Interfaces implemented by this class, as verbatim in Java source, e.g. `java.util.function.IntSupplier`; Their methods are implemented by the class' (native) methods
+        interfaces: Vec<&'static str>,
+        /// Methods for this class
+        methods: Vec<JMethod>,
+        /// Java source written verbatim at the end of the class body, such as non-native convenience methods
+        body: Vec<&'static str>,
+        /// If true, the class is not final; Java subclasses take over the rust value of an existing object through the protected `(Name base)` constructor, and may override [`JMethod::overridable`] methods
+        subclassable: bool,
     },
     /// Java enum; Equivalent to a field-less rust enum
     Enum {
@@ -104,31 +279,208 @@ pub enum JClassDecl {
         name: &'static str,
         /// Fully qualified package, as verbatim in Java source
         package: &'static str,
+        /// Outer class this class is nested in, if any. Dot-separated path of classnames within the package, e.g. "Outer" or "Outer.Inner"
+        nested_in: Option<&'static str>,
+        /// Access modifier of the class; Top-level classes are public or package-private, only nested classes may be protected or private
+        access: JAccessModifier,
+        /// Annotations written before the class declaration, as verbatim in Java source, e.g. `@com.example.Audited`
+        annotations: Vec<&'static str>,
+        /// Imports of the class file, as verbatim in Java source, e.g. `java.util.List` or `java.util.*`; Imports of nested classes are written to the file of their outer class
+        imports: Vec<&'static str>,
         /// Enum variant names, as verbatim in Java source
         variants: Vec<&'static str>,
         /// Methods for this class
         methods: Vec<JMethod>,
+        /// Java source written verbatim at the end of the class body, such as non-native convenience methods
+        body: Vec<&'static str>,
     },
     /// Java 'tagged union'; A sealed class with a fixed set of direct subclasses, emulating rust enums with fields
+    ///
+    /// Each variant has an `int` tag constant in the outer class, see [`JUnionVariant::tag_constant`], returned by the variant's `getTag()` method. Reserves the method name `getTag`
     EnumTaggedUnion {
         /// Classname for the outer type, as verbatim in Java source
         name: &'static str,
         /// Fully qualified package, as verbatim in Java source
         package: &'static str,
+        /// Outer class this class is nested in, if any. Dot-separated path of classnames within the package, e.g. "Outer" or "Outer.Inner"
+        nested_in: Option<&'static str>,
+        /// Access modifier of the class; Top-level classes are public or package-private, only nested classes may be protected or private
+        access: JAccessModifier,
+        /// Annotations written before the class declaration, as verbatim in Java source, e.g. `@com.example.Audited`
+        annotations: Vec<&'static str>,
+        /// Imports of the class file, as verbatim in Java source, e.g. `java.util.List` or `java.util.*`; Imports of nested classes are written to the file of their outer class
+        imports: Vec<&'static str>,
         /// Enum variants; Inner subclasses
         variants: Vec<JUnionVariant>,
         /// Methods for the outer class
         methods: Vec<JMethod>,
+        /// Java source written verbatim at the end of the class body, such as non-native convenience methods
+        body: Vec<&'static str>,
+    },
+    /// Support class provided by instant-coffee, such as the Java side of [`crate::interop::StreamPublisher`]; Never nested
+    Source {
+        /// Classname, as verbatim in Java source
+        name: &'static str,
+        /// Fully qualified package, as verbatim in Java source
+        package: &'static str,
+        /// Class declaration source, written verbatim after the package declaration
+        source: &'static str,
     },
 }
 
+impl JField {
+    /// ` = <initializer>` suffix of this field's declaration, or an empty string if the field has no initializer
+    fn initializer_clause(&self) -> String {
+        self.initializer.map(|initializer| format!(" = {}", initializer)).unwrap_or_default()
+    }
+
+    /// Rewrite qualified names in this field's type and initializer, see [`JModuleDecl::remap_package`]
+    fn remap_package(&mut self, from: &str, to: &str) {
+        self.jtype = remap_qualified_names(self.jtype, from, to);
+        self.initializer = self.initializer.map(|initializer| remap_qualified_names(initializer, from, to));
+    }
+}
+
 impl JClassDecl {
     /// Classname, as verbatim in Java source
     pub fn class_name(&self) -> &'static str {
         match self {
             JClassDecl::Class { name, .. } => name,
+            JClassDecl::Handle { name, .. } => name,
             JClassDecl::Enum { name, .. } => name,
-            JClassDecl::EnumTaggedUnion { name, .. } => name
+            JClassDecl::EnumTaggedUnion { name, .. } => name,
+            JClassDecl::Source { name, .. } => name
+        }
+    }
+
+    /// Fully qualified package, as verbatim in Java source
+    pub fn package(&self) -> &'static str {
+        match self {
+            JClassDecl::Class { package, .. } => package,
+            JClassDecl::Handle { package, .. } => package,
+            JClassDecl::Enum { package, .. } => package,
+            JClassDecl::EnumTaggedUnion { package, .. } => package,
+            JClassDecl::Source { package, .. } => package
+        }
+    }
+
+    /// Outer class this class is nested in, if any. Dot-separated path of classnames within the package
+    pub fn nested_in(&self) -> Option<&'static str> {
+        match self {
+            JClassDecl::Class { nested_in, .. } => *nested_in,
+            JClassDecl::Handle { nested_in, .. } => *nested_in,
+            JClassDecl::Enum { nested_in, .. } => *nested_in,
+            JClassDecl::EnumTaggedUnion { nested_in, .. } => *nested_in,
+            JClassDecl::Source { .. } => None
+        }
+    }
+
+    /// Access modifier of the class; Source classes are public
+    pub fn access(&self) -> JAccessModifier {
+        match self {
+            JClassDecl::Class { access, .. } => *access,
+            JClassDecl::Handle { access, .. } => *access,
+            JClassDecl::Enum { access, .. } => *access,
+            JClassDecl::EnumTaggedUnion { access, .. } => *access,
+            JClassDecl::Source { .. } => JAccessModifier::Public
+        }
+    }
+
+    /// Annotations written before the class declaration, as verbatim in Java source
+    pub fn annotations(&self) -> &[&'static str] {
+        match self {
+            JClassDecl::Class { annotations, .. } => annotations,
+            JClassDecl::Handle { annotations, .. } => annotations,
+            JClassDecl::Enum { annotations, .. } => annotations,
+            JClassDecl::EnumTaggedUnion { annotations, .. } => annotations,
+            JClassDecl::Source { .. } => &[]
+        }
+    }
+
+    /// Interfaces implemented by this class, as verbatim in Java source
+    pub fn interfaces(&self) -> &[&'static str] {
+        match self {
+            JClassDecl::Class { interfaces, .. } => interfaces,
+            JClassDecl::Handle { interfaces, .. } => interfaces,
+            JClassDecl::Enum { .. } | JClassDecl::EnumTaggedUnion { .. } | JClassDecl::Source { .. } => &[]
+        }
+    }
+
+    /// Imports of the class file, as verbatim in Java source
+    pub fn imports(&self) -> &[&'static str] {
+        match self {
+            JClassDecl::Class { imports, .. } => imports,
+            JClassDecl::Handle { imports, .. } => imports,
+            JClassDecl::Enum { imports, .. } => imports,
+            JClassDecl::EnumTaggedUnion { imports, .. } => imports,
+            JClassDecl::Source { .. } => &[]
+        }
+    }
+
+    /// Methods of this class; Source classes have none
+    pub fn methods(&self) -> &[JMethod] {
+        match self {
+            JClassDecl::Class { methods, .. } => methods,
+            JClassDecl::Handle { methods, .. } => methods,
+            JClassDecl::Enum { methods, .. } => methods,
+            JClassDecl::EnumTaggedUnion { methods, .. } => methods,
+            JClassDecl::Source { .. } => &[]
+        }
+    }
+
+    /// Java source written verbatim at the end of the class body
+    pub fn body(&self) -> &[&'static str] {
+        match self {
+            JClassDecl::Class { body, .. } => body,
+            JClassDecl::Handle { body, .. } => body,
+            JClassDecl::Enum { body, .. } => body,
+            JClassDecl::EnumTaggedUnion { body, .. } => body,
+            JClassDecl::Source { .. } => &[]
+        }
+    }
+
+    /// Dot-separated path of classnames of this class within its package, e.g. "Outer.Inner" for a class "Inner" nested in "Outer"
+    pub fn class_path(&self) -> String {
+        match self.nested_in() {
+            Some(outer) => format!("{}.{}", outer, self.class_name()),
+            None => self.class_name().to_string()
+        }
+    }
+
+    /// Rewrite the package of this class, and qualified names in its members, see [`JModuleDecl::remap_package`]
+    fn remap_package(&mut self, from: &str, to: &str) {
+        match self {
+            JClassDecl::Class { imports, .. } | JClassDecl::Handle { imports, .. } | JClassDecl::Enum { imports, .. } | JClassDecl::EnumTaggedUnion { imports, .. } => {
+                imports.iter_mut().for_each(|import| *import = remap_qualified_names(import, from, to));
+            }
+            JClassDecl::Source { .. } => {}
+        }
+        match self {
+            JClassDecl::Class { package, interfaces, fields, methods, .. } => {
+                *package = remap_qualified_names(package, from, to);
+                interfaces.iter_mut().for_each(|interface| *interface = remap_qualified_names(interface, from, to));
+                fields.iter_mut().for_each(|field| field.remap_package(from, to));
+                methods.iter_mut().for_each(|method| method.remap_package(from, to));
+            }
+            JClassDecl::Handle { package, interfaces, methods, .. } => {
+                *package = remap_qualified_names(package, from, to);
+                interfaces.iter_mut().for_each(|interface| *interface = remap_qualified_names(interface, from, to));
+                methods.iter_mut().for_each(|method| method.remap_package(from, to));
+            }
+            JClassDecl::Enum { package, methods, .. } => {
+                *package = remap_qualified_names(package, from, to);
+                methods.iter_mut().for_each(|method| method.remap_package(from, to));
+            }
+            JClassDecl::EnumTaggedUnion { package, variants, methods, .. } => {
+                *package = remap_qualified_names(package, from, to);
+                for variant in variants {
+                    variant.fields.iter_mut().for_each(|field| field.remap_package(from, to));
+                }
+                methods.iter_mut().for_each(|method| method.remap_package(from, to));
+            }
+            JClassDecl::Source { package, .. } => {
+                *package = remap_qualified_names(package, from, to);
+            }
         }
     }
 
@@ -136,55 +488,272 @@ impl JClassDecl {
     ///
     /// This must write to a .java file with the same name ([`Self::class_name()`]) as the class
     /// [`JModuleDecl::write_to_dir`] and [`JModuleDecl::write_jar`] perform this automatically
+    ///
+    /// Nested classes are not written, see [`Self::write_class_file_with_nested`]
     pub fn write_class_file<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.write_class_file_with_nested(&[], out)
+    }
+
+    /// Write this class declaration's Java source to the specified io::Write, including all classes in `classes` that are nested in this class
+    ///
+    /// `classes` may contain unrelated classes, which are ignored
+    pub fn write_class_file_with_nested<W: io::Write>(&self, classes: &[JClassDecl], out: &mut W) -> io::Result<()> {
+        writeln!(out, "package {};\n", self.package())?;
+        self.write_imports(classes, out)?;
+
+        self.write_class(classes, &WriteOptions::default(), "", out)
+    }
+
+    /// Write this class declaration's Java source as part of `module`, including nested classes and applying the module's options
+    fn write_class_file_in_module<W: io::Write>(&self, module: &JModuleDecl, out: &mut W) -> io::Result<()> {
+        writeln!(out, "package {};\n", self.package())?;
+        self.write_imports(&module.classes, out)?;
+
+        let options = WriteOptions {
+            load_library: module.load_library,
+            class_load_hook: module.class_load_hook,
+            records: module.records,
+            java_version: module.java_version,
+            generated: module.generated.as_ref(),
+            json_annotations: module.json_annotations,
+        };
+        self.write_class(&module.classes, &options, "", out)
+    }
+
+    /// Write the imports of this class and all classes in `classes` nested in it, followed by a blank line, if there are any
+    fn write_imports<W: io::Write>(&self, classes: &[JClassDecl], out: &mut W) -> io::Result<()> {
+        let class_path = self.class_path();
+        let nested_prefix = format!("{}.", class_path);
+        let nested = classes.iter().filter(|class| {
+            class.package() == self.package() && class.nested_in().is_some_and(|outer| outer == class_path || outer.starts_with(&nested_prefix))
+        });
+        let imports = std::iter::once(self).chain(nested)
+            .flat_map(|class| class.imports().iter().copied())
+            .collect::<std::collections::BTreeSet<_>>();
+        for import in &imports {
+            writeln!(out, "import {};", import)?;
+        }
+        if !imports.is_empty() {
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// Write the class declaration itself, with all lines prefixed by `indent`
+    fn write_class<W: io::Write>(&self, classes: &[JClassDecl], options: &WriteOptions, indent: &str, out: &mut W) -> io::Result<()> {
+        let access_modifier = self.access().prefix();
+        let static_modifier = if self.nested_in().is_some() { "static " } else { "" };
+        // Nested classes are initialized with their outer class; Only top-level classes load the library
+        let load_library = options.load_library.filter(|_| self.nested_in().is_none());
+        // Source classes are written verbatim
+        if !matches!(self, JClassDecl::Source { .. }) {
+            write_generated(out, indent, options)?;
+        }
+        write_annotations(out, indent, self.annotations())?;
+
         match self {
-            JClassDecl::Class { name, package, fields, methods } => {
-                writeln!(out, "package {};\n", package)?;
+            JClassDecl::Class { name, fields, methods, factory, with_methods, bean: false, default_constructor, .. } if options.records => {
+                // Record components are private final fields, with a public canonical constructor
+                write!(out, "{}{}{}record {}(", indent, access_modifier, static_modifier, name)?;
+                write_record_components(out, fields, options)?;
+                writeln!(out, "){} {{", implements_clause(self.interfaces()))?;
+                write_load_library(out, indent, load_library, options.class_load_hook)?;
+
+                // Default constructor; The canonical constructor of a record without components is its no-arg constructor
+                if *default_constructor && !fields.is_empty() {
+                    write_default_constructor(out, indent, name, fields)?;
+                }
 
-                write!(out, "public final class {} {{", name)?;
-                if fields.len() > 0 || methods.len() > 0 {
+                // Factory
+                if *factory {
+                    if *default_constructor && !fields.is_empty() {
+                        writeln!(out)?;
+                    }
+                    write!(out, "{}\tpublic static {} of(", indent, name)?;
+                    write_field_parameters(out, fields)?;
+                    writeln!(out, ") {{")?;
+                    write!(out, "{}\t\treturn new {}(", indent, name)?;
+                    for (idx, field) in fields.iter().enumerate() {
+                        write!(out, "{}", field.name)?;
+                        if idx != fields.len() - 1 {
+                            write!(out, ", ")?;
+                        }
+                    }
+                    writeln!(out, ");")?;
+                    writeln!(out, "{}\t}}", indent)?;
+                }
+
+                // Copy methods
+                if *with_methods {
+                    for field in fields {
+                        writeln!(out)?;
+                        write_with_method(out, indent, name, field, fields)?;
+                    }
+                }
+
+                if !methods.is_empty() && (*factory || *with_methods || (*default_constructor && !fields.is_empty())) {
                     writeln!(out)?;
                 }
+                // Methods; Records may not declare native methods, these forward to the nested `Native` class
+                for (idx, method) in methods.iter().enumerate() {
+                    if idx != 0 {
+                        writeln!(out)?;
+                    }
+                    if method.body.is_some() {
+                        method.write_method_indented(out, indent, false)?
+                    } else {
+                        method.write_record_forwarder(out, indent)?
+                    }
+                }
+                if methods.iter().any(|method| method.body.is_none()) {
+                    writeln!(out)?;
+                    writeln!(out, "{}\tprivate static final class Native {{", indent)?;
+                    for method in methods.iter().filter(|method| method.body.is_none()) {
+                        method.write_record_native(out, indent, name)?
+                    }
+                    writeln!(out, "{}\t}}", indent)?;
+                }
+            }
+            JClassDecl::Class { name, fields, methods, factory, with_methods, bean, default_constructor, .. } => {
+                // Classes always declare a constructor
+                writeln!(out, "{}{}{}final class {}{} {{", indent, access_modifier, static_modifier, name, implements_clause(self.interfaces()))?;
+                write_load_library(out, indent, load_library, options.class_load_hook)?;
                 // Fields
                 for field in fields {
-                    writeln!(out, "\t{} {} {};", field.access, field.jtype, field.name)?;
+                    write_json_annotation(out, &format!("{}\t", indent), field, options)?;
+                    writeln!(out, "{}\t{} {} {}{};", indent, field.access, field.jtype, field.name, field.initializer_clause())?;
                 }
                 if fields.len() > 0 {
                     writeln!(out)?;
                 }
 
-                // Constructor
-                write!(out, "\tprivate {}(", name)?;
-                for (idx, field) in fields.iter().enumerate() {
-                    write!(out, "{} {}", field.jtype, field.name)?;
-                    if idx != fields.len() - 1 {
-                        write!(out, ", ")?;
-                    }
+                // Constructor; Bean classes also have a public no-arg constructor, which is the only constructor of field-less classes
+                // Bean fields are initialized by their declaration, others are passed to the constructor by the default constructor
+                if *bean && !fields.is_empty() {
+                    writeln!(out, "{}\tpublic {}() {{}}", indent, name)?;
+                    writeln!(out)?;
+                } else if *default_constructor && !fields.is_empty() {
+                    write_default_constructor(out, indent, name, fields)?;
+                    writeln!(out)?;
                 }
+                let constructor_access = if (*bean || *default_constructor) && fields.is_empty() { "public" } else { "private" };
+                write!(out, "{}\t{} {}(", indent, constructor_access, name)?;
+                write_field_parameters(out, fields)?;
                 if fields.len() > 0 {
                     writeln!(out, ") {{")?;
                     for field in fields {
-                        writeln!(out, "\t\tthis.{} = {};", field.name, field.name)?;
+                        writeln!(out, "{}\t\tthis.{} = {};", indent, field.name, field.name)?;
                     }
-                    writeln!(out, "\t}}")?;
+                    writeln!(out, "{}\t}}", indent)?;
                 } else {
                     writeln!(out, ") {{}}")?;
                 }
 
+                // Factory
+                if *factory {
+                    writeln!(out)?;
+                    write!(out, "{}\tpublic static {} of(", indent, name)?;
+                    write_field_parameters(out, fields)?;
+                    writeln!(out, ") {{")?;
+                    write!(out, "{}\t\treturn new {}(", indent, name)?;
+                    for (idx, field) in fields.iter().enumerate() {
+                        write!(out, "{}", field.name)?;
+                        if idx != fields.len() - 1 {
+                            write!(out, ", ")?;
+                        }
+                    }
+                    writeln!(out, ");")?;
+                    writeln!(out, "{}\t}}", indent)?;
+                }
+
+                // Copy methods
+                if *with_methods {
+                    for field in fields {
+                        writeln!(out)?;
+                        write_with_method(out, indent, name, field, fields)?;
+                    }
+                }
+
+                // Setters
+                if *bean {
+                    for field in fields {
+                        writeln!(out)?;
+                        writeln!(out, "{}\tpublic void set{}({} {}) {{", indent, capitalize(field.name), field.jtype, field.name)?;
+                        writeln!(out, "{}\t\tthis.{} = {};", indent, field.name, field.name)?;
+                        writeln!(out, "{}\t}}", indent)?;
+                    }
+                }
+
                 if methods.len() > 0 {
                     writeln!(out)?;
                 }
                 // Methods
                 for method in methods {
-                    method.write_method(out)?
+                    method.write_method_indented(out, indent, false)?
                 }
-
-                write!(out, "}}")?;
             }
-            JClassDecl::Enum { name, package, variants, methods } => {
-                writeln!(out, "package {};\n", package)?;
+            JClassDecl::Handle { name, methods, subclassable, .. } => {
+                let interfaces = std::iter::once(&"AutoCloseable").chain(self.interfaces()).copied().collect::<Vec<_>>();
+                let final_modifier = if *subclassable { "" } else { "final " };
+                writeln!(out, "{}{}{}{}class {}{} {{", indent, access_modifier, static_modifier, final_modifier, name, implements_clause(&interfaces))?;
+                write_load_library(out, indent, load_library, options.class_load_hook)?;
+                writeln!(out, "{}\tprivate static final java.lang.ref.Cleaner CLEANER = java.lang.ref.Cleaner.create();", indent)?;
+                writeln!(out)?;
+                writeln!(out, "{}\tprivate volatile long handle;", indent)?;
+                writeln!(out, "{}\tprivate final java.lang.ref.Cleaner.Cleanable cleanable;", indent)?;
+                writeln!(out)?;
 
-                write!(out, "public enum {} {{", name)?;
+                // Constructor; The cleaning action must not reference this object, or it would never become unreachable
+                writeln!(out, "{}\tprivate {}(long handle) {{", indent, name)?;
+                writeln!(out, "{}\t\tthis.handle = handle;", indent)?;
+                writeln!(out, "{}\t\tthis.cleanable = CLEANER.register(this, new Release(handle));", indent)?;
+                writeln!(out, "{}\t}}", indent)?;
+                writeln!(out)?;
+
+                // Subclasses take over the rust value of an object created by rust; The base object is left closed
+                if *subclassable {
+                    writeln!(out, "{}\tprotected {}({} base) {{", indent, name, name)?;
+                    writeln!(out, "{}\t\tthis(transfer(base));", indent)?;
+                    writeln!(out, "{}\t}}", indent)?;
+                    writeln!(out)?;
+                    writeln!(out, "{}\tprivate static native long transfer({} base);", indent, name)?;
+                    writeln!(out)?;
+                }
+
+                // Cleanable.clean() runs the cleaning action at most once, across both close() and the Cleaner
+                writeln!(out, "{}\t@Override", indent)?;
+                writeln!(out, "{}\tpublic void close() {{", indent)?;
+                writeln!(out, "{}\t\tthis.handle = 0;", indent)?;
+                writeln!(out, "{}\t\tthis.cleanable.clean();", indent)?;
+                writeln!(out, "{}\t}}", indent)?;
+                writeln!(out)?;
+
+                writeln!(out, "{}\tprivate static native void release(long handle);", indent)?;
+                writeln!(out)?;
+
+                writeln!(out, "{}\tprivate static final class Release implements Runnable {{", indent)?;
+                writeln!(out, "{}\t\tprivate final long handle;", indent)?;
+                writeln!(out)?;
+                writeln!(out, "{}\t\tprivate Release(long handle) {{", indent)?;
+                writeln!(out, "{}\t\t\tthis.handle = handle;", indent)?;
+                writeln!(out, "{}\t\t}}", indent)?;
+                writeln!(out)?;
+                writeln!(out, "{}\t\t@Override", indent)?;
+                writeln!(out, "{}\t\tpublic void run() {{", indent)?;
+                writeln!(out, "{}\t\t\trelease(handle);", indent)?;
+                writeln!(out, "{}\t\t}}", indent)?;
+                writeln!(out, "{}\t}}", indent)?;
+
+                if !methods.is_empty() {
+                    writeln!(out)?;
+                }
+                // Methods
+                for method in methods {
+                    method.write_method_indented(out, indent, *subclassable)?
+                }
+            }
+            JClassDecl::Enum { name, variants, methods, body, .. } => {
+                write!(out, "{}{}enum {} {{", indent, access_modifier, name)?;
 
                 if variants.len() > 0 {
                     writeln!(out)?;
@@ -196,39 +765,51 @@ impl JClassDecl {
                     } else {
                         writeln!(out, ",")?;
                     }
-                    write!(out, "\t{}", variant)?;
+                    write!(out, "{}\t{}", indent, variant)?;
                 }
                 if variants.len() > 0 {
                     writeln!(out, ";")?;
                 }
-
-                if methods.len() > 0 {
+                // Enum constants must come first; The initializer and verbatim body follow them
+                if variants.is_empty() && (load_library.is_some() || !body.is_empty()) {
+                    writeln!(out)?;
+                    writeln!(out, "{}\t;", indent)?;
+                }
+                if load_library.is_some() {
+                    writeln!(out)?;
+                    write_load_library(out, indent, load_library, options.class_load_hook)?;
+                } else if !methods.is_empty() {
                     writeln!(out)?;
                 }
                 // Methods
                 for method in methods {
-                    method.write_method(out)?
+                    method.write_method_indented(out, indent, false)?
                 }
-
-                write!(out, "}}")?;
             }
-            JClassDecl::EnumTaggedUnion { name: enum_name, package, variants, methods } => {
-                writeln!(out, "package {};\n", package)?;
+            JClassDecl::EnumTaggedUnion { name: enum_name, variants, methods, .. } => {
+                // Sealed classes require Java 17; Older versions fall back to an open abstract class
+                let sealed_modifier = if options.java_version >= 17 { "sealed " } else { "" };
+                writeln!(out, "{}{}{}abstract {}class {} {{", indent, access_modifier, static_modifier, sealed_modifier, enum_name)?;
+                write_load_library(out, indent, load_library, options.class_load_hook)?;
 
-                write!(out, "public abstract sealed class {} {{", enum_name)?;
-
-                if variants.len() > 0 {
+                // Tags; Consumers without pattern matching switch over `getTag()` rather than testing each variant with instanceof
+                for (tag, variant) in variants.iter().enumerate() {
+                    writeln!(out, "{}\tpublic static final int {} = {};", indent, variant.tag_constant(), tag)?;
+                }
+                if !variants.is_empty() {
                     writeln!(out)?;
                 }
+                writeln!(out, "{}\tpublic abstract int getTag();", indent)?;
+
                 for variant in variants {
-                    write!(out, "\tpublic static final class {} extends {} {{", variant.name, enum_name)?;
+                    writeln!(out)?;
+                    write_generated(out, &format!("{}\t", indent), options)?;
+                    writeln!(out, "{}\tpublic static final class {} extends {} {{", indent, variant.name, enum_name)?;
 
-                    if variant.fields.len() > 0 {
-                        writeln!(out)?;
-                    }
                     // Fields
                     for field in &variant.fields {
-                        writeln!(out, "\t\t{} {} {};", field.access, field.jtype, field.name)?;
+                        write_json_annotation(out, &format!("{}\t\t", indent), field, options)?;
+                        writeln!(out, "{}\t\t{} {} {}{};", indent, field.access, field.jtype, field.name, field.initializer_clause())?;
                     }
 
                     if variant.fields.len() > 0 {
@@ -236,7 +817,7 @@ impl JClassDecl {
                     }
 
                     // Constructor
-                    write!(out, "\t\tpublic {}(", variant.name)?;
+                    write!(out, "{}\t\tpublic {}(", indent, variant.name)?;
                     for (idx, field) in variant.fields.iter().enumerate() {
                         write!(out, "{} {}", field.jtype, field.name)?;
                         if idx != variant.fields.len() - 1 {
@@ -246,14 +827,19 @@ impl JClassDecl {
                     if variant.fields.len() > 0 {
                         writeln!(out, ") {{")?;
                         for field in &variant.fields {
-                            writeln!(out, "\t\t\tthis.{} = {};", field.name, field.name)?;
+                            writeln!(out, "{}\t\t\tthis.{} = {};", indent, field.name, field.name)?;
                         }
-                        writeln!(out, "\t\t}}")?;
+                        writeln!(out, "{}\t\t}}", indent)?;
                     } else {
                         writeln!(out, ") {{}}")?;
                     }
+                    writeln!(out)?;
 
-                    writeln!(out, "\t}}")?;
+                    writeln!(out, "{}\t\t@Override", indent)?;
+                    writeln!(out, "{}\t\tpublic int getTag() {{", indent)?;
+                    writeln!(out, "{}\t\t\treturn {};", indent, variant.tag_constant())?;
+                    writeln!(out, "{}\t\t}}", indent)?;
+                    writeln!(out, "{}\t}}", indent)?;
                 }
 
                 if methods.len() > 0 {
@@ -261,71 +847,592 @@ impl JClassDecl {
                 }
                 // Methods
                 for method in methods {
-                    method.write_method(out)?
+                    method.write_method_indented(out, indent, false)?
                 }
+            }
+            JClassDecl::Source { source, .. } => {
+                // Source includes the closing brace
+                let lines = source.trim().lines()
+                    .map(|line| if line.is_empty() { String::new() } else { format!("{}{}", indent, line) })
+                    .collect::<Vec<_>>();
+                write!(out, "{}", lines.join("\n"))?;
+                return Ok(());
+            }
+        }
 
-                write!(out, "}}")?;
+        // Verbatim body, re-indented to the class
+        for block in self.body() {
+            writeln!(out)?;
+            for line in block.trim().lines() {
+                if line.is_empty() {
+                    writeln!(out)?;
+                } else {
+                    writeln!(out, "{}\t{}", indent, line)?;
+                }
             }
         }
 
+        // Nested classes
+        let class_path = self.class_path();
+        let nested_indent = format!("{}\t", indent);
+        for nested in classes.iter().filter(|class| class.package() == self.package() && class.nested_in() == Some(&*class_path)) {
+            writeln!(out)?;
+            nested.write_class(classes, options, &nested_indent, out)?;
+            writeln!(out)?;
+        }
+
+        write!(out, "{}}}", indent)?;
+
         Ok(())
     }
 }
 
+/// Module-wide options applied when writing classes, see [`JModuleDecl`]
+struct WriteOptions<'m> {
+    load_library: Option<&'static str>,
+    class_load_hook: Option<&'static str>,
+    records: bool,
+    java_version: u32,
+    generated: Option<&'m GeneratedAnnotation>,
+    json_annotations: Option<JsonAnnotations>,
+}
+
+impl Default for WriteOptions<'_> {
+    fn default() -> Self {
+        WriteOptions { load_library: None, class_load_hook: None, records: false, java_version: DEFAULT_JAVA_VERSION, generated: None, json_annotations: None }
+    }
+}
+
+/// Write the `@Generated` annotation on its own line, if the module is annotated
+fn write_generated<W: io::Write>(out: &mut W, indent: &str, options: &WriteOptions) -> io::Result<()> {
+    if let Some(generated) = options.generated {
+        // javax.annotation.Generated was replaced in Java 9, and removed from the JDK in Java 11
+        let annotation = if options.java_version >= 9 { "javax.annotation.processing.Generated" } else { "javax.annotation.Generated" };
+        match &generated.date {
+            Some(date) => writeln!(out, "{}@{}(value = \"instant-coffee\", date = \"{}\")", indent, annotation, date.escape_default())?,
+            None => writeln!(out, "{}@{}(\"instant-coffee\")", indent, annotation)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write the JSON annotation of a field on its own line, if the module is annotated
+fn write_json_annotation<W: io::Write>(out: &mut W, indent: &str, field: &JField, options: &WriteOptions) -> io::Result<()> {
+    if let Some(json_annotations) = options.json_annotations {
+        writeln!(out, "{}{}", indent, json_annotations.annotation(field))?;
+    }
+    Ok(())
+}
+
+/// Write fields as the component list of a record, with their JSON annotations if the module is annotated
+fn write_record_components<W: io::Write>(out: &mut W, fields: &[JField], options: &WriteOptions) -> io::Result<()> {
+    for (idx, field) in fields.iter().enumerate() {
+        if let Some(json_annotations) = options.json_annotations {
+            write!(out, "{} ", json_annotations.annotation(field))?;
+        }
+        write!(out, "{} {}", field.jtype, field.name)?;
+        if idx != fields.len() - 1 {
+            write!(out, ", ")?;
+        }
+    }
+    Ok(())
+}
+
+/// Write each annotation on its own line
+fn write_annotations<W: io::Write>(out: &mut W, indent: &str, annotations: &[&str]) -> io::Result<()> {
+    for annotation in annotations {
+        writeln!(out, "{}{}", indent, annotation)?;
+    }
+    Ok(())
+}
+
+/// Write a public no-arg constructor passing the initializers of `fields` to the constructor taking all fields, see `JClassDecl::Class::default_constructor`
+fn write_default_constructor<W: io::Write>(out: &mut W, indent: &str, class_name: &str, fields: &[JField]) -> io::Result<()> {
+    writeln!(out, "{}\tpublic {}() {{", indent, class_name)?;
+    let arguments = fields.iter()
+        .map(|field| field.initializer.unwrap_or_else(|| zero_value(field.jtype)))
+        .collect::<Vec<_>>();
+    writeln!(out, "{}\t\tthis({});", indent, arguments.join(", "))?;
+    writeln!(out, "{}\t}}", indent)
+}
+
+/// Default value of fields of the given Java type, as verbatim in Java source; Narrowing casts are explicit, as constructor arguments are not narrowed implicitly
+fn zero_value(jtype: &str) -> &'static str {
+    match jtype {
+        "boolean" => "false",
+        "byte" => "(byte) 0",
+        "short" => "(short) 0",
+        "char" => "'\\0'",
+        "int" => "0",
+        "long" => "0L",
+        "float" => "0.0f",
+        "double" => "0.0",
+        _ => "null"
+    }
+}
+
+/// Write a static initializer loading the native library and calling the class-load hook, followed by a blank line, if there is a library to load
+fn write_load_library<W: io::Write>(out: &mut W, indent: &str, load_library: Option<&str>, class_load_hook: Option<&str>) -> io::Result<()> {
+    if let Some(library) = load_library {
+        writeln!(out, "{}\tstatic {{", indent)?;
+        writeln!(out, "{}\t\tSystem.loadLibrary(\"{}\");", indent, library.escape_default())?;
+        if let Some(hook_class) = class_load_hook {
+            writeln!(out, "{}\t\t{}.initialize();", indent, hook_class)?;
+        }
+        writeln!(out, "{}\t}}", indent)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Write a `withFieldName(value)` method, returning a copy of the object with `field` replaced
+fn write_with_method<W: io::Write>(out: &mut W, indent: &str, class_name: &str, field: &JField, fields: &[JField]) -> io::Result<()> {
+    writeln!(out, "{}\tpublic {} with{}({} {}) {{", indent, class_name, capitalize(field.name), field.jtype, field.name)?;
+    write!(out, "{}\t\treturn new {}(", indent, class_name)?;
+    for (idx, other) in fields.iter().enumerate() {
+        if other.name == field.name {
+            write!(out, "{}", other.name)?;
+        } else {
+            write!(out, "this.{}", other.name)?;
+        }
+        if idx != fields.len() - 1 {
+            write!(out, ", ")?;
+        }
+    }
+    writeln!(out, ");")?;
+    writeln!(out, "{}\t}}", indent)
+}
+
+/// Uppercase the first character of a Java identifier, e.g. for `withField` method names
+fn capitalize(identifier: &str) -> String {
+    let mut chars = identifier.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
+
+/// Write fields as a comma-separated parameter list, e.g. for constructors
+fn write_field_parameters<W: io::Write>(out: &mut W, fields: &[JField]) -> io::Result<()> {
+    for (idx, field) in fields.iter().enumerate() {
+        write!(out, "{} {}", field.jtype, field.name)?;
+        if idx != fields.len() - 1 {
+            write!(out, ", ")?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a method parameter list (without parentheses) from `(name, type)` pairs
+fn write_parameters<'a, W: io::Write>(out: &mut W, parameters: impl Iterator<Item=(&'a str, &'a str)>) -> io::Result<()> {
+    for (idx, (name, param_type)) in parameters.enumerate() {
+        if idx != 0 {
+            write!(out, ", ")?;
+        }
+        write!(out, "{} {}", param_type, name)?;
+    }
+    Ok(())
+}
+
 /// Struct representing an abstract Java package
 ///
+/// Classes may be in sub-packages of the module package, when generated from nested rust modules
+///
 /// (Currently) does not support module-info files
 pub struct JModuleDecl {
     /// Module name, fully qualified, as verbatim in Java source
     pub name: &'static str,
-    /// Classes in this module
+    /// Classes in this module and its sub-packages
     pub classes: Vec<JClassDecl>,
+    /// Native library loaded with `System.loadLibrary` by a static initializer in each top-level class, if any
+    pub load_library: Option<&'static str>,
+    /// Qualified name of the class whose static `initialize()` method is called by the same static initializers, once the library is loaded; None if there is no class-load hook
+    ///
+    /// Set by the `#[java(init, on_class_load)]` lifecycle hook of the `jmodule` macro, so that rust-side setup has run before any exported method is called
+    pub class_load_hook: Option<&'static str>,
+    /// If true, regular classes ([`JClassDecl::Class`]) are written as Java records
+    pub records: bool,
+    /// Java language version the sources are written for, see [`DEFAULT_JAVA_VERSION`]
+    pub java_version: u32,
+    /// Output directory for the generated sources, configured by the `out_dir` key of `instant-coffee.toml`; See [`JModuleDecl::write_to_out_dir`]
+    pub out_dir: Option<&'static str>,
+    /// `@Generated` annotation written on every class of this module, if any
+    pub generated: Option<GeneratedAnnotation>,
+    /// JSON library whose annotations are written on every field of this module, if any
+    pub json_annotations: Option<JsonAnnotations>,
+    /// Name of a facade class in the module package, forwarding to every static method of the module's exported classes; None if no facade is written
+    ///
+    /// Gives Java consumers a single entry point into the module. Methods are named as declared, or prefixed with their class name where
+    /// two classes declare the same static method, e.g. `counterCreate` or `counter_create`
+    pub facade: Option<&'static str>,
 }
 
+/// Options for [`JModuleDecl::write_to_dir_with`]
+#[derive(Clone, Debug, Default)]
+pub struct DirOptions {
+    /// If true, `.java` files in the package directories of this module which do not belong to any of its classes are removed,
+    /// so that classes of deleted rust types do not linger. Sub-packages which no longer have any classes are not cleaned
+    ///
+    /// Hand-written sources in the same package directories are removed as well; Only use this on a directory dedicated to generated sources
+    pub clean: bool,
+}
+
+/// Options for [`JModuleDecl::write_jar_with`]
+#[cfg(feature = "codegen-jar")]
+#[derive(Clone, Debug)]
+pub struct JarOptions {
+    /// Compression of every file in the jar
+    pub compression: JarCompression,
+    /// Deflate compression level, from 0 (fastest) to 9 (smallest); None for the default level. Ignored for stored files
+    pub compression_level: Option<i64>,
+    /// Additional files written after the sources, as pairs of path within the jar and content, e.g. `("META-INF/LICENSE", ...)`
+    ///
+    /// Paths use `/` as separator; Files in `META-INF/` are written before the sources. A path which is already in the jar is an error
+    pub extra_files: Vec<(String, Vec<u8>)>,
+}
+
+#[cfg(feature = "codegen-jar")]
+impl Default for JarOptions {
+    fn default() -> Self {
+        JarOptions { compression: JarCompression::Deflated, compression_level: None, extra_files: Vec::new() }
+    }
+}
+
+#[cfg(feature = "codegen-jar")]
+impl JarOptions {
+    /// These options, with a default `META-INF/MANIFEST.MF` added unless they have one
+    fn with_manifest(&self) -> JarOptions {
+        let mut options = self.clone();
+        if !options.extra_files.iter().any(|(path, _)| path == "META-INF/MANIFEST.MF") {
+            options.extra_files.insert(0, ("META-INF/MANIFEST.MF".to_string(), b"Manifest-Version: 1.0\r\nCreated-By: instant-coffee\r\n\r\n".to_vec()));
+        }
+        options
+    }
+
+    /// Zip options of each file written with these options
+    fn file_options(&self) -> zip::write::SimpleFileOptions {
+        match self.compression {
+            JarCompression::Stored => zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Stored),
+            JarCompression::Deflated => zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated)
+                .compression_level(self.compression_level),
+        }
+    }
+}
+
+/// Compression method of files in a jar, see [`JarOptions`]
+#[cfg(feature = "codegen-jar")]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum JarCompression {
+    /// Files are stored uncompressed, for jars which are compressed as a whole or read through memory mapping
+    Stored,
+    /// Files are compressed with deflate, the default for jar files
+    Deflated,
+}
+
+/// JSON library annotations marking the serialized name of each field, so generated classes serialize with the rust field names (or `#[java(json_name = "...")]`) regardless of the naming policy
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum JsonAnnotations {
+    /// `@com.fasterxml.jackson.annotation.JsonProperty`
+    Jackson,
+    /// `@com.google.gson.annotations.SerializedName`
+    Gson,
+}
+
+impl JsonAnnotations {
+    /// Annotation for the given field, as verbatim in Java source
+    pub fn annotation(self, field: &JField) -> String {
+        let annotation = match self {
+            JsonAnnotations::Jackson => "com.fasterxml.jackson.annotation.JsonProperty",
+            JsonAnnotations::Gson => "com.google.gson.annotations.SerializedName",
+        };
+        format!("@{}(\"{}\")", annotation, field.serialized_name.escape_default())
+    }
+}
+
+/// `@javax.annotation.processing.Generated("instant-coffee")` annotation, which static analysis and coverage tools use to recognize and exclude generated code
+///
+/// Sources targeting Java 8 use `javax.annotation.Generated` instead
+#[derive(Clone, Debug, Default)]
+pub struct GeneratedAnnotation {
+    /// Date the sources were generated, in ISO 8601 format such as "2024-05-01T12:00:00Z"; Omitted if None, keeping the generated sources reproducible
+    pub date: Option<String>,
+}
+
+impl GeneratedAnnotation {
+    /// Annotation dated with the current time, in UTC
+    pub fn dated_now() -> GeneratedAnnotation {
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let (days, time) = (seconds / 86400, seconds % 86400);
+
+        // Civil date from days since 1970-01-01, for the proleptic Gregorian calendar
+        let days = days + 719468;
+        let era = days / 146097;
+        let day_of_era = days % 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+        let year = year_of_era + era * 400 + (month <= 2) as u64;
+
+        GeneratedAnnotation {
+            date: Some(format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, time / 3600, time / 60 % 60, time % 60))
+        }
+    }
+}
+
+/// Java language version generated sources target if not configured otherwise
+///
+/// Tagged unions are written as sealed classes from Java 17, records require Java 16
+pub const DEFAULT_JAVA_VERSION: u32 = 17;
+
 impl JModuleDecl {    // TODO: module-info.java generation
+    /// Remap this module from package `from` to package `to`, e.g. `com.example.internal` to `com.example.bindings.v2`
+    ///
+    /// Rewrites the module name, class packages, and qualified names in field and method types. Sub-packages of `from` are moved along with it.
+    /// Intended for shading and versioned publishing; Call before [`JModuleDecl::write_to_dir`] or [`JModuleDecl::write_jar`]
+    ///
+    /// Note: This only affects the generated Java source. JNI symbol names and class lookups in the compiled binary still refer to the original package.
+    pub fn remap_package(&mut self, from: &str, to: &str) {
+        self.name = remap_qualified_names(self.name, from, to);
+        for class in &mut self.classes {
+            class.remap_package(from, to);
+        }
+    }
+
+    /// True if Java code outside the package of `class` can use it; The class, and every class it is nested in, is public or protected
+    fn is_exported(&self, class: &JClassDecl) -> bool {
+        matches!(class.access(), JAccessModifier::Public | JAccessModifier::Protected)
+            && class.nested_in().is_none_or(|outer_path| {
+                self.classes.iter()
+                    .find(|outer| outer.package() == class.package() && outer.class_path() == outer_path)
+                    .is_none_or(|outer| self.is_exported(outer))
+            })
+    }
+
     /// Write this module to the specified directory
     ///
-    /// If module name is fully qualified, package directory tree is generated
+    /// Package directory tree is generated for each class package, nested classes are written into their outer class' file
     ///
-    /// Does not clear specified directory, but may overwrite files
+    /// Does not clear specified directory, but may overwrite files. Files whose content is unchanged are not rewritten, keeping their modification time
+    /// so that Java build tools do not recompile them
     pub fn write_to_dir<T: AsRef<std::path::Path>>(&self, path: T) -> io::Result<()> {
-        let mut package_path = PathBuf::from(path.as_ref());
-        package_path.push(self.name.replace('.', "/"));
+        self.write_to_dir_with(path, &DirOptions::default())
+    }
+
+    /// Write this module to the specified directory, see [`JModuleDecl::write_to_dir`] and [`DirOptions`]
+    pub fn write_to_dir_with<T: AsRef<std::path::Path>>(&self, path: T, options: &DirOptions) -> io::Result<()> {
+        let mut written = Vec::new();
 
-        std::fs::create_dir_all(&package_path)?;
+        if let Some(facade) = self.facade {
+            let package_path = path.as_ref().join(self.name.replace('.', "/"));
+            std::fs::create_dir_all(&package_path)?;
+
+            let mut buffer = Vec::new();
+            self.write_facade(facade, &mut buffer)?;
+            let file_path = package_path.join(format!("{}.java", facade));
+            write_if_changed(&file_path, &buffer)?;
+            written.push(file_path);
+        }
 
-        for class in &self.classes {
+        for class in self.classes.iter().filter(|class| class.nested_in().is_none()) {
+            let mut package_path = PathBuf::from(path.as_ref());
+            package_path.push(class.package().replace('.', "/"));
+
+            std::fs::create_dir_all(&package_path)?;
+
+            let mut buffer = Vec::new();
+            class.write_class_file_in_module(self, &mut buffer)?;
             let file_path = package_path.join(format!("{}.java", class.class_name()));
-            class.write_class_file(&mut File::create(file_path)?)?;
+            write_if_changed(&file_path, &buffer)?;
+            written.push(file_path);
+        }
+
+        if options.clean {
+            // The module package is cleaned even if no class is left in it
+            let mut package_paths = written.iter().filter_map(|file_path| file_path.parent()).map(PathBuf::from).collect::<Vec<_>>();
+            package_paths.push(path.as_ref().join(self.name.replace('.', "/")));
+            package_paths.sort();
+            package_paths.dedup();
+
+            for package_path in package_paths {
+                let entries = match std::fs::read_dir(&package_path) {
+                    Ok(entries) => entries,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => Err(e)?
+                };
+                for entry in entries {
+                    let file_path = entry?.path();
+                    if file_path.extension().is_some_and(|extension| extension == "java") && file_path.is_file() && !written.contains(&file_path) {
+                        std::fs::remove_file(file_path)?;
+                    }
+                }
+            }
         }
 
         Ok(())
     }
 
+    /// Write the facade class `name` of this module, see [`JModuleDecl::facade`]
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidInput`] if the module package already has a class of that name
+    fn write_facade<W: io::Write>(&self, name: &str, out: &mut W) -> io::Result<()> {
+        if self.classes.iter().any(|class| class.package() == self.name && class.nested_in().is_none() && class.class_name() == name) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("facade {} conflicts with a class of the same name in {}", name, self.name)));
+        }
+
+        // Classes that are not exported are internal to their package, and are left out of the facade
+        let static_methods = self.classes.iter()
+            .filter(|class| self.is_exported(class))
+            .flat_map(|class| class.methods().iter().filter(|method| method.is_static).map(move |method| (class, method)))
+            .collect::<Vec<_>>();
+        // Java overloads may share a name, but not their parameter types
+        let overloads_of = |method: &JMethod| static_methods.iter()
+            .filter(|(_, other)| other.name == method.name && other.inputs.iter().map(|(_, jtype)| jtype).eq(method.inputs.iter().map(|(_, jtype)| jtype)))
+            .count();
+
+        writeln!(out, "package {};\n", self.name)?;
+        let options = WriteOptions { generated: self.generated.as_ref(), java_version: self.java_version, ..WriteOptions::default() };
+        write_generated(out, "", &options)?;
+        writeln!(out, "public final class {} {{", name)?;
+        writeln!(out, "\tprivate {}() {{}}", name)?;
+        for (class, method) in &static_methods {
+            let class_name = format!("{}.{}", class.package(), class.class_path());
+            let method_name = if overloads_of(method) > 1 {
+                let prefix = class.class_path().replace('.', "");
+                let mut prefix_chars = prefix.chars();
+                let prefix = prefix_chars.next().into_iter().flat_map(char::to_lowercase).chain(prefix_chars).collect::<String>();
+                if method.name.contains('_') { format!("{}_{}", prefix, method.name) } else { format!("{}{}", prefix, capitalize(method.name)) }
+            } else {
+                method.name.to_string()
+            };
+
+            writeln!(out)?;
+            write!(out, "\tpublic static {} {}(", method.output, method_name)?;
+            write_parameters(out, method.inputs.iter().copied())?;
+            write!(out, ")")?;
+            method.write_throws(out)?;
+            writeln!(out, " {{")?;
+            let return_keyword = if method.output == "void" { "" } else { "return " };
+            write!(out, "\t\t{}{}.{}(", return_keyword, class_name, method.name)?;
+            for (idx, (name, _)) in method.inputs.iter().enumerate() {
+                if idx != 0 {
+                    write!(out, ", ")?;
+                }
+                write!(out, "{}", name)?;
+            }
+            writeln!(out, ");")?;
+            writeln!(out, "\t}}")?;
+        }
+        writeln!(out, "}}")
+    }
+
+    /// Write this module to the output directory configured in `instant-coffee.toml`, see [`JModuleDecl::write_to_dir`]
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if no output directory is configured
+    pub fn write_to_out_dir(&self) -> io::Result<()> {
+        match self.out_dir {
+            Some(out_dir) => self.write_to_dir(out_dir),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "no out_dir configured in instant-coffee.toml"))
+        }
+    }
+
     /// Write this module to a jar file output stream
     ///
-    /// If module name is fully qualified, package directory tree is generated
+    /// Package directory tree is generated for each class package, nested classes are written into their outer class' file
     #[cfg(feature = "codegen-jar")]
     pub fn write_jar<W: io::Write + io::Seek>(&self, out: &mut W) -> io::Result<()> {
-        use zip::result::ZipError;
-        use zip::write::SimpleFileOptions;
+        self.write_jar_with(out, &JarOptions::default())
+    }
 
-        let path = self.name.replace('.', "/");
-        let mut writer = zip::ZipWriter::new(out);
-        for class in &self.classes {
-            writer.start_file(format!("{}/{}.java", path, class.class_name()), SimpleFileOptions::default()).unwrap();
+    /// Write this module to a jar file output stream, see [`JModuleDecl::write_jar`] and [`JarOptions`]
+    #[cfg(feature = "codegen-jar")]
+    pub fn write_jar_with<W: io::Write + io::Seek>(&self, out: &mut W, options: &JarOptions) -> io::Result<()> {
+        write_jar_files(out, options, |writer, file_options| {
+            if let Some(facade) = self.facade {
+                writer.start_file(format!("{}/{}.java", self.name.replace('.', "/"), facade), file_options).map_err(zip_error)?;
+                self.write_facade(facade, writer)?;
+            }
+            for class in self.classes.iter().filter(|class| class.nested_in().is_none()) {
+                let path = class.package().replace('.', "/");
+                writer.start_file(format!("{}/{}.java", path, class.class_name()), file_options).map_err(zip_error)?;
 
-            class.write_class_file(&mut writer)?;
-        }
+                class.write_class_file_in_module(self, writer)?;
+            }
+            Ok(())
+        })
+    }
 
-        writer.finish()
-            .map_err(|e| match e {
-                ZipError::Io(err) => err,
-                e => io::Error::new(io::ErrorKind::Other, e)
-            })
-            .map(|_| ())
+    /// Write this module as a Maven-style sources jar (`<artifact>-<version>-sources.jar`), with a `META-INF/MANIFEST.MF`
+    ///
+    /// The layout matches [`JModuleDecl::write_jar`]; See [`JModuleDecl::write_classes_jar`] for the matching binary jar
+    #[cfg(feature = "codegen-jar")]
+    pub fn write_sources_jar<W: io::Write + io::Seek>(&self, out: &mut W, options: &JarOptions) -> io::Result<()> {
+        self.write_jar_with(out, &options.with_manifest())
+    }
+
+    /// Write this module as a Maven-style binary jar (`<artifact>-<version>.jar`), with a `META-INF/MANIFEST.MF`
+    ///
+    /// The sources are compiled with `javac` for [`JModuleDecl::java_version`], in a temporary directory which is removed afterwards.
+    /// `javac` is run from `$JAVA_HOME/bin` if `JAVA_HOME` is set, and from the `PATH` otherwise. Libraries the sources depend on, such as those of [`JModuleDecl::json_annotations`], must be on the `classpath`
+    ///
+    /// Returns an error of kind [`io::ErrorKind::Other`] with the output of `javac` if compilation fails
+    #[cfg(feature = "codegen-jar")]
+    pub fn write_classes_jar<W: io::Write + io::Seek>(&self, out: &mut W, options: &JarOptions, classpath: &[&std::path::Path]) -> io::Result<()> {
+        static BUILD_COUNT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+        let build_count = BUILD_COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let build_dir = std::env::temp_dir().join(format!("instant-coffee-{}-{}-{}", std::process::id(), build_count, self.name));
+        let result = self.compile_classes_jar(&build_dir, out, options, classpath);
+        // The jar is written or failed already; A leftover directory in the temporary directory is not an error
+        let _ = std::fs::remove_dir_all(&build_dir);
+        result
     }
 
+    /// Compile this module in `build_dir` and write the class files as a jar, see [`JModuleDecl::write_classes_jar`]
+    #[cfg(feature = "codegen-jar")]
+    fn compile_classes_jar<W: io::Write + io::Seek>(&self, build_dir: &std::path::Path, out: &mut W, options: &JarOptions, classpath: &[&std::path::Path]) -> io::Result<()> {
+        let sources_dir = build_dir.join("sources");
+        let classes_dir = build_dir.join("classes");
+        self.write_to_dir(&sources_dir)?;
+        std::fs::create_dir_all(&classes_dir)?;
+
+        let javac = match std::env::var_os("JAVA_HOME") {
+            Some(java_home) => PathBuf::from(java_home).join("bin").join("javac"),
+            None => PathBuf::from("javac"),
+        };
+        let mut command = std::process::Command::new(javac);
+        command.arg("--release").arg(self.java_version.to_string())
+            .arg("-encoding").arg("UTF-8")
+            .arg("-d").arg(&classes_dir);
+        if !classpath.is_empty() {
+            command.arg("-classpath").arg(std::env::join_paths(classpath).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?);
+        }
+        command.args(files_with_extension(&sources_dir, "java")?);
+
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!("javac failed with {}:\n{}", output.status, String::from_utf8_lossy(&output.stderr))));
+        }
+
+        write_jar_files(out, &options.with_manifest(), |writer, file_options| {
+            for class_file in files_with_extension(&classes_dir, "class")? {
+                let path = class_file.strip_prefix(&classes_dir)
+                    .map_err(io::Error::other)?
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                writer.start_file(path, file_options).map_err(zip_error)?;
+                io::Write::write_all(writer, &std::fs::read(&class_file)?)?;
+            }
+            Ok(())
+        })
+    }
 
     /// Write this module to a [`FFIJarBlob`]
     ///
@@ -339,6 +1446,132 @@ impl JModuleDecl {    // TODO: module-info.java generation
     }
 }
 
+/// Convert a zip error to an I/O error, unwrapping I/O errors of the underlying stream
+#[cfg(feature = "codegen-jar")]
+fn zip_error(error: zip::result::ZipError) -> io::Error {
+    match error {
+        zip::result::ZipError::Io(err) => err,
+        e => io::Error::new(io::ErrorKind::Other, e)
+    }
+}
+
+/// Write a jar of the files written by `write_files`, and the extra files of `options`
+///
+/// Extra files in `META-INF/` are written first, as JarInputStream only finds the manifest among the first entries; Other extra files are written last
+#[cfg(feature = "codegen-jar")]
+fn write_jar_files<W: io::Write + io::Seek>(out: &mut W, options: &JarOptions, write_files: impl FnOnce(&mut zip::ZipWriter<&mut W>, zip::write::SimpleFileOptions) -> io::Result<()>) -> io::Result<()> {
+    let file_options = options.file_options();
+    let mut writer = zip::ZipWriter::new(out);
+    let (meta_files, extra_files) = options.extra_files.iter().partition::<Vec<_>, _>(|(path, _)| path.starts_with("META-INF/"));
+    for (path, contents) in meta_files {
+        writer.start_file(path.as_str(), file_options).map_err(zip_error)?;
+        io::Write::write_all(&mut writer, contents)?;
+    }
+    write_files(&mut writer, file_options)?;
+    for (path, contents) in extra_files {
+        writer.start_file(path.as_str(), file_options).map_err(zip_error)?;
+        io::Write::write_all(&mut writer, contents)?;
+    }
+
+    writer.finish()
+        .map_err(zip_error)
+        .map(|_| ())
+}
+
+/// Files with extension `extension` in directory `dir` and its subdirectories, sorted by path so that jars are reproducible
+#[cfg(feature = "codegen-jar")]
+fn files_with_extension(dir: &std::path::Path, extension: &str) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|file_extension| file_extension == extension) {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Write `contents` to the file at `path`, unless that file already has exactly this content
+fn write_if_changed(path: &std::path::Path, contents: &[u8]) -> io::Result<()> {
+    match std::fs::read(path) {
+        Ok(existing) if existing == contents => Ok(()),
+        Ok(_) => std::fs::write(path, contents),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => std::fs::write(path, contents),
+        Err(e) => Err(e)
+    }
+}
+
+/// ` implements A, B` clause of a class declaration, or an empty string if the class implements no interfaces
+fn implements_clause(interfaces: &[&str]) -> String {
+    if interfaces.is_empty() {
+        String::new()
+    } else {
+        format!(" implements {}", interfaces.join(", "))
+    }
+}
+
+/// Rewrite all qualified names in `source` that are in package `from` (or a sub-package thereof) to package `to`
+///
+/// `source` may be any fragment of Java source, such as `com.example.Foo[]`; Only complete qualified names are rewritten, `com.exampleFoo` does not match package `com.example`
+///
+/// returns: `source` if nothing was rewritten, otherwise a newly allocated (leaked) string
+fn remap_qualified_names(source: &'static str, from: &str, to: &str) -> &'static str {
+    let is_name_char = |char: char| char.is_ascii_alphanumeric() || char == '_' || char == '$' || char == '.';
+
+    let mut remapped = String::with_capacity(source.len());
+    let mut changed = false;
+    let mut rest = source;
+    while let Some(start) = rest.find(is_name_char) {
+        remapped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|char| !is_name_char(char)).unwrap_or(rest.len());
+        let name = &rest[..end];
+        match name.strip_prefix(from) {
+            Some(suffix) if suffix.is_empty() || suffix.starts_with('.') => {
+                remapped.push_str(to);
+                remapped.push_str(suffix);
+                changed = true;
+            }
+            _ => remapped.push_str(name),
+        }
+        rest = &rest[end..];
+    }
+    remapped.push_str(rest);
+
+    if changed {
+        remapped.leak()
+    } else {
+        source
+    }
+}
+
+/// Symbol of the [`FFIJarBlob`] export of a `jmodule` with package `package`, in crate `crate_name` version `crate_version`
+///
+/// Exports are namespaced by crate so that several crates using instant-coffee can be loaded into one process, while linking two copies of the same crate version
+/// still fails with a duplicate symbol error rather than one silently replacing the other. `crate_name` is the name as used in Rust code, with dashes replaced by underscores
+#[cfg(feature = "codegen-ffi")]
+pub fn ffi_export_symbol(crate_name: &str, crate_version: &str, package: &str) -> String {
+    // Must match the ffi_export_symbol! macro
+    format!("jmodule_export_{}_{}_{}", crate_name, crate_version, package)
+}
+
+/// Symbol of the [`FFIJarBlob`](crate::codegen::FFIJarBlob) export of a `jmodule` with package `$package`, in the crate being compiled; Expands to a string literal, see [`codegen::ffi_export_symbol`](crate::codegen::ffi_export_symbol)
+///
+/// Used by `jmodule` as the `export_name` of the export
+#[cfg(feature = "codegen-ffi")]
+#[macro_export]
+macro_rules! ffi_export_symbol {
+    ($package:literal) => {
+        concat!("jmodule_export_", env!("CARGO_CRATE_NAME"), "_", env!("CARGO_PKG_VERSION"), "_", $package)
+    };
+}
+
 /// FFI-safe byte array, enabling retrieval of java code from compiled binary
 #[cfg(feature = "codegen-ffi")]
 #[repr(C)]
@@ -365,9 +1598,176 @@ impl FFIJarBlob {
     }
 }
 
+/// Rust values that can be written as a Java expression, for defaults baked into the generated sources; See `JClassDecl::Class::default_constructor`
+///
+/// The expression must evaluate to the Java value the rust value converts to
+pub trait JavaLiteral {
+    /// Java expression of this value, as verbatim in Java source
+    fn java_literal(&self) -> String;
+}
+
+impl JavaLiteral for bool {
+    fn java_literal(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl JavaLiteral for i8 {
+    fn java_literal(&self) -> String {
+        format!("(byte) {}", self)
+    }
+}
+
+impl JavaLiteral for u8 {
+    fn java_literal(&self) -> String {
+        format!("(byte) {}", *self as i8)
+    }
+}
+
+impl JavaLiteral for i16 {
+    fn java_literal(&self) -> String {
+        format!("(short) {}", self)
+    }
+}
+
+impl JavaLiteral for u16 {
+    fn java_literal(&self) -> String {
+        format!("(short) {}", *self as i16)
+    }
+}
+
+impl JavaLiteral for i32 {
+    fn java_literal(&self) -> String {
+        // -2147483648 is not a valid literal, as 2147483648 is out of range before it is negated
+        if *self == i32::MIN { "Integer.MIN_VALUE".to_string() } else { self.to_string() }
+    }
+}
+
+impl JavaLiteral for u32 {
+    fn java_literal(&self) -> String {
+        (*self as i32).java_literal()
+    }
+}
+
+impl JavaLiteral for i64 {
+    fn java_literal(&self) -> String {
+        if *self == i64::MIN { "Long.MIN_VALUE".to_string() } else { format!("{}L", self) }
+    }
+}
+
+impl JavaLiteral for u64 {
+    fn java_literal(&self) -> String {
+        (*self as i64).java_literal()
+    }
+}
+
+impl JavaLiteral for f32 {
+    fn java_literal(&self) -> String {
+        match *self {
+            value if value.is_nan() => "Float.NaN".to_string(),
+            f32::INFINITY => "Float.POSITIVE_INFINITY".to_string(),
+            f32::NEG_INFINITY => "Float.NEGATIVE_INFINITY".to_string(),
+            value => format!("{:?}f", value)
+        }
+    }
+}
+
+impl JavaLiteral for f64 {
+    fn java_literal(&self) -> String {
+        match *self {
+            value if value.is_nan() => "Double.NaN".to_string(),
+            f64::INFINITY => "Double.POSITIVE_INFINITY".to_string(),
+            f64::NEG_INFINITY => "Double.NEGATIVE_INFINITY".to_string(),
+            value => format!("{:?}", value)
+        }
+    }
+}
+
+impl JavaLiteral for crate::interop::JavaChar {
+    fn java_literal(&self) -> String {
+        // Unicode escapes are translated before Java source is parsed, and do not escape quotes, backslashes and line terminators
+        format!("(char) {}", self.0)
+    }
+}
+
+impl JavaLiteral for String {
+    fn java_literal(&self) -> String {
+        let mut literal = String::with_capacity(self.len() + 2);
+        literal.push('"');
+        for c in self.chars() {
+            match c {
+                '"' => literal.push_str("\\\""),
+                '\\' => literal.push_str("\\\\"),
+                '\n' => literal.push_str("\\n"),
+                '\r' => literal.push_str("\\r"),
+                '\t' => literal.push_str("\\t"),
+                ' '..='~' => literal.push(c),
+                // Other characters are escaped as UTF-16 code units, keeping sources ASCII
+                _ => {
+                    let mut units = [0; 2];
+                    for unit in c.encode_utf16(&mut units) {
+                        literal.push_str(&format!("\\u{:04x}", unit));
+                    }
+                }
+            }
+        }
+        literal.push('"');
+        literal
+    }
+}
+
+impl<T: JavaLiteral> JavaLiteral for Option<T> {
+    fn java_literal(&self) -> String {
+        match self {
+            Some(value) => value.java_literal(),
+            None => "null".to_string()
+        }
+    }
+}
+
+impl<T: JavaType + JavaLiteral> JavaLiteral for Box<[T]> {
+    fn java_literal(&self) -> String {
+        let elements = self.iter().map(JavaLiteral::java_literal).collect::<Vec<_>>();
+        format!("new {}[] {{{}}}", T::QUALIFIED_NAME(), elements.join(", "))
+    }
+}
+
 /// Trait for types that declare a Java class
 ///
 /// Custom/Derive'd [`JavaType`] structs will implement this
 pub trait JavaClass: Sized + JavaType {
     fn declaration() -> JClassDecl;
+
+    /// Resolve the cached class references of this class (and its variant subclasses), for use from `JNI_OnLoad`
+    fn resolve_classes(_env: &mut jni::JNIEnv) {}
+
+    /// Check that the loaded Java class has every field, constructor and method used by the conversions and exported methods of this type
+    fn verify_schema(_env: &mut jni::JNIEnv, _errors: &mut crate::jni_util::SchemaErrors) {}
+
+    /// Add the native functions exported for this class itself, such as the `release` function of handle-backed classes; Exported functions of impl blocks are listed by the `jmodule`
+    fn exports(_exports: &mut Vec<ExportInfo>) {}
+}
+
+/// Native function exported by a `jmodule`, as listed by the `jmodule_exports()` function it generates
+///
+/// For diagnosing an `UnsatisfiedLinkError`, or for tooling checking a native library against the classes of a jar
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExportInfo {
+    /// Symbol of the exported function, e.g. `Java_com_example_Counter_create`
+    pub symbol: &'static str,
+    /// Binary name of the class declaring the native method, e.g. `com.example.Counter`, or `com.example.Point$Native` for records
+    pub class: &'static str,
+    /// Name of the native method
+    pub method: &'static str,
+    /// JVM descriptor of the native method, e.g. `(J)Lcom/example/Counter;`
+    pub signature: String,
+    /// If true, the native method is static
+    pub is_static: bool,
+}
+
+impl Display for ExportInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let static_modifier = if self.is_static { "static " } else { "" };
+        write!(f, "{} -> {}{}.{}{}", self.symbol, static_modifier, self.class, self.method, self.signature)
+    }
 }
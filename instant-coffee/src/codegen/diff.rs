@@ -0,0 +1,367 @@
+//! Comparison of the Java API of two module declarations, such as those of two releases; See [`diff`]
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use crate::codegen::{JAccessModifier, JClassDecl, JField, JMethod, JModuleDecl};
+
+/// Change to the Java API of a module, as reported by [`diff`]
+///
+/// Classes are identified by their qualified name, fields by name, and methods and constructors by name and parameter types. Members are described as verbatim in Java source, such as `public static int parse(java.lang.String)`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ApiChange {
+    ClassAdded { class: String },
+    ClassRemoved { class: String },
+    /// The class was generated as a different kind of class, such as a record instead of a final class
+    ClassKindChanged { class: String, old: &'static str, new: &'static str },
+    InterfaceAdded { class: String, interface: &'static str },
+    InterfaceRemoved { class: String, interface: &'static str },
+    FieldAdded { class: String, field: String },
+    FieldRemoved { class: String, field: String },
+    /// The type or access of a field changed
+    FieldChanged { class: String, old: String, new: String },
+    MethodAdded { class: String, method: String },
+    MethodRemoved { class: String, method: String },
+    /// The return type of a method changed, or it became (non-)static
+    MethodChanged { class: String, old: String, new: String },
+    /// Enum constant added
+    ConstantAdded { class: String, constant: &'static str },
+    /// Enum constant removed
+    ConstantRemoved { class: String, constant: &'static str },
+}
+
+/// Semantic version increment required by a change to the Java API, ordered from least to most significant
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum VersionBump {
+    /// No change to the Java API
+    Patch,
+    /// Backwards compatible additions
+    Minor,
+    /// Changes that break binary compatibility of existing Java code
+    Major,
+}
+
+impl Display for VersionBump {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionBump::Patch => write!(f, "patch"),
+            VersionBump::Minor => write!(f, "minor"),
+            VersionBump::Major => write!(f, "major"),
+        }
+    }
+}
+
+impl ApiChange {
+    /// Version increment required by this change, following the binary compatibility rules of JLS chapter 13
+    ///
+    /// Additions of classes, interfaces, members, and enum constants are compatible; Any removal or change is not.
+    /// Changes to fields are treated as breaking even if they only widen access
+    pub fn required_bump(&self) -> VersionBump {
+        match self {
+            ApiChange::ClassAdded { .. }
+            | ApiChange::InterfaceAdded { .. }
+            | ApiChange::FieldAdded { .. }
+            | ApiChange::MethodAdded { .. }
+            | ApiChange::ConstantAdded { .. } => VersionBump::Minor,
+            ApiChange::ClassRemoved { .. }
+            | ApiChange::ClassKindChanged { .. }
+            | ApiChange::InterfaceRemoved { .. }
+            | ApiChange::FieldRemoved { .. }
+            | ApiChange::FieldChanged { .. }
+            | ApiChange::MethodRemoved { .. }
+            | ApiChange::MethodChanged { .. }
+            | ApiChange::ConstantRemoved { .. } => VersionBump::Major,
+        }
+    }
+
+    /// Qualified name of the class this change applies to
+    pub fn class(&self) -> &str {
+        match self {
+            ApiChange::ClassAdded { class }
+            | ApiChange::ClassRemoved { class }
+            | ApiChange::ClassKindChanged { class, .. }
+            | ApiChange::InterfaceAdded { class, .. }
+            | ApiChange::InterfaceRemoved { class, .. }
+            | ApiChange::FieldAdded { class, .. }
+            | ApiChange::FieldRemoved { class, .. }
+            | ApiChange::FieldChanged { class, .. }
+            | ApiChange::MethodAdded { class, .. }
+            | ApiChange::MethodRemoved { class, .. }
+            | ApiChange::MethodChanged { class, .. }
+            | ApiChange::ConstantAdded { class, .. }
+            | ApiChange::ConstantRemoved { class, .. } => class
+        }
+    }
+}
+
+/// One line per change; `+` for additions, `-` for removals, and `~` for changes
+impl Display for ApiChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiChange::ClassAdded { class } => write!(f, "+ class {}", class),
+            ApiChange::ClassRemoved { class } => write!(f, "- class {}", class),
+            ApiChange::ClassKindChanged { class, old, new } => write!(f, "~ class {}: {} -> {}", class, old, new),
+            ApiChange::InterfaceAdded { class, interface } => write!(f, "+ interface {}: {}", class, interface),
+            ApiChange::InterfaceRemoved { class, interface } => write!(f, "- interface {}: {}", class, interface),
+            ApiChange::FieldAdded { class, field } => write!(f, "+ field {}: {}", class, field),
+            ApiChange::FieldRemoved { class, field } => write!(f, "- field {}: {}", class, field),
+            ApiChange::FieldChanged { class, old, new } => write!(f, "~ field {}: {} -> {}", class, old, new),
+            ApiChange::MethodAdded { class, method } => write!(f, "+ method {}: {}", class, method),
+            ApiChange::MethodRemoved { class, method } => write!(f, "- method {}: {}", class, method),
+            ApiChange::MethodChanged { class, old, new } => write!(f, "~ method {}: {} -> {}", class, old, new),
+            ApiChange::ConstantAdded { class, constant } => write!(f, "+ constant {}: {}", class, constant),
+            ApiChange::ConstantRemoved { class, constant } => write!(f, "- constant {}: {}", class, constant),
+        }
+    }
+}
+
+/// Changes to the Java API between two module declarations, see [`diff`]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ApiDiff {
+    /// Changes, ordered by class
+    pub changes: Vec<ApiChange>,
+}
+
+impl ApiDiff {
+    /// True if the Java API is unchanged
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Version increment required by these changes; The most significant of [`ApiChange::required_bump`], or [`VersionBump::Patch`] if the API is unchanged
+    ///
+    /// ```ignore
+    /// let bump = codegen::diff(&previous_release, &my_module::jmodule_decl()).required_bump();
+    /// assert!(bump < VersionBump::Major, "breaking Java API change");
+    /// ```
+    pub fn required_bump(&self) -> VersionBump {
+        self.changes.iter().map(ApiChange::required_bump).max().unwrap_or(VersionBump::Patch)
+    }
+
+    /// Changes that break binary compatibility
+    pub fn breaking_changes(&self) -> impl Iterator<Item=&ApiChange> {
+        self.changes.iter().filter(|change| change.required_bump() == VersionBump::Major)
+    }
+}
+
+impl Display for ApiDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the Java API of two module declarations, such as those of the previous and current release
+///
+/// Only the API visible to Java code is compared; Public and protected members, including the constructors, factory, copy and setter methods generated for a class, and the accessors of records.
+/// Classes that are not exported, such as package-private classes, are not compared, and a class that is no longer exported is reported as removed.
+/// Private fields, annotations, method parameter names, and verbatim class bodies are not compared. A change to [`JModuleDecl::records`] changes the kind and members of every regular class
+///
+/// # Arguments
+///
+/// * `old`: Previous declaration
+/// * `new`: Current declaration
+///
+/// returns: ApiDiff
+pub fn diff(old: &JModuleDecl, new: &JModuleDecl) -> ApiDiff {
+    let old_classes = module_api(old);
+    let new_classes = module_api(new);
+
+    let mut changes = Vec::new();
+    let class_names = old_classes.keys().chain(new_classes.keys()).collect::<BTreeSet<_>>();
+    for class in class_names {
+        match (old_classes.get(class), new_classes.get(class)) {
+            (Some(_), None) => changes.push(ApiChange::ClassRemoved { class: class.clone() }),
+            (None, Some(_)) => changes.push(ApiChange::ClassAdded { class: class.clone() }),
+            (Some(old_class), Some(new_class)) => old_class.diff(class, new_class, &mut changes),
+            (None, None) => unreachable!("class names are taken from either module")
+        }
+    }
+    ApiDiff { changes }
+}
+
+/// API of a single class; Its kind, and its members keyed by name or signature
+struct ClassApi {
+    /// Kind of class, as in Java source, such as "final class" or "enum"
+    kind: &'static str,
+    interfaces: BTreeSet<&'static str>,
+    /// Non-private fields by name, with their declaration
+    fields: BTreeMap<String, String>,
+    /// Public methods and constructors by signature, with their declaration
+    methods: BTreeMap<String, String>,
+    /// Enum constants, in declaration order
+    constants: Vec<&'static str>,
+}
+
+impl ClassApi {
+    fn new(kind: &'static str) -> ClassApi {
+        ClassApi { kind, interfaces: BTreeSet::new(), fields: BTreeMap::new(), methods: BTreeMap::new(), constants: Vec::new() }
+    }
+
+    /// Add a non-private field
+    fn add_field(&mut self, field: &JField) {
+        if field.access != JAccessModifier::Private {
+            let access = if field.access == JAccessModifier::PackagePrivate { String::new() } else { format!("{} ", field.access) };
+            self.fields.insert(field.name.to_string(), format!("{}{} {}", access, field.jtype, field.name));
+        }
+    }
+
+    /// Add a public method or constructor; Constructors have no return type
+    fn add_method<'a>(&mut self, is_static: bool, output: Option<&str>, name: &str, parameter_types: impl IntoIterator<Item=&'a str>) {
+        let signature = format!("{}({})", name, parameter_types.into_iter().collect::<Vec<_>>().join(", "));
+        let declaration = match output {
+            Some(output) if is_static => format!("public static {} {}", output, signature),
+            Some(output) => format!("public {} {}", output, signature),
+            None => format!("public {}", signature),
+        };
+        self.methods.insert(signature, declaration);
+    }
+
+    /// Add the exported methods of a class
+    fn add_methods(&mut self, methods: &[JMethod]) {
+        for method in methods {
+            self.add_method(method.is_static, Some(method.output), method.name, method.inputs.iter().map(|(_, param_type)| *param_type));
+        }
+    }
+
+    /// Compare against the API of the same class in a newer declaration
+    fn diff(&self, class: &str, new: &ClassApi, changes: &mut Vec<ApiChange>) {
+        if self.kind != new.kind {
+            changes.push(ApiChange::ClassKindChanged { class: class.to_string(), old: self.kind, new: new.kind });
+        }
+
+        for interface in self.interfaces.difference(&new.interfaces) {
+            changes.push(ApiChange::InterfaceRemoved { class: class.to_string(), interface });
+        }
+        for interface in new.interfaces.difference(&self.interfaces) {
+            changes.push(ApiChange::InterfaceAdded { class: class.to_string(), interface });
+        }
+
+        diff_members(&self.fields, &new.fields, changes,
+            |field| ApiChange::FieldRemoved { class: class.to_string(), field },
+            |field| ApiChange::FieldAdded { class: class.to_string(), field },
+            |old, new| ApiChange::FieldChanged { class: class.to_string(), old, new },
+        );
+        diff_members(&self.methods, &new.methods, changes,
+            |method| ApiChange::MethodRemoved { class: class.to_string(), method },
+            |method| ApiChange::MethodAdded { class: class.to_string(), method },
+            |old, new| ApiChange::MethodChanged { class: class.to_string(), old, new },
+        );
+
+        for constant in self.constants.iter().filter(|constant| !new.constants.contains(constant)) {
+            changes.push(ApiChange::ConstantRemoved { class: class.to_string(), constant });
+        }
+        for constant in new.constants.iter().filter(|constant| !self.constants.contains(constant)) {
+            changes.push(ApiChange::ConstantAdded { class: class.to_string(), constant });
+        }
+    }
+}
+
+/// Report removed, added, and changed members between two member maps
+fn diff_members<K: Ord>(
+    old: &BTreeMap<K, String>,
+    new: &BTreeMap<K, String>,
+    changes: &mut Vec<ApiChange>,
+    removed: impl Fn(String) -> ApiChange,
+    added: impl Fn(String) -> ApiChange,
+    changed: impl Fn(String, String) -> ApiChange,
+) {
+    for (key, old_declaration) in old {
+        match new.get(key) {
+            None => changes.push(removed(old_declaration.clone())),
+            Some(new_declaration) if new_declaration != old_declaration => changes.push(changed(old_declaration.clone(), new_declaration.clone())),
+            Some(_) => {}
+        }
+    }
+    for (key, new_declaration) in new {
+        if !old.contains_key(key) {
+            changes.push(added(new_declaration.clone()));
+        }
+    }
+}
+
+/// API of every class in a module, by qualified name; Tagged union variants are listed as classes of their own
+fn module_api(module: &JModuleDecl) -> BTreeMap<String, ClassApi> {
+    let mut classes = BTreeMap::new();
+    for class in module.classes.iter().filter(|class| module.is_exported(class)) {
+        let qualified_name = format!("{}.{}", class.package(), class.class_path());
+        let mut api;
+        match class {
+            JClassDecl::Class { name, fields, methods, factory, with_methods, bean, default_constructor, .. } => {
+                let field_types = || fields.iter().map(|field| field.jtype);
+                if module.records && !*bean {
+                    // Record components are private, with a public canonical constructor and accessors
+                    api = ClassApi::new("record");
+                    api.add_method(false, None, name, field_types());
+                    for field in fields {
+                        api.add_method(false, Some(field.jtype), field.name, []);
+                    }
+                } else {
+                    api = ClassApi::new("final class");
+                    fields.iter().for_each(|field| api.add_field(field));
+                    if *bean {
+                        api.add_method(false, None, name, []);
+                        for field in fields {
+                            api.add_method(false, Some("void"), &format!("set{}", super::capitalize(field.name)), [field.jtype]);
+                        }
+                    }
+                }
+                if *default_constructor {
+                    api.add_method(false, None, name, []);
+                }
+                if *factory {
+                    api.add_method(true, Some(name), "of", field_types());
+                }
+                if *with_methods {
+                    for field in fields {
+                        api.add_method(false, Some(name), &format!("with{}", super::capitalize(field.name)), [field.jtype]);
+                    }
+                }
+                api.add_methods(methods);
+            }
+            JClassDecl::Handle { name, methods, subclassable, .. } => {
+                api = ClassApi::new(if *subclassable { "class" } else { "final class" });
+                api.interfaces.insert("AutoCloseable");
+                api.add_method(false, Some("void"), "close", []);
+                api.add_methods(methods);
+                if *subclassable {
+                    // Subclasses depend on the protected constructor, and on which methods they may override
+                    api.methods.insert(format!("{}({})", name, name), format!("protected {}({})", name, name));
+                    for method in methods.iter().filter(|method| !method.is_static && !method.overridable) {
+                        let signature = format!("{}({})", method.name, method.inputs.iter().map(|(_, param_type)| *param_type).collect::<Vec<_>>().join(", "));
+                        api.methods.insert(signature.clone(), format!("public final {} {}", method.output, signature));
+                    }
+                }
+            }
+            JClassDecl::Enum { variants, methods, .. } => {
+                api = ClassApi::new("enum");
+                api.constants = variants.clone();
+                api.add_methods(methods);
+            }
+            JClassDecl::EnumTaggedUnion { variants, methods, .. } => {
+                api = ClassApi::new("abstract class");
+                api.add_methods(methods);
+                api.add_method(false, Some("int"), "getTag", []);
+                // Tag constants are inlined into consumers; A renumbered tag is a changed field
+                for (tag, variant) in variants.iter().enumerate() {
+                    let constant = variant.tag_constant();
+                    let declaration = format!("public static final int {} = {}", constant, tag);
+                    api.fields.insert(constant, declaration);
+                }
+                for variant in variants {
+                    let mut variant_api = ClassApi::new("final class");
+                    variant.fields.iter().for_each(|field| variant_api.add_field(field));
+                    variant_api.add_method(false, None, variant.name, variant.fields.iter().map(|field| field.jtype));
+                    classes.insert(format!("{}.{}", qualified_name, variant.name), variant_api);
+                }
+            }
+            JClassDecl::Source { .. } => {
+                api = ClassApi::new("class");
+            }
+        }
+        api.interfaces.extend(class.interfaces());
+        classes.insert(qualified_name, api);
+    }
+    classes
+}
@@ -0,0 +1,313 @@
+//! Hostile inputs for [`JavaType::from_jni`] conversions, run inside a real JVM
+//!
+//! Java code normally only passes well-typed values, but reflection, stale Java sources, and objects created without running their constructor can all hand a conversion something else.
+//! [`check_from_jni`] feeds every applicable [`HostileObject`] into a conversion, and [`fuzz_from_jni`] feeds the one selected by fuzzer input, such as that of a `cargo fuzz` target.
+//!
+//! Both panic if a conversion panics, accepts an input that must be rejected, converts null to a value that does not convert back to null, or reports an exception inconsistently with the JVM:
+//! `Err(None)` must leave an exception pending, while `Ok` and `Err(Some(_))` must not.
+//!
+//! The generated Java classes must be on the class path of the JVM, such as one started by [`embedded_jvm`]:
+//!
+//! ```ignore
+//! let jvm = embedded_jvm(&["-Djava.class.path=target/java-classes", "-Xcheck:jni"]);
+//! let mut env = jvm.attach_current_thread().unwrap();
+//! check_from_jni::<Point>(&mut env);
+//! check_from_jni::<Box<[Point]>>(&mut env);
+//! ```
+
+use std::ffi::{c_void, CString};
+use std::panic::AssertUnwindSafe;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock, PoisonError};
+use jni::{JavaVM, JNIEnv};
+use jni::objects::{GlobalRef, JClass, JObject, JObjectArray, JString, JValue};
+use jni::sys::{jint, jsize, JavaVMInitArgs, JavaVMOption, JNI_FALSE, JNI_OK, JNI_VERSION_1_8};
+
+use crate::JavaType;
+
+/// Length of the array fed by [`check_from_jni`]; Large enough that converting it element by element exhausts local references unless they are managed
+pub const HUGE_ARRAY_LENGTH: jsize = 1 << 20;
+
+/// Upper bound on array lengths taken from fuzzer input by [`fuzz_from_jni`], keeping every input within the default heap size
+pub const MAX_FUZZ_ARRAY_LENGTH: jsize = 1 << 24;
+
+/// Malformed or hostile value passed in place of a Java object
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum HostileObject {
+    /// `null`; Must be rejected, unless converted to a value which converts back to null, such as `None`
+    Null,
+    /// A plain `java.lang.Object`; Must be rejected. Not fed to array conversions, nor to String conversions with the `unchecked-strings` feature, for which passing another class is undefined behaviour
+    WrongClass,
+    /// An instance of the expected class allocated without running a constructor, leaving object fields null and primitive fields zero; Skipped for abstract classes
+    Uninitialized,
+    /// An instance of a class declaring the fields of the expected class with other types, as an outdated copy of the generated sources would; Must be rejected
+    MismatchedFields,
+    /// An array of the expected type with the given length, holding null or zero elements
+    Array(jsize),
+}
+
+impl HostileObject {
+    /// Hostile objects applicable to conversions of `T`, with arrays of [`HUGE_ARRAY_LENGTH`]
+    pub fn cases_for<T: JavaType>() -> Vec<HostileObject> {
+        match TargetKind::of::<T>() {
+            TargetKind::Array => vec![HostileObject::Null, HostileObject::Array(0), HostileObject::Array(HUGE_ARRAY_LENGTH)],
+            TargetKind::String if cfg!(feature = "unchecked-strings") => vec![HostileObject::Null],
+            TargetKind::String => vec![HostileObject::Null, HostileObject::WrongClass],
+            TargetKind::Object => vec![HostileObject::Null, HostileObject::WrongClass, HostileObject::Uninitialized, HostileObject::MismatchedFields],
+        }
+    }
+
+    /// Select a hostile object applicable to conversions of `T` from fuzzer input; The first byte selects the case, and the following four the length of arrays
+    pub fn from_fuzz_input<T: JavaType>(data: &[u8]) -> HostileObject {
+        let cases = Self::cases_for::<T>();
+        let case = cases[data.first().copied().unwrap_or(0) as usize % cases.len()];
+        match case {
+            HostileObject::Array(_) => {
+                let length = data.get(1..5).map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+                HostileObject::Array((length % (MAX_FUZZ_ARRAY_LENGTH as u32 + 1)) as jsize)
+            }
+            case => case
+        }
+    }
+
+    /// Whether every conversion must reject this object
+    fn must_fail(&self) -> bool {
+        matches!(self, HostileObject::WrongClass | HostileObject::MismatchedFields)
+    }
+
+    /// Create this object for conversions of `T`; Returns None if it cannot be created for the expected class
+    fn create<'local, T: JavaType>(&self, env: &mut JNIEnv<'local>) -> Option<JObject<'local>> {
+        let signature = T::JVM_PARAM_SIGNATURE();
+        match self {
+            HostileObject::Null => Some(JObject::null()),
+            HostileObject::WrongClass => Some(setup(env.new_object("java/lang/Object", "()V", &[]), "create java.lang.Object")),
+            HostileObject::Uninitialized => {
+                let class = expected_class::<T>(env);
+                let object = env.alloc_object(&class);
+                if object.is_err() && env.exception_check().unwrap_or(false) {
+                    // InstantiationException, for abstract classes such as the base class of enums with fields
+                    let _ = env.exception_clear();
+                    return None;
+                }
+                Some(setup(object, "allocate instance"))
+            }
+            HostileObject::MismatchedFields => {
+                let mismatched = mismatched_class::<T>(env);
+                Some(setup(env.alloc_object(&mismatched), "allocate instance"))
+            }
+            HostileObject::Array(length) => {
+                let class = expected_class::<T>(env);
+                let component = setup(env.call_method(&class, "getComponentType", "()Ljava/lang/Class;", &[]).and_then(|value| value.l()), "get array component type");
+                let array = env.call_static_method(
+                    "java/lang/reflect/Array",
+                    "newInstance",
+                    "(Ljava/lang/Class;I)Ljava/lang/Object;",
+                    &[JValue::Object(&component), JValue::Int(*length)]
+                );
+                Some(setup(array.and_then(|value| value.l()), &format!("create {} of length {}", signature, length)))
+            }
+        }
+    }
+}
+
+/// Feed every [`HostileObject`] applicable to `T` into its `from_jni` conversion
+///
+/// # Panics
+///
+/// If a conversion panics, accepts an object which must be rejected, or reports an exception inconsistently with the JVM; Also if the hostile objects cannot be created, such as when the class of `T` is not on the class path
+pub fn check_from_jni<T: JavaType>(env: &mut JNIEnv) where for<'local> T::JniType<'local>: From<JObject<'local>> + AsRef<JObject<'local>> {
+    for case in HostileObject::cases_for::<T>() {
+        run_case::<T>(case, env);
+    }
+}
+
+/// Feed the [`HostileObject`] selected by fuzzer input into the `from_jni` conversion of `T`
+///
+/// # Panics
+///
+/// As [`check_from_jni`]
+pub fn fuzz_from_jni<T: JavaType>(env: &mut JNIEnv, data: &[u8]) where for<'local> T::JniType<'local>: From<JObject<'local>> + AsRef<JObject<'local>> {
+    run_case::<T>(HostileObject::from_fuzz_input::<T>(data), env);
+}
+
+fn run_case<T: JavaType>(case: HostileObject, env: &mut JNIEnv) where for<'local> T::JniType<'local>: From<JObject<'local>> + AsRef<JObject<'local>> {
+    let name = T::QUALIFIED_NAME();
+    let result = env.with_local_frame(8, |env| -> jni::errors::Result<()> {
+        let Some(object) = case.create::<T>(env) else {
+            return Ok(());
+        };
+
+        let converted = std::panic::catch_unwind(AssertUnwindSafe(|| T::from_jni(object.into(), env)));
+        let pending = env.exception_check()?;
+        env.exception_clear()?;
+
+        match converted {
+            Err(_) => panic!("fuzzing: converting {:?} to {} panicked", case, name),
+            Ok(Ok(_)) if case.must_fail() => panic!("fuzzing: converting {:?} to {} succeeded", case, name),
+            Ok(Ok(_)) if pending => panic!("fuzzing: converting {:?} to {} succeeded with an exception pending", case, name),
+            Ok(Ok(value)) if case == HostileObject::Null => {
+                // Nullable types such as Option<T> accept null, but must convert back to null
+                let round_trip = value.into_jni(env);
+                env.exception_clear()?;
+                match round_trip {
+                    Ok(object) if object.as_ref().is_null() => Ok(()),
+                    _ => panic!("fuzzing: converting Null to {} succeeded with a value other than null", name),
+                }
+            }
+            Ok(Err(None)) if !pending => panic!("fuzzing: converting {:?} to {} failed without an exception pending", case, name),
+            Ok(Err(Some(exception))) if pending => panic!("fuzzing: converting {:?} to {} failed with {} while an exception is pending", case, name, exception.class),
+            Ok(_) => Ok(())
+        }
+    });
+    setup(result, "run conversion");
+}
+
+/// Broad kinds of conversions, which are fed different hostile objects
+enum TargetKind {
+    Array,
+    String,
+    Object,
+}
+
+impl TargetKind {
+    fn of<T: JavaType>() -> TargetKind {
+        match T::JVM_PARAM_SIGNATURE() {
+            signature if signature.starts_with('[') => TargetKind::Array,
+            "Ljava/lang/String;" => TargetKind::String,
+            _ => TargetKind::Object,
+        }
+    }
+}
+
+/// Unwrap the result of a JNI call creating a hostile object; Failures are errors in the test setup, not in the conversion under test
+fn setup<T>(result: jni::errors::Result<T>, action: &str) -> T {
+    result.unwrap_or_else(|error| panic!("fuzzing: could not {}: {}", action, error))
+}
+
+/// Class of `T`, looked up by signature
+fn expected_class<'local, T: JavaType>(env: &mut JNIEnv<'local>) -> JClass<'local> {
+    let signature = T::JVM_PARAM_SIGNATURE();
+    let class = crate::jni_util::signature_class(signature, env).unwrap_or_else(|_| {
+        let _ = env.exception_clear();
+        panic!("fuzzing: class {} not found; The generated Java classes must be on the class path", crate::jni_util::signature_class_name(signature))
+    });
+    JClass::from(setup(env.new_local_ref(&class), "reference class"))
+}
+
+/// Class declaring the instance fields of the class of `T` with other types; Defined once per class, with the bootstrap class loader
+fn mismatched_class<'local, T: JavaType>(env: &mut JNIEnv<'local>) -> JClass<'local> {
+    static CLASSES: Mutex<Vec<(&'static str, GlobalRef)>> = Mutex::new(Vec::new());
+
+    let signature = T::JVM_PARAM_SIGNATURE();
+    let mut classes = CLASSES.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((_, class)) = classes.iter().find(|(defined, _)| *defined == signature) {
+        return JClass::from(setup(env.new_local_ref(class), "reference class"));
+    }
+
+    let class = expected_class::<T>(env);
+    let fields = setup(env.call_method(&class, "getDeclaredFields", "()[Ljava/lang/reflect/Field;", &[]).and_then(|value| value.l()), "list fields");
+    let fields = JObjectArray::from(fields);
+    let mut mismatched_fields = Vec::new();
+    for idx in 0..setup(env.get_array_length(&fields), "list fields") {
+        let field = setup(env.get_object_array_element(&fields, idx), "list fields");
+        let modifiers = setup(env.call_method(&field, "getModifiers", "()I", &[]).and_then(|value| value.i()), "read field modifiers");
+        if modifiers & 0x0008 != 0 {    // ACC_STATIC
+            continue;
+        }
+        let name = JString::from(setup(env.call_method(&field, "getName", "()Ljava/lang/String;", &[]).and_then(|value| value.l()), "read field name"));
+        let field_type = setup(env.call_method(&field, "getType", "()Ljava/lang/Class;", &[]).and_then(|value| value.l()), "read field type");
+        let primitive = setup(env.call_method(&field_type, "isPrimitive", "()Z", &[]).and_then(|value| value.z()), "read field type");
+        // Field names are kept in modified UTF-8, as the class file format requires
+        let name = setup(env.get_string(&name), "read field name").to_bytes().to_vec();
+        mismatched_fields.push((name, if primitive { "Ljava/lang/Object;" } else { "I" }));
+    }
+
+    let name = format!("instant_coffee/fuzzing/Mismatched{}", classes.len());
+    let bytecode = class_file(&name, &mismatched_fields);
+    let defined = setup(env.define_class(&name, &JObject::null(), &bytecode), "define class with mismatched fields");
+    classes.push((signature, setup(env.new_global_ref(&defined), "reference class")));
+    defined
+}
+
+/// Java 8 class file for a public class with the given public instance fields, and no methods; Instances can only be allocated through JNI
+fn class_file(name: &str, fields: &[(Vec<u8>, &str)]) -> Vec<u8> {
+    fn utf8(out: &mut Vec<u8>, bytes: &[u8]) {
+        out.push(1);    // CONSTANT_Utf8
+        out.extend((bytes.len() as u16).to_be_bytes());
+        out.extend(bytes);
+    }
+
+    let mut constants = Vec::new();
+    utf8(&mut constants, name.as_bytes());  // #1
+    constants.extend([7, 0, 1]);            // #2 CONSTANT_Class #1
+    utf8(&mut constants, b"java/lang/Object");  // #3
+    constants.extend([7, 0, 3]);            // #4 CONSTANT_Class #3
+    for (name, descriptor) in fields {
+        utf8(&mut constants, name);
+        utf8(&mut constants, descriptor.as_bytes());
+    }
+
+    let mut out = Vec::new();
+    out.extend(0xCAFEBABEu32.to_be_bytes());
+    out.extend(0u16.to_be_bytes());     // Minor version
+    out.extend(52u16.to_be_bytes());    // Major version; Java 8
+    out.extend((5 + 2 * fields.len() as u16).to_be_bytes());    // Constant pool count, one more than the number of entries
+    out.extend(constants);
+    out.extend(0x0021u16.to_be_bytes());    // ACC_PUBLIC | ACC_SUPER
+    out.extend(2u16.to_be_bytes());     // This class
+    out.extend(4u16.to_be_bytes());     // Super class
+    out.extend(0u16.to_be_bytes());     // Interfaces
+    out.extend((fields.len() as u16).to_be_bytes());
+    for idx in 0..fields.len() as u16 {
+        out.extend(0x0001u16.to_be_bytes());    // ACC_PUBLIC
+        out.extend((5 + 2 * idx).to_be_bytes());    // Name
+        out.extend((6 + 2 * idx).to_be_bytes());    // Descriptor
+        out.extend(0u16.to_be_bytes());     // Attributes
+    }
+    out.extend(0u16.to_be_bytes());     // Methods
+    out.extend(0u16.to_be_bytes());     // Attributes
+    out
+}
+
+/// Start a JVM in this process, loading libjvm from `JAVA_HOME`; Later calls return the same JVM, as a process can only host one
+///
+/// # Arguments
+///
+/// * `options`: JVM options, such as "-Djava.class.path=..." or "-Xcheck:jni"; Only those of the first call are used
+///
+/// # Panics
+///
+/// If `JAVA_HOME` is not set, libjvm cannot be loaded, or the JVM does not start
+pub fn embedded_jvm(options: &[&str]) -> &'static JavaVM {
+    static JVM: OnceLock<JavaVM> = OnceLock::new();
+
+    JVM.get_or_init(|| {
+        type CreateJavaVM = unsafe extern "system" fn(*mut *mut jni::sys::JavaVM, *mut *mut c_void, *mut c_void) -> jint;
+
+        let java_home = PathBuf::from(std::env::var_os("JAVA_HOME").expect("fuzzing: JAVA_HOME must be set to start a JVM"));
+        let libjvm = ["lib/server/libjvm.so", "lib/server/libjvm.dylib", "bin/server/jvm.dll"].iter()
+            .map(|path| java_home.join(path))
+            .find(|path| path.exists())
+            .expect("fuzzing: could not find libjvm in JAVA_HOME");
+
+        let options = options.iter()
+            .map(|option| CString::new(*option).expect("fuzzing: JVM options cannot contain nul bytes"))
+            .collect::<Vec<_>>();
+        let mut raw_options = options.iter()
+            .map(|option| JavaVMOption { optionString: option.as_ptr() as *mut _, extraInfo: std::ptr::null_mut() })
+            .collect::<Vec<_>>();
+
+        unsafe {
+            // Leaked; The JVM cannot be unloaded
+            let library = Box::leak(Box::new(libloading::Library::new(libjvm).expect("fuzzing: could not load libjvm")));
+            let create_java_vm = library.get::<CreateJavaVM>(b"JNI_CreateJavaVM").expect("fuzzing: libjvm does not export JNI_CreateJavaVM");
+
+            let mut args = JavaVMInitArgs { version: JNI_VERSION_1_8, nOptions: raw_options.len() as jint, options: raw_options.as_mut_ptr(), ignoreUnrecognized: JNI_FALSE };
+            let mut vm = std::ptr::null_mut();
+            let mut env = std::ptr::null_mut();
+            assert_eq!(create_java_vm(&mut vm, &mut env, &mut args as *mut JavaVMInitArgs as *mut c_void), JNI_OK, "fuzzing: could not start JVM");
+
+            JavaVM::from_raw(vm).expect("fuzzing: could not start JVM")
+        }
+    })
+}
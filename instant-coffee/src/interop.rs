@@ -1,5 +1,93 @@
 //! Specialized interop for Java types/features that do not cleanly map onto rust
 
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::CString;
+use std::fmt::{Debug, Formatter};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use jni::errors::Exception;
+use jni::{JavaVM, JNIEnv};
+use jni::objects::{GlobalRef, JLongArray, JMethodID, JObject, JObjectArray, JString, JThrowable, JValue, JValueOwned};
+use jni::signature::ReturnType;
+use crate::{JavaBorrow, JavaType};
+use crate::jni_util::{array_length, check_array_length, map_jni_error, register_global_cache, signature_class, vm_key, CachedClass, GlobalCache, CLASS_CAST_EXCEPTION, ILLEGAL_ARGUMENT_EXCEPTION, NULL_POINTER_EXCEPTION};
+
+#[cfg(feature = "streams")]
+mod streams;
+
+#[cfg(feature = "streams")]
+pub use streams::{StreamPublisher, stream_publisher_declaration};
+
+#[cfg(feature = "streams")]
+pub(crate) use streams::cancel_stream_subscriptions;
+
+#[cfg(feature = "bitflags")]
+mod flags;
+
+#[cfg(feature = "bitflags")]
+pub use flags::{enum_set_from_jni, enum_set_into_jni};
+
+/// Re-export for `#[java(flags)]` derives
+#[cfg(feature = "bitflags")]
+pub use bitflags;
+
+#[cfg(feature = "ndarray")]
+mod nd_array;
+
+#[cfg(feature = "ndarray")]
+pub use nd_array::{DirectNdArray, nd_array_declaration};
+
+#[cfg(feature = "image")]
+mod pixels;
+
+#[cfg(feature = "image")]
+pub use pixels::{DirectPixelBuffer, pixel_buffer_declaration};
+
+#[cfg(feature = "msgpack")]
+mod msgpack;
+
+#[cfg(feature = "msgpack")]
+pub use msgpack::MsgpackTunneled;
+
+#[cfg(feature = "rayon")]
+mod parallel;
+
+#[cfg(feature = "rayon")]
+pub use parallel::{ParArray, PARALLEL_THRESHOLD};
+
+#[cfg(feature = "bytes")]
+mod buffers;
+
+#[cfg(feature = "bytes")]
+pub use buffers::{DirectBytes, direct_bytes_declaration};
+
+#[cfg(feature = "shared-memory")]
+mod shared;
+
+#[cfg(feature = "shared-memory")]
+pub use shared::{SharedMemory, shared_memory_declaration};
+
+mod result;
+
+pub use result::{JavaResult, java_result_declaration};
+
+mod closures;
+
+pub use closures::native_closure_declaration;
+
+mod chunked;
+
+pub use chunked::{ChunkedArray, chunked_array_declaration, DEFAULT_CHUNK_LENGTH};
+
+mod tuples;
+
+pub use tuples::tuple_declaration;
+
 /// Struct representing Java `char` type. 16-bits numerical value for UTF-16 code units.
 ///
 /// Unlike Rust's char, permits all u16 values (0..=0xFFFF), and may be directly created from u16
@@ -25,4 +113,1002 @@ impl JavaChar {
     pub fn into_char(self) -> Option<char> {
         char::try_from(self.0 as u32).ok()
     }
-}
\ No newline at end of file
+}
+
+/// [`CString`] mapped to Java String, restricted to ASCII; For C strings that are text, rather than raw bytes
+///
+/// Non-ASCII strings (and strings containing a nul character) are rejected with an IllegalArgumentException, in both directions
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AsciiCString(pub CString);
+
+impl JavaType for AsciiCString {
+    type JniType<'local> = JString<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "java.lang.String" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Ljava/lang/String;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JString::from(JObject::null()) }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        let string = String::from_jni(jni_value, env)?;
+        if !string.is_ascii() {
+            return Err(Some(Exception { class: ILLEGAL_ARGUMENT_EXCEPTION.to_string(), msg: "C string contains non-ASCII characters".to_string() }));
+        }
+
+        CString::new(string)
+            .map(AsciiCString)
+            .map_err(|error| Some(Exception { class: ILLEGAL_ARGUMENT_EXCEPTION.to_string(), msg: format!("C string contains a nul character at index {}", error.nul_position()) }))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match self.0.to_str() {
+            Ok(string) if string.is_ascii() => String::into_jni(string.to_string(), env),
+            _ => Err(Some(Exception { class: ILLEGAL_ARGUMENT_EXCEPTION.to_string(), msg: "C string contains non-ASCII characters".to_string() })),
+        }
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        String::from_jvalue(jvalue, env)
+    }
+}
+
+/// Flag set mapped to `java.util.BitSet`; Used for `Box<[bool]>` fields with `#[java(bitset)]`
+///
+/// Java BitSets do not have a fixed length; Converting from Java yields flags up to and including the highest set bit, trailing `false` flags are not preserved
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct BitSet(pub Box<[bool]>);
+
+impl From<Box<[bool]>> for BitSet {
+    fn from(flags: Box<[bool]>) -> Self {
+        BitSet(flags)
+    }
+}
+
+impl From<BitSet> for Box<[bool]> {
+    fn from(bitset: BitSet) -> Self {
+        bitset.0
+    }
+}
+
+impl JavaType for BitSet {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "java.util.BitSet" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Ljava/util/BitSet;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        if jni_value.is_null() {
+            return Err(Some(Exception { class: NULL_POINTER_EXCEPTION.to_string(), msg: format!("expected {}", <Self as JavaType>::QUALIFIED_NAME()) }));
+        }
+
+        let length = env.call_method(&jni_value, "length", "()I", &[])
+            .map_err(map_jni_error)?
+            .i()
+            .map_err(map_jni_error)?;
+        check_array_length(length.max(0) as usize, "java.util.BitSet")?;
+        let words = JLongArray::from(
+            env.call_method(&jni_value, "toLongArray", "()[J", &[])
+                .map_err(map_jni_error)?
+                .l()
+                .map_err(map_jni_error)?
+        );
+
+        let mut buffer = vec![0; env.get_array_length(&words).map_err(map_jni_error)? as usize];
+        env.get_long_array_region(&words, 0, &mut buffer).map_err(map_jni_error)?;
+
+        Ok(BitSet(
+            (0..length as usize)
+                .map(|idx| (buffer[idx / 64] >> (idx % 64)) & 1 != 0)
+                .collect()
+        ))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let mut buffer = vec![0i64; self.0.len().div_ceil(64)];
+        for (idx, _) in self.0.iter().enumerate().filter(|(_, flag)| **flag) {
+            buffer[idx / 64] |= 1 << (idx % 64);
+        }
+
+        let words = env.new_long_array(array_length(buffer.len(), "long")?).map_err(map_jni_error)?;
+        env.set_long_array_region(&words, 0, &buffer).map_err(map_jni_error)?;
+
+        env.call_static_method("java/util/BitSet", "valueOf", "([J)Ljava/util/BitSet;", &[JValue::Object(&words)])
+            .map_err(map_jni_error)?
+            .l()
+            .map_err(map_jni_error)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Typed handle to a value stored in a [`HandleMap`]; Maps to Java `long`
+///
+/// Handles are generational, a handle to a removed value remains invalid even if its slot in the map is reused. Raw handle values are never 0
+#[repr(transparent)]
+pub struct Handle<T> {
+    raw: i64,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    fn new(index: u32, generation: u32) -> Handle<T> {
+        Handle { raw: ((generation as i64) << 32) | (index as i64), _type: PhantomData }
+    }
+
+    /// Create a handle from a raw `long` value, such as one received from Java
+    ///
+    /// This is always safe; Invalid handles are rejected by [`HandleMap`]
+    pub fn from_raw(raw: i64) -> Handle<T> {
+        Handle { raw, _type: PhantomData }
+    }
+
+    /// Raw `long` value of this handle
+    pub fn into_raw(self) -> i64 {
+        self.raw
+    }
+
+    fn index(self) -> usize {
+        (self.raw as u64 & 0xFFFF_FFFF) as usize
+    }
+
+    fn generation(self) -> u32 {
+        (self.raw as u64 >> 32) as u32
+    }
+}
+
+// Manual impls, as derives would require T to implement these traits
+impl<T> Copy for Handle<T> {}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.raw.hash(state)
+    }
+}
+
+impl<T> Debug for Handle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({:#x})", self.raw)
+    }
+}
+
+struct HandleSlot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+/// Generational map of values addressed by [`Handle`]s, for passing long-lived rust state to Java without exposing raw pointers
+///
+/// Stale and forged handles are detected and yield `None`. The map itself is not synchronized; Wrap it in a `Mutex` to share it between threads, e.g.
+/// `static SESSIONS: Mutex<HandleMap<Session>> = Mutex::new(HandleMap::new());`
+pub struct HandleMap<T> {
+    slots: Vec<HandleSlot<T>>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> HandleMap<T> {
+    /// Create a new, empty HandleMap
+    pub const fn new() -> HandleMap<T> {
+        HandleMap { slots: Vec::new(), free: Vec::new(), len: 0 }
+    }
+
+    /// Insert a value, returning a handle to it
+    ///
+    /// # Panics
+    ///
+    /// If more than `u32::MAX` values are stored at once
+    pub fn insert(&mut self, value: T) -> Handle<T> {
+        self.len += 1;
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.value = Some(value);
+            Handle::new(index, slot.generation)
+        } else {
+            let index = u32::try_from(self.slots.len()).expect("HandleMap capacity exceeded");
+            self.slots.push(HandleSlot { generation: 1, value: Some(value) });
+            Handle::new(index, 1)
+        }
+    }
+
+    /// Get a reference to the value for the given handle, or None if the handle is invalid or its value has been removed
+    pub fn get(&self, handle: Handle<T>) -> Option<&T> {
+        self.slots.get(handle.index())
+            .filter(|slot| slot.generation == handle.generation())
+            .and_then(|slot| slot.value.as_ref())
+    }
+
+    /// Get a mutable reference to the value for the given handle, or None if the handle is invalid or its value has been removed
+    pub fn get_mut(&mut self, handle: Handle<T>) -> Option<&mut T> {
+        self.slots.get_mut(handle.index())
+            .filter(|slot| slot.generation == handle.generation())
+            .and_then(|slot| slot.value.as_mut())
+    }
+
+    /// Remove and return the value for the given handle, invalidating the handle
+    ///
+    /// Returns None if the handle is invalid or its value has already been removed
+    pub fn remove(&mut self, handle: Handle<T>) -> Option<T> {
+        let slot = self.slots.get_mut(handle.index())
+            .filter(|slot| slot.generation == handle.generation())?;
+        let value = slot.value.take()?;
+
+        // Generation 0 is skipped so that raw handles are never 0
+        slot.generation = slot.generation.checked_add(1).unwrap_or(1);
+        self.free.push(handle.index() as u32);
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Returns true if the given handle refers to a value in this map
+    pub fn contains(&self, handle: Handle<T>) -> bool {
+        self.get(handle).is_some()
+    }
+
+    /// Number of values in this map
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if this map contains no values
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over all values in this map, in no particular order
+    pub fn values(&self) -> impl Iterator<Item=&T> {
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
+    }
+}
+
+impl<T> Default for HandleMap<T> {
+    fn default() -> Self {
+        HandleMap::new()
+    }
+}
+
+
+/// Trait for rust types backing a handle-based Java class; Implemented by `#[derive(JavaType)]` with `#[java(handle)]`
+///
+/// The rust value is owned by its Java object, and stored in a per-type [`HandleMap`] until the Java object is closed or garbage collected, or the value is moved out by a conversion by value, see [`handle_take`].
+/// The Java object only holds the (generational) handle, so use-after-close and double-free are detected rather than dereferencing freed memory.
+pub trait HandleClass: JavaType + Send + Sync + 'static {
+    /// Storage for live instances of this type
+    fn handles() -> &'static Mutex<HandleMap<Arc<Self>>>;
+}
+
+/// Live handle-backed objects, per qualified class name
+static LIVE_HANDLE_OBJECTS: Mutex<BTreeMap<&'static str, usize>> = Mutex::new(BTreeMap::new());
+
+/// Lock a mutex, ignoring poisoning; Handle maps remain consistent if a panic occurs while locked
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Owners of the memory of direct buffers handed to Java, see [`direct_buffer_into_jni`]
+#[cfg(any(feature = "bytes", feature = "image", feature = "ndarray"))]
+static DIRECT_BUFFERS: Mutex<HandleMap<Box<dyn Any + Send>>> = Mutex::new(HandleMap::new());
+
+/// Hand memory to Java as a direct `java.nio.ByteBuffer`, without copying
+///
+/// The owner of the memory is stored in a [`HandleMap`], the handle of which `from_native` passes to Java to register with a cleaner of the buffer; See [`release_direct_buffer`].
+/// The owner is dropped if the buffer cannot be created or `from_native` fails.
+///
+/// # Arguments
+///
+/// * `owner`: Owner of the memory, released once the buffer is unreachable
+/// * `address`: Start of the memory
+/// * `length`: Length of the memory in bytes
+/// * `env`: JNI Env
+/// * `from_native`: Java call creating the returned object from the buffer and the raw handle
+///
+/// returns: Result<JObject, Option<Exception>>
+///
+/// # Safety
+///
+/// `address` must point to `length` bytes owned by `owner`, which are not moved or freed when `owner` is moved
+#[cfg(any(feature = "bytes", feature = "image", feature = "ndarray"))]
+pub(crate) unsafe fn direct_buffer_into_jni<'local, T: Send + 'static>(
+    owner: T,
+    address: *mut u8,
+    length: usize,
+    env: &mut JNIEnv<'local>,
+    from_native: impl FnOnce(&mut JNIEnv<'local>, &jni::objects::JByteBuffer<'local>, i64) -> jni::errors::Result<JValueOwned<'local>>,
+) -> Result<JObject<'local>, Option<Exception>> {
+    // SAFETY: The memory is not moved or freed until the owner is removed from the handle map
+    let buffer = unsafe { env.new_direct_byte_buffer(address, length) }.map_err(map_jni_error)?;
+    let handle = lock(&DIRECT_BUFFERS).insert(Box::new(owner));
+    from_native(env, &buffer, handle.into_raw())
+        .map_err(|error| {
+            let removed = lock(&DIRECT_BUFFERS).remove(handle);
+            drop(removed);
+            map_jni_error(error)
+        })?
+        .l().map_err(map_jni_error)
+}
+
+/// Release the owner of a direct buffer created by [`direct_buffer_into_jni`]; Called by the Java cleaner of the buffer
+///
+/// Stale and forged handles are ignored
+#[cfg(any(feature = "bytes", feature = "image", feature = "ndarray"))]
+pub(crate) fn release_direct_buffer(handle: i64) {
+    // Dropped outside of the lock
+    let removed = lock(&DIRECT_BUFFERS).remove(Handle::from_raw(handle));
+    drop(removed);
+}
+
+/// Move a value into the handle map for its type, and create a Java object owning it
+///
+/// # Arguments
+///
+/// * `value`: Value to move to Java
+/// * `class`: Cached class of the handle-backed class
+/// * `env`: JNI Env
+///
+/// returns: Result<JObject, Option<Exception>>
+pub fn handle_into_jni<'local, T: HandleClass>(value: T, class: &'static CachedClass, env: &mut JNIEnv<'local>) -> Result<JObject<'local>, Option<Exception>> {
+    let class = &class.get(env)?;
+    let handle = lock(T::handles()).insert(Arc::new(value));
+    *lock(&LIVE_HANDLE_OBJECTS).entry(T::QUALIFIED_NAME()).or_insert(0) += 1;
+
+    env.new_object(class, "(J)V", &[JValue::Long(handle.into_raw())])
+        .map_err(|error| {
+            handle_release::<T>(handle.into_raw());
+            map_jni_error(error)
+        })
+}
+
+/// Borrow the rust value owned by a handle-backed Java object
+///
+/// Throws IllegalStateException if the object has been closed
+pub fn handle_borrow<'local, T: HandleClass>(object: &JObject<'local>, env: &mut JNIEnv<'local>) -> Result<Arc<T>, Option<Exception>> {
+    if object.is_null() {
+        return Err(Some(Exception { class: NULL_POINTER_EXCEPTION.to_string(), msg: format!("expected {}", T::QUALIFIED_NAME()) }));
+    }
+
+    let raw = env.get_field(object, "handle", "J")
+        .map_err(map_jni_error)?
+        .j()
+        .map_err(map_jni_error)?;
+
+    lock(T::handles())
+        .get(Handle::from_raw(raw))
+        .cloned()
+        .ok_or_else(|| Some(Exception { class: "java/lang/IllegalStateException".to_string(), msg: format!("{} has been closed", T::QUALIFIED_NAME()) }))
+}
+
+/// Release the rust value owned by a handle; Called by the generated `release` native method when the Java object is closed or cleaned
+///
+/// Releasing an already-released handle has no effect. The value is dropped once in-progress calls borrowing it have completed
+pub fn handle_release<T: HandleClass>(raw: i64) {
+    let removed = lock(T::handles()).remove(Handle::from_raw(raw));
+    if removed.is_some() {
+        count_released(T::QUALIFIED_NAME());
+    }
+    // Value is dropped here, outside of the locks, as dropping may call into other handle-backed types
+}
+
+/// Move the rust value out of a handle-backed Java object, for passing it by value such as to `fn finish(self)` methods
+///
+/// The Java object is left closed; Further calls on it throw IllegalStateException, as after `close()`.
+/// Throws IllegalStateException if the object has already been closed, or if its value is borrowed by an in-progress call
+pub fn handle_take<'local, T: HandleClass>(object: &JObject<'local>, env: &mut JNIEnv<'local>) -> Result<T, Option<Exception>> {
+    if object.is_null() {
+        return Err(Some(Exception { class: NULL_POINTER_EXCEPTION.to_string(), msg: format!("expected {}", T::QUALIFIED_NAME()) }));
+    }
+
+    let raw = env.get_field(object, "handle", "J")
+        .map_err(map_jni_error)?
+        .j()
+        .map_err(map_jni_error)?;
+
+    // Borrows are cloned from the map while locked, so the value cannot be borrowed while it is checked and removed
+    let value = {
+        let mut handles = lock(T::handles());
+        let handle = Handle::from_raw(raw);
+        match handles.get(handle) {
+            None => return Err(Some(Exception { class: "java/lang/IllegalStateException".to_string(), msg: format!("{} has been closed", T::QUALIFIED_NAME()) })),
+            Some(value) if Arc::strong_count(value) > 1 => return Err(Some(Exception { class: "java/lang/IllegalStateException".to_string(), msg: format!("{} is in use, and cannot be moved", T::QUALIFIED_NAME()) })),
+            Some(_) => {}
+        }
+        handles.remove(handle)
+    };
+    count_released(T::QUALIFIED_NAME());
+    let value = value.and_then(Arc::into_inner).expect("unique handle value");
+
+    // The object's Cleaner releases the removed handle, which has no effect
+    env.set_field(object, "handle", "J", JValue::Long(0)).map_err(map_jni_error)?;
+    Ok(value)
+}
+
+/// Decrement the live object count of a handle-backed class, see [`live_handle_objects`]
+fn count_released(qualified_name: &'static str) {
+    let mut live_objects = lock(&LIVE_HANDLE_OBJECTS);
+    if let Some(count) = live_objects.get_mut(qualified_name) {
+        *count -= 1;
+        if *count == 0 {
+            live_objects.remove(qualified_name);
+        }
+    }
+}
+
+/// Move the rust value owned by a handle-backed Java object to a new handle; Called by the generated `transfer` native method of subclassable classes, when a Java subclass takes over an existing object
+///
+/// The object is left closed, releasing it has no effect. Throws IllegalStateException if the object has already been closed
+pub fn handle_transfer<'local, T: HandleClass>(object: &JObject<'local>, env: &mut JNIEnv<'local>) -> Result<i64, Option<Exception>> {
+    if object.is_null() {
+        return Err(Some(Exception { class: NULL_POINTER_EXCEPTION.to_string(), msg: format!("expected {}", T::QUALIFIED_NAME()) }));
+    }
+
+    let raw = env.get_field(object, "handle", "J")
+        .map_err(map_jni_error)?
+        .j()
+        .map_err(map_jni_error)?;
+
+    let handle = {
+        let mut handles = lock(T::handles());
+        let value = handles.remove(Handle::from_raw(raw))
+            .ok_or_else(|| Some(Exception { class: "java/lang/IllegalStateException".to_string(), msg: format!("{} has been closed", T::QUALIFIED_NAME()) }))?;
+        handles.insert(value)
+    };
+
+    env.set_field(object, "handle", "J", JValue::Long(0))
+        .map(|_| handle.into_raw())
+        .map_err(|error| {
+            handle_release::<T>(handle.into_raw());
+            map_jni_error(error)
+        })
+}
+
+/// Currently alive handle-backed objects, as (qualified class name, count) pairs; For hunting down leaked native objects
+pub fn live_handle_objects() -> Vec<(&'static str, usize)> {
+    lock(&LIVE_HANDLE_OBJECTS)
+        .iter()
+        .map(|(name, count)| (*name, *count))
+        .collect()
+}
+
+/// Handle-backed object of a subclassable class, together with its Java object; For calling overridable methods, which may be overridden by a Java subclass
+///
+/// `#[derive(JavaType)]` generates a `virtual_name(this: &Virtual<Self>, env, ...)` function for each `#[java(overridable)]` method, which calls the method on the Java object.
+/// Maps to the Java class of `T` in exported functions, and dereferences to the rust value
+pub struct Virtual<T: HandleClass> {
+    object: GlobalRef,
+    value: Arc<T>,
+}
+
+impl<T: HandleClass> Virtual<T> {
+    /// The Java object, which may be an instance of a subclass
+    pub fn as_obj(&self) -> &JObject<'static> {
+        self.object.as_obj()
+    }
+}
+
+impl<T: HandleClass> Clone for Virtual<T> {
+    fn clone(&self) -> Self {
+        Virtual { object: self.object.clone(), value: self.value.clone() }
+    }
+}
+
+impl<T: HandleClass> Deref for Virtual<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+/// Java class of `T` = rust [`Virtual<T>`]
+impl<T: HandleClass> JavaType for Virtual<T> {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { T::QUALIFIED_NAME() }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { T::JVM_PARAM_SIGNATURE() }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        let value = handle_borrow(&jni_value, env)?;
+        Ok(Virtual { object: env.new_global_ref(jni_value).map_err(map_jni_error)?, value })
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        env.new_local_ref(self.object)
+            .map_err(map_jni_error)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), T::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Java instance method called virtually, with its method ID resolved once per class of the receiving objects; Usable in statics
+///
+/// Resolved classes are held by global references until [`jni_util::release_global_state`], keyed by their Java VM
+pub struct VirtualMethod {
+    name: &'static str,
+    resolved: Mutex<Vec<(usize, GlobalRef, JMethodID)>>,
+}
+
+impl VirtualMethod {
+    /// Create a method cache for the method with the given Java name
+    pub const fn new(name: &'static str) -> VirtualMethod {
+        VirtualMethod { name, resolved: Mutex::new(Vec::new()) }
+    }
+
+    /// Call this method on `object`, dispatching to the implementation of the object's class
+    ///
+    /// # Arguments
+    ///
+    /// * `object`: Receiving object
+    /// * `signature`: JVM method signature, such as "(I)Ljava/lang/String;"
+    /// * `args`: Arguments, matching the signature
+    /// * `env`: JNI Env
+    ///
+    /// returns: Result<JValueOwned, Option<Exception>>
+    pub fn call<'local>(&'static self, object: &JObject, signature: &str, args: &[JValue], env: &mut JNIEnv<'local>) -> Result<JValueOwned<'local>, Option<Exception>> {
+        let return_type = signature.rsplit_once(')')
+            .and_then(|(_, return_type)| ReturnType::from_str(return_type).ok())
+            .ok_or_else(|| Some(Exception { class: ILLEGAL_ARGUMENT_EXCEPTION.to_string(), msg: format!("invalid method signature {}", signature) }))?;
+
+        let vm = vm_key(env)?;
+        let class = env.get_object_class(object).map_err(map_jni_error)?;
+        let cached = lock(&self.resolved).iter()
+            .find(|(resolved_vm, resolved_class, _)| *resolved_vm == vm && env.is_same_object(resolved_class, &class).unwrap_or(false))
+            .map(|(_, _, method_id)| *method_id);
+        let method_id = match cached {
+            Some(method_id) => method_id,
+            None => {
+                // Resolved outside of the lock; Resolving may initialize the class, running Java code
+                let method_id = env.get_method_id(&class, self.name, signature).map_err(map_jni_error)?;
+                let class_ref = env.new_global_ref(&class).map_err(map_jni_error)?;
+                let mut resolved = lock(&self.resolved);
+                if resolved.is_empty() {
+                    register_global_cache(self);
+                }
+                resolved.push((vm, class_ref, method_id));
+                method_id
+            }
+        };
+        env.delete_local_ref(class).map_err(map_jni_error)?;
+
+        let args = args.iter().map(JValue::as_jni).collect::<Vec<_>>();
+        // SAFETY: The method ID was resolved for the class of `object` with the same signature, and the return type is taken from that signature
+        unsafe { env.call_method_unchecked(object, method_id, return_type, &args) }
+            .map_err(map_jni_error)
+    }
+}
+
+impl GlobalCache for VirtualMethod {
+    fn release(&self) {
+        lock(&self.resolved).clear();
+    }
+}
+
+/// Java object converted from a shared `Rc`/`Arc` value
+struct SharedObject {
+    object: GlobalRef,
+    /// Clone of the `Rc`/`Arc`, so that the allocation is not freed and its address reused by another value within the scope
+    _keep_alive: Box<dyn Any>,
+}
+
+thread_local! {
+    /// Java objects converted from shared `Rc`/`Arc` values, by (allocation address, JVM signature); Some while a [`with_preserved_identity`] scope is running
+    static SHARED_OBJECTS: RefCell<Option<HashMap<(usize, &'static str), SharedObject>>> = const { RefCell::new(None) };
+}
+
+/// Run conversions into Java that preserve the identity of shared values; [`Rc`](std::rc::Rc) and [`Arc`] values aliasing the same allocation become one Java object, rather than a copy per occurrence
+///
+/// Preserves the shape of object graphs, and converts each shared value once. Nested scopes share the outermost scope
+///
+/// # Arguments
+///
+/// * `env`: JNI Env
+/// * `f`: Conversions to run
+///
+/// returns: Result<T, Option<Exception>>
+pub fn with_preserved_identity<'local, T>(env: &mut JNIEnv<'local>, f: impl FnOnce(&mut JNIEnv<'local>) -> Result<T, Option<Exception>>) -> Result<T, Option<Exception>> {
+    if SHARED_OBJECTS.with_borrow(Option::is_some) {
+        return f(env);
+    }
+
+    /// Ends the scope when dropped, also on panic
+    struct Scope;
+
+    impl Drop for Scope {
+        fn drop(&mut self) {
+            SHARED_OBJECTS.take();
+        }
+    }
+
+    SHARED_OBJECTS.set(Some(HashMap::new()));
+    let _scope = Scope;
+    f(env)
+}
+
+/// Convert a value held by an `Rc` or `Arc` at `address`, reusing the Java object it was converted into earlier in the current [`with_preserved_identity`] scope
+///
+/// Values that are not shared with another `Rc`/`Arc` cannot be encountered again, and are not recorded; Shared values pass a clone of their `Rc`/`Arc` as `keep_alive`, held until the scope ends
+pub(crate) fn shared_into_jni<'local, T: JavaType>(address: usize, keep_alive: Option<Box<dyn Any>>, env: &mut JNIEnv<'local>, value: impl FnOnce() -> T) -> Result<T::JniType<'local>, Option<Exception>>
+where T::JniType<'local>: From<JObject<'local>> + AsRef<JObject<'local>> {
+    let key = (address, T::JVM_PARAM_SIGNATURE());
+    let existing = SHARED_OBJECTS.with_borrow(|objects| objects.as_ref().map(|objects| objects.get(&key).map(|shared| shared.object.clone())));
+
+    match (existing, keep_alive) {
+        (Some(Some(object)), _) => env.new_local_ref(object.as_obj())
+            .map(T::JniType::from)
+            .map_err(map_jni_error),
+        (Some(None), Some(keep_alive)) => {
+            let object = value().into_jni(env)?;
+            let global = env.new_global_ref(object.as_ref()).map_err(map_jni_error)?;
+            SHARED_OBJECTS.with_borrow_mut(|objects| objects.as_mut().map(|objects| objects.insert(key, SharedObject { object: global, _keep_alive: keep_alive })));
+            Ok(object)
+        }
+        _ => value().into_jni(env)
+    }
+}
+
+/// Converts as `T`, inside a [`with_preserved_identity`] scope; For exported functions returning object graphs with shared [`Rc`](std::rc::Rc) or [`Arc`] values
+///
+/// Maps to the Java type of `T`. Conversions from Java create a separate `Rc`/`Arc` per occurrence
+pub struct PreserveIdentity<T>(pub T);
+
+impl<T: JavaType> JavaType for PreserveIdentity<T> where for<'local> T::JniType<'local>: From<JObject<'local>> + AsRef<JObject<'local>> {
+    type JniType<'local> = T::JniType<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { T::QUALIFIED_NAME() }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { T::JVM_PARAM_SIGNATURE() }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { T::EXCEPTION_NULL() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        T::from_jni(jni_value, env).map(PreserveIdentity)
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        with_preserved_identity(env, |env| self.0.into_jni(env))
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        T::from_jvalue(jvalue, env)
+    }
+}
+
+/// Converts as `T`, but maps to `java.lang.Object`; For parameters and results of generic Java methods, whose types are erased
+///
+/// Such as the element of `List.add(Object)`, imported with [`jimport!`](crate::proc_macro::jimport)
+pub struct Erased<T>(pub T);
+
+/// java.lang.Object = rust [`Erased<T>`]
+impl<T: JavaType> JavaType for Erased<T> where for<'local> T::JniType<'local>: From<JObject<'local>> + Into<JObject<'local>> {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "java.lang.Object" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Ljava/lang/Object;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        T::from_jni(T::JniType::from(jni_value), env).map(Erased)
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        self.0.into_jni(env).map(Into::into)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to java.lang.Object", jvalue.type_name()) }))
+        }
+    }
+}
+
+/// Java object which is converted only if it is an instance of `T`; For parameters typed `java.lang.Object`, such as that of `equals(Object)`
+///
+/// Maps to `java.lang.Object`. Null and objects of other classes convert to `None`, instances of `T` are borrowed as with `&T` parameters. Cannot be converted back into Java
+pub struct InstanceOf<T: JavaBorrow>(pub Option<T::Guard>);
+
+impl<T: JavaBorrow> InstanceOf<T> {
+    /// The borrowed value, if the object was an instance of `T`
+    pub fn get(&self) -> Option<&T> {
+        self.0.as_deref()
+    }
+}
+
+/// java.lang.Object = rust [`InstanceOf<T>`]
+impl<T: JavaBorrow> JavaType for InstanceOf<T> where for<'local> T::JniType<'local>: From<JObject<'local>> {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "java.lang.Object" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Ljava/lang/Object;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        if jni_value.is_null() {
+            return Ok(InstanceOf(None));
+        }
+
+        let class = &signature_class(T::JVM_PARAM_SIGNATURE(), env)?;
+        let is_instance = env.is_instance_of(&jni_value, class).map_err(map_jni_error)?;
+
+        if is_instance {
+            T::borrow_jni(T::JniType::from(jni_value), env).map(|guard| InstanceOf(Some(guard)))
+        } else {
+            Ok(InstanceOf(None))
+        }
+    }
+
+    fn into_jni<'local>(self, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        Err(Some(Exception { class: "java/lang/UnsupportedOperationException".to_string(), msg: format!("InstanceOf<{}> cannot be converted into Java", T::QUALIFIED_NAME()) }))
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to java.lang.Object", jvalue.type_name()) }))
+        }
+    }
+}
+
+thread_local! {
+    /// (JavaVM, JNI Env) of the current thread, if it was attached by [`callback_env`]
+    static CALLBACK_ENV: Cell<Option<(*mut jni::sys::JavaVM, *mut jni::sys::JNIEnv)>> = const { Cell::new(None) };
+}
+
+/// JNI Env for calling into Java from the current thread, and whether the thread was attached for callbacks rather than running Java code
+///
+/// Threads that are not attached are attached as daemon threads, which stay attached until they exit. Their env is cached in a thread-local,
+/// so that repeated callbacks from rust threads neither call `GetEnv` nor attach again
+fn callback_env(vm: &JavaVM) -> Result<(JNIEnv<'_>, bool), Option<Exception>> {
+    if let Some((_, env)) = CALLBACK_ENV.get().filter(|(cached_vm, _)| *cached_vm == vm.get_java_vm_pointer()) {
+        // SAFETY: The env is that of the current thread, which remains attached until it exits
+        let env = unsafe { JNIEnv::from_raw(env) }.map_err(map_jni_error)?;
+        return Ok((env, true));
+    }
+
+    if let Ok(env) = vm.get_env() {
+        return Ok((env, false));
+    }
+    let env = vm.attach_current_thread_as_daemon().map_err(map_jni_error)?;
+    CALLBACK_ENV.set(Some((vm.get_java_vm_pointer(), env.get_raw())));
+    Ok((env, true))
+}
+
+/// Java `hashCode()` of a rust value, from its [`Hash`] implementation
+///
+/// The 64-bit hash is folded as by `Long.hashCode`. Hashes are stable for the lifetime of the process, but not between runs
+pub fn java_hash_code<T: Hash + ?Sized>(value: &T) -> i32 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    value.hash(&mut hasher);
+    let hash = hasher.finish();
+    (hash ^ (hash >> 32)) as i32
+}
+
+/// Marker trait for Java interfaces implemented on the Java side, such as listener interfaces
+///
+/// The interface itself is not generated, and must be declared in Java
+pub trait JavaInterface: 'static {
+    /// Fully qualified java name of this interface, such as "com.example.EventListener"
+    fn QUALIFIED_NAME() -> &'static str;
+
+    /// JVM "internal" type signature, such as "Lcom/example/EventListener;"
+    fn JVM_PARAM_SIGNATURE() -> &'static str;
+}
+
+/// Java object implementing interface `T`, held as a global reference so that it may be stored and used from other threads
+///
+/// Maps to the Java interface type `T` in exported functions
+pub struct Listener<T: JavaInterface> {
+    object: GlobalRef,
+    vm: Arc<JavaVM>,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T: JavaInterface> Listener<T> {
+    /// The listener object
+    pub fn as_obj(&self) -> &JObject<'static> {
+        self.object.as_obj()
+    }
+}
+
+impl<T: JavaInterface> Clone for Listener<T> {
+    fn clone(&self) -> Self {
+        Listener { object: self.object.clone(), vm: self.vm.clone(), _type: PhantomData }
+    }
+}
+
+/// Java interface `T` = rust [`Listener<T>`]
+impl<T: JavaInterface> JavaType for Listener<T> {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { T::QUALIFIED_NAME() }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { T::JVM_PARAM_SIGNATURE() }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        if jni_value.is_null() {
+            return Err(Some(Exception { class: NULL_POINTER_EXCEPTION.to_string(), msg: format!("expected {}", T::QUALIFIED_NAME()) }));
+        }
+
+        Ok(Listener {
+            object: env.new_global_ref(jni_value).map_err(map_jni_error)?,
+            vm: Arc::new(env.get_java_vm().map_err(map_jni_error)?),
+            _type: PhantomData,
+        })
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        env.new_local_ref(self.object)
+            .map_err(map_jni_error)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), T::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Thread-safe registry of Java listener objects, for pushing events from rust to Java subscribers
+///
+/// Listeners are added/removed from exported functions taking a [`Listener<T>`] parameter, and notified with [`Listeners::dispatch`] from any thread
+pub struct Listeners<T: JavaInterface> {
+    listeners: Mutex<Vec<Listener<T>>>,
+}
+
+impl<T: JavaInterface> Listeners<T> {
+    /// Create an empty registry; Usable in statics
+    pub const fn new() -> Listeners<T> {
+        Listeners { listeners: Mutex::new(Vec::new()) }
+    }
+
+    /// Register a listener. A listener that is added multiple times is notified multiple times
+    pub fn add(&self, listener: Listener<T>) {
+        lock(&self.listeners).push(listener);
+    }
+
+    /// Unregister a listener, compared by Java object identity; Returns false if the listener was not registered
+    ///
+    /// If the listener was added multiple times, only one registration is removed
+    pub fn remove(&self, listener: &Listener<T>) -> bool {
+        let Ok((env, _)) = callback_env(&listener.vm) else { return false };
+
+        let mut listeners = lock(&self.listeners);
+        let position = listeners.iter()
+            .position(|registered| env.is_same_object(registered.as_obj(), listener.as_obj()).unwrap_or(false));
+
+        match position {
+            Some(idx) => {
+                listeners.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Unregister all listeners
+    pub fn clear(&self) {
+        lock(&self.listeners).clear();
+    }
+
+    /// Number of registered listeners
+    pub fn len(&self) -> usize {
+        lock(&self.listeners).len()
+    }
+
+    /// Returns true if no listeners are registered
+    pub fn is_empty(&self) -> bool {
+        lock(&self.listeners).is_empty()
+    }
+
+    /// Call `f` for every registered listener, attaching the current thread to the JVM if required
+    ///
+    /// Listeners added or removed during dispatch take effect on the next dispatch. Each call runs in its own local reference frame.
+    /// A failing listener does not prevent the remaining listeners from being notified; The first error is returned, with Java exceptions thrown by listeners re-thrown once all listeners have been called.
+    /// If the thread was attached for dispatch, a thrown exception is printed and cleared instead, as there is no Java caller to receive it.
+    /// Threads attached for dispatch stay attached as daemon threads until they exit, so that repeated dispatch from rust threads does not attach them again
+    ///
+    /// # Arguments
+    ///
+    /// * `f`: Callback, receiving the JNI Env and listener object
+    ///
+    /// returns: Result<(), Option<Exception>>
+    pub fn dispatch<F>(&self, mut f: F) -> Result<(), Option<Exception>>
+    where
+        F: FnMut(&mut JNIEnv, &JObject<'static>) -> Result<(), Option<Exception>>,
+    {
+        // Snapshot, so that listeners may (un)register from within their callback without deadlocking
+        let listeners = lock(&self.listeners).clone();
+        let Some(vm) = listeners.first().map(|listener| listener.vm.clone()) else { return Ok(()) };
+
+        let (mut env, attached_for_callbacks) = callback_env(&vm)?;
+
+        let mut first_error: Option<Option<Exception>> = None;
+        let mut first_throwable: Option<GlobalRef> = None;
+        for listener in &listeners {
+            env.push_local_frame(16).map_err(map_jni_error)?;
+            let result = f(&mut env, listener.as_obj());
+
+            if let Err(error) = result {
+                if env.exception_check().unwrap_or(false) {
+                    let throwable = env.exception_occurred().map_err(map_jni_error)?;
+                    env.exception_clear().map_err(map_jni_error)?;
+                    if first_error.is_none() {
+                        first_throwable = Some(env.new_global_ref(throwable).map_err(map_jni_error)?);
+                    }
+                }
+                first_error.get_or_insert(error);
+            }
+
+            unsafe { env.pop_local_frame(&JObject::null()) }.map_err(map_jni_error)?;
+        }
+
+        match first_error {
+            None => Ok(()),
+            Some(error) => {
+                if let Some(throwable) = first_throwable {
+                    if attached_for_callbacks {
+                        // Local references of attached threads are only freed when the thread exits; The throwable is referenced within a frame
+                        env.with_local_frame(1, |env| {
+                            let throwable = JThrowable::from(env.new_local_ref(&throwable)?);
+                            env.throw(throwable)?;
+                            let _ = env.exception_describe();
+                            env.exception_clear()
+                        }).map_err(map_jni_error)?;
+                    } else {
+                        let throwable = JThrowable::from(env.new_local_ref(throwable).map_err(map_jni_error)?);
+                        env.throw(throwable).map_err(map_jni_error)?;
+                    }
+                }
+                Err(error)
+            }
+        }
+    }
+}
+
+impl<T: JavaInterface> Default for Listeners<T> {
+    fn default() -> Self {
+        Listeners::new()
+    }
+}
@@ -0,0 +1,201 @@
+//! [`bytes`] interop; Maps `Bytes` and `BytesMut` onto Java `byte[]`, without an intermediate `Box<[u8]>`
+//!
+//! Payloads are copied into a `byte[]` by default. [`DirectBytes`] instead hands its memory to Java as a read-only direct `java.nio.ByteBuffer`, without copying
+
+use bytes::{Bytes, BytesMut};
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::objects::{JByteArray, JByteBuffer, JClass, JObject, JObjectArray, JValue, JValueOwned};
+use jni::sys::{jbyte, jlong};
+use crate::codegen::JClassDecl;
+use crate::{JavaType, JniArray};
+use crate::interop::{direct_buffer_into_jni, release_direct_buffer};
+use crate::jni_util::{array_length, check_array_length, map_jni_error, non_null, CachedClass, CLASS_CAST_EXCEPTION};
+
+/// Java support class of direct buffers, see [`direct_bytes_declaration`]
+static DIRECT_BYTES_CLASS: CachedClass = CachedClass::new("instantcoffee/DirectBytes");
+
+/// Java source of the support class; Natives are implemented below
+const DIRECT_BYTES_SOURCE: &str = r#"
+/**
+ * Read-only direct byte buffers sharing the memory of rust {@code bytes::Bytes}, which is released once the buffer is unreachable
+ */
+public final class DirectBytes {
+	private static final java.lang.ref.Cleaner CLEANER = java.lang.ref.Cleaner.create();
+
+	private DirectBytes() {}
+
+	private static java.nio.ByteBuffer fromNative(java.nio.ByteBuffer buffer, long handle) {
+		// Views of the buffer keep it reachable
+		CLEANER.register(buffer, new Release(handle));
+		return buffer.asReadOnlyBuffer();
+	}
+
+	private static final class Release implements Runnable {
+		private final long handle;
+
+		private Release(long handle) {
+			this.handle = handle;
+		}
+
+		@Override
+		public void run() {
+			release(handle);
+		}
+	}
+
+	private static native void release(long handle);
+}
+"#;
+
+/// Declaration of the Java support class `instantcoffee.DirectBytes`; Added to modules using [`DirectBytes`] by the `jmodule` macro
+pub fn direct_bytes_declaration() -> JClassDecl {
+    JClassDecl::Source {
+        name: "DirectBytes",
+        package: "instantcoffee",
+        source: DIRECT_BYTES_SOURCE,
+    }
+}
+
+/// Bytes mapped to a read-only direct Java `java.nio.ByteBuffer`, sharing their memory with Java without copying
+///
+/// Converting from Java copies the remaining bytes of the buffer, which may be any byte buffer
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct DirectBytes(pub Bytes);
+
+impl From<Bytes> for DirectBytes {
+    fn from(bytes: Bytes) -> Self {
+        DirectBytes(bytes)
+    }
+}
+
+/// Java `byte[]` = rust [`Bytes`]
+impl JavaType for Bytes {
+    type JniType<'local> = JByteArray<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "byte[]" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "[B" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JByteArray::default() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        non_null(&jni_value, "byte[]")?;
+        // The array is read into a Vec, which Bytes takes over without copying
+        <JByteArray as JniArray<u8>>::from_jni(jni_value, env).map(|bytes| Bytes::from(bytes.into_vec()))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        byte_array_into_jni(&self, env)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(JByteArray::from(obj)),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Java `byte[]` = rust [`BytesMut`]
+impl JavaType for BytesMut {
+    type JniType<'local> = JByteArray<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "byte[]" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "[B" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JByteArray::default() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        non_null(&jni_value, "byte[]")?;
+        <JByteArray as JniArray<u8>>::from_jni(jni_value, env).map(|bytes| BytesMut::from(Bytes::from(bytes.into_vec())))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        byte_array_into_jni(&self, env)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(JByteArray::from(obj)),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+impl JavaType for DirectBytes {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "java.nio.ByteBuffer" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Ljava/nio/ByteBuffer;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        non_null(&jni_value, "java.nio.ByteBuffer")?;
+        let buffer = JByteBuffer::from(jni_value);
+        let position = env.call_method(&buffer, "position", "()I", &[]).map_err(map_jni_error)?.i().map_err(map_jni_error)? as usize;
+        let remaining = env.call_method(&buffer, "remaining", "()I", &[]).map_err(map_jni_error)?.i().map_err(map_jni_error)? as usize;
+        check_array_length(remaining, "java.nio.ByteBuffer")?;
+
+        let is_direct = env.call_method(&buffer, "isDirect", "()Z", &[]).map_err(map_jni_error)?.z().map_err(map_jni_error)?;
+        if is_direct {
+            let mut bytes = vec![0u8; remaining];
+            if remaining > 0 {
+                let address = env.get_direct_buffer_address(&buffer).map_err(map_jni_error)?;
+                // SAFETY: The buffer holds `remaining` bytes after its position
+                unsafe { std::ptr::copy_nonoverlapping(address.add(position), bytes.as_mut_ptr(), remaining) };
+            }
+            Ok(DirectBytes(Bytes::from(bytes)))
+        } else {
+            // Read-only heap buffers do not expose their array; The remaining bytes are copied out through a duplicate, leaving the position of the buffer unchanged
+            let array = env.new_byte_array(array_length(remaining, "byte")?).map_err(map_jni_error)?;
+            let duplicate = env.call_method(&buffer, "duplicate", "()Ljava/nio/ByteBuffer;", &[]).map_err(map_jni_error)?.l().map_err(map_jni_error)?;
+            // `get` returns the duplicate itself
+            let returned = env.call_method(&duplicate, "get", "([B)Ljava/nio/ByteBuffer;", &[JValue::Object(&array)]).map_err(map_jni_error)?.l().map_err(map_jni_error)?;
+            env.delete_local_ref(returned).map_err(map_jni_error)?;
+            env.delete_local_ref(duplicate).map_err(map_jni_error)?;
+            <Bytes as JavaType>::from_jni(array, env).map(DirectBytes)
+        }
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let length = self.0.len();
+        // Java never writes to the buffer, it is only handed out as a read-only view
+        let address = self.0.as_ptr() as *mut u8;
+
+        let class = &DIRECT_BYTES_CLASS.get(env)?;
+        // SAFETY: The bytes are kept alive by the Bytes, and hold `length` bytes
+        unsafe {
+            direct_buffer_into_jni(self.0, address, length, env, |env, buffer, handle| {
+                env.call_static_method(<&JClass>::from(class.as_obj()), "fromNative", "(Ljava/nio/ByteBuffer;J)Ljava/nio/ByteBuffer;", &[JValue::Object(buffer), JValue::Long(handle)])
+            })
+        }
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Copy bytes into a new Java `byte[]`
+fn byte_array_into_jni<'local>(bytes: &[u8], env: &mut JNIEnv<'local>) -> Result<JByteArray<'local>, Option<Exception>> {
+    let array = env.new_byte_array(array_length(bytes.len(), "byte")?).map_err(map_jni_error)?;
+    // SAFETY: jbyte is i8, of the same layout as u8
+    let slice: &[jbyte] = unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const jbyte, bytes.len()) };
+    env.set_byte_array_region(&array, 0, slice).map_err(map_jni_error)?;
+    Ok(array)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_instantcoffee_DirectBytes_release<'local>(_env: JNIEnv<'local>, _class: JClass<'local>, handle: jlong) {
+    release_direct_buffer(handle);
+}
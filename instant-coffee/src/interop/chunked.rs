@@ -0,0 +1,255 @@
+//! Chunked transfer of very large arrays; Maps [`ChunkedArray`] onto the Java iterator class `instantcoffee.ChunkedArray`
+//!
+//! Elements are converted one chunk at a time as Java pulls them, so that neither side allocates the whole array at once
+
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::objects::{JClass, JObject, JObjectArray, JValue, JValueOwned};
+use jni::sys::{jlong, jsize};
+use crate::codegen::JClassDecl;
+use crate::interop::{Handle, HandleMap, lock};
+use crate::JavaType;
+use crate::jni_util::{generic_type_name, map_jni_error, CachedClass, CLASS_CAST_EXCEPTION};
+
+/// Number of elements per chunk of [`ChunkedArray::new`]
+pub const DEFAULT_CHUNK_LENGTH: usize = 65536;
+
+/// Java iterator class, see [`chunked_array_declaration`]
+static CHUNKED_ARRAY_CLASS: CachedClass = CachedClass::new("instantcoffee/ChunkedArray");
+
+/// Java source of the iterator class; Natives are implemented below
+const CHUNKED_ARRAY_SOURCE: &str = r#"
+/**
+ * Iterator over the chunks of a large rust array; Each chunk is converted when it is requested, so that the array is never held whole on either side
+ * <p>
+ * Chunks hold at most {@link #chunkLength()} elements, and only the last chunk may be shorter. Closing the iterator drops the remaining elements without converting them
+ *
+ * @param <A> Array type of the chunks, such as {@code int[]}
+ */
+public final class ChunkedArray<A> implements java.util.Iterator<A>, AutoCloseable {
+	private static final java.lang.ref.Cleaner CLEANER = java.lang.ref.Cleaner.create();
+
+	private final long handle;
+	private final long length;
+	private final int chunkLength;
+	private final java.lang.ref.Cleaner.Cleanable cleanable;
+	private A next;
+	private boolean closed;
+
+	private ChunkedArray(long handle, long length, int chunkLength) {
+		this.handle = handle;
+		this.length = length;
+		this.chunkLength = chunkLength;
+		this.cleanable = CLEANER.register(this, new Release(handle));
+	}
+
+	/**
+	 * @return Total number of elements, or -1 if not known in advance
+	 */
+	public long length() {
+		return length;
+	}
+
+	/**
+	 * @return Greatest number of elements in a chunk
+	 */
+	public int chunkLength() {
+		return chunkLength;
+	}
+
+	@Override
+	public synchronized boolean hasNext() {
+		if (next == null && !closed) {
+			@SuppressWarnings("unchecked")
+			A chunk = (A) nextChunk(handle);
+			if (chunk == null) {
+				close();
+			} else {
+				next = chunk;
+			}
+		}
+		return next != null;
+	}
+
+	@Override
+	public synchronized A next() {
+		if (!hasNext()) {
+			throw new java.util.NoSuchElementException();
+		}
+		A chunk = next;
+		next = null;
+		return chunk;
+	}
+
+	@Override
+	public synchronized void close() {
+		closed = true;
+		next = null;
+		cleanable.clean();
+	}
+
+	private static final class Release implements Runnable {
+		private final long handle;
+
+		private Release(long handle) {
+			this.handle = handle;
+		}
+
+		@Override
+		public void run() {
+			release(handle);
+		}
+	}
+
+	private static native Object nextChunk(long handle);
+
+	private static native void release(long handle);
+}
+"#;
+
+/// Declaration of the Java iterator class `instantcoffee.ChunkedArray`; Added to modules using [`ChunkedArray`] by the `jmodule` macro
+pub fn chunked_array_declaration() -> JClassDecl {
+    JClassDecl::Source {
+        name: "ChunkedArray",
+        package: "instantcoffee",
+        source: CHUNKED_ARRAY_SOURCE,
+    }
+}
+
+/// Remaining elements, erased to conversions of Java arrays
+trait ErasedChunks: Send {
+    /// Convert the next chunk into a Java array, or null once all elements have been converted
+    fn next_chunk<'local>(&mut self, env: &mut JNIEnv<'local>) -> Result<JObject<'local>, Option<Exception>>;
+}
+
+type SharedChunks = Arc<Mutex<Box<dyn ErasedChunks>>>;
+
+struct Chunks<I> {
+    elements: I,
+    chunk_length: usize,
+}
+
+impl<T: JavaType, I: Iterator<Item=T> + Send> ErasedChunks for Chunks<I> {
+    fn next_chunk<'local>(&mut self, env: &mut JNIEnv<'local>) -> Result<JObject<'local>, Option<Exception>> {
+        let chunk = self.elements.by_ref().take(self.chunk_length).collect::<Box<[T]>>();
+        if chunk.is_empty() {
+            return Ok(JObject::null());
+        }
+        let array = chunk.into_jni(env)?;
+        // SAFETY: The array is a local reference of this frame, owned by the returned object
+        Ok(unsafe { JObject::from_raw(array.as_ref().as_raw()) })
+    }
+}
+
+/// Java `instantcoffee.ChunkedArray<T[]>` = rust elements of type `T`, transferred in chunks; For datasets too large to convert into a single array
+///
+/// Java pulls the chunks through repeated native calls, and only one chunk is converted at a time. The elements may be produced lazily by any iterator,
+/// such that the whole dataset need not be held in memory on the rust side either. Elements not pulled before the Java iterator is closed or unreachable are dropped unconverted
+pub struct ChunkedArray<T> {
+    chunks: Box<dyn ErasedChunks>,
+    chunk_length: usize,
+    /// Total number of elements, if known from the iterator's size hint
+    length: Option<usize>,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T: JavaType + 'static> ChunkedArray<T> {
+    /// Transfer `elements` in chunks of [`DEFAULT_CHUNK_LENGTH`]
+    pub fn new<I>(elements: I) -> ChunkedArray<T> where I: IntoIterator<Item=T>, I::IntoIter: Send + 'static {
+        Self::with_chunk_length(elements, DEFAULT_CHUNK_LENGTH)
+    }
+
+    /// Transfer `elements` in chunks of `chunk_length`
+    ///
+    /// # Panics
+    ///
+    /// If `chunk_length` is zero, or greater than the maximum length of a Java array
+    pub fn with_chunk_length<I>(elements: I, chunk_length: usize) -> ChunkedArray<T> where I: IntoIterator<Item=T>, I::IntoIter: Send + 'static {
+        assert!(chunk_length > 0 && jsize::try_from(chunk_length).is_ok(), "chunk length must be between 1 and {}, got {}", jsize::MAX, chunk_length);
+        let elements = elements.into_iter();
+        let length = match elements.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower),
+            _ => None,
+        };
+        ChunkedArray { chunks: Box::new(Chunks { elements, chunk_length }), chunk_length, length, _type: PhantomData }
+    }
+}
+
+impl<T: JavaType + Send + 'static> From<Vec<T>> for ChunkedArray<T> {
+    fn from(value: Vec<T>) -> Self {
+        ChunkedArray::new(value)
+    }
+}
+
+impl<T: JavaType + Send + 'static> From<Box<[T]>> for ChunkedArray<T> {
+    fn from(value: Box<[T]>) -> Self {
+        ChunkedArray::new(value.into_vec())
+    }
+}
+
+/// Java `instantcoffee.ChunkedArray` = rust [`ChunkedArray<T>`]; Return-only, cannot be passed from Java
+impl<T: JavaType> JavaType for ChunkedArray<T> {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str {
+        generic_type_name("instantcoffee.ChunkedArray<", <Box<[T]> as JavaType>::QUALIFIED_NAME(), ">")
+    }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Linstantcoffee/ChunkedArray;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(_jni_value: Self::JniType<'local>, _env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        Err(Some(Exception { class: "java/lang/UnsupportedOperationException".to_string(), msg: format!("{} cannot be passed to rust", <Self as JavaType>::QUALIFIED_NAME()) }))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let class = &CHUNKED_ARRAY_CLASS.get(env)?;
+        let length = self.length.and_then(|length| jlong::try_from(length).ok()).unwrap_or(-1);
+        let chunk_length = self.chunk_length as jsize;
+        let handle = lock(&CHUNKS).insert(Arc::new(Mutex::new(self.chunks)));
+
+        env.new_object(class, "(JJI)V", &[JValue::Long(handle.into_raw()), JValue::Long(length), JValue::Int(chunk_length)])
+            .map_err(|error| {
+                lock(&CHUNKS).remove(handle);
+                map_jni_error(error)
+            })
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Remaining elements of live Java iterators; Each is locked separately, so that iterators convert their chunks concurrently
+static CHUNKS: Mutex<HandleMap<SharedChunks>> = Mutex::new(HandleMap::new());
+
+#[no_mangle]
+pub extern "system" fn Java_instantcoffee_ChunkedArray_nextChunk<'local>(mut env: JNIEnv<'local>, _class: JClass<'local>, handle: jlong) -> JObject<'local> {
+    let Some(chunks) = lock(&CHUNKS).get(Handle::from_raw(handle)).cloned() else {
+        let _ = env.throw_new("java/lang/IllegalStateException", "instantcoffee.ChunkedArray has been closed");
+        return JObject::null();
+    };
+    let result = lock(&*chunks).next_chunk(&mut env);
+    match result {
+        Ok(chunk) => chunk,
+        Err(Some(exception)) => {
+            let _ = env.throw_new(exception.class, exception.msg);
+            JObject::null()
+        }
+        Err(None) => JObject::null(),
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_instantcoffee_ChunkedArray_release<'local>(_env: JNIEnv<'local>, _class: JClass<'local>, handle: jlong) {
+    // Dropped outside of the lock, as dropping the remaining elements may call into other handle-backed types
+    let removed = lock(&CHUNKS).remove(Handle::from_raw(handle));
+    drop(removed);
+}
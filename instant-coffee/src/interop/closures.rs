@@ -0,0 +1,212 @@
+//! Closure interop; Maps boxed rust closures onto `java.util.function.Supplier` and `java.util.function.Function`, implemented by `instantcoffee.NativeClosure`
+
+use std::sync::{Arc, Mutex};
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::objects::{JClass, JObject, JObjectArray, JValue, JValueOwned};
+use jni::sys::jlong;
+use crate::codegen::JClassDecl;
+use crate::interop::{Handle, HandleMap, lock};
+use crate::interop::result::check_instance;
+use crate::JavaType;
+use crate::jni_util::{generic_type_name, map_jni_error, CachedClass, CLASS_CAST_EXCEPTION};
+
+/// Java support class of closures, see [`native_closure_declaration`]
+static NATIVE_CLOSURE_CLASS: CachedClass = CachedClass::new("instantcoffee/NativeClosure");
+
+/// Java source of the support class; Natives are implemented below
+const NATIVE_CLOSURE_SOURCE: &str = r#"
+/**
+ * Functional interfaces implemented by rust closures; Each closure is dropped once the object implementing it is unreachable
+ */
+public final class NativeClosure {
+	private static final java.lang.ref.Cleaner CLEANER = java.lang.ref.Cleaner.create();
+
+	private NativeClosure() {}
+
+	private static <T> java.util.function.Supplier<T> supplier(long handle) {
+		Supplier<T> supplier = new Supplier<>(handle);
+		CLEANER.register(supplier, new Release(handle));
+		return supplier;
+	}
+
+	private static <T, R> java.util.function.Function<T, R> function(long handle) {
+		Function<T, R> function = new Function<>(handle);
+		CLEANER.register(function, new Release(handle));
+		return function;
+	}
+
+	private static final class Supplier<T> implements java.util.function.Supplier<T> {
+		private final long handle;
+
+		private Supplier(long handle) {
+			this.handle = handle;
+		}
+
+		@Override
+		@SuppressWarnings("unchecked")
+		public T get() {
+			return (T) call(handle, null);
+		}
+	}
+
+	private static final class Function<T, R> implements java.util.function.Function<T, R> {
+		private final long handle;
+
+		private Function(long handle) {
+			this.handle = handle;
+		}
+
+		@Override
+		@SuppressWarnings("unchecked")
+		public R apply(T argument) {
+			return (R) call(handle, argument);
+		}
+	}
+
+	private static final class Release implements Runnable {
+		private final long handle;
+
+		private Release(long handle) {
+			this.handle = handle;
+		}
+
+		@Override
+		public void run() {
+			release(handle);
+		}
+	}
+
+	private static native Object call(long handle, Object argument);
+
+	private static native void release(long handle);
+}
+"#;
+
+/// Declaration of the Java support class `instantcoffee.NativeClosure`; Added to modules returning boxed closures by the `jmodule` macro
+pub fn native_closure_declaration() -> JClassDecl {
+    JClassDecl::Source {
+        name: "NativeClosure",
+        package: "instantcoffee",
+        source: NATIVE_CLOSURE_SOURCE,
+    }
+}
+
+/// Closure erased to a conversion of Java objects; Suppliers ignore their (null) argument
+type ErasedClosure = dyn for<'local> Fn(&mut JNIEnv<'local>, JObject<'local>) -> Result<JObject<'local>, Option<Exception>> + Send + Sync;
+
+/// Erase a closure; Passed through a function, so that its parameter lifetimes are inferred as those of [`ErasedClosure`]
+fn erase<F>(closure: F) -> Arc<ErasedClosure> where F: for<'local> Fn(&mut JNIEnv<'local>, JObject<'local>) -> Result<JObject<'local>, Option<Exception>> + Send + Sync + 'static {
+    Arc::new(closure)
+}
+
+/// Closures referenced from Java; Shared with in-progress calls, so that a closure released while it runs is only dropped once the call completes
+static CLOSURES: Mutex<HandleMap<Arc<ErasedClosure>>> = Mutex::new(HandleMap::new());
+
+/// Hand an erased closure to Java, as the object returned by the static factory `factory` of the support class
+fn closure_into_jni<'local>(closure: Arc<ErasedClosure>, factory: &str, signature: &str, env: &mut JNIEnv<'local>) -> Result<JObject<'local>, Option<Exception>> {
+    let class = &NATIVE_CLOSURE_CLASS.get(env)?;
+    let handle = lock(&CLOSURES).insert(closure);
+    env.call_static_method(<&JClass>::from(class.as_obj()), factory, signature, &[JValue::Long(handle.into_raw())])
+        .map_err(|error| {
+            lock(&CLOSURES).remove(handle);
+            map_jni_error(error)
+        })?
+        .l().map_err(map_jni_error)
+}
+
+/// Java `java.util.function.Supplier<R>` = rust `Box<dyn Fn() -> R + Send + Sync>`; Return-only, cannot be passed from Java
+///
+/// Java may call the closure from any thread, and after the call that returned it has completed. The result must be a Java object; Primitive results can use [`Option<i32>`](Option) or similar
+impl<R: JavaType + 'static> JavaType for Box<dyn Fn() -> R + Send + Sync> where for<'local> R::JniType<'local>: Into<JObject<'local>> {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str {
+        generic_type_name("java.util.function.Supplier<", R::QUALIFIED_NAME(), ">")
+    }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Ljava/util/function/Supplier;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(_jni_value: Self::JniType<'local>, _env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        Err(Some(Exception { class: "java/lang/UnsupportedOperationException".to_string(), msg: format!("{} cannot be passed to rust", <Self as JavaType>::QUALIFIED_NAME()) }))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let closure = erase(move |env, _argument| self().into_jni(env).map(Into::into));
+        closure_into_jni(closure, "supplier", "(J)Ljava/util/function/Supplier;", env)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Java `java.util.function.Function<T, R>` = rust `Box<dyn Fn(T) -> R + Send + Sync>`; Return-only, cannot be passed from Java
+///
+/// Java may call the closure from any thread, and after the call that returned it has completed. The argument and result must be Java objects; Primitives can use [`Option<i32>`](Option) or similar
+impl<T: JavaType + 'static, R: JavaType + 'static> JavaType for Box<dyn Fn(T) -> R + Send + Sync>
+where
+    for<'local> T::JniType<'local>: From<JObject<'local>>,
+    for<'local> R::JniType<'local>: Into<JObject<'local>>,
+{
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str {
+        generic_type_name(generic_type_name("java.util.function.Function<", T::QUALIFIED_NAME(), ", "), R::QUALIFIED_NAME(), ">")
+    }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Ljava/util/function/Function;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(_jni_value: Self::JniType<'local>, _env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        Err(Some(Exception { class: "java/lang/UnsupportedOperationException".to_string(), msg: format!("{} cannot be passed to rust", <Self as JavaType>::QUALIFIED_NAME()) }))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let closure = erase(move |env, argument| {
+            // Type parameters are erased in Java, the argument is checked before converting it
+            check_instance(&argument, T::JVM_PARAM_SIGNATURE(), env)?;
+            let argument = T::from_jni(T::JniType::from(argument), env)?;
+            self(argument).into_jni(env).map(Into::into)
+        });
+        closure_into_jni(closure, "function", "(J)Ljava/util/function/Function;", env)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_instantcoffee_NativeClosure_call<'local>(mut env: JNIEnv<'local>, _class: JClass<'local>, handle: jlong, argument: JObject<'local>) -> JObject<'local> {
+    let Some(closure) = lock(&CLOSURES).get(Handle::from_raw(handle)).cloned() else {
+        let _ = env.throw_new("java/lang/IllegalStateException", "instantcoffee.NativeClosure has been released");
+        return JObject::null();
+    };
+    match closure(&mut env, argument) {
+        Ok(result) => result,
+        Err(Some(exception)) => {
+            let _ = env.throw_new(exception.class, exception.msg);
+            JObject::null()
+        }
+        Err(None) => JObject::null(),
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_instantcoffee_NativeClosure_release<'local>(_env: JNIEnv<'local>, _class: JClass<'local>, handle: jlong) {
+    // Dropped outside of the lock, as dropping the closure may call into other handle-backed types
+    let removed = lock(&CLOSURES).remove(Handle::from_raw(handle));
+    drop(removed);
+}
@@ -0,0 +1,73 @@
+//! Conversion of [`bitflags`] types to `java.util.EnumSet`; See `#[java(flags)]` on the [`JavaType`](crate::JavaType) derive
+
+use bitflags::Flags;
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::objects::{GlobalRef, JClass, JObject, JObjectArray, JString, JValue};
+use crate::JavaType;
+use crate::jni_util::{map_jni_error, non_null, with_local_frame, CachedClass};
+
+static ENUM_SET_CLASS: CachedClass = CachedClass::new("java/util/EnumSet");
+
+/// Convert flags to a `java.util.EnumSet` of a generated Java enum, which declares a constant for each named flag
+///
+/// Composite flags are only added if they are not covered by previously added flags, and unnamed bits are dropped; See [`Flags::iter_names`]
+///
+/// # Arguments
+///
+/// * `flags`: Flags to convert
+/// * `class`: Class of the Java enum
+/// * `signature`: JVM type signature of the Java enum, such as "Lcom/example/Permissions;"
+/// * `env`: JNI environment
+///
+/// returns: The EnumSet
+pub fn enum_set_into_jni<'local, F: Flags>(flags: &F, class: &GlobalRef, signature: &str, env: &mut JNIEnv<'local>) -> Result<JObject<'local>, Option<Exception>> {
+    let enum_set_class = &ENUM_SET_CLASS.get(env)?;
+    let set = env.call_static_method(enum_set_class, "noneOf", "(Ljava/lang/Class;)Ljava/util/EnumSet;", &[JValue::Object(class.as_obj())])
+        .map_err(map_jni_error)?
+        .l().map_err(map_jni_error)?;
+
+    for (name, _) in flags.iter_names() {
+        let constant = env.get_static_field(<&JClass>::from(class.as_obj()), name, signature)
+            .map_err(map_jni_error)?
+            .l().map_err(map_jni_error)?;
+        env.call_method(&set, "add", "(Ljava/lang/Object;)Z", &[JValue::Object(&constant)])
+            .map_err(map_jni_error)?;
+        env.delete_local_ref(constant).map_err(map_jni_error)?;
+    }
+    Ok(set)
+}
+
+/// Convert a `java.util.Set` of Java enum constants to flags, by constant name; Constants without a flag of the same name are ignored
+///
+/// # Arguments
+///
+/// * `set`: Set to convert, must not be null
+/// * `qualified_name`: Qualified Java name of the flags type, for errors
+/// * `env`: JNI environment
+///
+/// returns: The flags
+pub fn enum_set_from_jni<'local, F: Flags>(set: JObject<'local>, qualified_name: &str, env: &mut JNIEnv<'local>) -> Result<F, Option<Exception>> {
+    non_null(&set, qualified_name)?;
+    let constants = JObjectArray::from(
+        env.call_method(&set, "toArray", "()[Ljava/lang/Object;", &[])
+            .map_err(map_jni_error)?
+            .l().map_err(map_jni_error)?
+    );
+
+    let mut flags = F::empty();
+    for idx in 0..env.get_array_length(&constants).map_err(map_jni_error)? {
+        // The constant and its name
+        let name = with_local_frame(2, qualified_name, env, |env| {
+            let constant = env.get_object_array_element(&constants, idx).map_err(map_jni_error)?;
+            let name = env.call_method(&constant, "name", "()Ljava/lang/String;", &[])
+                .map_err(map_jni_error)?
+                .l().map_err(map_jni_error)?;
+            <String as JavaType>::from_jni(JString::from(name), env)
+        })?;
+        if let Some(flag) = F::from_name(&name) {
+            flags.insert(flag);
+        }
+    }
+    Ok(flags)
+}
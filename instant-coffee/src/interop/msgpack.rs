@@ -0,0 +1,54 @@
+//! MessagePack tunneling; Passes types without a Java class of their own as `byte[]`, see [`MsgpackTunneled`]
+
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::objects::{JByteArray, JObjectArray, JValueOwned};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::JavaType;
+use crate::jni_util::{check_array_length, map_jni_error, non_null};
+
+/// Value tunneled through Java as MessagePack encoded `byte[]`, using [`rmp_serde`]; For complex types that need not be inspected from Java, or are decoded there with a MessagePack library
+///
+/// Structs are encoded as maps keyed by field name, see [`rmp_serde::to_vec_named`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct MsgpackTunneled<T>(pub T);
+
+impl<T> From<T> for MsgpackTunneled<T> {
+    fn from(value: T) -> Self {
+        MsgpackTunneled(value)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> JavaType for MsgpackTunneled<T> {
+    type JniType<'local> = JByteArray<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "byte[]" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "[B" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JByteArray::default() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        non_null(&jni_value, "byte[]")?;
+        check_array_length(env.get_array_length(&jni_value).map_err(map_jni_error)? as usize, "byte[]")?;
+        let bytes = env.convert_byte_array(&jni_value).map_err(map_jni_error)?;
+        rmp_serde::from_slice(&bytes)
+            .map(MsgpackTunneled)
+            .map_err(|error| Some(Exception { class: "java/lang/IllegalArgumentException".to_string(), msg: format!("invalid MessagePack encoding of {}: {}", std::any::type_name::<T>(), error) }))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let bytes = rmp_serde::to_vec_named(&self.0)
+            .map_err(|error| Some(Exception { class: "java/lang/IllegalStateException".to_string(), msg: format!("could not encode {} as MessagePack: {}", std::any::type_name::<T>(), error) }))?;
+        env.byte_array_from_slice(&bytes).map_err(map_jni_error)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(JByteArray::from(obj)),
+            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
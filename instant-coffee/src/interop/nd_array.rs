@@ -0,0 +1,329 @@
+//! [`ndarray`] interop; Maps arrays of `f64` onto the Java class `instantcoffee.NdArray`, holding the elements in row-major order and the shape
+//!
+//! Arrays are copied into a `double[]` by default. [`DirectNdArray`] instead hands its memory to Java as a direct buffer, without copying
+
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::objects::{JByteBuffer, JClass, JDoubleArray, JIntArray, JObject, JObjectArray, JValue, JValueOwned};
+use jni::sys::{jint, jlong};
+use ndarray::{Array, Dimension, IxDyn};
+use crate::codegen::JClassDecl;
+use crate::JavaType;
+use crate::interop::{direct_buffer_into_jni, release_direct_buffer};
+use crate::jni_util::{array_length, check_array_length, map_jni_error, non_null, CachedClass};
+
+/// Java array class, see [`nd_array_declaration`]
+static ND_ARRAY_CLASS: CachedClass = CachedClass::new("instantcoffee/NdArray");
+
+/// Java source of the array class; Natives are implemented below
+const ND_ARRAY_SOURCE: &str = r#"
+/**
+ * N-dimensional array of doubles in row-major order, converted to and from rust ndarray arrays
+ * <p>
+ * Elements are held either in a double[], or in a direct buffer in native byte order. Direct arrays returned from rust share its memory, which is released once the buffer is unreachable
+ */
+public final class NdArray {
+	private static final java.lang.ref.Cleaner CLEANER = java.lang.ref.Cleaner.create();
+
+	private final double[] data;
+	private final java.nio.DoubleBuffer buffer;
+	private final int[] shape;
+
+	/**
+	 * @param data Elements in row-major order
+	 * @param shape Length of each dimension, the product of which must equal the number of elements
+	 */
+	public NdArray(double[] data, int... shape) {
+		this(java.util.Objects.requireNonNull(data), null, shape);
+	}
+
+	private NdArray(double[] data, java.nio.DoubleBuffer buffer, int[] shape) {
+		this.shape = shape.clone();
+		long elements = data != null ? data.length : buffer.remaining();
+		if (size(this.shape) != elements) {
+			throw new IllegalArgumentException("shape " + java.util.Arrays.toString(this.shape) + " does not hold " + elements + " elements");
+		}
+		this.data = data;
+		this.buffer = buffer;
+	}
+
+	/**
+	 * Array sharing the remaining elements of a direct buffer; Changes to the buffer are visible to the array, and vice versa
+	 */
+	public static NdArray direct(java.nio.DoubleBuffer buffer, int... shape) {
+		if (!buffer.isDirect()) {
+			throw new IllegalArgumentException("buffer is not direct");
+		}
+		if (buffer.order() != java.nio.ByteOrder.nativeOrder()) {
+			throw new IllegalArgumentException("buffer is not in native byte order");
+		}
+		return new NdArray(null, buffer.slice(), shape);
+	}
+
+	/**
+	 * Zero-filled array backed by a new direct buffer
+	 */
+	public static NdArray allocateDirect(int... shape) {
+		int bytes = Math.toIntExact(Math.multiplyExact(size(shape), Double.BYTES));
+		return direct(java.nio.ByteBuffer.allocateDirect(bytes).order(java.nio.ByteOrder.nativeOrder()).asDoubleBuffer(), shape);
+	}
+
+	private static NdArray fromNative(java.nio.ByteBuffer buffer, int[] shape, long handle) {
+		// Views of the buffer keep it reachable
+		CLEANER.register(buffer, new Release(handle));
+		return new NdArray(null, buffer.order(java.nio.ByteOrder.nativeOrder()).asDoubleBuffer(), shape);
+	}
+
+	private static long size(int[] shape) {
+		long size = 1;
+		for (int length : shape) {
+			if (length < 0) {
+				throw new IllegalArgumentException("negative length in shape " + java.util.Arrays.toString(shape));
+			}
+			size = Math.multiplyExact(size, length);
+		}
+		return size;
+	}
+
+	/**
+	 * @return Length of each dimension
+	 */
+	public int[] shape() {
+		return shape.clone();
+	}
+
+	/**
+	 * @return Number of dimensions
+	 */
+	public int ndim() {
+		return shape.length;
+	}
+
+	/**
+	 * @return Number of elements
+	 */
+	public long size() {
+		return size(shape);
+	}
+
+	/**
+	 * @return True if the elements are held in a direct buffer
+	 */
+	public boolean isDirect() {
+		return buffer != null;
+	}
+
+	/**
+	 * @param index Index along each dimension
+	 * @return Element at the index
+	 */
+	public double get(int... index) {
+		if (index.length != shape.length) {
+			throw new IllegalArgumentException("expected " + shape.length + " indices, got " + index.length);
+		}
+		int offset = 0;
+		for (int i = 0; i < shape.length; i++) {
+			offset = offset * shape[i] + java.util.Objects.checkIndex(index[i], shape[i]);
+		}
+		return data != null ? data[offset] : buffer.get(offset);
+	}
+
+	/**
+	 * @return Elements as a buffer sharing the memory of this array
+	 */
+	public java.nio.DoubleBuffer buffer() {
+		return data != null ? java.nio.DoubleBuffer.wrap(data) : buffer.duplicate();
+	}
+
+	/**
+	 * @return Copy of the elements in row-major order
+	 */
+	public double[] toArray() {
+		if (data != null) {
+			return data.clone();
+		}
+		double[] copy = new double[buffer.capacity()];
+		buffer.duplicate().get(copy);
+		return copy;
+	}
+
+	@Override
+	public String toString() {
+		return "NdArray" + java.util.Arrays.toString(shape);
+	}
+
+	private static final class Release implements Runnable {
+		private final long handle;
+
+		private Release(long handle) {
+			this.handle = handle;
+		}
+
+		@Override
+		public void run() {
+			release(handle);
+		}
+	}
+
+	private static native void release(long handle);
+}
+"#;
+
+/// Declaration of the Java array class `instantcoffee.NdArray`; Added to modules using ndarray arrays by the `jmodule` macro
+pub fn nd_array_declaration() -> JClassDecl {
+    JClassDecl::Source {
+        name: "NdArray",
+        package: "instantcoffee",
+        source: ND_ARRAY_SOURCE,
+    }
+}
+
+/// Array mapped to a Java `instantcoffee.NdArray` backed by a direct buffer, handing its memory to Java without copying
+///
+/// Arrays not in standard (row-major) layout are copied once into it. Converting from Java copies the elements, directly from the buffer of direct Java arrays
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirectNdArray<D: Dimension>(pub Array<f64, D>);
+
+impl<D: Dimension> From<Array<f64, D>> for DirectNdArray<D> {
+    fn from(array: Array<f64, D>) -> Self {
+        DirectNdArray(array)
+    }
+}
+
+impl<D: Dimension> JavaType for Array<f64, D> {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "instantcoffee.NdArray" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Linstantcoffee/NdArray;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        nd_array_from_jni(jni_value, env)
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let shape = shape_into_jni(self.shape(), env)?;
+        let elements = self.as_standard_layout();
+        let elements = elements.as_slice().expect("standard layout arrays are contiguous");
+        let data = env.new_double_array(array_length(elements.len(), "double")?).map_err(map_jni_error)?;
+        env.set_double_array_region(&data, 0, elements).map_err(map_jni_error)?;
+
+        let class = &ND_ARRAY_CLASS.get(env)?;
+        env.new_object(<&JClass>::from(class.as_obj()), "([D[I)V", &[JValue::Object(&data), JValue::Object(&shape)])
+            .map_err(map_jni_error)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+impl<D: Dimension> JavaType for DirectNdArray<D> {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "instantcoffee.NdArray" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Linstantcoffee/NdArray;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        nd_array_from_jni(jni_value, env).map(DirectNdArray)
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let shape = shape_into_jni(self.0.shape(), env)?;
+        let mut elements = into_standard_vec(self.0);
+        let length = elements.len() * size_of::<f64>();
+        let address = elements.as_mut_ptr() as *mut u8;
+
+        let class = &ND_ARRAY_CLASS.get(env)?;
+        // SAFETY: The elements are owned by the Vec, and hold `length` bytes
+        unsafe {
+            direct_buffer_into_jni(elements, address, length, env, |env, buffer, handle| {
+                env.call_static_method(<&JClass>::from(class.as_obj()), "fromNative", "(Ljava/nio/ByteBuffer;[IJ)Linstantcoffee/NdArray;", &[JValue::Object(buffer), JValue::Object(&shape), JValue::Long(handle)])
+            })
+        }
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Elements of an array in row-major order, reusing its allocation if it is in standard layout
+fn into_standard_vec<D: Dimension>(array: Array<f64, D>) -> Vec<f64> {
+    if !array.is_standard_layout() {
+        return array.iter().copied().collect();
+    }
+    let length = array.len();
+    let (mut elements, offset) = array.into_raw_vec_and_offset();
+    let offset = offset.unwrap_or(0).min(elements.len());
+    elements.truncate(offset + length);
+    elements.drain(..offset);
+    elements
+}
+
+/// Convert a shape to a Java `int[]`
+fn shape_into_jni<'local>(shape: &[usize], env: &mut JNIEnv<'local>) -> Result<JIntArray<'local>, Option<Exception>> {
+    let shape = shape.iter()
+        .map(|length| jint::try_from(*length))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| Some(Exception { class: "java/lang/IllegalArgumentException".to_string(), msg: format!("cannot convert array of shape {:?} to NdArray; Lengths must fit an int", shape) }))?;
+    let array = env.new_int_array(array_length(shape.len(), "int")?).map_err(map_jni_error)?;
+    env.set_int_array_region(&array, 0, &shape).map_err(map_jni_error)?;
+    Ok(array)
+}
+
+/// Convert a Java `instantcoffee.NdArray` to an array of dimension `D`, copying its elements
+fn nd_array_from_jni<'local, D: Dimension>(object: JObject<'local>, env: &mut JNIEnv<'local>) -> Result<Array<f64, D>, Option<Exception>> {
+    non_null(&object, "instantcoffee.NdArray")?;
+
+    let shape = JIntArray::from(env.get_field(&object, "shape", "[I").map_err(map_jni_error)?.l().map_err(map_jni_error)?);
+    let mut shape_buffer = vec![0; env.get_array_length(&shape).map_err(map_jni_error)? as usize];
+    env.get_int_array_region(&shape, 0, &mut shape_buffer).map_err(map_jni_error)?;
+    // Lengths are checked to be non-negative by the Java constructor
+    let shape = shape_buffer.into_iter().map(|length| length as usize).collect::<Vec<_>>();
+
+    let data = JDoubleArray::from(env.get_field(&object, "data", "[D").map_err(map_jni_error)?.l().map_err(map_jni_error)?);
+    let elements = if !data.is_null() {
+        let length = env.get_array_length(&data).map_err(map_jni_error)? as usize;
+        check_array_length(length, "double[]")?;
+        let mut elements = vec![0.0; length];
+        env.get_double_array_region(&data, 0, &mut elements).map_err(map_jni_error)?;
+        elements
+    } else {
+        let buffer = JByteBuffer::from(env.get_field(&object, "buffer", "Ljava/nio/DoubleBuffer;").map_err(map_jni_error)?.l().map_err(map_jni_error)?);
+        // Capacity of a DoubleBuffer is in elements
+        let length = env.get_direct_buffer_capacity(&buffer).map_err(map_jni_error)?;
+        check_array_length(length, "java.nio.DoubleBuffer")?;
+        let mut elements = vec![0.0f64; length];
+        if length > 0 {
+            let address = env.get_direct_buffer_address(&buffer).map_err(map_jni_error)?;
+            // SAFETY: The buffer holds `length` doubles, in native byte order; Copied bytewise as direct buffers need not be aligned
+            unsafe { std::ptr::copy_nonoverlapping(address, elements.as_mut_ptr() as *mut u8, length * size_of::<f64>()) };
+        }
+        elements
+    };
+
+    let dimensions = shape.len();
+    Array::from_shape_vec(IxDyn(&shape), elements)
+        .map_err(|error| Some(Exception { class: "java/lang/IllegalArgumentException".to_string(), msg: format!("invalid NdArray of shape {:?}: {}", shape, error) }))?
+        .into_dimensionality::<D>()
+        .map_err(|_| Some(Exception { class: "java/lang/IllegalArgumentException".to_string(), msg: format!("expected NdArray of {} dimensions, got {}", D::NDIM.unwrap_or(dimensions), dimensions) }))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_instantcoffee_NdArray_release<'local>(_env: JNIEnv<'local>, _class: JClass<'local>, handle: jlong) {
+    release_direct_buffer(handle);
+}
@@ -0,0 +1,176 @@
+//! [`rayon`] interop; Converts very large object arrays on several attached threads at once, see [`ParArray`]
+
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::objects::{GlobalRef, JObject, JObjectArray, JThrowable, JValueOwned};
+use jni::sys::jsize;
+use rayon::prelude::*;
+use crate::{jni_util, JavaType, JniArray};
+use crate::jni_util::{map_jni_error, LazyName, CLASS_CAST_EXCEPTION};
+
+/// Arrays shorter than this are converted on the calling thread, as attaching worker threads costs more than it saves
+pub const PARALLEL_THRESHOLD: usize = 4096;
+
+/// Fewest elements converted by one worker in a single attachment
+const MIN_CHUNK_LENGTH: usize = 1024;
+
+/// Object array `T[]` = rust `Box<[T]>`, converted in parallel; For data-export APIs moving hundreds of thousands of records
+///
+/// Arrays of at least [`PARALLEL_THRESHOLD`] elements are split into chunks, each converted on a [`rayon`] worker thread attached to the JVM for the chunk.
+/// The array itself is created on the calling thread and shared with the workers through a global reference; Smaller arrays are converted as `Box<[T]>`.
+/// Element conversions must not depend on the calling thread, such as through thread-local state
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct ParArray<T>(pub Box<[T]>);
+
+impl<T> From<Box<[T]>> for ParArray<T> {
+    fn from(value: Box<[T]>) -> Self {
+        ParArray(value)
+    }
+}
+
+impl<T> From<Vec<T>> for ParArray<T> {
+    fn from(value: Vec<T>) -> Self {
+        ParArray(value.into_boxed_slice())
+    }
+}
+
+impl<T: JavaType + Send> JavaType for ParArray<T> where for<'local> T::JniType<'local>: From<JObject<'local>> + AsRef<JObject<'local>> {
+    type JniType<'local> = JObjectArray<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str {
+        jni_util::generic_type_name("", T::QUALIFIED_NAME(), "[]")
+    }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str {
+        jni_util::generic_type_name("[", T::JVM_PARAM_SIGNATURE(), "")
+    }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> {
+        JObjectArray::default()
+    }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        jni_util::non_null(&jni_value, LazyName(<Self as JavaType>::QUALIFIED_NAME))?;
+        let length = env.get_array_length(&jni_value).map_err(map_jni_error)? as usize;
+        jni_util::check_array_length(length, LazyName(<Self as JavaType>::QUALIFIED_NAME))?;
+        if length < PARALLEL_THRESHOLD {
+            return <JObjectArray as JniArray<T>>::from_jni(jni_value, env).map(ParArray);
+        }
+
+        let array = env.new_global_ref(&jni_value).map_err(map_jni_error)?;
+        let vm = env.get_java_vm().map_err(map_jni_error)?;
+        let chunks = chunk_ranges(length)
+            .into_par_iter()
+            .map(|(start, end)| jni_util::with_java_scope(&vm, |env| {
+                let array = <&JObjectArray>::from(array.as_obj());
+                let mut chunk = Vec::with_capacity(end - start);
+                for idx in start..end {
+                    let element = match env.get_object_array_element(array, idx as jsize).map_err(map_jni_error) {
+                        Ok(element) => element,
+                        Err(error) => return take_exception(error, env).map(Err),
+                    };
+                    let raw_element = element.as_raw();
+                    let converted = T::from_jni(element.into(), env);
+                    // The conversion took the reference by value without releasing it; Converted rust values cannot hold local references
+                    unsafe { env.delete_local_ref(JObject::from_raw(raw_element)) }.map_err(map_jni_error)?;
+                    match converted {
+                        Ok(value) => chunk.push(value),
+                        Err(error) => return take_exception(error, env).map(Err),
+                    }
+                }
+                Ok(Ok(chunk))
+            }))
+            .collect::<Vec<_>>();
+
+        let mut buffer = Vec::with_capacity(length);
+        for chunk in chunks {
+            buffer.extend(rethrow(chunk?, env)?);
+        }
+        Ok(ParArray(buffer.into_boxed_slice()))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        if self.0.len() < PARALLEL_THRESHOLD {
+            return <JObjectArray as JniArray<T>>::into_jni(self.0, env);
+        }
+
+        let length = jni_util::array_length(self.0.len(), LazyName(T::QUALIFIED_NAME))?;
+        let element_class = &jni_util::signature_class(T::JVM_PARAM_SIGNATURE(), env)?;
+        let local_array = env.new_object_array(length, element_class, JObject::null()).map_err(map_jni_error)?;
+
+        let array = env.new_global_ref(&local_array).map_err(map_jni_error)?;
+        let vm = env.get_java_vm().map_err(map_jni_error)?;
+        let mut elements = self.0.into_vec();
+        let mut chunks = Vec::new();
+        for (start, _) in chunk_ranges(elements.len()).into_iter().rev() {
+            chunks.push((start, elements.split_off(start)));
+        }
+
+        let results = chunks
+            .into_par_iter()
+            .map(|(start, chunk)| jni_util::with_java_scope(&vm, |env| {
+                let array = <&JObjectArray>::from(array.as_obj());
+                for (offset, element) in chunk.into_iter().enumerate() {
+                    let jelement = match element.into_jni(env) {
+                        Ok(jelement) => jelement,
+                        Err(error) => return take_exception(error, env).map(Err),
+                    };
+                    if let Err(error) = env.set_object_array_element(array, (start + offset) as jsize, jelement.as_ref()).map_err(map_jni_error) {
+                        return take_exception(error, env).map(Err);
+                    }
+                    unsafe { env.delete_local_ref(JObject::from_raw(jelement.as_ref().as_raw())) }.map_err(map_jni_error)?;
+                }
+                Ok(Ok(()))
+            }))
+            .collect::<Vec<_>>();
+
+        for result in results {
+            rethrow(result?, env)?;
+        }
+        Ok(local_array)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(JObjectArray::from(obj)),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Split `length` elements into `(start, end)` ranges, about four per worker thread so that uneven conversions still balance out
+fn chunk_ranges(length: usize) -> Vec<(usize, usize)> {
+    let chunk_length = length.div_ceil(rayon::current_num_threads() * 4).max(MIN_CHUNK_LENGTH);
+    (0..length).step_by(chunk_length)
+        .map(|start| (start, (start + chunk_length).min(length)))
+        .collect()
+}
+
+/// Take a conversion error on a worker thread, so that it can be rethrown on the calling thread
+///
+/// A Java exception pending on the worker is cleared and returned, as it would otherwise be lost when the worker detaches
+fn take_exception(error: Option<Exception>, env: &mut JNIEnv) -> Result<GlobalRef, Option<Exception>> {
+    match error {
+        Some(exception) => Err(Some(exception)),
+        None => {
+            let throwable = env.exception_occurred().map_err(map_jni_error)?;
+            if throwable.is_null() {
+                return Err(None);
+            }
+            env.exception_clear().map_err(map_jni_error)?;
+            env.new_global_ref(throwable).map_err(map_jni_error)
+        }
+    }
+}
+
+/// Rethrow an exception taken by [`take_exception`] on the calling thread
+fn rethrow<T>(result: Result<T, GlobalRef>, env: &mut JNIEnv) -> Result<T, Option<Exception>> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(throwable) => {
+            env.throw(<&JThrowable>::from(throwable.as_obj())).map_err(map_jni_error)?;
+            Err(None)
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! [`image`] interop; Maps RGBA image buffers onto the Java class `instantcoffee.PixelBuffer`, holding the pixels as bytes without per-pixel conversion
+//!
+//! Images are copied into a `byte[]` by default. [`DirectPixelBuffer`] instead hands its memory to Java as a direct buffer, without copying
+
+use image::RgbaImage;
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::objects::{JByteArray, JByteBuffer, JClass, JObject, JObjectArray, JValue, JValueOwned};
+use jni::sys::{jbyte, jint, jlong};
+use crate::codegen::JClassDecl;
+use crate::JavaType;
+use crate::interop::{direct_buffer_into_jni, release_direct_buffer};
+use crate::jni_util::{array_length, map_jni_error, non_null, CachedClass};
+
+/// Java pixel buffer class, see [`pixel_buffer_declaration`]
+static PIXEL_BUFFER_CLASS: CachedClass = CachedClass::new("instantcoffee/PixelBuffer");
+
+/// Java source of the pixel buffer class; Natives are implemented below
+const PIXEL_BUFFER_SOURCE: &str = r#"
+/**
+ * Image of RGBA pixels with 8 bits per channel, in row-major order without padding; Converted to and from rust image buffers without per-pixel conversion
+ * <p>
+ * Pixels are held in a heap or direct buffer. Direct buffers returned from rust share its memory, which is released once the buffer is unreachable
+ */
+public final class PixelBuffer {
+	private static final java.lang.ref.Cleaner CLEANER = java.lang.ref.Cleaner.create();
+
+	private final int width;
+	private final int height;
+	private final java.nio.ByteBuffer pixels;
+
+	/**
+	 * @param pixels RGBA bytes of the pixels, 4 per pixel; Shared with the pixel buffer
+	 */
+	public PixelBuffer(int width, int height, byte[] pixels) {
+		this(width, height, java.nio.ByteBuffer.wrap(pixels));
+	}
+
+	/**
+	 * @param pixels Buffer with the RGBA bytes of the pixels remaining, 4 per pixel; Shared with the pixel buffer, unless it is a read-only heap buffer
+	 */
+	public PixelBuffer(int width, int height, java.nio.ByteBuffer pixels) {
+		long bytes = byteSize(width, height);
+		if (pixels.remaining() != bytes) {
+			throw new IllegalArgumentException("expected " + bytes + " bytes for " + width + "x" + height + " pixels, got " + pixels.remaining());
+		}
+		if (pixels.isDirect() || pixels.hasArray()) {
+			this.pixels = pixels.slice();
+		} else {
+			this.pixels = java.nio.ByteBuffer.allocate(pixels.remaining()).put(pixels.duplicate()).flip();
+		}
+		this.width = width;
+		this.height = height;
+	}
+
+	/**
+	 * Transparent black pixel buffer backed by a new direct buffer
+	 */
+	public static PixelBuffer allocateDirect(int width, int height) {
+		return new PixelBuffer(width, height, java.nio.ByteBuffer.allocateDirect(Math.toIntExact(byteSize(width, height))));
+	}
+
+	/**
+	 * Pixel buffer of packed ARGB pixels, as used by {@code BufferedImage.TYPE_INT_ARGB} and JavaFX {@code PixelFormat.getIntArgbInstance()}
+	 */
+	public static PixelBuffer fromArgb(int width, int height, int[] argb) {
+		if (argb.length != (long) width * height) {
+			throw new IllegalArgumentException("expected " + (long) width * height + " pixels for " + width + "x" + height + ", got " + argb.length);
+		}
+		java.nio.ByteBuffer pixels = java.nio.ByteBuffer.allocate(Math.toIntExact(byteSize(width, height)));
+		for (int pixel : argb) {
+			pixels.putInt((pixel << 8) | (pixel >>> 24));
+		}
+		return new PixelBuffer(width, height, pixels.flip());
+	}
+
+	private static PixelBuffer fromNative(java.nio.ByteBuffer pixels, int width, int height, long handle) {
+		// Slices of the buffer keep it reachable
+		CLEANER.register(pixels, new Release(handle));
+		return new PixelBuffer(width, height, pixels);
+	}
+
+	private static long byteSize(int width, int height) {
+		if (width < 0 || height < 0) {
+			throw new IllegalArgumentException("negative size " + width + "x" + height);
+		}
+		return 4L * width * height;
+	}
+
+	public int width() {
+		return width;
+	}
+
+	public int height() {
+		return height;
+	}
+
+	/**
+	 * @return True if the pixels are held in a direct buffer
+	 */
+	public boolean isDirect() {
+		return pixels.isDirect();
+	}
+
+	/**
+	 * @return RGBA bytes of the pixels, sharing the memory of this pixel buffer
+	 */
+	public java.nio.ByteBuffer pixels() {
+		return pixels.duplicate();
+	}
+
+	/**
+	 * @return Pixel at the coordinates as packed ARGB
+	 */
+	public int getArgb(int x, int y) {
+		int rgba = pixels.getInt(4 * (java.util.Objects.checkIndex(y, height) * width + java.util.Objects.checkIndex(x, width)));
+		return (rgba >>> 8) | (rgba << 24);
+	}
+
+	/**
+	 * @return Pixels as packed ARGB, as used by {@code BufferedImage.TYPE_INT_ARGB} and JavaFX {@code PixelFormat.getIntArgbInstance()}
+	 */
+	public int[] toArgb() {
+		int[] argb = new int[width * height];
+		java.nio.ByteBuffer pixels = pixels();
+		for (int i = 0; i < argb.length; i++) {
+			int rgba = pixels.getInt();
+			argb[i] = (rgba >>> 8) | (rgba << 24);
+		}
+		return argb;
+	}
+
+	@Override
+	public String toString() {
+		return "PixelBuffer[" + width + "x" + height + "]";
+	}
+
+	private static final class Release implements Runnable {
+		private final long handle;
+
+		private Release(long handle) {
+			this.handle = handle;
+		}
+
+		@Override
+		public void run() {
+			release(handle);
+		}
+	}
+
+	private static native void release(long handle);
+}
+"#;
+
+/// Declaration of the Java pixel buffer class `instantcoffee.PixelBuffer`; Added to modules using RGBA images by the `jmodule` macro
+pub fn pixel_buffer_declaration() -> JClassDecl {
+    JClassDecl::Source {
+        name: "PixelBuffer",
+        package: "instantcoffee",
+        source: PIXEL_BUFFER_SOURCE,
+    }
+}
+
+/// Image mapped to a Java `instantcoffee.PixelBuffer` backed by a direct buffer, handing its memory to Java without copying
+///
+/// Converting from Java copies the pixels, directly from the buffer of direct Java pixel buffers
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirectPixelBuffer(pub RgbaImage);
+
+impl From<RgbaImage> for DirectPixelBuffer {
+    fn from(image: RgbaImage) -> Self {
+        DirectPixelBuffer(image)
+    }
+}
+
+impl JavaType for RgbaImage {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "instantcoffee.PixelBuffer" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Linstantcoffee/PixelBuffer;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        pixel_buffer_from_jni(jni_value, env)
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let (width, height) = size_into_jni(&self)?;
+        // Image buffers may be backed by a larger buffer than their pixels
+        let pixels = &self.as_raw()[..self.as_raw().len().min(4 * self.width() as usize * self.height() as usize)];
+        let array = env.new_byte_array(array_length(pixels.len(), "byte")?).map_err(map_jni_error)?;
+        // SAFETY: jbyte is i8, of the same layout as u8
+        let slice: &[jbyte] = unsafe { std::slice::from_raw_parts(pixels.as_ptr() as *const jbyte, pixels.len()) };
+        env.set_byte_array_region(&array, 0, slice).map_err(map_jni_error)?;
+
+        let class = &PIXEL_BUFFER_CLASS.get(env)?;
+        env.new_object(<&JClass>::from(class.as_obj()), "(II[B)V", &[JValue::Int(width), JValue::Int(height), JValue::Object(&array)])
+            .map_err(map_jni_error)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+impl JavaType for DirectPixelBuffer {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "instantcoffee.PixelBuffer" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Linstantcoffee/PixelBuffer;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        pixel_buffer_from_jni(jni_value, env).map(DirectPixelBuffer)
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let (width, height) = size_into_jni(&self.0)?;
+        let length = 4 * self.0.width() as usize * self.0.height() as usize;
+        let mut pixels = self.0.into_raw();
+        pixels.truncate(length);
+        let address = pixels.as_mut_ptr();
+
+        let class = &PIXEL_BUFFER_CLASS.get(env)?;
+        // SAFETY: The pixels are owned by the Vec, and hold `length` bytes
+        unsafe {
+            direct_buffer_into_jni(pixels, address, length, env, |env, buffer, handle| {
+                env.call_static_method(<&JClass>::from(class.as_obj()), "fromNative", "(Ljava/nio/ByteBuffer;IIJ)Linstantcoffee/PixelBuffer;", &[JValue::Object(buffer), JValue::Int(width), JValue::Int(height), JValue::Long(handle)])
+            })
+        }
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Width and height of an image as Java `int`s
+fn size_into_jni(image: &RgbaImage) -> Result<(jint, jint), Option<Exception>> {
+    match (jint::try_from(image.width()), jint::try_from(image.height())) {
+        (Ok(width), Ok(height)) => Ok((width, height)),
+        _ => Err(Some(Exception { class: "java/lang/IllegalArgumentException".to_string(), msg: format!("cannot convert {}x{} image to PixelBuffer; Width and height must fit an int", image.width(), image.height()) }))
+    }
+}
+
+/// Convert a Java `instantcoffee.PixelBuffer` to an image, copying its pixels
+fn pixel_buffer_from_jni<'local>(object: JObject<'local>, env: &mut JNIEnv<'local>) -> Result<RgbaImage, Option<Exception>> {
+    non_null(&object, "instantcoffee.PixelBuffer")?;
+
+    // Sizes are checked to be non-negative, and to match the buffer, by the Java constructor
+    let width = env.get_field(&object, "width", "I").map_err(map_jni_error)?.i().map_err(map_jni_error)? as u32;
+    let height = env.get_field(&object, "height", "I").map_err(map_jni_error)?.i().map_err(map_jni_error)? as u32;
+    let buffer = JByteBuffer::from(env.get_field(&object, "pixels", "Ljava/nio/ByteBuffer;").map_err(map_jni_error)?.l().map_err(map_jni_error)?);
+    let length = 4 * width as usize * height as usize;
+
+    let mut pixels = vec![0u8; length];
+    if length > 0 {
+        let is_direct = env.call_method(&buffer, "isDirect", "()Z", &[]).map_err(map_jni_error)?.z().map_err(map_jni_error)?;
+        if is_direct {
+            let address = env.get_direct_buffer_address(&buffer).map_err(map_jni_error)?;
+            // SAFETY: The buffer holds `length` bytes
+            unsafe { std::ptr::copy_nonoverlapping(address, pixels.as_mut_ptr(), length) };
+        } else {
+            // Heap buffers are backed by an accessible array
+            let array = JByteArray::from(env.call_method(&buffer, "array", "()[B", &[]).map_err(map_jni_error)?.l().map_err(map_jni_error)?);
+            let offset = env.call_method(&buffer, "arrayOffset", "()I", &[]).map_err(map_jni_error)?.i().map_err(map_jni_error)?;
+            // SAFETY: jbyte is i8, of the same layout as u8
+            let slice: &mut [jbyte] = unsafe { std::slice::from_raw_parts_mut(pixels.as_mut_ptr() as *mut jbyte, length) };
+            env.get_byte_array_region(&array, offset, slice).map_err(map_jni_error)?;
+        }
+    }
+
+    Ok(RgbaImage::from_raw(width, height, pixels).expect("buffer holds 4 bytes per pixel"))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_instantcoffee_PixelBuffer_release<'local>(_env: JNIEnv<'local>, _class: JClass<'local>, handle: jlong) {
+    release_direct_buffer(handle);
+}
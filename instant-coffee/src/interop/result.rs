@@ -0,0 +1,319 @@
+//! Railway-style error handling; Maps rust results onto the Java class `instantcoffee.Result`, as an alternative to throwing exceptions
+
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::objects::{JClass, JObject, JObjectArray, JValue, JValueOwned};
+use crate::codegen::JClassDecl;
+use crate::JavaType;
+use crate::jni_util::{generic_type_name, map_jni_error, non_null, obj_classname, signature_class, signature_class_name, CachedClass, LazyName};
+
+/// Java result class, see [`java_result_declaration`]
+static RESULT_CLASS: CachedClass = CachedClass::new("instantcoffee/Result");
+
+/// Java source of the result class
+const RESULT_SOURCE: &str = r#"
+/**
+ * Result of an operation which may fail; Either {@link Ok} holding a value, or {@link Err} holding an error
+ * <p>
+ * Returned by native methods in place of throwing an exception. There are no other subclasses than {@link Ok} and {@link Err}
+ *
+ * @param <T> Type of the value
+ * @param <E> Type of the error
+ */
+public abstract class Result<T, E> {
+	private Result() {}
+
+	/**
+	 * Successful result holding {@code value}
+	 */
+	public static <T, E> Result<T, E> ok(T value) {
+		return new Ok<>(value);
+	}
+
+	/**
+	 * Failed result holding {@code error}
+	 */
+	public static <T, E> Result<T, E> err(E error) {
+		return new Err<>(error);
+	}
+
+	/**
+	 * @return true if this is an {@link Ok} result
+	 */
+	public abstract boolean isOk();
+
+	/**
+	 * @return true if this is an {@link Err} result
+	 */
+	public final boolean isErr() {
+		return !isOk();
+	}
+
+	/**
+	 * @return The value of this result
+	 * @throws IllegalStateException If this is an {@link Err} result
+	 */
+	public abstract T unwrap();
+
+	/**
+	 * @return The error of this result
+	 * @throws IllegalStateException If this is an {@link Ok} result
+	 */
+	public abstract E unwrapErr();
+
+	/**
+	 * @return The value of this result, or {@code other} if this is an {@link Err} result
+	 */
+	public abstract T orElse(T other);
+
+	/**
+	 * Result with the value mapped by {@code mapper}; Errors are passed along unchanged
+	 */
+	public abstract <U> Result<U, E> map(java.util.function.Function<? super T, ? extends U> mapper);
+
+	/**
+	 * Result with the error mapped by {@code mapper}; Values are passed along unchanged
+	 */
+	public abstract <F> Result<T, F> mapErr(java.util.function.Function<? super E, ? extends F> mapper);
+
+	/**
+	 * Result of {@code next} applied to the value; Errors are passed along without calling {@code next}
+	 */
+	public abstract <U> Result<U, E> andThen(java.util.function.Function<? super T, Result<U, E>> next);
+
+	/**
+	 * Successful result
+	 */
+	public static final class Ok<T, E> extends Result<T, E> {
+		private final T value;
+
+		private Ok(T value) {
+			this.value = value;
+		}
+
+		public T value() {
+			return value;
+		}
+
+		@Override
+		public boolean isOk() {
+			return true;
+		}
+
+		@Override
+		public T unwrap() {
+			return value;
+		}
+
+		@Override
+		public E unwrapErr() {
+			throw new IllegalStateException("called unwrapErr on Ok: " + value);
+		}
+
+		@Override
+		public T orElse(T other) {
+			return value;
+		}
+
+		@Override
+		public <U> Result<U, E> map(java.util.function.Function<? super T, ? extends U> mapper) {
+			return new Ok<>(mapper.apply(value));
+		}
+
+		@Override
+		public <F> Result<T, F> mapErr(java.util.function.Function<? super E, ? extends F> mapper) {
+			return new Ok<>(value);
+		}
+
+		@Override
+		public <U> Result<U, E> andThen(java.util.function.Function<? super T, Result<U, E>> next) {
+			return next.apply(value);
+		}
+
+		@Override
+		public boolean equals(Object other) {
+			return other instanceof Ok && java.util.Objects.equals(value, ((Ok<?, ?>) other).value);
+		}
+
+		@Override
+		public int hashCode() {
+			return 31 * java.util.Objects.hashCode(value);
+		}
+
+		@Override
+		public String toString() {
+			return "Ok(" + value + ")";
+		}
+	}
+
+	/**
+	 * Failed result
+	 */
+	public static final class Err<T, E> extends Result<T, E> {
+		private final E error;
+
+		private Err(E error) {
+			this.error = error;
+		}
+
+		public E error() {
+			return error;
+		}
+
+		@Override
+		public boolean isOk() {
+			return false;
+		}
+
+		@Override
+		public T unwrap() {
+			throw new IllegalStateException("called unwrap on Err: " + error);
+		}
+
+		@Override
+		public E unwrapErr() {
+			return error;
+		}
+
+		@Override
+		public T orElse(T other) {
+			return other;
+		}
+
+		@Override
+		public <U> Result<U, E> map(java.util.function.Function<? super T, ? extends U> mapper) {
+			return new Err<>(error);
+		}
+
+		@Override
+		public <F> Result<T, F> mapErr(java.util.function.Function<? super E, ? extends F> mapper) {
+			return new Err<>(mapper.apply(error));
+		}
+
+		@Override
+		public <U> Result<U, E> andThen(java.util.function.Function<? super T, Result<U, E>> next) {
+			return new Err<>(error);
+		}
+
+		@Override
+		public boolean equals(Object other) {
+			return other instanceof Err && java.util.Objects.equals(error, ((Err<?, ?>) other).error);
+		}
+
+		@Override
+		public int hashCode() {
+			return 31 * java.util.Objects.hashCode(error) + 1;
+		}
+
+		@Override
+		public String toString() {
+			return "Err(" + error + ")";
+		}
+	}
+}
+"#;
+
+/// Declaration of the Java result class `instantcoffee.Result`; Added to modules using [`JavaResult`] by the `jmodule` macro
+pub fn java_result_declaration() -> JClassDecl {
+    JClassDecl::Source {
+        name: "Result",
+        package: "instantcoffee",
+        source: RESULT_SOURCE,
+    }
+}
+
+/// Result returned to Java as an `instantcoffee.Result`, rather than throwing the error as an exception
+///
+/// Both the value and the error must be Java objects; Primitive types cannot be used. Unit results can use [`JavaResult<Option<String>, E>`](Option) or similar
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct JavaResult<T, E>(pub Result<T, E>);
+
+impl<T, E> From<Result<T, E>> for JavaResult<T, E> {
+    fn from(value: Result<T, E>) -> Self {
+        JavaResult(value)
+    }
+}
+
+impl<T, E> From<JavaResult<T, E>> for Result<T, E> {
+    fn from(value: JavaResult<T, E>) -> Self {
+        value.0
+    }
+}
+
+impl<T: JavaType, E: JavaType> JavaType for JavaResult<T, E>
+where
+    for<'local> T::JniType<'local>: From<JObject<'local>> + AsRef<JObject<'local>>,
+    for<'local> E::JniType<'local>: From<JObject<'local>> + AsRef<JObject<'local>>,
+{
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str {
+        generic_type_name(generic_type_name("instantcoffee.Result<", T::QUALIFIED_NAME(), ", "), E::QUALIFIED_NAME(), ">")
+    }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Linstantcoffee/Result;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        non_null(&jni_value, LazyName(<Self as JavaType>::QUALIFIED_NAME))?;
+
+        let is_ok = env.call_method(&jni_value, "isOk", "()Z", &[])
+            .map_err(map_jni_error)?
+            .z().map_err(map_jni_error)?;
+        let (method, signature) = if is_ok { ("unwrap", T::JVM_PARAM_SIGNATURE()) } else { ("unwrapErr", E::JVM_PARAM_SIGNATURE()) };
+        let inner = env.call_method(&jni_value, method, "()Ljava/lang/Object;", &[])
+            .map_err(map_jni_error)?
+            .l().map_err(map_jni_error)?;
+
+        // Type parameters are erased in Java, the inner object is checked before converting it
+        check_instance(&inner, signature, env)?;
+        if is_ok {
+            T::from_jni(T::JniType::from(inner), env).map(|value| JavaResult(Ok(value)))
+        } else {
+            E::from_jni(E::JniType::from(inner), env).map(|error| JavaResult(Err(error)))
+        }
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let class = &RESULT_CLASS.get(env)?;
+        let class = <&JClass>::from(class.as_obj());
+        let result = match self.0 {
+            Ok(value) => {
+                let value = value.into_jni(env)?;
+                env.call_static_method(class, "ok", "(Ljava/lang/Object;)Linstantcoffee/Result;", &[JValue::Object(value.as_ref())])
+            }
+            Err(error) => {
+                let error = error.into_jni(env)?;
+                env.call_static_method(class, "err", "(Ljava/lang/Object;)Linstantcoffee/Result;", &[JValue::Object(error.as_ref())])
+            }
+        };
+        result.map_err(map_jni_error)?
+            .l().map_err(map_jni_error)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Throw a ClassCastException if non-null `object` is not an instance of the class with JVM signature `signature`
+pub(crate) fn check_instance<'local>(object: &JObject<'local>, signature: &'static str, env: &mut JNIEnv<'local>) -> Result<(), Option<Exception>> {
+    if object.is_null() {
+        return Ok(());
+    }
+
+    let class = &signature_class(signature, env)?;
+    let is_instance = env.is_instance_of(object, class).map_err(map_jni_error)?;
+
+    if is_instance {
+        Ok(())
+    } else {
+        let object_class = obj_classname(object, env)?;
+        Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", object_class, signature_class_name(signature).replace('/', ".")) }))
+    }
+}
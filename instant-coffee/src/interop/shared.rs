@@ -0,0 +1,281 @@
+//! Shared memory interop; Exchanges large buffers through memory-mapped files or anonymous shared memory, see [`SharedMemory`]
+//!
+//! Both sides access the same pages, so that nothing is copied or converted. Memory-mapped files may additionally be mapped by other processes
+
+use std::fs::OpenOptions;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::objects::{JClass, JObject, JObjectArray, JString, JValue, JValueOwned};
+use jni::sys::{jlong, jsize};
+use memmap2::{MmapOptions, MmapRaw};
+use crate::codegen::JClassDecl;
+use crate::interop::{Handle, HandleMap, lock};
+use crate::JavaType;
+use crate::jni_util::{map_jni_error, non_null, CachedClass, CLASS_CAST_EXCEPTION, ILLEGAL_ARGUMENT_EXCEPTION};
+
+/// Java support class of shared memory, see [`shared_memory_declaration`]
+static SHARED_MEMORY_CLASS: CachedClass = CachedClass::new("instantcoffee/SharedMemory");
+
+/// Java source of the support class; Natives are implemented below
+const SHARED_MEMORY_SOURCE: &str = r#"
+/**
+ * Region of shared memory; Either a memory-mapped file, which other processes may map as well, or anonymous memory shared with the native library
+ * <p>
+ * Buffers of the region are big-endian, as all byte buffers are when created. Anonymous memory is released once all of its buffers are unreachable
+ */
+public final class SharedMemory {
+	private static final java.lang.ref.Cleaner CLEANER = java.lang.ref.Cleaner.create();
+
+	private final java.nio.ByteBuffer buffer;
+	private final String path;
+	// Handle of anonymous memory, 0 for memory-mapped files
+	private final long handle;
+
+	private SharedMemory(java.nio.ByteBuffer buffer, String path, long handle) {
+		this.buffer = buffer;
+		this.path = path;
+		this.handle = handle;
+	}
+
+	/**
+	 * Map a file for sharing with the native library or other processes; The file is created, or extended to {@code size} bytes if it is shorter
+	 *
+	 * @param path Path of the file
+	 * @param size Size of the region in bytes, at most {@link Integer#MAX_VALUE}
+	 */
+	public static SharedMemory mapFile(String path, long size) throws java.io.IOException {
+		java.util.Objects.requireNonNull(path);
+		try (java.nio.channels.FileChannel channel = java.nio.channels.FileChannel.open(java.nio.file.Path.of(path), java.nio.file.StandardOpenOption.CREATE, java.nio.file.StandardOpenOption.READ, java.nio.file.StandardOpenOption.WRITE)) {
+			return new SharedMemory(channel.map(java.nio.channels.FileChannel.MapMode.READ_WRITE, 0, size), path, 0);
+		}
+	}
+
+	private static SharedMemory fromNative(java.nio.ByteBuffer buffer, long handle) {
+		// Views of the buffer keep it reachable
+		CLEANER.register(buffer, new Release(handle));
+		return new SharedMemory(buffer, null, handle);
+	}
+
+	/**
+	 * @return New buffer over the whole region, with independent position and limit; A {@link java.nio.MappedByteBuffer} for memory-mapped files
+	 */
+	public java.nio.ByteBuffer buffer() {
+		return buffer.duplicate();
+	}
+
+	/**
+	 * @return Path of the memory-mapped file, or null for anonymous memory
+	 */
+	public String path() {
+		return path;
+	}
+
+	/**
+	 * @return Size of the region in bytes
+	 */
+	public int size() {
+		return buffer.capacity();
+	}
+
+	/**
+	 * Write changes of a memory-mapped file back to storage; Has no effect on anonymous memory
+	 */
+	public void force() {
+		if (buffer instanceof java.nio.MappedByteBuffer) {
+			((java.nio.MappedByteBuffer) buffer).force();
+		}
+	}
+
+	private static final class Release implements Runnable {
+		private final long handle;
+
+		private Release(long handle) {
+			this.handle = handle;
+		}
+
+		@Override
+		public void run() {
+			release(handle);
+		}
+	}
+
+	private static native void release(long handle);
+}
+"#;
+
+/// Declaration of the Java support class `instantcoffee.SharedMemory`; Added to modules using [`SharedMemory`] by the `jmodule` macro
+pub fn shared_memory_declaration() -> JClassDecl {
+    JClassDecl::Source {
+        name: "SharedMemory",
+        package: "instantcoffee",
+        source: SHARED_MEMORY_SOURCE,
+    }
+}
+
+/// Region of shared memory = Java `instantcoffee.SharedMemory`; For exchanging data volumes at which even populating a direct buffer is too slow
+///
+/// Regions are either memory-mapped files, which Java maps itself as a `MappedByteBuffer`, or anonymous memory handed to Java as a direct buffer.
+/// Passing a region in either direction shares the same pages; Anonymous memory stays mapped while either side still refers to it.
+///
+/// The memory may be written at any time by Java, or by other processes mapping the same file. It is therefore only exposed through raw pointers and unsafe slices,
+/// and access must be synchronized by the application, such as by handing the region back and forth
+#[derive(Clone, Debug)]
+pub struct SharedMemory {
+    map: Arc<MmapRaw>,
+    /// Path of memory-mapped files, None for anonymous memory
+    path: Option<PathBuf>,
+}
+
+impl SharedMemory {
+    /// Allocate `len` bytes of zeroed anonymous memory
+    pub fn anonymous(len: usize) -> io::Result<SharedMemory> {
+        let map = MmapOptions::new().len(len).map_anon()?;
+        Ok(SharedMemory { map: Arc::new(MmapRaw::from(map)), path: None })
+    }
+
+    /// Map `len` bytes of the file at `path`; The file is created, or extended to `len` bytes if it is shorter
+    pub fn create(path: impl AsRef<Path>, len: usize) -> io::Result<SharedMemory> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        if file.metadata()?.len() < len as u64 {
+            file.set_len(len as u64)?;
+        }
+        let map = MmapOptions::new().len(len).map_raw(&file)?;
+        Ok(SharedMemory { map: Arc::new(map), path: Some(path.to_path_buf()) })
+    }
+
+    /// Map the whole existing file at `path`
+    pub fn open(path: impl AsRef<Path>) -> io::Result<SharedMemory> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let map = MmapRaw::map_raw(&file)?;
+        Ok(SharedMemory { map: Arc::new(map), path: Some(path.to_path_buf()) })
+    }
+
+    /// Size of the region in bytes
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// True if the region is empty
+    pub fn is_empty(&self) -> bool {
+        self.map.len() == 0
+    }
+
+    /// Path of the memory-mapped file, or None for anonymous memory
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Pointer to the start of the region, valid for reads and writes of [`len`](Self::len) bytes while this region is alive
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.map.as_mut_ptr()
+    }
+
+    /// The region as a byte slice
+    ///
+    /// # Safety
+    ///
+    /// The region must not be written by Java, or by another process, while the slice is alive
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        std::slice::from_raw_parts(self.map.as_ptr(), self.map.len())
+    }
+
+    /// The region as a mutable byte slice
+    ///
+    /// # Safety
+    ///
+    /// The region must not be accessed by Java, by another process, or through other [`SharedMemory`] values of the same region, while the slice is alive
+    #[allow(clippy::mut_from_ref)] // Regions are shared with Java, exclusive access cannot be expressed through the borrow of this value
+    pub unsafe fn as_mut_slice(&self) -> &mut [u8] {
+        std::slice::from_raw_parts_mut(self.map.as_mut_ptr(), self.map.len())
+    }
+
+    /// Write changes of a memory-mapped file back to storage; Has no effect on anonymous memory
+    pub fn flush(&self) -> io::Result<()> {
+        match self.path {
+            Some(_) => self.map.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Java `instantcoffee.SharedMemory` = rust [`SharedMemory`]
+impl JavaType for SharedMemory {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "instantcoffee.SharedMemory" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Linstantcoffee/SharedMemory;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        non_null(&jni_value, "instantcoffee.SharedMemory")?;
+        let handle = env.get_field(&jni_value, "handle", "J").map_err(map_jni_error)?.j().map_err(map_jni_error)?;
+        if handle != 0 {
+            // Anonymous memory stays mapped while the Java object is reachable, as it holds the buffer
+            return lock(&REGIONS)
+                .get(Handle::from_raw(handle))
+                .map(|map| SharedMemory { map: map.clone(), path: None })
+                .ok_or_else(|| Some(Exception { class: "java/lang/IllegalStateException".to_string(), msg: "instantcoffee.SharedMemory has been released".to_string() }));
+        }
+
+        let path = JString::from(env.get_field(&jni_value, "path", "Ljava/lang/String;").map_err(map_jni_error)?.l().map_err(map_jni_error)?);
+        let path: String = env.get_string(&path).map_err(map_jni_error)?.into();
+        let size = env.call_method(&jni_value, "size", "()I", &[]).map_err(map_jni_error)?.i().map_err(map_jni_error)?;
+        SharedMemory::create(&path, size as usize)
+            .map_err(|error| Some(Exception { class: "java/lang/IllegalStateException".to_string(), msg: format!("cannot map shared memory file {}: {}", path, error) }))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let size = jsize::try_from(self.len())
+            .map_err(|_| Some(Exception { class: ILLEGAL_ARGUMENT_EXCEPTION.to_string(), msg: format!("cannot share {} bytes of memory; Java buffers hold at most {} bytes", self.len(), jsize::MAX) }))?;
+        let class = &SHARED_MEMORY_CLASS.get(env)?;
+        let class = <&JClass>::from(class.as_obj());
+
+        match self.path {
+            Some(path) => {
+                // Java maps the file itself, as MappedByteBuffers cannot be created over native memory
+                let path = path.to_str()
+                    .ok_or_else(|| Some(Exception { class: ILLEGAL_ARGUMENT_EXCEPTION.to_string(), msg: format!("shared memory path {} is not valid unicode", path.display()) }))?;
+                let path = env.new_string(path).map_err(map_jni_error)?;
+                env.call_static_method(class, "mapFile", "(Ljava/lang/String;J)Linstantcoffee/SharedMemory;", &[JValue::Object(&path), JValue::Long(size as jlong)])
+                    .map_err(map_jni_error)?
+                    .l().map_err(map_jni_error)
+            }
+            None => {
+                // SAFETY: The region stays mapped until the buffer is unreachable, as the handle holds a reference to it
+                let buffer = unsafe { env.new_direct_byte_buffer(self.map.as_mut_ptr(), size as usize) }.map_err(map_jni_error)?;
+                let handle = lock(&REGIONS).insert(self.map);
+                env.call_static_method(class, "fromNative", "(Ljava/nio/ByteBuffer;J)Linstantcoffee/SharedMemory;", &[JValue::Object(&buffer), JValue::Long(handle.into_raw())])
+                    .map_err(|error| {
+                        lock(&REGIONS).remove(handle);
+                        map_jni_error(error)
+                    })?
+                    .l().map_err(map_jni_error)
+            }
+        }
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Anonymous memory referenced from Java
+static REGIONS: Mutex<HandleMap<Arc<MmapRaw>>> = Mutex::new(HandleMap::new());
+
+#[no_mangle]
+pub extern "system" fn Java_instantcoffee_SharedMemory_release<'local>(_env: JNIEnv<'local>, _class: JClass<'local>, handle: jlong) {
+    // Unmapped once rust no longer refers to the region either
+    let removed = lock(&REGIONS).remove(Handle::from_raw(handle));
+    drop(removed);
+}
@@ -0,0 +1,323 @@
+//! Reactive Streams interop; Maps rust [`Stream`]s onto `java.util.concurrent.Flow.Publisher`
+//!
+//! Each subscription is driven by its own (daemon) thread, which polls the stream only while the subscriber has outstanding demand
+
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use futures::{Stream, StreamExt};
+use futures::executor::block_on;
+use jni::errors::Exception;
+use jni::{JavaVM, JNIEnv};
+use jni::objects::{GlobalRef, JClass, JObject, JObjectArray, JThrowable, JValue, JValueOwned};
+use jni::sys::jlong;
+use crate::codegen::JClassDecl;
+use crate::interop::{Handle, HandleMap, lock};
+use crate::JavaType;
+use crate::jni_util::{generic_type_name, map_jni_error, CachedClass};
+
+/// Java publisher class, see [`stream_publisher_declaration`]
+static PUBLISHER_CLASS: CachedClass = CachedClass::new("instantcoffee/StreamPublisher");
+
+/// Java source of the publisher class; Natives are implemented below
+const PUBLISHER_SOURCE: &str = r#"
+/**
+ * Flow.Publisher for a rust Stream; Supports a single subscriber, items are only produced on request
+ */
+public final class StreamPublisher<T> implements java.util.concurrent.Flow.Publisher<T> {
+	private static final java.lang.ref.Cleaner CLEANER = java.lang.ref.Cleaner.create();
+
+	private final long handle;
+	private final java.util.concurrent.atomic.AtomicBoolean subscribed = new java.util.concurrent.atomic.AtomicBoolean();
+
+	private StreamPublisher(long handle) {
+		this.handle = handle;
+		CLEANER.register(this, new Release(handle));
+	}
+
+	@Override
+	public void subscribe(java.util.concurrent.Flow.Subscriber<? super T> subscriber) {
+		java.util.Objects.requireNonNull(subscriber);
+		if (subscribed.compareAndSet(false, true)) {
+			subscriber.onSubscribe(new Subscription());
+			start(handle, subscriber);
+		} else {
+			subscriber.onSubscribe(new java.util.concurrent.Flow.Subscription() {
+				@Override
+				public void request(long n) {}
+
+				@Override
+				public void cancel() {}
+			});
+			subscriber.onError(new IllegalStateException("StreamPublisher only supports a single subscriber"));
+		}
+	}
+
+	private final class Subscription implements java.util.concurrent.Flow.Subscription {
+		@Override
+		public void request(long n) {
+			StreamPublisher.request(handle, n);
+		}
+
+		@Override
+		public void cancel() {
+			StreamPublisher.cancel(handle);
+		}
+	}
+
+	private static final class Release implements Runnable {
+		private final long handle;
+
+		private Release(long handle) {
+			this.handle = handle;
+		}
+
+		@Override
+		public void run() {
+			release(handle);
+		}
+	}
+
+	private static native void start(long handle, java.util.concurrent.Flow.Subscriber<?> subscriber);
+
+	private static native void request(long handle, long n);
+
+	private static native void cancel(long handle);
+
+	private static native void release(long handle);
+}
+"#;
+
+/// Declaration of the Java publisher class `instantcoffee.StreamPublisher`; Added to modules with methods returning streams by the `jmodule` macro
+pub fn stream_publisher_declaration() -> JClassDecl {
+    JClassDecl::Source {
+        name: "StreamPublisher",
+        package: "instantcoffee",
+        source: PUBLISHER_SOURCE,
+    }
+}
+
+/// Stream item, erased to a Java object conversion
+trait ErasedItem: Send {
+    fn into_object<'local>(self: Box<Self>, env: &mut JNIEnv<'local>) -> Result<JObject<'local>, Option<Exception>>;
+}
+
+impl<T> ErasedItem for T where T: JavaType + Send, for<'local> T::JniType<'local>: Into<JObject<'local>> {
+    fn into_object<'local>(self: Box<Self>, env: &mut JNIEnv<'local>) -> Result<JObject<'local>, Option<Exception>> {
+        T::into_jni(*self, env).map(Into::into)
+    }
+}
+
+type ErasedStream = Pin<Box<dyn Stream<Item=Box<dyn ErasedItem>> + Send>>;
+
+/// Java `Flow.Publisher<T>` = rust `impl Stream<Item = T>`
+///
+/// Exported functions may return `impl Stream<Item = T>` directly, which the `jmodule` macro wraps in a StreamPublisher.
+/// Items must be Java objects, primitive items are not supported. The stream is polled on a dedicated thread; Java's `request(n)` drives polling, and `cancel()` stops it
+pub struct StreamPublisher<T> {
+    stream: ErasedStream,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> StreamPublisher<T> where T: JavaType + Send + 'static, for<'local> T::JniType<'local>: Into<JObject<'local>> {
+    /// Wrap a stream for publishing to Java
+    pub fn new<S: Stream<Item=T> + Send + 'static>(stream: S) -> StreamPublisher<T> {
+        StreamPublisher {
+            stream: Box::pin(stream.map(|item| Box::new(item) as Box<dyn ErasedItem>)),
+            _type: PhantomData,
+        }
+    }
+}
+
+/// Java Flow.Publisher = rust [`StreamPublisher<T>`]
+impl<T> JavaType for StreamPublisher<T> where T: JavaType + Send + 'static, for<'local> T::JniType<'local>: Into<JObject<'local>> {
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str {
+        generic_type_name("java.util.concurrent.Flow.Publisher<", T::QUALIFIED_NAME(), ">")
+    }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Ljava/util/concurrent/Flow$Publisher;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(_jni_value: Self::JniType<'local>, _env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        Err(Some(Exception { class: "java/lang/UnsupportedOperationException".to_string(), msg: format!("{} cannot be passed to rust", <Self as JavaType>::QUALIFIED_NAME()) }))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let state = PublisherState {
+            stream: Mutex::new(Some(self.stream)),
+            demand: Mutex::new(Demand { requested: 0, cancelled: false, invalid_request: false }),
+            signal: Condvar::new(),
+        };
+        let class = &PUBLISHER_CLASS.get(env)?;
+        let handle = lock(&PUBLISHERS).insert(Arc::new(state));
+
+        env.new_object(class, "(J)V", &[JValue::Long(handle.into_raw())])
+            .map_err(|error| {
+                lock(&PUBLISHERS).remove(handle);
+                map_jni_error(error)
+            })
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Outstanding demand of a subscription
+struct Demand {
+    /// Number of requested items; Saturates, values of `i64::MAX` or greater are unbounded as per the Reactive Streams specification
+    requested: u64,
+    cancelled: bool,
+    /// Set if the subscriber requested a non-positive number of items, which is signalled as an error
+    invalid_request: bool,
+}
+
+struct PublisherState {
+    /// Stream, taken by the subscription thread on subscribe
+    stream: Mutex<Option<ErasedStream>>,
+    demand: Mutex<Demand>,
+    /// Notified on demand changes
+    signal: Condvar,
+}
+
+impl PublisherState {
+    fn update_demand(&self, update: impl FnOnce(&mut Demand)) {
+        update(&mut lock(&self.demand));
+        self.signal.notify_all();
+    }
+}
+
+static PUBLISHERS: Mutex<HandleMap<Arc<PublisherState>>> = Mutex::new(HandleMap::new());
+
+fn publisher(handle: jlong) -> Option<Arc<PublisherState>> {
+    lock(&PUBLISHERS).get(Handle::from_raw(handle)).cloned()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_instantcoffee_StreamPublisher_start<'local>(mut env: JNIEnv<'local>, _class: JClass<'local>, handle: jlong, subscriber: JObject<'local>) {
+    let Some(state) = publisher(handle) else { return };
+    let Some(stream) = lock(&state.stream).take() else { return };
+
+    let (Ok(subscriber), Ok(vm)) = (env.new_global_ref(subscriber), env.get_java_vm()) else {
+        let _ = env.throw_new("java/lang/IllegalStateException", "could not start stream subscription");
+        return;
+    };
+    let spawned = std::thread::Builder::new()
+        .name("instant-coffee-stream".to_string())
+        .spawn(move || drive(vm, state, stream, subscriber));
+    if let Err(error) = spawned {
+        let _ = env.throw_new("java/lang/IllegalStateException", format!("could not start stream subscription: {}", error));
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_instantcoffee_StreamPublisher_request<'local>(_env: JNIEnv<'local>, _class: JClass<'local>, handle: jlong, n: jlong) {
+    if let Some(state) = publisher(handle) {
+        state.update_demand(|demand| {
+            if n <= 0 {
+                demand.invalid_request = true;
+            } else {
+                demand.requested = demand.requested.saturating_add(n as u64);
+            }
+        });
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_instantcoffee_StreamPublisher_cancel<'local>(_env: JNIEnv<'local>, _class: JClass<'local>, handle: jlong) {
+    if let Some(state) = publisher(handle) {
+        state.update_demand(|demand| demand.cancelled = true);
+    }
+}
+
+/// Cancel all stream subscriptions, see [`crate::jni_util::release_global_state`]; Subscription threads exit once the item they are polling is ready
+pub(crate) fn cancel_stream_subscriptions() {
+    for state in lock(&PUBLISHERS).values() {
+        state.update_demand(|demand| demand.cancelled = true);
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_instantcoffee_StreamPublisher_release<'local>(_env: JNIEnv<'local>, _class: JClass<'local>, handle: jlong) {
+    let removed = lock(&PUBLISHERS).remove(Handle::from_raw(handle));
+    if let Some(state) = removed {
+        state.update_demand(|demand| demand.cancelled = true);
+    }
+}
+
+/// Subscription thread; Polls the stream while there is demand, and signals the subscriber
+fn drive(vm: JavaVM, state: Arc<PublisherState>, mut stream: ErasedStream, subscriber: GlobalRef) {
+    // Daemon, so that a pending subscription does not keep the JVM alive
+    let Ok(mut env) = vm.attach_current_thread_as_daemon() else { return };
+
+    loop {
+        {
+            let mut demand = lock(&state.demand);
+            while demand.requested == 0 && !demand.cancelled && !demand.invalid_request {
+                demand = state.signal.wait(demand).unwrap_or_else(PoisonError::into_inner);
+            }
+            if demand.cancelled {
+                return;
+            }
+            if demand.invalid_request {
+                drop(demand);
+                let error = Exception { class: "java/lang/IllegalArgumentException".to_string(), msg: "non-positive subscription request".to_string() };
+                signal_error(&mut env, &subscriber, Some(error));
+                return;
+            }
+            if demand.requested < i64::MAX as u64 {
+                demand.requested -= 1;
+            }
+        }
+
+        let Some(item) = block_on(stream.next()) else {
+            let _ = env.call_method(&subscriber, "onComplete", "()V", &[]);
+            let _ = env.exception_clear();
+            return;
+        };
+
+        if env.push_local_frame(4).is_err() {
+            return;
+        }
+        let delivered = match item.into_object(&mut env) {
+            Ok(object) => {
+                // A throwing onNext violates the Reactive Streams specification; The subscription is cancelled
+                let result = env.call_method(&subscriber, "onNext", "(Ljava/lang/Object;)V", &[JValue::Object(&object)]);
+                let _ = env.exception_clear();
+                result.is_ok()
+            }
+            Err(error) => {
+                signal_error(&mut env, &subscriber, error);
+                false
+            }
+        };
+        unsafe { env.pop_local_frame(&JObject::null()) }.ok();
+
+        if !delivered {
+            return;
+        }
+    }
+}
+
+/// Signal a failed conversion to the subscriber with onError; The exception is either `error`, or the currently thrown Java exception
+fn signal_error(env: &mut JNIEnv, subscriber: &GlobalRef, error: Option<Exception>) {
+    let throwable = match error {
+        Some(exception) => env.new_string(exception.msg)
+            .and_then(|message| env.new_object(exception.class, "(Ljava/lang/String;)V", &[JValue::Object(&message)]))
+            .map(JThrowable::from),
+        None => env.exception_occurred()
+            .and_then(|throwable| env.exception_clear().map(|_| throwable)),
+    };
+
+    if let Ok(throwable) = throwable {
+        let _ = env.call_method(subscriber, "onError", "(Ljava/lang/Throwable;)V", &[JValue::Object(&throwable)]);
+    }
+    let _ = env.exception_clear();
+}
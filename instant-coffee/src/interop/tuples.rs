@@ -0,0 +1,270 @@
+//! Tuple interop; Maps rust tuples of two to four elements onto the Java classes `instantcoffee.Tuple2` through `instantcoffee.Tuple4`
+
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::objects::{JObject, JObjectArray, JValue, JValueOwned};
+use crate::codegen::JClassDecl;
+use crate::interop::result::check_instance;
+use crate::{JavaType, JniObject};
+use crate::jni_util::{generic_type_name, map_jni_error, non_null, CachedClass, LazyName, CLASS_CAST_EXCEPTION, NULL_POINTER_EXCEPTION};
+
+/// Java source of the pair class
+const TUPLE2_SOURCE: &str = r#"
+/**
+ * Pair of values; Rust tuples {@code (A, B)}
+ *
+ * @param <A> Type of the first value
+ * @param <B> Type of the second value
+ */
+public final class Tuple2<A, B> {
+	private final A first;
+	private final B second;
+
+	public Tuple2(A first, B second) {
+		this.first = first;
+		this.second = second;
+	}
+
+	public A first() {
+		return first;
+	}
+
+	public B second() {
+		return second;
+	}
+
+	@Override
+	public boolean equals(Object other) {
+		if (!(other instanceof Tuple2)) {
+			return false;
+		}
+		Tuple2<?, ?> tuple = (Tuple2<?, ?>) other;
+		return java.util.Objects.equals(first, tuple.first) && java.util.Objects.equals(second, tuple.second);
+	}
+
+	@Override
+	public int hashCode() {
+		return java.util.Objects.hash(first, second);
+	}
+
+	@Override
+	public String toString() {
+		return "(" + first + ", " + second + ")";
+	}
+}
+"#;
+
+/// Java source of the triple class
+const TUPLE3_SOURCE: &str = r#"
+/**
+ * Triple of values; Rust tuples {@code (A, B, C)}
+ *
+ * @param <A> Type of the first value
+ * @param <B> Type of the second value
+ * @param <C> Type of the third value
+ */
+public final class Tuple3<A, B, C> {
+	private final A first;
+	private final B second;
+	private final C third;
+
+	public Tuple3(A first, B second, C third) {
+		this.first = first;
+		this.second = second;
+		this.third = third;
+	}
+
+	public A first() {
+		return first;
+	}
+
+	public B second() {
+		return second;
+	}
+
+	public C third() {
+		return third;
+	}
+
+	@Override
+	public boolean equals(Object other) {
+		if (!(other instanceof Tuple3)) {
+			return false;
+		}
+		Tuple3<?, ?, ?> tuple = (Tuple3<?, ?, ?>) other;
+		return java.util.Objects.equals(first, tuple.first) && java.util.Objects.equals(second, tuple.second) && java.util.Objects.equals(third, tuple.third);
+	}
+
+	@Override
+	public int hashCode() {
+		return java.util.Objects.hash(first, second, third);
+	}
+
+	@Override
+	public String toString() {
+		return "(" + first + ", " + second + ", " + third + ")";
+	}
+}
+"#;
+
+/// Java source of the quadruple class
+const TUPLE4_SOURCE: &str = r#"
+/**
+ * Quadruple of values; Rust tuples {@code (A, B, C, D)}
+ *
+ * @param <A> Type of the first value
+ * @param <B> Type of the second value
+ * @param <C> Type of the third value
+ * @param <D> Type of the fourth value
+ */
+public final class Tuple4<A, B, C, D> {
+	private final A first;
+	private final B second;
+	private final C third;
+	private final D fourth;
+
+	public Tuple4(A first, B second, C third, D fourth) {
+		this.first = first;
+		this.second = second;
+		this.third = third;
+		this.fourth = fourth;
+	}
+
+	public A first() {
+		return first;
+	}
+
+	public B second() {
+		return second;
+	}
+
+	public C third() {
+		return third;
+	}
+
+	public D fourth() {
+		return fourth;
+	}
+
+	@Override
+	public boolean equals(Object other) {
+		if (!(other instanceof Tuple4)) {
+			return false;
+		}
+		Tuple4<?, ?, ?, ?> tuple = (Tuple4<?, ?, ?, ?>) other;
+		return java.util.Objects.equals(first, tuple.first) && java.util.Objects.equals(second, tuple.second) && java.util.Objects.equals(third, tuple.third) && java.util.Objects.equals(fourth, tuple.fourth);
+	}
+
+	@Override
+	public int hashCode() {
+		return java.util.Objects.hash(first, second, third, fourth);
+	}
+
+	@Override
+	public String toString() {
+		return "(" + first + ", " + second + ", " + third + ", " + fourth + ")";
+	}
+}
+"#;
+
+/// Declaration of the Java tuple class `instantcoffee.Tuple{arity}`; Added to modules using tuples of that arity by the `jmodule` macro
+///
+/// # Panics
+///
+/// If `arity` is not between 2 and 4
+pub fn tuple_declaration(arity: usize) -> JClassDecl {
+    let (name, source) = match arity {
+        2 => ("Tuple2", TUPLE2_SOURCE),
+        3 => ("Tuple3", TUPLE3_SOURCE),
+        4 => ("Tuple4", TUPLE4_SOURCE),
+        _ => panic!("tuples of {} elements have no Java class, arity must be between 2 and 4", arity)
+    };
+    JClassDecl::Source {
+        name,
+        package: "instantcoffee",
+        source,
+    }
+}
+
+/// Convert the element held in field `field` of a tuple object
+///
+/// Elements are held as objects; Primitive elements are boxed, as with [`Option<A>`](Option)
+fn element_from_jni<'local, A: JavaType>(tuple: &JObject<'local>, field: &str, env: &mut JNIEnv<'local>) -> Result<A, Option<Exception>>
+where
+    Option<A>: JavaType,
+    for<'a> <Option<A> as JavaType>::JniType<'a>: JniObject<'a>,
+{
+    let null_element = || Some(Exception { class: NULL_POINTER_EXCEPTION.to_string(), msg: format!("expected {}", A::QUALIFIED_NAME()) });
+    let element = env.get_field(tuple, field, "Ljava/lang/Object;")
+        .map_err(map_jni_error)?
+        .l().map_err(map_jni_error)?;
+    if element.is_null() {
+        // Null converts as the element type converts it, such that `Option` elements are None; Primitive elements cannot be null
+        return match A::from_jvalue(JValueOwned::Object(element), env) {
+            Ok(jni_value) => A::from_jni(jni_value, env),
+            Err(_) => Err(null_element()),
+        };
+    }
+
+    // Type parameters are erased in Java, the element is checked before converting it
+    check_instance(&element, <Option<A> as JavaType>::JVM_PARAM_SIGNATURE(), env)?;
+    <Option<A> as JavaType>::from_jni(From::from(element), env)?.ok_or_else(null_element)
+}
+
+macro_rules! tuple_java_type {
+    ($($class:literal, $constructor_signature:literal => $(($idx:tt, $element:ident, $field:literal)),+;)*) => {$(
+        const _: () = {
+            static CLASS: CachedClass = CachedClass::new(concat!("instantcoffee/", $class));
+
+            /// Java `instantcoffee.TupleN<A, ...>` = rust tuple `(A, ...)`; Primitive elements are boxed, as with [`Option<A>`](Option)
+            impl<$($element: JavaType),+> JavaType for ($($element,)+)
+            where
+                $(
+                    Option<$element>: JavaType,
+                    for<'local> <Option<$element> as JavaType>::JniType<'local>: JniObject<'local>,
+                )+
+            {
+                type JniType<'local> = JObject<'local>;
+                type ArrayType<'local> = JObjectArray<'local>;
+
+                fn QUALIFIED_NAME() -> &'static str {
+                    let elements = [$(<Option<$element> as JavaType>::QUALIFIED_NAME()),+];
+                    let mut name = concat!("instantcoffee.", $class, "<");
+                    for (idx, element) in elements.iter().enumerate() {
+                        name = generic_type_name(name, element, if idx + 1 == elements.len() { ">" } else { ", " });
+                    }
+                    name
+                }
+
+                fn JVM_PARAM_SIGNATURE() -> &'static str { concat!("Linstantcoffee/", $class, ";") }
+
+                fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+                fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+                    non_null(&jni_value, LazyName(<Self as JavaType>::QUALIFIED_NAME))?;
+                    Ok(($(element_from_jni::<$element>(&jni_value, $field, env)?,)+))
+                }
+
+                fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+                    let class = &CLASS.get(env)?;
+                    let elements = ($(<Option<$element> as JavaType>::into_jni(Some(self.$idx), env)?,)+);
+                    env.new_object(class, $constructor_signature, &[$(JValue::Object(elements.$idx.as_ref())),+])
+                        .map_err(map_jni_error)
+                }
+
+                fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+                    match jvalue {
+                        JValueOwned::Object(obj) => Ok(obj),
+                        _ => Err(Some(Exception { class: CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+                    }
+                }
+            }
+        };
+    )*};
+}
+
+tuple_java_type! {
+    "Tuple2", "(Ljava/lang/Object;Ljava/lang/Object;)V" => (0, A, "first"), (1, B, "second");
+    "Tuple3", "(Ljava/lang/Object;Ljava/lang/Object;Ljava/lang/Object;)V" => (0, A, "first"), (1, B, "second"), (2, C, "third");
+    "Tuple4", "(Ljava/lang/Object;Ljava/lang/Object;Ljava/lang/Object;Ljava/lang/Object;)V" => (0, A, "first"), (1, B, "second"), (2, C, "third"), (3, D, "fourth");
+}
@@ -1,8 +1,13 @@
 //! Utility functions
 
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, PoisonError, RwLock};
 use jni::errors::{Error, Exception};
-use jni::JNIEnv;
-use jni::objects::{JObject, JString};
+use jni::{JavaVM, JNIEnv};
+use jni::objects::{GlobalRef, JClass, JObject, JString, JValue, JValueOwned};
+use jni::sys::{jint, jsize, JNI_FALSE};
 
 /// Maps JNI errors into Exceptions
 ///
@@ -41,3 +46,718 @@ pub fn obj_classname<'local>(object: &JObject<'local>, env: &mut JNIEnv<'local>)
         .map_err(map_jni_error)
         .map(|string| string.into())
 }
+
+/// JVM class name of the exception thrown by conversions receiving an unexpected null
+pub const NULL_POINTER_EXCEPTION: &str = "java/lang/NullPointerException";
+/// JVM class name of the exception thrown by conversions receiving an object of the wrong class
+pub const CLASS_CAST_EXCEPTION: &str = "java/lang/ClassCastException";
+/// JVM class name of the exception thrown by conversions receiving a value they cannot represent
+pub const ILLEGAL_ARGUMENT_EXCEPTION: &str = "java/lang/IllegalArgumentException";
+
+/// Name of a converted type, for exception messages; Only read once an exception is thrown
+///
+/// Names of generic types are built with [`generic_type_name`], which takes a lock; Pass such names as a [`LazyName`], so that conversions which succeed do not look them up
+pub trait TypeName {
+    /// Java name of the type
+    fn type_name(&self) -> &str;
+}
+
+impl TypeName for &str {
+    fn type_name(&self) -> &str {
+        self
+    }
+}
+
+/// Type name computed by a function once an exception is thrown, such as `LazyName(<T as JavaType>::QUALIFIED_NAME)`
+#[derive(Copy, Clone)]
+pub struct LazyName(pub fn() -> &'static str);
+
+impl TypeName for LazyName {
+    fn type_name(&self) -> &str {
+        (self.0)()
+    }
+}
+
+/// Throws NullPointerException if the given JObject is null, for conversions which cannot accept null
+///
+/// # Arguments
+///
+/// * `object`: JObject to check
+/// * `expected`: Java type expected in place of null, for the exception message
+///
+/// returns: Result<(), Option<Exception>>
+pub fn non_null(object: &JObject, expected: impl TypeName) -> Result<(), Option<Exception>> {
+    if object.is_null() {
+        Err(Some(Exception { class: NULL_POINTER_EXCEPTION.to_string(), msg: format!("expected {}", expected.type_name()) }))
+    } else {
+        Ok(())
+    }
+}
+
+/// Convert the value of a field which may be left uninitialized, such as a field of a bean class constructed reflectively; A null object converts to `T::default()`
+///
+/// # Arguments
+///
+/// * `jvalue`: Value of the field
+/// * `env`: JNI Env
+///
+/// returns: Result<T, Option<Exception>>
+pub fn from_field_or_default<'local, T: crate::JavaType + Default>(jvalue: JValueOwned<'local>, env: &mut JNIEnv<'local>) -> Result<T, Option<Exception>> {
+    if matches!(&jvalue, JValueOwned::Object(object) if object.is_null()) {
+        return Ok(T::default());
+    }
+
+    let jni_value = T::from_jvalue(jvalue, env)?;
+    T::from_jni(jni_value, env)
+}
+
+/// Length of a Java array holding `length` elements; Throws IllegalArgumentException if the length exceeds the maximum size of a Java array
+///
+/// # Arguments
+///
+/// * `length`: Number of elements
+/// * `element_type`: Java type of the elements, for the exception message
+///
+/// returns: Result<jsize, Option<Exception>>
+pub fn array_length(length: usize, element_type: impl TypeName) -> Result<jsize, Option<Exception>> {
+    jsize::try_from(length)
+        .map_err(|_| Some(Exception { class: ILLEGAL_ARGUMENT_EXCEPTION.to_string(), msg: format!("cannot convert {} elements to {}[]; Java arrays hold at most {} elements", length, element_type.type_name(), jsize::MAX) }))
+}
+
+/// Reserve local reference capacity ahead of a conversion creating many local references, such as that of a large object array
+///
+/// Throws OutOfMemoryError naming the conversion if the capacity cannot be reserved, rather than failing partway through the conversion
+///
+/// # Arguments
+///
+/// * `capacity`: Number of local references the conversion will create
+/// * `conversion`: Description of the conversion, for the exception message
+/// * `env`: JNI Env
+///
+/// returns: Result<(), Option<Exception>>
+pub fn ensure_local_capacity(capacity: usize, conversion: impl TypeName, env: &mut JNIEnv) -> Result<(), Option<Exception>> {
+    let requested = jint::try_from(capacity).unwrap_or(jint::MAX);
+    if env.ensure_local_capacity(requested).is_ok() {
+        return Ok(());
+    }
+
+    let _ = env.exception_clear();
+    Err(Some(Exception { class: "java/lang/OutOfMemoryError".to_string(), msg: format!("could not reserve {} local references to convert {}", capacity, conversion.type_name()) }))
+}
+
+/// Run a conversion producing a rust value inside its own local reference frame, releasing every local reference it creates
+///
+/// Throws OutOfMemoryError naming the conversion if the frame cannot be allocated, and StackOverflowError if conversions nest deeper than the [`Limits::max_depth`] in effect
+///
+/// # Arguments
+///
+/// * `capacity`: Number of local references the conversion will create
+/// * `conversion`: Description of the conversion, for the exception message
+/// * `env`: JNI Env
+/// * `f`: Conversion to run
+///
+/// returns: Result<T, Option<Exception>>
+pub fn with_local_frame<'local, T>(capacity: usize, conversion: impl TypeName, env: &mut JNIEnv<'local>, f: impl FnOnce(&mut JNIEnv<'local>) -> Result<T, Option<Exception>>) -> Result<T, Option<Exception>> {
+    // Nested conversions each run in their own frame; A cyclic object graph would otherwise recurse until the native stack overflows
+    let _depth = ConversionDepth::enter(&conversion)?;
+
+    let requested = jint::try_from(capacity).unwrap_or(jint::MAX);
+    if env.push_local_frame(requested).is_err() {
+        let _ = env.exception_clear();
+        return Err(Some(Exception { class: "java/lang/OutOfMemoryError".to_string(), msg: format!("could not reserve {} local references to convert {}", capacity, conversion.type_name()) }));
+    }
+
+    let result = f(env);
+    // No references are kept from the frame
+    unsafe { env.pop_local_frame(&JObject::null()) }.map_err(map_jni_error)?;
+    result
+}
+
+/// Default maximum nesting depth of conversions from Java
+pub const DEFAULT_MAX_CONVERSION_DEPTH: usize = 256;
+
+/// Limits on the size of inputs converted from Java, for native libraries exposed to untrusted Java callers; Larger inputs throw a descriptive exception instead of being converted
+///
+/// Each `#[jmodule]` declares its limits as `JMODULE_LIMITS`, from its `max_array_length`, `max_string_bytes` and `max_depth` options, and its exported functions convert within them. See [`with_limits`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum number of elements of arrays and maps; Larger inputs throw IllegalArgumentException before their elements are read, so that callers cannot make the library allocate without bound
+    pub max_array_length: usize,
+    /// Maximum length in bytes of strings, in (modified) UTF-8; Longer strings throw IllegalArgumentException
+    pub max_string_bytes: usize,
+    /// Maximum nesting depth of conversions, such as a struct field holding a struct; Deeper conversions throw StackOverflowError instead of overflowing the native stack, as a cyclic or adversarial object graph would otherwise do.
+    /// Each level uses native stack; Raise the limit only on threads with stacks large enough to hold it
+    pub max_depth: usize,
+}
+
+impl Limits {
+    /// Arrays and strings of any size, nested at most [`DEFAULT_MAX_CONVERSION_DEPTH`] levels deep
+    pub const DEFAULT: Limits = Limits { max_array_length: usize::MAX, max_string_bytes: usize::MAX, max_depth: DEFAULT_MAX_CONVERSION_DEPTH };
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::DEFAULT
+    }
+}
+
+thread_local! {
+    static LIMITS: Cell<Limits> = const { Cell::new(Limits::DEFAULT) };
+    static CONVERSION_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Run `f` with the conversions on this thread limited by `limits`, restoring the previous limits afterwards; Conversions outside of it are limited by [`Limits::DEFAULT`]
+///
+/// Generated exports run their conversions within the `JMODULE_LIMITS` of their module, so modules of a library do not share limits
+pub fn with_limits<T>(limits: Limits, f: impl FnOnce() -> T) -> T {
+    /// Restores the previous limits when dropped, including on panics
+    struct Restore(Limits);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            LIMITS.set(self.0);
+        }
+    }
+
+    let _restore = Restore(LIMITS.replace(limits));
+    f()
+}
+
+/// Check the number of elements of an array or map converted from Java against the [`Limits::max_array_length`] in effect
+///
+/// # Arguments
+///
+/// * `length`: Number of elements of the Java array or map
+/// * `conversion`: Java type of the array or map, for the exception message
+///
+/// returns: Result<(), Option<Exception>>
+pub fn check_array_length(length: usize, conversion: impl TypeName) -> Result<(), Option<Exception>> {
+    let max_length = LIMITS.get().max_array_length;
+    if length > max_length {
+        return Err(Some(Exception { class: ILLEGAL_ARGUMENT_EXCEPTION.to_string(), msg: format!("cannot convert {} of {} elements; conversions are limited to {} elements", conversion.type_name(), length, max_length) }));
+    }
+    Ok(())
+}
+
+/// Whether the [`Limits::max_string_bytes`] in effect is limited; Conversions skip measuring strings when unlimited
+pub fn string_bytes_limited() -> bool {
+    LIMITS.get().max_string_bytes != usize::MAX
+}
+
+/// Check the length of a string converted from Java against the [`Limits::max_string_bytes`] in effect
+///
+/// # Arguments
+///
+/// * `bytes`: Length of the string in (modified) UTF-8, or a lower bound of it
+///
+/// returns: Result<(), Option<Exception>>
+pub fn check_string_bytes(bytes: usize) -> Result<(), Option<Exception>> {
+    let max_bytes = LIMITS.get().max_string_bytes;
+    if bytes > max_bytes {
+        return Err(Some(Exception { class: ILLEGAL_ARGUMENT_EXCEPTION.to_string(), msg: format!("cannot convert java.lang.String of at least {} bytes; conversions are limited to {} bytes", bytes, max_bytes) }));
+    }
+    Ok(())
+}
+
+/// Nesting level of a running conversion, on this thread; Leaves the level when dropped
+struct ConversionDepth;
+
+impl ConversionDepth {
+    fn enter(conversion: &impl TypeName) -> Result<ConversionDepth, Option<Exception>> {
+        let depth = CONVERSION_DEPTH.get() + 1;
+        let max_depth = LIMITS.get().max_depth;
+        if depth > max_depth {
+            return Err(Some(Exception { class: "java/lang/StackOverflowError".to_string(), msg: format!("could not convert {}; conversions nested deeper than {} levels, the object graph may be cyclic", conversion.type_name(), max_depth) }));
+        }
+
+        CONVERSION_DEPTH.set(depth);
+        Ok(ConversionDepth)
+    }
+}
+
+impl Drop for ConversionDepth {
+    fn drop(&mut self) {
+        CONVERSION_DEPTH.set(CONVERSION_DEPTH.get() - 1);
+    }
+}
+
+/// Name of a generic type composed from the name of its type parameter, such as "int[]" from "int"; Each name is created once, and leaked
+///
+/// For use in [`crate::JavaType`] implementations on generic types, as statics in generic functions are shared between all type parameters, and cannot cache per-type names
+///
+/// # Arguments
+///
+/// * `prefix`: Text before the parameter name, such as "[" for array signatures
+/// * `inner`: Name of the type parameter
+/// * `suffix`: Text after the parameter name, such as "[]" for array names
+///
+/// returns: &'static str
+pub fn generic_type_name(prefix: &'static str, inner: &'static str, suffix: &'static str) -> &'static str {
+    static NAMES: Mutex<BTreeMap<(&'static str, &'static str, &'static str), &'static str>> = Mutex::new(BTreeMap::new());
+
+    NAMES.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry((prefix, inner, suffix))
+        .or_insert_with(|| format!("{}{}{}", prefix, inner, suffix).leak())
+}
+
+/// Strategy used to look up classes by name, by [`CachedClass`] and object array conversions
+///
+/// `FindClass` uses the class loader of the calling Java method, which is the system class loader on threads attached from rust; Under application servers and on Android, classes of the library are then not found
+pub enum ClassResolution {
+    /// Look up classes with `FindClass`
+    FindClass,
+    /// Load classes with the class loader of the library's classes, captured in `JNI_OnLoad`; Uses `FindClass` if no class loader was captured
+    LoadTimeLoader,
+    /// Load classes with the given `java.lang.ClassLoader`
+    ClassLoader(GlobalRef),
+}
+
+static CLASS_RESOLUTION: RwLock<ClassResolution> = RwLock::new(ClassResolution::LoadTimeLoader);
+/// Class loader of the library's classes in each VM
+static LOAD_TIME_LOADER: PerVm<GlobalRef> = PerVm::new();
+
+/// Set the strategy for looking up classes; Defaults to [`ClassResolution::LoadTimeLoader`]
+///
+/// Classes already resolved by a [`CachedClass`] are not looked up again. The strategy applies to every Java VM in the process;
+/// A [`ClassResolution::ClassLoader`] is only valid in the VM it was created in
+pub fn set_class_resolution(resolution: ClassResolution) {
+    *CLASS_RESOLUTION.write().unwrap_or_else(PoisonError::into_inner) = resolution;
+}
+
+/// Look up a class with the current [`ClassResolution`] strategy
+///
+/// # Arguments
+///
+/// * `jvm_class_name`: JVM class name, such as "com/example/Foo", or array class signature, such as "[Lcom/example/Foo;"
+/// * `env`: JNI Env
+///
+/// returns: Result<JClass, Option<Exception>>
+pub fn find_class<'local>(jvm_class_name: &str, env: &mut JNIEnv<'local>) -> Result<JClass<'local>, Option<Exception>> {
+    // Loading a class may run its initializer, which may look up classes in turn; The lock is not held while loading
+    let loader = match &*CLASS_RESOLUTION.read().unwrap_or_else(PoisonError::into_inner) {
+        ClassResolution::FindClass => None,
+        ClassResolution::LoadTimeLoader => LOAD_TIME_LOADER.get(vm_key(env)?),
+        ClassResolution::ClassLoader(loader) => Some(loader.clone()),
+    };
+
+    let Some(loader) = loader else {
+        return env.find_class(jvm_class_name).map_err(map_jni_error);
+    };
+
+    let binary_name = env.new_string(jvm_class_name.replace('/', "."))
+        .map_err(map_jni_error)?;
+    let class = env.call_static_method(
+        "java/lang/Class",
+        "forName",
+        "(Ljava/lang/String;ZLjava/lang/ClassLoader;)Ljava/lang/Class;",
+        &[JValue::Object(&binary_name), JValue::Bool(JNI_FALSE), JValue::Object(loader.as_obj())]
+    )
+        .map_err(map_jni_error)?
+        .l()
+        .map_err(map_jni_error)?;
+    env.delete_local_ref(binary_name).map_err(map_jni_error)?;
+
+    Ok(JClass::from(class))
+}
+
+/// JVM class name of the type with JVM signature `signature`; Object signatures such as "Lcom/example/Foo;" name class "com/example/Foo", array signatures such as "[I" are class names as-is
+pub fn signature_class_name(signature: &str) -> &str {
+    signature.strip_prefix('L')
+        .and_then(|name| name.strip_suffix(';'))
+        .unwrap_or(signature)
+}
+
+/// Classes looked up by [`signature_class`], by VM and signature
+struct SignatureClasses {
+    classes: RwLock<BTreeMap<(usize, &'static str), GlobalRef>>,
+}
+
+impl GlobalCache for SignatureClasses {
+    fn release(&self) {
+        self.classes.write().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+}
+
+static SIGNATURE_CLASSES: SignatureClasses = SignatureClasses { classes: RwLock::new(BTreeMap::new()) };
+
+/// Class of the type with JVM signature `signature`, such as the element class of an object array; Looked up once per VM and reused, as with [`CachedClass`]
+///
+/// For classes known only through a type parameter, such as `T::JVM_PARAM_SIGNATURE()`, which cannot have a `CachedClass` of their own.
+/// Classes are released by [`release_global_state`], and looked up again on their next use
+pub fn signature_class<'local>(signature: &'static str, env: &mut JNIEnv<'local>) -> Result<GlobalRef, Option<Exception>> {
+    let key = (vm_key(env)?, signature);
+    if let Some(class) = SIGNATURE_CLASSES.classes.read().unwrap_or_else(PoisonError::into_inner).get(&key) {
+        return Ok(class.clone());
+    }
+
+    let class = find_class(signature_class_name(signature), env)?;
+    let global = env.new_global_ref(&class).map_err(map_jni_error)?;
+    env.delete_local_ref(class).map_err(map_jni_error)?;
+
+    let mut classes = SIGNATURE_CLASSES.classes.write().unwrap_or_else(PoisonError::into_inner);
+    if classes.is_empty() {
+        register_global_cache(&SIGNATURE_CLASSES);
+    }
+    Ok(classes.entry(key).or_insert(global).clone())
+}
+
+/// A global reference to a Java class, looked up once and reused for every later access
+///
+/// Generated code resolves these from `JNI_OnLoad`, where the class loader of the library is in context, and captures that class loader for [`ClassResolution::LoadTimeLoader`]. Classes that could not be resolved at load are looked up when first used
+///
+/// Classes are resolved separately in each Java VM of the process. Resolved classes are released by [`release_global_state`], and looked up again on their next use
+pub struct CachedClass {
+    name: &'static str,
+    class: PerVm<GlobalRef>,
+}
+
+impl CachedClass {
+    /// Create an unresolved class cache
+    ///
+    /// # Arguments
+    ///
+    /// * `jvm_class_name`: JVM class name, such as "com/example/Foo"
+    pub const fn new(jvm_class_name: &'static str) -> Self {
+        CachedClass { name: jvm_class_name, class: PerVm::new() }
+    }
+
+    /// JVM class name of the cached class
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Retrieve the cached class, looking it up with [`find_class`] if it has not been resolved yet
+    pub fn get<'local>(&'static self, env: &mut JNIEnv<'local>) -> Result<GlobalRef, Option<Exception>> {
+        let vm = vm_key(env)?;
+        if let Some(class) = self.class.get(vm) {
+            return Ok(class);
+        }
+
+        let class = find_class(self.name, env)?;
+        let global = env.new_global_ref(&class).map_err(map_jni_error)?;
+        env.delete_local_ref(class).map_err(map_jni_error)?;
+
+        Ok(self.class.get_or_insert(vm, global, &self.class))
+    }
+
+    /// Resolve the class ahead of its first use, and capture its class loader if none was yet; Intended to be called from `JNI_OnLoad`
+    ///
+    /// If the class cannot be found, the pending exception is cleared and the lookup is retried on first use
+    pub fn resolve<'local>(&'static self, env: &mut JNIEnv<'local>) {
+        let captured = self.get(env).and_then(|class| {
+            let vm = vm_key(env)?;
+            if LOAD_TIME_LOADER.get(vm).is_some() {
+                return Ok(());
+            }
+
+            let loader = env.call_method(&class, "getClassLoader", "()Ljava/lang/ClassLoader;", &[])
+                .map_err(map_jni_error)?
+                .l()
+                .map_err(map_jni_error)?;
+            if !loader.is_null() {
+                let global = env.new_global_ref(&loader).map_err(map_jni_error)?;
+                LOAD_TIME_LOADER.get_or_insert(vm, global, &LOAD_TIME_LOADER);
+            }
+            env.delete_local_ref(loader).map_err(map_jni_error)
+        });
+
+        if captured.is_err() {
+            let _ = env.exception_clear();
+        }
+    }
+}
+
+/// Key of the Java VM of `env`, for values cached per VM; Global references and JNI IDs are only valid in the VM they were obtained from
+pub(crate) fn vm_key(env: &JNIEnv) -> Result<usize, Option<Exception>> {
+    env.get_java_vm()
+        .map(|vm| vm.get_java_vm_pointer() as usize)
+        .map_err(map_jni_error)
+}
+
+/// Value cached separately for each Java VM in the process, keyed by [`vm_key`]; Almost always holds a single entry
+pub(crate) struct PerVm<T> {
+    entries: RwLock<Vec<(usize, T)>>,
+}
+
+impl<T: Clone + Send + Sync> PerVm<T> {
+    pub(crate) const fn new() -> Self {
+        PerVm { entries: RwLock::new(Vec::new()) }
+    }
+
+    /// Cached value for VM `vm`, if any
+    pub(crate) fn get(&self, vm: usize) -> Option<T> {
+        self.entries.read().unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .find(|(key, _)| *key == vm)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// Cache `value` for VM `vm` unless a value was cached concurrently, returning the cached value
+    ///
+    /// `cache` is registered with [`register_global_cache`] when its first value is cached
+    pub(crate) fn get_or_insert(&self, vm: usize, value: T, cache: &'static dyn GlobalCache) -> T {
+        let mut entries = self.entries.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some((_, cached)) = entries.iter().find(|(key, _)| *key == vm) {
+            return cached.clone();
+        }
+        if entries.is_empty() {
+            register_global_cache(cache);
+        }
+        entries.push((vm, value.clone()));
+        value
+    }
+}
+
+impl<T: Clone + Send + Sync> GlobalCache for PerVm<T> {
+    fn release(&self) {
+        self.entries.write().unwrap_or_else(PoisonError::into_inner).clear();
+    }
+}
+
+/// Cache of global references or JNI IDs, which pin the classes of the library and with them its class loader
+pub(crate) trait GlobalCache: Sync {
+    /// Drop the cached references, to be looked up again on next use
+    fn release(&self);
+}
+
+/// Caches that have been filled since they were last released
+static GLOBAL_CACHES: Mutex<Vec<&'static dyn GlobalCache>> = Mutex::new(Vec::new());
+
+/// Register a cache to be released by [`release_global_state`]; Caches register when they are filled, and are unregistered once released
+pub(crate) fn register_global_cache(cache: &'static dyn GlobalCache) {
+    GLOBAL_CACHES.lock().unwrap_or_else(PoisonError::into_inner).push(cache);
+}
+
+/// Release all global state of the library referring to Java; Cached classes, method IDs, and the class loader captured in `JNI_OnLoad`, and resets [`LoadOnce`] guards.
+/// Stream subscriptions are cancelled
+///
+/// Global references to classes keep their class loader, and with it the library, from being unloaded. Calling this once the library is no longer used,
+/// such as when an application server undeploys the application, allows the class loader to be collected and the library to be unloaded and loaded again.
+/// The generated `jmodule_on_unload` calls this as well, in case the library stays loaded in the process and is loaded again by a new class loader
+///
+/// State is released for every Java VM in the process. Caches are filled again on their next use, calling into the library afterwards is safe; Rust values owned by Java objects, such as those of handle
+/// classes or listeners holding global references, are not released
+pub fn release_global_state() {
+    let caches = std::mem::take(&mut *GLOBAL_CACHES.lock().unwrap_or_else(PoisonError::into_inner));
+    for cache in caches {
+        cache.release();
+    }
+    // Class loaders set by the application belong to it, and are dropped as well
+    set_class_resolution(ClassResolution::LoadTimeLoader);
+
+    #[cfg(feature = "streams")]
+    crate::interop::cancel_stream_subscriptions();
+}
+
+/// Guard running a function at most once per load of the library, such as module lifecycle hooks
+///
+/// Unlike [`std::sync::Once`], the guard is reset by [`release_global_state`], so that the function runs again when the library is loaded again
+pub struct LoadOnce {
+    done: Mutex<bool>,
+}
+
+impl LoadOnce {
+    /// Create a guard that has not run yet
+    pub const fn new() -> Self {
+        LoadOnce { done: Mutex::new(false) }
+    }
+
+    /// Call `f` if no function has been called through this guard since the library was loaded, or since the last [`release_global_state`]
+    ///
+    /// Concurrent callers wait until `f` has returned. If `f` panics the guard is not marked done, and `f` runs again on the next call
+    pub fn call_once(&'static self, f: impl FnOnce()) {
+        let mut done = self.done.lock().unwrap_or_else(PoisonError::into_inner);
+        if !*done {
+            f();
+            *done = true;
+            register_global_cache(self);
+        }
+    }
+}
+
+impl Default for LoadOnce {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GlobalCache for LoadOnce {
+    fn release(&self) {
+        *self.done.lock().unwrap_or_else(PoisonError::into_inner) = false;
+    }
+}
+
+/// Attach the current thread to the JVM as a daemon thread, for long-running rust worker threads calling into Java
+///
+/// Daemon threads do not keep the JVM from shutting down. The thread is detached when the returned guard is dropped, unless it was already attached
+/// or is kept attached with [`DaemonGuard::keep_attached`]; Threads kept attached are detached when they exit.
+///
+/// # Arguments
+///
+/// * `vm`: Java VM to attach to
+///
+/// returns: Result<DaemonGuard, Option<Exception>>
+pub fn attach_daemon(vm: &JavaVM) -> Result<DaemonGuard<'_>, Option<Exception>> {
+    if let Ok(env) = vm.get_env() {
+        return Ok(DaemonGuard { env, vm, detach: false });
+    }
+
+    let env = vm.attach_current_thread_as_daemon().map_err(map_jni_error)?;
+    Ok(DaemonGuard { env, vm, detach: true })
+}
+
+/// Attachment of the current thread to the JVM, created by [`attach_daemon`]; Dereferences to the thread's JNI Env
+///
+/// Local references created through the guard must not be used once it is dropped, as the thread may be detached
+pub struct DaemonGuard<'vm> {
+    env: JNIEnv<'vm>,
+    vm: &'vm JavaVM,
+    /// True if this guard attached the thread, and detaches it when dropped
+    detach: bool,
+}
+
+impl<'vm> DaemonGuard<'vm> {
+    /// Keep the thread attached once the guard is dropped, until the thread exits; Avoids attaching again for every burst of calls on threads that call into Java repeatedly
+    pub fn keep_attached(mut self) -> JNIEnv<'vm> {
+        self.detach = false;
+        // SAFETY: The guard is dropped without detaching, so the env remains valid for the thread
+        unsafe { self.env.unsafe_clone() }
+    }
+}
+
+impl<'vm> Deref for DaemonGuard<'vm> {
+    type Target = JNIEnv<'vm>;
+
+    fn deref(&self) -> &JNIEnv<'vm> {
+        &self.env
+    }
+}
+
+impl<'vm> DerefMut for DaemonGuard<'vm> {
+    fn deref_mut(&mut self) -> &mut JNIEnv<'vm> {
+        &mut self.env
+    }
+}
+
+impl Drop for DaemonGuard<'_> {
+    fn drop(&mut self) {
+        if self.detach {
+            // An exception pending on a detached thread has no Java caller to receive it
+            if self.env.exception_check().unwrap_or(false) {
+                let _ = self.env.exception_describe();
+                let _ = self.env.exception_clear();
+            }
+            // SAFETY: The thread was attached by this guard, and local references do not outlive it
+            unsafe { self.vm.detach_current_thread() }
+        }
+    }
+}
+
+/// Run a burst of calls into Java from a rust thread, under a single attachment and local reference frame
+///
+/// The thread is attached as with [`attach_daemon`] if required, and detached again afterwards. Every local reference created by `f` is released once it returns;
+/// As `f` must accept an env of any lifetime, local references cannot escape it, return Java objects as a [`GlobalRef`] instead.
+/// The frame is popped and the thread detached even if `f` panics, and a Java exception left pending on a thread attached for the scope is printed and cleared
+///
+/// # Arguments
+///
+/// * `vm`: Java VM to attach to
+/// * `f`: Calls to run, receiving the JNI Env; Use [`JNIEnv::ensure_local_capacity`] for more than 16 local references
+///
+/// returns: Result<T, Option<Exception>>
+pub fn with_java_scope<T>(vm: &JavaVM, f: impl for<'scope> FnOnce(&mut JNIEnv<'scope>) -> Result<T, Option<Exception>>) -> Result<T, Option<Exception>> {
+    /// Pops the local frame of the scope when dropped, including while unwinding
+    struct ScopeFrame<'vm>(JNIEnv<'vm>);
+
+    impl Drop for ScopeFrame<'_> {
+        fn drop(&mut self) {
+            // No references are kept from the frame
+            let _ = unsafe { self.0.pop_local_frame(&JObject::null()) };
+        }
+    }
+
+    let mut guard = attach_daemon(vm)?;
+    guard.push_local_frame(16).map_err(map_jni_error)?;
+    // Declared after the guard, so that the frame is popped before the thread is detached
+    // SAFETY: The frame is popped on this thread, while the guard keeps it attached
+    let _frame = ScopeFrame(unsafe { guard.unsafe_clone() });
+    f(&mut guard)
+}
+
+/// Mismatches between the conversions of the native library and the loaded Java classes, collected by [`crate::codegen::JavaClass::verify_schema`]
+///
+/// Conversions look up fields, constructors and methods by name and signature when first used; A stale jar fails those lookups one by one, at whatever point they are first reached.
+/// Verifying the schema reports every mismatch at once instead
+#[derive(Debug, Default)]
+pub struct SchemaErrors {
+    errors: Vec<String>,
+}
+
+impl SchemaErrors {
+    /// Create an empty error collection
+    pub fn new() -> Self {
+        SchemaErrors::default()
+    }
+
+    /// Check that `class` can be found, returning it if so
+    pub fn class<'local>(&mut self, class: &'static CachedClass, env: &mut JNIEnv<'local>) -> Option<GlobalRef> {
+        match class.get(env) {
+            Ok(class) => Some(class),
+            Err(_) => {
+                let _ = env.exception_clear();
+                self.errors.push(format!("{}: class not found", class.name()));
+                None
+            }
+        }
+    }
+
+    /// Check that `class` has a field `name` with JVM type signature `signature`, such as "I" or "Ljava/lang/String;"
+    pub fn field<'local>(&mut self, class: &'static CachedClass, name: &str, signature: &str, is_static: bool, env: &mut JNIEnv<'local>) {
+        let Some(class_ref) = self.class(class, env) else { return };
+        let found = if is_static {
+            env.get_static_field_id(&class_ref, name, signature).is_ok()
+        } else {
+            env.get_field_id(&class_ref, name, signature).is_ok()
+        };
+        if !found {
+            let _ = env.exception_clear();
+            let static_modifier = if is_static { "static " } else { "" };
+            self.errors.push(format!("{}: no {}field {} of type {}", class.name(), static_modifier, name, signature));
+        }
+    }
+
+    /// Check that `class` has a method `name` with JVM method signature `signature`, such as "(I)V"; Constructors are named `<init>`
+    pub fn method<'local>(&mut self, class: &'static CachedClass, name: &str, signature: &str, is_static: bool, env: &mut JNIEnv<'local>) {
+        let Some(class_ref) = self.class(class, env) else { return };
+        let found = if is_static {
+            env.get_static_method_id(&class_ref, name, signature).is_ok()
+        } else {
+            env.get_method_id(&class_ref, name, signature).is_ok()
+        };
+        if !found {
+            let _ = env.exception_clear();
+            let static_modifier = if is_static { "static " } else { "" };
+            self.errors.push(format!("{}: no {}method {}{}", class.name(), static_modifier, name, signature));
+        }
+    }
+
+    /// Returns true if no mismatches were found
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Mismatches found, one message per missing class or member
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// Ok if no mismatches were found, otherwise an error listing every mismatch
+    pub fn into_result(self) -> Result<(), String> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Java classes do not match the native library:\n\t{}", self.errors.join("\n\t")))
+        }
+    }
+}
@@ -1,24 +1,36 @@
-#![feature(associated_type_defaults)]
 #![allow(non_snake_case)]
-#![feature(utf16_extra)]
 #![allow(clippy::needless_lifetimes)]
 
-use std::any::TypeId;
-use std::sync::OnceLock;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::hash::{BuildHasher, Hash};
+use std::ops::Deref;
+use std::rc::Rc;
+use std::sync::Arc;
 use jni::errors::Exception;
 use jni::JNIEnv;
-use jni::objects::{JBooleanArray, JByteArray, JCharArray, JDoubleArray, JFloatArray, JIntArray, JLongArray, JObject, JObjectArray, JShortArray, JString, JValueOwned, ReleaseMode};
+use jni::objects::{AsJArrayRaw, JBooleanArray, JByteArray, JByteBuffer, JCharArray, JClass, JDoubleArray, JFloatArray, JIntArray, JLongArray, JMethodID, JObject, JObjectArray, JPrimitiveArray, JShortArray, JStaticMethodID, JString, JThrowable, JValue, JValueOwned, TypeArray};
+use jni::signature::{Primitive, ReturnType};
+#[cfg(not(feature = "unchecked-strings"))]
 use jni::strings::JavaStr;
 use jni::sys::{jboolean, jbyte, jchar, jdouble, jfloat, jint, jlong, jshort, jsize};
 
-use jni_util::map_jni_error;
+use jni_util::{map_jni_error, LazyName};
 
-use crate::interop::JavaChar;
+use crate::interop::{Handle, JavaChar};
+
+// Lets code generated by the derive macros, which names the crate, be used in this crate's tests
+#[cfg(test)]
+extern crate self as instant_coffee;
 
 /// Module for proc_macro re-exports, from instant-coffee-proc-macro
 pub mod proc_macro {
     pub use instant_coffee_proc_macro::JavaType;
     pub use instant_coffee_proc_macro::jmodule;
+    pub use instant_coffee_proc_macro::jmodule_impl;
+    pub use instant_coffee_proc_macro::java_external;
+    pub use instant_coffee_proc_macro::jimport;
     pub use instant_coffee_proc_macro::jmodule_package;
     pub use instant_coffee_proc_macro::jmodule_methods;
 }
@@ -29,6 +41,15 @@ pub mod interop;
 
 pub mod codegen;
 
+#[cfg(feature = "mock-env")]
+pub mod mock;
+
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+
+#[cfg(feature = "bench")]
+pub mod bench;
+
 /// Trait describing a mapping between a JNI array type, and a [`JavaType`] 'T'
 ///
 /// Implementations for boolean/byte/short/int/long/float/double/char and their respective rust types are provided, as well as a blanket implementation for all object arrays
@@ -45,24 +66,39 @@ pub trait JniArray<'local, T: JavaType>: From<JObject<'local>> + AsRef<JObject<'
     fn into_jni(input: Box<[T]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>>;
 }
 
+/// Marker trait for JNI reference types, which may be null
+///
+/// [`Option<T>`] maps `None` to null for types with a JNI reference type. Primitives are instead boxed, such that `Option<i32>` maps to a nullable `java.lang.Integer`
+pub trait JniObject<'local>: From<JObject<'local>> + AsRef<JObject<'local>> {}
+
+impl<'local> JniObject<'local> for JObject<'local> {}
+impl<'local> JniObject<'local> for JString<'local> {}
+impl<'local> JniObject<'local> for JClass<'local> {}
+impl<'local> JniObject<'local> for JThrowable<'local> {}
+impl<'local> JniObject<'local> for JByteBuffer<'local> {}
+impl<'local> JniObject<'local> for JObjectArray<'local> {}
+impl<'local, T: TypeArray> JniObject<'local> for JPrimitiveArray<'local, T> {}
+
 impl<'local> JniArray<'local, bool> for JBooleanArray<'local> {
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[bool]>, Option<Exception>> {
-        Ok(
-            unsafe { env.get_array_elements(&jni_value, ReleaseMode::NoCopyBack) }
-                .map_err(map_jni_error)?
-                .iter()
-                .map(|jboolean| *jboolean != 0)
-                .collect::<Box<[bool]>>()
-        )
+        let get_region = unsafe { (**env.get_raw()).GetBooleanArrayRegion };
+        let mut bytes = unsafe { read_array_region::<jboolean, u8>(&jni_value, "boolean[]", env, get_region) }?;
+
+        // Normalize to 0 or 1, after which the bytes are valid bools
+        bytes.iter_mut().for_each(|byte| *byte = (*byte != 0) as u8);
+        Ok(unsafe { Box::from_raw(Box::into_raw(bytes) as *mut [bool]) })
     }
 
     fn into_jni(input: Box<[bool]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_boolean_array(input.len() as jsize)
+        let array = env.new_boolean_array(jni_util::array_length(input.len(), <bool as JavaType>::QUALIFIED_NAME())?)
             .map_err(map_jni_error)?;
 
-        let jslice = input.iter().map(|bool| *bool as jboolean).collect::<Vec<jboolean>>();
+        // if this fails, jboolean is no longer a single byte, and the following pointer cast is unsafe
+        // rust bools are always 0 or 1, which are exactly JNI_FALSE and JNI_TRUE
+        assert_eq!(TypeId::of::<u8>(), TypeId::of::<jboolean>());
 
-        env.set_boolean_array_region(&array, 0, &*jslice).map_err(map_jni_error)?;
+        let slice: &[jboolean] = unsafe { std::slice::from_raw_parts(input.as_ptr() as *const jboolean, input.len()) };
+        env.set_boolean_array_region(&array, 0, slice).map_err(map_jni_error)?;
 
         Ok(array)
     }
@@ -70,17 +106,12 @@ impl<'local> JniArray<'local, bool> for JBooleanArray<'local> {
 
 impl<'local> JniArray<'local, u8> for JByteArray<'local> {
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[u8]>, Option<Exception>> {
-        Ok(
-            unsafe { env.get_array_elements(&jni_value, ReleaseMode::NoCopyBack) }
-                .map_err(map_jni_error)?
-                .iter()
-                .map(|byte| *byte as u8)
-                .collect::<Box<[u8]>>()
-        )
+        let get_region = unsafe { (**env.get_raw()).GetByteArrayRegion };
+        unsafe { read_array_region::<jbyte, u8>(&jni_value, "byte[]", env, get_region) }
     }
 
     fn into_jni(input: Box<[u8]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_byte_array(input.len() as jsize)
+        let array = env.new_byte_array(jni_util::array_length(input.len(), <u8 as JavaType>::QUALIFIED_NAME())?)
             .map_err(map_jni_error)?;
 
         // if this fails, jbyte is no longer identical to i8, and the following pointer cast is unsafe
@@ -95,17 +126,12 @@ impl<'local> JniArray<'local, u8> for JByteArray<'local> {
 
 impl<'local> JniArray<'local, i8> for JByteArray<'local> {
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[i8]>, Option<Exception>> {
-        Ok(
-            unsafe { env.get_array_elements(&jni_value, ReleaseMode::NoCopyBack) }
-                .map_err(map_jni_error)?
-                .iter()
-                .map(|byte| *byte as i8)
-                .collect::<Box<[i8]>>()
-        )
+        let get_region = unsafe { (**env.get_raw()).GetByteArrayRegion };
+        unsafe { read_array_region::<jbyte, i8>(&jni_value, "byte[]", env, get_region) }
     }
 
     fn into_jni(input: Box<[i8]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_byte_array(input.len() as jsize)
+        let array = env.new_byte_array(jni_util::array_length(input.len(), <i8 as JavaType>::QUALIFIED_NAME())?)
             .map_err(map_jni_error)?;
 
         // if this fails, jbyte is no longer identical to i8, and the following pointer cast is unsafe
@@ -120,17 +146,12 @@ impl<'local> JniArray<'local, i8> for JByteArray<'local> {
 
 impl<'local> JniArray<'local, u16> for JShortArray<'local> {
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[u16]>, Option<Exception>> {
-        Ok(
-            unsafe { env.get_array_elements(&jni_value, ReleaseMode::NoCopyBack) }
-                .map_err(map_jni_error)?
-                .iter()
-                .map(|short| *short as u16)
-                .collect::<Box<[u16]>>()
-        )
+        let get_region = unsafe { (**env.get_raw()).GetShortArrayRegion };
+        unsafe { read_array_region::<jshort, u16>(&jni_value, "short[]", env, get_region) }
     }
 
     fn into_jni(input: Box<[u16]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_short_array(input.len() as jsize)
+        let array = env.new_short_array(jni_util::array_length(input.len(), <u16 as JavaType>::QUALIFIED_NAME())?)
             .map_err(map_jni_error)?;
 
         // if this fails, jshort is no longer identical to i16, and the following pointer cast is unsafe
@@ -145,17 +166,12 @@ impl<'local> JniArray<'local, u16> for JShortArray<'local> {
 
 impl<'local> JniArray<'local, i16> for JShortArray<'local> {
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[i16]>, Option<Exception>> {
-        Ok(
-            unsafe { env.get_array_elements(&jni_value, ReleaseMode::NoCopyBack) }
-                .map_err(map_jni_error)?
-                .iter()
-                .map(|short| *short as i16)
-                .collect::<Box<[i16]>>()
-        )
+        let get_region = unsafe { (**env.get_raw()).GetShortArrayRegion };
+        unsafe { read_array_region::<jshort, i16>(&jni_value, "short[]", env, get_region) }
     }
 
     fn into_jni(input: Box<[i16]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_short_array(input.len() as jsize)
+        let array = env.new_short_array(jni_util::array_length(input.len(), <i16 as JavaType>::QUALIFIED_NAME())?)
             .map_err(map_jni_error)?;
 
         // if this fails, jshort is no longer identical to i16, and the following pointer cast is unsafe
@@ -170,17 +186,12 @@ impl<'local> JniArray<'local, i16> for JShortArray<'local> {
 
 impl<'local> JniArray<'local, u32> for JIntArray<'local> {
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[u32]>, Option<Exception>> {
-        Ok(
-            unsafe { env.get_array_elements(&jni_value, ReleaseMode::NoCopyBack) }
-                .map_err(map_jni_error)?
-                .iter()
-                .map(|int| *int as u32)
-                .collect::<Box<[u32]>>()
-        )
+        let get_region = unsafe { (**env.get_raw()).GetIntArrayRegion };
+        unsafe { read_array_region::<jint, u32>(&jni_value, "int[]", env, get_region) }
     }
 
     fn into_jni(input: Box<[u32]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_int_array(input.len() as jsize)
+        let array = env.new_int_array(jni_util::array_length(input.len(), <u32 as JavaType>::QUALIFIED_NAME())?)
             .map_err(map_jni_error)?;
 
         // if this fails, jint is no longer identical to i32, and the following pointer cast is unsafe
@@ -195,17 +206,12 @@ impl<'local> JniArray<'local, u32> for JIntArray<'local> {
 
 impl<'local> JniArray<'local, i32> for JIntArray<'local> {
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[i32]>, Option<Exception>> {
-        Ok(
-            unsafe { env.get_array_elements(&jni_value, ReleaseMode::NoCopyBack) }
-                .map_err(map_jni_error)?
-                .iter()
-                .map(|int| *int as i32)
-                .collect::<Box<[i32]>>()
-        )
+        let get_region = unsafe { (**env.get_raw()).GetIntArrayRegion };
+        unsafe { read_array_region::<jint, i32>(&jni_value, "int[]", env, get_region) }
     }
 
     fn into_jni(input: Box<[i32]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_int_array(input.len() as jsize)
+        let array = env.new_int_array(jni_util::array_length(input.len(), <i32 as JavaType>::QUALIFIED_NAME())?)
             .map_err(map_jni_error)?;
 
         // if this fails, jint is no longer identical to i32, and the following pointer cast is unsafe
@@ -220,17 +226,12 @@ impl<'local> JniArray<'local, i32> for JIntArray<'local> {
 
 impl<'local> JniArray<'local, u64> for JLongArray<'local> {
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[u64]>, Option<Exception>> {
-        Ok(
-            unsafe { env.get_array_elements(&jni_value, ReleaseMode::NoCopyBack) }
-                .map_err(map_jni_error)?
-                .iter()
-                .map(|long| *long as u64)
-                .collect::<Box<[u64]>>()
-        )
+        let get_region = unsafe { (**env.get_raw()).GetLongArrayRegion };
+        unsafe { read_array_region::<jlong, u64>(&jni_value, "long[]", env, get_region) }
     }
 
     fn into_jni(input: Box<[u64]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_long_array(input.len() as jsize)
+        let array = env.new_long_array(jni_util::array_length(input.len(), <u64 as JavaType>::QUALIFIED_NAME())?)
             .map_err(map_jni_error)?;
 
         // if this fails, jlong is no longer identical to i64, and the following pointer cast is unsafe
@@ -245,17 +246,12 @@ impl<'local> JniArray<'local, u64> for JLongArray<'local> {
 
 impl<'local> JniArray<'local, i64> for JLongArray<'local> {
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[i64]>, Option<Exception>> {
-        Ok(
-            unsafe { env.get_array_elements(&jni_value, ReleaseMode::NoCopyBack) }
-                .map_err(map_jni_error)?
-                .iter()
-                .map(|long| *long as i64)
-                .collect::<Box<[i64]>>()
-        )
+        let get_region = unsafe { (**env.get_raw()).GetLongArrayRegion };
+        unsafe { read_array_region::<jlong, i64>(&jni_value, "long[]", env, get_region) }
     }
 
     fn into_jni(input: Box<[i64]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_long_array(input.len() as jsize)
+        let array = env.new_long_array(jni_util::array_length(input.len(), <i64 as JavaType>::QUALIFIED_NAME())?)
             .map_err(map_jni_error)?;
 
         // if this fails, jlong is no longer identical to i64, and the following pointer cast is unsafe
@@ -270,17 +266,12 @@ impl<'local> JniArray<'local, i64> for JLongArray<'local> {
 
 impl<'local> JniArray<'local, f32> for JFloatArray<'local> {
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[f32]>, Option<Exception>> {
-        Ok(
-            unsafe { env.get_array_elements(&jni_value, ReleaseMode::NoCopyBack) }
-                .map_err(map_jni_error)?
-                .iter()
-                .map(|float| *float as f32)
-                .collect::<Box<[f32]>>()
-        )
+        let get_region = unsafe { (**env.get_raw()).GetFloatArrayRegion };
+        unsafe { read_array_region::<jfloat, f32>(&jni_value, "float[]", env, get_region) }
     }
 
     fn into_jni(input: Box<[f32]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_float_array(input.len() as jsize)
+        let array = env.new_float_array(jni_util::array_length(input.len(), <f32 as JavaType>::QUALIFIED_NAME())?)
             .map_err(map_jni_error)?;
 
         // if this fails, jfloat is no longer identical to f32, and the following pointer cast is unsafe
@@ -295,17 +286,12 @@ impl<'local> JniArray<'local, f32> for JFloatArray<'local> {
 
 impl<'local> JniArray<'local, f64> for JDoubleArray<'local> {
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[f64]>, Option<Exception>> {
-        Ok(
-            unsafe { env.get_array_elements(&jni_value, ReleaseMode::NoCopyBack) }
-                .map_err(map_jni_error)?
-                .iter()
-                .map(|double| *double as f64)
-                .collect::<Box<[f64]>>()
-        )
+        let get_region = unsafe { (**env.get_raw()).GetDoubleArrayRegion };
+        unsafe { read_array_region::<jdouble, f64>(&jni_value, "double[]", env, get_region) }
     }
 
     fn into_jni(input: Box<[f64]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_double_array(input.len() as jsize)
+        let array = env.new_double_array(jni_util::array_length(input.len(), <f64 as JavaType>::QUALIFIED_NAME())?)
             .map_err(map_jni_error)?;
 
         // if this fails, jdouble is no longer identical to f64, and the following pointer cast is unsafe
@@ -320,21 +306,39 @@ impl<'local> JniArray<'local, f64> for JDoubleArray<'local> {
 
 impl<'local> JniArray<'local, JavaChar> for JCharArray<'local> {
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[JavaChar]>, Option<Exception>> {
-        Ok(
-            unsafe { env.get_array_elements(&jni_value, ReleaseMode::NoCopyBack) }
-                .map_err(map_jni_error)?
-                .iter()
-                .map(|char| JavaChar(*char))
-                .collect::<Box<[JavaChar]>>()
-        )
+        let get_region = unsafe { (**env.get_raw()).GetCharArrayRegion };
+        unsafe { read_array_region::<jchar, JavaChar>(&jni_value, "char[]", env, get_region) }
     }
 
     fn into_jni(input: Box<[JavaChar]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_char_array(input.len() as jsize)
+        let array = env.new_char_array(jni_util::array_length(input.len(), <JavaChar as JavaType>::QUALIFIED_NAME())?)
             .map_err(map_jni_error)?;
 
-        let jslice = input.iter().map(|char| char.0 as jchar).collect::<Vec<jchar>>();
-        env.set_char_array_region(&array, 0, &*jslice).map_err(map_jni_error)?;
+        // if this fails, jchar is no longer identical to u16, and the following pointer cast is unsafe; JavaChar is a transparent u16
+        assert_eq!(TypeId::of::<u16>(), TypeId::of::<jchar>());
+
+        let slice: &[jchar] = unsafe { std::slice::from_raw_parts(input.as_ptr() as *const jchar, input.len()) };
+        env.set_char_array_region(&array, 0, slice).map_err(map_jni_error)?;
+
+        Ok(array)
+    }
+}
+
+impl<'local, T> JniArray<'local, Handle<T>> for JLongArray<'local> {
+    fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[Handle<T>]>, Option<Exception>> {
+        let get_region = unsafe { (**env.get_raw()).GetLongArrayRegion };
+        unsafe { read_array_region::<jlong, Handle<T>>(&jni_value, "long[]", env, get_region) }
+    }
+
+    fn into_jni(input: Box<[Handle<T>]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        let array = env.new_long_array(jni_util::array_length(input.len(), <i64 as JavaType>::QUALIFIED_NAME())?)
+            .map_err(map_jni_error)?;
+
+        // if this fails, jlong is no longer identical to i64, and the following pointer cast is unsafe; Handle is a transparent i64
+        assert_eq!(TypeId::of::<i64>(), TypeId::of::<jlong>());
+
+        let slice: &[jlong] = unsafe { std::slice::from_raw_parts(input.as_ptr() as *const jlong, input.len()) };
+        env.set_long_array_region(&array, 0, slice).map_err(map_jni_error)?;
 
         Ok(array)
     }
@@ -344,28 +348,67 @@ impl<'local, T: JavaType<JniType<'local>: From<JObject<'local>> + AsRef<JObject<
     fn from_jni(jni_value: Self, env: &mut JNIEnv<'local>) -> Result<Box<[T]>, Option<Exception>> {
         let mut buffer = Vec::new();
         let array_size = env.get_array_length(&jni_value).map_err(map_jni_error)?;
+        jni_util::check_array_length(array_size as usize, LazyName(<Box<[T]> as JavaType>::QUALIFIED_NAME))?;
 
+        // Each element's reference is released before the next is read, so no capacity is reserved per element
         for i in 0..array_size {
             let value = env.get_object_array_element(&jni_value, i).map_err(map_jni_error)?;
+            let raw_value = value.as_raw();
+            let converted = T::from_jni(value.into(), env);
+            // The conversion took the reference by value without releasing it; Converted rust values cannot hold local references
+            unsafe { env.delete_local_ref(JObject::from_raw(raw_value)) }.map_err(map_jni_error)?;
 
-            buffer.push(T::from_jni(value.into(), env)?);
+            buffer.push(converted?);
         }
 
         Ok(buffer.into_boxed_slice())
     }
 
     fn into_jni(input: Box<[T]>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
-        let array = env.new_object_array(input.len() as jsize, T::JVM_PARAM_SIGNATURE(), JObject::null()).map_err(map_jni_error)?;
+        let length = jni_util::array_length(input.len(), LazyName(T::QUALIFIED_NAME))?;
+        let element_class = &jni_util::signature_class(T::JVM_PARAM_SIGNATURE(), env)?;
+        let array = env.new_object_array(length, element_class, JObject::null()).map_err(map_jni_error)?;
 
+        // Only the array and a single element are referenced at once, within the local references guaranteed by JNI
         for (idx, element) in input.into_vec().into_iter().enumerate() {
             let jelement = element.into_jni(env)?;
             env.set_object_array_element(&array, idx as jsize, jelement.as_ref()).map_err(map_jni_error)?;
+            unsafe { env.delete_local_ref(JObject::from_raw(jelement.as_ref().as_raw())) }.map_err(map_jni_error)?;
         }
 
         Ok(array)
     }
 }
 
+/// Read an entire primitive array with a single `Get<Primitive>ArrayRegion` call, into an uninitialized buffer
+///
+/// Arrays longer than the [`jni_util::Limits::max_array_length`] in effect throw IllegalArgumentException, naming the `conversion`
+///
+/// # Safety
+///
+/// `T` must have the same size and alignment as `J`, and every value of `J` must be a valid `T`. `get_region` must be the region function for the type of `array`
+unsafe fn read_array_region<'local, J, T>(
+    array: &impl AsJArrayRaw<'local>,
+    conversion: &str,
+    env: &mut JNIEnv<'local>,
+    get_region: Option<unsafe extern "system" fn(*mut jni::sys::JNIEnv, jni::sys::jarray, jsize, jsize, *mut J)>,
+) -> Result<Box<[T]>, Option<Exception>> {
+    assert_eq!((size_of::<T>(), align_of::<T>()), (size_of::<J>(), align_of::<J>()));
+
+    let get_region = get_region.ok_or_else(|| map_jni_error(jni::errors::Error::JNIEnvMethodNotFound("Get<Primitive>ArrayRegion")))?;
+    let length = env.get_array_length(array)
+        .map_err(map_jni_error)?;
+    jni_util::check_array_length(length as usize, conversion)?;
+
+    let mut buffer = Box::<[T]>::new_uninit_slice(length as usize);
+    get_region(env.get_raw(), array.as_jarray_raw(), 0, length, buffer.as_mut_ptr() as *mut J);
+    if env.exception_check().map_err(map_jni_error)? {
+        return Err(None);
+    }
+
+    Ok(buffer.assume_init())
+}
+
 /// Main trait for types with a Java equivalent
 pub trait JavaType: Sized {
     /// Jni equivalent to this type; Used as type in FFI functions
@@ -391,6 +434,19 @@ pub trait JavaType: Sized {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>>;
 }
 
+/// Types that can be borrowed from their Java representation; Used for `&self` receivers and `&T` parameters of exported functions
+///
+/// Derived [`JavaType`]s implement this. Value classes borrow a converted copy, handle-backed classes borrow the rust value owned by the Java object
+///
+/// Java arrays borrow a converted copy as well; `&[T]` parameters are borrowed as `Box<[T]>`
+pub trait JavaBorrow: JavaType {
+    /// Smart pointer to the borrowed value
+    type Guard: Deref<Target=Self>;
+
+    /// Borrow from JNI type
+    fn borrow_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self::Guard, Option<Exception>>;
+}
+
 /// Java boolean = rust bool
 impl JavaType for bool {
     type JniType<'local> = jboolean;
@@ -413,7 +469,7 @@ impl JavaType for bool {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Bool(boolean) => Ok(boolean),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -440,7 +496,7 @@ impl JavaType for i8 {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Byte(byte) => Ok(byte),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -469,7 +525,7 @@ impl JavaType for u8 {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Byte(byte) => Ok(byte),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -496,7 +552,7 @@ impl JavaType for i16 {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Short(short) => Ok(short),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -523,7 +579,7 @@ impl JavaType for u16 {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Short(short) => Ok(short),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -550,7 +606,7 @@ impl JavaType for i32 {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Int(int) => Ok(int),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -577,7 +633,7 @@ impl JavaType for u32 {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Int(int) => Ok(int),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -604,7 +660,7 @@ impl JavaType for i64 {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Long(long) => Ok(long),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -631,7 +687,7 @@ impl JavaType for u64 {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Long(long) => Ok(long),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -658,7 +714,7 @@ impl JavaType for f32 {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Float(float) => Ok(float),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -685,7 +741,7 @@ impl JavaType for f64 {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Double(double) => Ok(double),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -712,7 +768,34 @@ impl JavaType for JavaChar {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Char(char) => Ok(char),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Java long = rust [`Handle<T>`], see [`interop::HandleMap`]
+impl<T> JavaType for Handle<T> {
+    type JniType<'local> = jlong;
+    type ArrayType<'local> = JLongArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "long" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "J" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { 0 }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, _env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        Ok(Handle::from_raw(jni_value))
+    }
+
+    fn into_jni<'local>(self, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        Ok(self.into_raw())
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Long(long) => Ok(long),
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -728,13 +811,58 @@ impl JavaType for String {
 
     fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JString::from(JObject::null()) }
 
+    #[cfg(not(feature = "unchecked-strings"))]
     fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
         if jni_value.is_null() {
-            Err(Some(Exception { class: "java/lang/NullPointerException".to_string(), msg: format!("expected {}", <Self as JavaType>::QUALIFIED_NAME()) }))
-        } else {
-            env.get_string(&jni_value)
-                .map(JavaStr::into)
-                .map_err(map_jni_error)
+            return Err(Some(Exception { class: jni_util::NULL_POINTER_EXCEPTION.to_string(), msg: format!("expected {}", <Self as JavaType>::QUALIFIED_NAME()) }));
+        }
+
+        // JNIEnv::get_string checks the class with the arguments of IsAssignableFrom swapped, accepting any object
+        if !env.is_instance_of(&jni_value, "java/lang/String").map_err(map_jni_error)? {
+            let class_name = jni_util::obj_classname(&jni_value, env)?;
+            return Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", class_name, <Self as JavaType>::QUALIFIED_NAME()) }));
+        }
+
+        if jni_util::string_bytes_limited() {
+            let raw_env = env.get_raw();
+            let get_utf_length = unsafe { (**raw_env).GetStringUTFLength }
+                .ok_or_else(|| map_jni_error(jni::errors::Error::JNIEnvMethodNotFound("GetStringUTFLength")))?;
+            jni_util::check_string_bytes(unsafe { get_utf_length(raw_env, jni_value.as_raw()) }.max(0) as usize)?;
+        }
+
+        unsafe { env.get_string_unchecked(&jni_value) }
+            .map(JavaStr::into)
+            .map_err(map_jni_error)
+    }
+
+    /// Fast path; Reads the UTF-16 contents with GetStringCritical, without the instance-of check of the default conversion
+    ///
+    /// No JNI calls are made while the string is held. jni_value must be a java.lang.String, which is guaranteed for parameters and fields of generated classes
+    #[cfg(feature = "unchecked-strings")]
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        if jni_value.is_null() {
+            return Err(Some(Exception { class: jni_util::NULL_POINTER_EXCEPTION.to_string(), msg: format!("expected {}", <Self as JavaType>::QUALIFIED_NAME()) }));
+        }
+
+        let raw_env = env.get_raw();
+        unsafe {
+            let interface = &**raw_env;
+            let (Some(get_length), Some(get_critical), Some(release_critical)) = (interface.GetStringLength, interface.GetStringCritical, interface.ReleaseStringCritical) else {
+                return Err(map_jni_error(jni::errors::Error::JNIEnvMethodNotFound("GetStringCritical")));
+            };
+
+            let length = get_length(raw_env, jni_value.as_raw());
+            // Every UTF-16 unit takes at least one byte; The exact length is checked once decoded
+            jni_util::check_string_bytes(length.max(0) as usize)?;
+            let chars = get_critical(raw_env, jni_value.as_raw(), std::ptr::null_mut());
+            if chars.is_null() {
+                return Err(None);   // OutOfMemoryError thrown
+            }
+
+            let string = String::from_utf16_lossy(std::slice::from_raw_parts(chars, length as usize));
+            release_critical(raw_env, jni_value.as_raw(), chars);
+            jni_util::check_string_bytes(string.len())?;
+            Ok(string)
         }
     }
 
@@ -746,7 +874,48 @@ impl JavaType for String {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Object(obj) => Ok(JString::from(obj)),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Java byte[] = rust [`CString`]
+///
+/// Bytes are passed through unchanged, without the nul terminator. Arrays containing a zero byte are rejected with an IllegalArgumentException.
+/// For a Java String mapping, see [`interop::AsciiCString`]
+impl JavaType for CString {
+    type JniType<'local> = JByteArray<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "byte[]" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "[B" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JByteArray::from(JObject::null()) }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        if jni_value.is_null() {
+            return Err(Some(Exception { class: jni_util::NULL_POINTER_EXCEPTION.to_string(), msg: format!("expected {}", <Self as JavaType>::QUALIFIED_NAME()) }));
+        }
+
+        jni_util::check_array_length(env.get_array_length(&jni_value).map_err(map_jni_error)? as usize, "byte[]")?;
+        let bytes = env.convert_byte_array(jni_value)
+            .map_err(map_jni_error)?;
+
+        CString::new(bytes)
+            .map_err(|error| Some(Exception { class: jni_util::ILLEGAL_ARGUMENT_EXCEPTION.to_string(), msg: format!("C string contains a nul byte at index {}", error.nul_position()) }))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        jni_util::array_length(self.as_bytes().len(), "byte")?;
+        env.byte_array_from_slice(self.as_bytes())
+            .map_err(map_jni_error)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(JByteArray::from(obj)),
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
@@ -759,15 +928,11 @@ impl<T: JavaType> JavaType for Box<[T]> {
     type ArrayType<'local> = JObjectArray<'local>;
 
     fn QUALIFIED_NAME() -> &'static str {
-        static NAME: OnceLock<&'static str> = OnceLock::new();
-
-        NAME.get_or_init(|| format!("{}[]", T::QUALIFIED_NAME()).leak())
+        jni_util::generic_type_name("", T::QUALIFIED_NAME(), "[]")
     }
 
     fn JVM_PARAM_SIGNATURE() -> &'static str {
-        static SIGNATURE: OnceLock<&'static str> = OnceLock::new();
-
-        SIGNATURE.get_or_init(|| format!("[{}", T::JVM_PARAM_SIGNATURE()).leak())
+        jni_util::generic_type_name("[", T::JVM_PARAM_SIGNATURE(), "")
     }
 
     fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> {
@@ -775,6 +940,7 @@ impl<T: JavaType> JavaType for Box<[T]> {
     }
 
     fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        jni_util::non_null(jni_value.as_ref(), LazyName(<Self as JavaType>::QUALIFIED_NAME))?;
         Self::JniType::from_jni(jni_value, env)
     }
 
@@ -785,11 +951,423 @@ impl<T: JavaType> JavaType for Box<[T]> {
     fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
         match jvalue {
             JValueOwned::Object(obj) => Ok(Self::JniType::from(obj)),
-            _ => Err(Some(Exception { class: "java/lang/ClassCastException".to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
         }
     }
 }
 
+/// `&[T]` parameters of exported functions are borrowed through this impl
+impl<T: JavaType> JavaBorrow for Box<[T]> {
+    type Guard = Box<Self>;
+
+    fn borrow_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self::Guard, Option<Exception>> {
+        <Self as JavaType>::from_jni(jni_value, env).map(Box::new)
+    }
+}
+
+/// Java array = rust [`Vec<T>`]; Converted through [`Box<[T]>`]
+///
+/// e.g. int[] = `Vec<i32>`, String[] = `Vec<String>`
+impl<T: JavaType> JavaType for Vec<T> {
+    type JniType<'local> = T::ArrayType<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { <Box<[T]> as JavaType>::QUALIFIED_NAME() }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { <Box<[T]> as JavaType>::JVM_PARAM_SIGNATURE() }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> {
+        Self::JniType::EXCEPTION_NULL()
+    }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        <Box<[T]> as JavaType>::from_jni(jni_value, env).map(Vec::from)
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        Self::JniType::into_jni(self.into_boxed_slice(), env)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        <Box<[T]> as JavaType>::from_jvalue(jvalue, env)
+    }
+}
+
+impl<T: JavaType> JavaBorrow for Vec<T> {
+    type Guard = Box<Self>;
+
+    fn borrow_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self::Guard, Option<Exception>> {
+        <Self as JavaType>::from_jni(jni_value, env).map(Box::new)
+    }
+}
+
+/// Nullable Java object = rust [`Option<T>`]; `null` is `None`
+///
+/// Object types are optional as themselves, optional primitives are boxed; `Option<i32>` maps to a nullable `java.lang.Integer`. Together with [`Box<T>`], allows self-referential types such as `next: Option<Box<Node>>`
+impl<T: JavaType> JavaType for Option<T> where for<'local> T::JniType<'local>: JniObject<'local> {
+    type JniType<'local> = T::JniType<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { T::QUALIFIED_NAME() }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { T::JVM_PARAM_SIGNATURE() }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { T::JniType::from(JObject::null()) }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        if jni_value.as_ref().is_null() {
+            Ok(None)
+        } else {
+            T::from_jni(jni_value, env).map(Some)
+        }
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match self {
+            Some(value) => value.into_jni(env),
+            None => Ok(T::JniType::from(JObject::null())),
+        }
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        T::from_jvalue(jvalue, env)
+    }
+}
+
+/// Boxed primitive class, with method IDs of its `valueOf` and unboxing methods resolved on first use
+struct BoxedClass {
+    class: jni_util::CachedClass,
+    value_of_signature: &'static str,
+    unbox_name: &'static str,
+    unbox_signature: &'static str,
+    unbox_type: Primitive,
+    /// Method IDs of the boxed class in each Java VM
+    methods: jni_util::PerVm<(JStaticMethodID, JMethodID)>,
+}
+
+impl BoxedClass {
+    const fn new(jvm_class_name: &'static str, value_of_signature: &'static str, unbox_name: &'static str, unbox_signature: &'static str, unbox_type: Primitive) -> Self {
+        BoxedClass { class: jni_util::CachedClass::new(jvm_class_name), value_of_signature, unbox_name, unbox_signature, unbox_type, methods: jni_util::PerVm::new() }
+    }
+
+    fn box_value<'local>(&'static self, value: JValue, env: &mut JNIEnv<'local>) -> Result<JObject<'local>, Option<Exception>> {
+        let (value_of, _) = self.methods(env)?;
+        let class = &self.class.get(env)?;
+        unsafe { env.call_static_method_unchecked(<&JClass>::from(class.as_obj()), value_of, ReturnType::Object, &[value.as_jni()]) }
+            .map_err(map_jni_error)?
+            .l()
+            .map_err(map_jni_error)
+    }
+
+    fn unbox_value<'local>(&'static self, object: &JObject<'local>, qualified_name: &str, env: &mut JNIEnv<'local>) -> Result<JValueOwned<'local>, Option<Exception>> {
+        let (_, unbox) = self.methods(env)?;
+        // Boxes reach rust through erased generics, and are checked before the unchecked call
+        let class = &self.class.get(env)?;
+        if !env.is_instance_of(object, class).map_err(map_jni_error)? {
+            let class_name = jni_util::obj_classname(object, env)?;
+            return Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", class_name, qualified_name) }));
+        }
+
+        unsafe { env.call_method_unchecked(object, unbox, ReturnType::Primitive(self.unbox_type), &[]) }.map_err(map_jni_error)
+    }
+
+    fn methods<'local>(&'static self, env: &mut JNIEnv<'local>) -> Result<(JStaticMethodID, JMethodID), Option<Exception>> {
+        let vm = jni_util::vm_key(env)?;
+        if let Some(methods) = self.methods.get(vm) {
+            return Ok(methods);
+        }
+
+        let class = &self.class.get(env)?;
+        let class = <&JClass>::from(class.as_obj());
+        let value_of = env.get_static_method_id(class, "valueOf", self.value_of_signature).map_err(map_jni_error)?;
+        let unbox = env.get_method_id(class, self.unbox_name, self.unbox_signature).map_err(map_jni_error)?;
+        Ok(self.methods.get_or_insert(vm, (value_of, unbox), &self.methods))
+    }
+}
+
+/// Optional primitives map to their nullable boxed class, with `None` as null; For example `Option<i32>` = Java `java.lang.Integer`
+macro_rules! boxed_option {
+    ($($rust_type:ty => $class:literal, $qualified_name:literal, $value_of_signature:literal, $unbox_name:literal, $unbox_signature:literal, $primitive:ident, $jvalue:ident;)*) => {$(
+        const _: () = {
+            static CLASS: BoxedClass = BoxedClass::new($class, $value_of_signature, $unbox_name, $unbox_signature, Primitive::$primitive);
+
+            impl JavaType for Option<$rust_type> {
+                type JniType<'local> = JObject<'local>;
+                type ArrayType<'local> = JObjectArray<'local>;
+
+                fn QUALIFIED_NAME() -> &'static str { $qualified_name }
+
+                fn JVM_PARAM_SIGNATURE() -> &'static str { concat!("L", $class, ";") }
+
+                fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+                fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+                    if jni_value.is_null() {
+                        return Ok(None);
+                    }
+
+                    let unboxed = CLASS.unbox_value(&jni_value, $qualified_name, env)?;
+                    let unboxed = <$rust_type as JavaType>::from_jvalue(unboxed, env)?;
+                    <$rust_type as JavaType>::from_jni(unboxed, env).map(Some)
+                }
+
+                fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+                    match self {
+                        Some(value) => {
+                            let value = <$rust_type as JavaType>::into_jni(value, env)?;
+                            CLASS.box_value(JValue::$jvalue(value), env)
+                        }
+                        None => Ok(JObject::null()),
+                    }
+                }
+
+                fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+                    match jvalue {
+                        JValueOwned::Object(obj) => Ok(obj),
+                        _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), $qualified_name) }))
+                    }
+                }
+            }
+        };
+    )*};
+}
+
+boxed_option! {
+    bool => "java/lang/Boolean", "java.lang.Boolean", "(Z)Ljava/lang/Boolean;", "booleanValue", "()Z", Boolean, Bool;
+    i8 => "java/lang/Byte", "java.lang.Byte", "(B)Ljava/lang/Byte;", "byteValue", "()B", Byte, Byte;
+    u8 => "java/lang/Byte", "java.lang.Byte", "(B)Ljava/lang/Byte;", "byteValue", "()B", Byte, Byte;
+    i16 => "java/lang/Short", "java.lang.Short", "(S)Ljava/lang/Short;", "shortValue", "()S", Short, Short;
+    u16 => "java/lang/Short", "java.lang.Short", "(S)Ljava/lang/Short;", "shortValue", "()S", Short, Short;
+    i32 => "java/lang/Integer", "java.lang.Integer", "(I)Ljava/lang/Integer;", "intValue", "()I", Int, Int;
+    u32 => "java/lang/Integer", "java.lang.Integer", "(I)Ljava/lang/Integer;", "intValue", "()I", Int, Int;
+    i64 => "java/lang/Long", "java.lang.Long", "(J)Ljava/lang/Long;", "longValue", "()J", Long, Long;
+    u64 => "java/lang/Long", "java.lang.Long", "(J)Ljava/lang/Long;", "longValue", "()J", Long, Long;
+    f32 => "java/lang/Float", "java.lang.Float", "(F)Ljava/lang/Float;", "floatValue", "()F", Float, Float;
+    f64 => "java/lang/Double", "java.lang.Double", "(D)Ljava/lang/Double;", "doubleValue", "()D", Double, Double;
+    JavaChar => "java/lang/Character", "java.lang.Character", "(C)Ljava/lang/Character;", "charValue", "()C", Char, Char;
+}
+
+/// Boxed rust value; Converted as the value itself
+///
+/// Only object types may be boxed. Allows self-referential types such as `next: Option<Box<Node>>`
+impl<T: JavaType> JavaType for Box<T> where for<'local> T::JniType<'local>: From<JObject<'local>> + AsRef<JObject<'local>> {
+    type JniType<'local> = T::JniType<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { T::QUALIFIED_NAME() }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { T::JVM_PARAM_SIGNATURE() }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { T::EXCEPTION_NULL() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        T::from_jni(jni_value, env).map(Box::new)
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        (*self).into_jni(env)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        T::from_jvalue(jvalue, env)
+    }
+}
+
+/// Reference-counted rust value; Converted as the value itself
+///
+/// Values aliasing the same allocation are converted into one Java object inside [`interop::with_preserved_identity`], and into a copy per occurrence otherwise.
+/// Conversions from Java create a separate `Rc` per occurrence
+impl<T: JavaType + Clone + 'static> JavaType for Rc<T> where for<'local> T::JniType<'local>: From<JObject<'local>> + AsRef<JObject<'local>> {
+    type JniType<'local> = T::JniType<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { T::QUALIFIED_NAME() }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { T::JVM_PARAM_SIGNATURE() }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { T::EXCEPTION_NULL() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        T::from_jni(jni_value, env).map(Rc::new)
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let address = Rc::as_ptr(&self) as usize;
+        let keep_alive = (Rc::strong_count(&self) > 1).then(|| Box::new(Rc::clone(&self)) as Box<dyn Any>);
+        interop::shared_into_jni(address, keep_alive, env, || Rc::unwrap_or_clone(self))
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        T::from_jvalue(jvalue, env)
+    }
+}
+
+/// Atomically reference-counted rust value; Converted as the value itself
+///
+/// Values aliasing the same allocation are converted into one Java object inside [`interop::with_preserved_identity`], and into a copy per occurrence otherwise.
+/// Conversions from Java create a separate `Arc` per occurrence
+impl<T: JavaType + Clone + 'static> JavaType for Arc<T> where for<'local> T::JniType<'local>: From<JObject<'local>> + AsRef<JObject<'local>> {
+    type JniType<'local> = T::JniType<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { T::QUALIFIED_NAME() }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { T::JVM_PARAM_SIGNATURE() }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { T::EXCEPTION_NULL() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        T::from_jni(jni_value, env).map(Arc::new)
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        let address = Arc::as_ptr(&self) as usize;
+        let keep_alive = (Arc::strong_count(&self) > 1).then(|| Box::new(Arc::clone(&self)) as Box<dyn Any>);
+        interop::shared_into_jni(address, keep_alive, env, || Arc::unwrap_or_clone(self))
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        T::from_jvalue(jvalue, env)
+    }
+}
+
+/// Java `java.util.Map` = rust [`HashMap<K, V>`]
+///
+/// Keys and values must be Java objects; Primitive types cannot be used. Maps are converted into a `java.util.HashMap`
+///
+/// Maps with [`String`] keys take a dedicated path, which checks the class of each key against a cached `java.lang.String` class rather than looking it up per key
+impl<K, V, S> JavaType for HashMap<K, V, S>
+where
+    K: JavaType + Eq + Hash + 'static,
+    V: JavaType + 'static,
+    S: BuildHasher + Default + 'static,
+    for<'local> K::JniType<'local>: From<JObject<'local>> + Into<JObject<'local>>,
+    for<'local> V::JniType<'local>: From<JObject<'local>> + Into<JObject<'local>>,
+{
+    type JniType<'local> = JObject<'local>;
+    type ArrayType<'local> = JObjectArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str {
+        jni_util::generic_type_name(jni_util::generic_type_name("java.util.Map<", K::QUALIFIED_NAME(), ", "), V::QUALIFIED_NAME(), ">")
+    }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "Ljava/util/Map;" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JObject::null() }
+
+    fn from_jni<'local>(jni_value: Self::JniType<'local>, env: &mut JNIEnv<'local>) -> Result<Self, Option<Exception>> {
+        jni_util::non_null(&jni_value, LazyName(<Self as JavaType>::QUALIFIED_NAME))?;
+
+        if TypeId::of::<K>() == TypeId::of::<String>() {
+            static STRING_CLASS: jni_util::CachedClass = jni_util::CachedClass::new("java/lang/String");
+            let string_class = &STRING_CLASS.get(env)?;
+            let map = read_map::<String, V, S>(&jni_value, LazyName(<Self as JavaType>::QUALIFIED_NAME), env, |key, env| {
+                if !env.is_instance_of(&key, string_class).map_err(map_jni_error)? {
+                    let class_name = jni_util::obj_classname(&key, env)?;
+                    return Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", class_name, <String as JavaType>::QUALIFIED_NAME()) }));
+                }
+
+                // Checked as any other string, the instance check only gives the map's exception message
+                String::from_jni(JString::from(key), env)
+            })?;
+
+            // K is String, making the map Self
+            let map: Box<dyn Any> = Box::new(map);
+            return Ok(*map.downcast::<Self>().unwrap_or_else(|_| unreachable!()));
+        }
+
+        read_map::<K, V, S>(&jni_value, LazyName(<Self as JavaType>::QUALIFIED_NAME), env, |key, env| K::from_jni(key.into(), env))
+    }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        // Initial capacity at which the default load factor of 0.75 holds every entry without rehashing
+        let capacity = jint::try_from(self.len().saturating_mul(4) / 3 + 1).unwrap_or(jint::MAX);
+        let map = env.new_object("java/util/HashMap", "(I)V", &[JValue::Int(capacity)])
+            .map_err(map_jni_error)?;
+        let put = env.get_method_id("java/util/HashMap", "put", "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;")
+            .map_err(map_jni_error)?;
+
+        for (key, value) in self {
+            let key: JObject = key.into_jni(env)?.into();
+            let value: JObject = value.into_jni(env)?.into();
+            let previous = unsafe { env.call_method_unchecked(&map, put, ReturnType::Object, &[JValue::Object(&key).as_jni(), JValue::Object(&value).as_jni()]) }
+                .map_err(map_jni_error)?
+                .l()
+                .map_err(map_jni_error)?;
+
+            // Release per-entry references, so that large maps do not exhaust the local reference table
+            env.delete_local_ref(previous).map_err(map_jni_error)?;
+            env.delete_local_ref(key).map_err(map_jni_error)?;
+            env.delete_local_ref(value).map_err(map_jni_error)?;
+        }
+
+        Ok(map)
+    }
+
+    fn from_jvalue<'local>(jvalue: JValueOwned<'local>, _env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match jvalue {
+            JValueOwned::Object(obj) => Ok(obj),
+            _ => Err(Some(Exception { class: jni_util::CLASS_CAST_EXCEPTION.to_string(), msg: format!("{} cannot be cast to {}", jvalue.type_name(), <Self as JavaType>::QUALIFIED_NAME()) }))
+        }
+    }
+}
+
+/// Read every entry of a `java.util.Map` in a single pass over its entry set, into a map pre-sized to the size of the Java map
+///
+/// Method IDs are looked up once per map. References to each entry are released once it has been converted, as converted rust values cannot hold local references
+fn read_map<'local, K: Eq + Hash, V: JavaType, S: BuildHasher + Default>(
+    map: &JObject<'local>,
+    map_name: LazyName,
+    env: &mut JNIEnv<'local>,
+    mut read_key: impl FnMut(JObject<'local>, &mut JNIEnv<'local>) -> Result<K, Option<Exception>>,
+) -> Result<HashMap<K, V, S>, Option<Exception>> where for<'other> V::JniType<'other>: From<JObject<'other>> {
+    jni_util::with_local_frame(8, map_name, env, |env| {
+        let size = env.call_method(map, "size", "()I", &[])
+            .map_err(map_jni_error)?
+            .i()
+            .map_err(map_jni_error)?;
+        jni_util::check_array_length(size.max(0) as usize, map_name)?;
+        let entries = env.call_method(map, "entrySet", "()Ljava/util/Set;", &[])
+            .map_err(map_jni_error)?
+            .l()
+            .map_err(map_jni_error)?;
+        let iterator = env.call_method(&entries, "iterator", "()Ljava/util/Iterator;", &[])
+            .map_err(map_jni_error)?
+            .l()
+            .map_err(map_jni_error)?;
+
+        let has_next = env.get_method_id("java/util/Iterator", "hasNext", "()Z").map_err(map_jni_error)?;
+        let next = env.get_method_id("java/util/Iterator", "next", "()Ljava/lang/Object;").map_err(map_jni_error)?;
+        let get_key = env.get_method_id("java/util/Map$Entry", "getKey", "()Ljava/lang/Object;").map_err(map_jni_error)?;
+        let get_value = env.get_method_id("java/util/Map$Entry", "getValue", "()Ljava/lang/Object;").map_err(map_jni_error)?;
+        let call_object = |env: &mut JNIEnv<'local>, object: &JObject, method| unsafe { env.call_method_unchecked(object, method, ReturnType::Object, &[]) }
+            .map_err(map_jni_error)?
+            .l()
+            .map_err(map_jni_error);
+
+        let mut output = HashMap::with_capacity_and_hasher(size.max(0) as usize, S::default());
+        while unsafe { env.call_method_unchecked(&iterator, has_next, ReturnType::Primitive(Primitive::Boolean), &[]) }.map_err(map_jni_error)?.z().map_err(map_jni_error)? {
+            let entry = call_object(env, &iterator, next)?;
+            let key = call_object(env, &entry, get_key)?;
+            let value = call_object(env, &entry, get_value)?;
+            env.delete_local_ref(entry).map_err(map_jni_error)?;
+
+            let (raw_key, raw_value) = (key.as_raw(), value.as_raw());
+            let key = read_key(key, env);
+            let value = V::from_jni(value.into(), env);
+            // The conversions took the references by value, without releasing them
+            unsafe {
+                env.delete_local_ref(JObject::from_raw(raw_key)).map_err(map_jni_error)?;
+                env.delete_local_ref(JObject::from_raw(raw_value)).map_err(map_jni_error)?;
+            }
+
+            output.insert(key?, value?);
+        }
+
+        Ok(output)
+    })
+}
+
 /// Types that may be used in FFI function returns
 ///
 /// Superset of [`JavaType`] and `()` (void)
@@ -809,6 +1387,9 @@ pub trait JavaReturn: Sized {
     /// Convert from rust type to JNI type
     fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>>;
 
+    /// Fully qualified java name of the exception thrown in place of returning this type, declared in the `throws` clause of native methods; None for types that are always returned
+    fn THROWS() -> Option<&'static str> { None }
+
     // No from_jni or from_jvalue as these types are never used as function parameters or fields
 }
 
@@ -829,6 +1410,74 @@ impl JavaReturn for () {
     }
 }
 
+/// Java byte[] = rust [`&CStr`](CStr), for returning static C strings; Bytes are copied without the nul terminator, as with [`CString`]
+impl JavaReturn for &'static CStr {
+    type JniType<'local> = JByteArray<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { "byte[]" }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { "[B" }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { JByteArray::from(JObject::null()) }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        jni_util::array_length(self.to_bytes().len(), "byte")?;
+        env.byte_array_from_slice(self.to_bytes())
+            .map_err(map_jni_error)
+    }
+}
+
+/// Java method returning `T`, or throwing `E` = rust [`Result<T, E>`]
+///
+/// Errors are thrown as exceptions of class [`E::QUALIFIED_NAME`](JavaThrowable::QUALIFIED_NAME), which is declared in the `throws` clause of the native method
+impl<T: JavaReturn, E: JavaThrowable> JavaReturn for Result<T, E> {
+    type JniType<'local> = T::JniType<'local>;
+
+    fn QUALIFIED_NAME() -> &'static str { T::QUALIFIED_NAME() }
+
+    fn JVM_PARAM_SIGNATURE() -> &'static str { T::JVM_PARAM_SIGNATURE() }
+
+    fn EXCEPTION_NULL<'local>() -> Self::JniType<'local> { T::EXCEPTION_NULL() }
+
+    fn into_jni<'local>(self, env: &mut JNIEnv<'local>) -> Result<Self::JniType<'local>, Option<Exception>> {
+        match self {
+            Ok(value) => value.into_jni(env),
+            Err(error) => Err(Some(error.into_exception()))
+        }
+    }
+
+    fn THROWS() -> Option<&'static str> { Some(E::QUALIFIED_NAME()) }
+}
+
+/// Rust errors that are thrown as Java exceptions; Errors of [`Result`] returns of exported functions
+///
+/// ```ignore
+/// impl JavaThrowable for ParseError {
+///     fn QUALIFIED_NAME() -> &'static str { "java.text.ParseException" }
+/// }
+/// ```
+pub trait JavaThrowable: std::fmt::Display {
+    /// Fully qualified java name of the thrown exception class, such as "java.io.IOException"
+    fn QUALIFIED_NAME() -> &'static str;
+
+    /// Exception thrown for this error; Of class [`QUALIFIED_NAME`](JavaThrowable::QUALIFIED_NAME), with this error's message
+    ///
+    /// The exception class must have a `(String)` constructor; Nested exception classes must override this, as their binary name differs from their qualified name
+    fn into_exception(self) -> Exception where Self: Sized {
+        Exception { class: Self::QUALIFIED_NAME().replace('.', "/"), msg: self.to_string() }
+    }
+}
+
+/// Java `java.io.IOException` = rust [`std::io::Error`]
+impl JavaThrowable for std::io::Error {
+    fn QUALIFIED_NAME() -> &'static str { "java.io.IOException" }
+}
+
+/// Java `java.lang.Exception` = rust boxed errors; For functions propagating arbitrary errors with `?`
+impl JavaThrowable for Box<dyn std::error::Error + Send + Sync> {
+    fn QUALIFIED_NAME() -> &'static str { "java.lang.Exception" }
+}
+
 /// All JavaTypes are valid return types
 impl<T: JavaType> JavaReturn for T {
     type JniType<'local> = T::JniType<'local>;
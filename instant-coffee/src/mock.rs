@@ -0,0 +1,878 @@
+//! In-memory stand-in for the JVM, for exercising [`JavaType`](crate::JavaType) conversions in plain `cargo test`
+//!
+//! [`with_mock_env`] runs a closure with a [`JNIEnv`] whose function table is implemented by a [`ConversionEnv`], such as [`MockJvm`].
+//! Only the JNI operations used by conversions are available: Classes, fields, constructors, strings and arrays. Other operations fail with `JNIEnvMethodNotFound`;
+//! Notably method calls, which are used by C-style enums, BitSet and listeners
+//!
+//! ```ignore
+//! let mut jvm = MockJvm::new();
+//! jvm.define::<Point>();
+//! let point = with_mock_env(&mut jvm, |env| {
+//!     let object = Point { x: 1, y: 2 }.into_jni(env)?;
+//!     Point::from_jni(object, env)
+//! });
+//! assert!(matches!(point, Ok(Ok(Point { x: 1, y: 2 }))));
+//! ```
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::ptr;
+use std::sync::{Mutex, PoisonError};
+use jni::errors::Exception;
+use jni::JNIEnv;
+use jni::strings::{JNIStr, JNIString};
+use jni::sys::{self, jboolean, jbyte, jchar, jclass, jdouble, jfieldID, jfloat, jint, jlong, jmethodID, jobject, jshort, jsize, jvalue, JNI_ERR, JNI_EDETACHED, JNI_FALSE, JNI_OK, JNI_TRUE, JNIInvokeInterface_, JNINativeInterface_};
+
+use crate::codegen::{JavaClass, JClassDecl};
+
+/// Reference to an object of a [`ConversionEnv`]
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ObjectRef(pub usize);
+
+/// Value of a field, array element or constructor argument
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Value {
+    Boolean(bool),
+    Byte(i8),
+    Char(u16),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    /// Object reference, None for null
+    Object(Option<ObjectRef>),
+}
+
+impl Value {
+    /// Default value for a field or array element of the given JVM type signature, such as `Int(0)` for "I", and null for "Ljava/lang/String;"
+    pub fn default_for(signature: &str) -> Value {
+        match signature.as_bytes().first() {
+            Some(b'Z') => Value::Boolean(false),
+            Some(b'B') => Value::Byte(0),
+            Some(b'C') => Value::Char(0),
+            Some(b'S') => Value::Short(0),
+            Some(b'I') => Value::Int(0),
+            Some(b'J') => Value::Long(0),
+            Some(b'F') => Value::Float(0.0),
+            Some(b'D') => Value::Double(0.0),
+            _ => Value::Object(None),
+        }
+    }
+}
+
+/// Subset of JNI operations used by conversions, implemented by fakes of the JVM
+///
+/// Classes are named by JVM class name, such as "java/lang/String", or by array signature, such as "[I". Failed operations return the exception to throw
+pub trait ConversionEnv {
+    /// True if the class exists; Looking up other classes throws NoClassDefFoundError
+    fn class_exists(&mut self, class: &str) -> bool;
+
+    /// Class of an object
+    fn class_of(&mut self, object: ObjectRef) -> Result<String, Exception>;
+
+    /// True if class `class` is `to`, or a subclass of it
+    fn is_assignable(&mut self, class: &str, to: &str) -> bool {
+        class == to || to == "java/lang/Object"
+    }
+
+    /// Read a field of an object
+    fn get_field(&mut self, object: ObjectRef, name: &str) -> Result<Value, Exception>;
+
+    /// Create an object with a constructor taking `args`
+    fn new_object(&mut self, class: &str, args: Vec<Value>) -> Result<ObjectRef, Exception>;
+
+    /// Create a java.lang.String
+    fn new_string(&mut self, value: String) -> Result<ObjectRef, Exception>;
+
+    /// Contents of a java.lang.String
+    fn get_string(&mut self, string: ObjectRef) -> Result<String, Exception>;
+
+    /// Create an array of `length` default values, with elements of the given JVM type signature
+    fn new_array(&mut self, element_signature: &str, length: usize) -> Result<ObjectRef, Exception>;
+
+    /// Length of an array
+    fn array_length(&mut self, array: ObjectRef) -> Result<usize, Exception>;
+
+    /// Read an array element
+    fn get_element(&mut self, array: ObjectRef, index: usize) -> Result<Value, Exception>;
+
+    /// Write an array element
+    fn set_element(&mut self, array: ObjectRef, index: usize, value: Value) -> Result<(), Exception>;
+}
+
+/// Object of a [`MockJvm`]
+#[derive(Clone, PartialEq, Debug)]
+pub enum MockObject {
+    /// Instance of a class, with its fields in declaration order
+    Instance { class: String, fields: Vec<(String, Value)> },
+    /// java.lang.String
+    String(String),
+    /// Array, with elements of the given JVM type signature
+    Array { element_signature: String, elements: Vec<Value> },
+}
+
+/// Class layout of a [`MockJvm`]
+struct MockClass {
+    superclass: Option<String>,
+    fields: Vec<String>,
+}
+
+/// In-memory [`ConversionEnv`]; Objects live until the MockJvm is dropped
+///
+/// Classes must be defined before objects of them are created, with their fields in constructor parameter order; See [`MockJvm::define`]
+#[derive(Default)]
+pub struct MockJvm {
+    classes: HashMap<String, MockClass>,
+    objects: Vec<MockObject>,
+}
+
+impl MockJvm {
+    /// Create a MockJvm without any classes defined
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define a class, whose constructor takes a value for each field in order
+    ///
+    /// # Arguments
+    ///
+    /// * `class`: JVM class name, such as "com/example/Point"
+    /// * `superclass`: JVM class name of the superclass, if any other than java.lang.Object
+    /// * `fields`: Field names, in constructor parameter order
+    pub fn define_class(&mut self, class: &str, superclass: Option<&str>, fields: &[&str]) {
+        self.classes.insert(class.to_string(), MockClass { superclass: superclass.map(str::to_string), fields: fields.iter().map(|field| field.to_string()).collect() });
+    }
+
+    /// Define the class(es) generated for a type, from its declaration
+    ///
+    /// Defines the class of structs and handles, and the class and variant subclasses of tagged unions. Enum constants are not supported
+    pub fn define<T: JavaClass>(&mut self) {
+        let declaration = T::declaration();
+        let class = format!("{}/{}", declaration.package().replace('.', "/"), declaration.class_path().replace('.', "$"));
+        match &declaration {
+            JClassDecl::Class { fields, .. } => {
+                self.define_class(&class, None, &fields.iter().map(|field| field.name).collect::<Vec<_>>());
+            }
+            JClassDecl::Handle { .. } => self.define_class(&class, None, &["handle"]),
+            JClassDecl::EnumTaggedUnion { variants, .. } => {
+                self.define_class(&class, None, &[]);
+                for variant in variants {
+                    let fields = variant.fields.iter().map(|field| field.name).collect::<Vec<_>>();
+                    self.define_class(&format!("{}${}", class, variant.name), Some(&class), &fields);
+                }
+            }
+            JClassDecl::Enum { .. } => self.define_class(&class, None, &[]),
+            JClassDecl::Source { .. } => {}
+        }
+    }
+
+    /// Look up an object, for inspecting the result of a conversion
+    pub fn object(&self, object: ObjectRef) -> Option<&MockObject> {
+        self.objects.get(object.0)
+    }
+
+    fn add(&mut self, object: MockObject) -> ObjectRef {
+        self.objects.push(object);
+        ObjectRef(self.objects.len() - 1)
+    }
+
+    fn lookup(&mut self, object: ObjectRef) -> Result<&mut MockObject, Exception> {
+        self.objects.get_mut(object.0)
+            .ok_or_else(|| exception("java/lang/IllegalArgumentException", format!("invalid object reference {}", object.0)))
+    }
+
+    fn array(&mut self, array: ObjectRef) -> Result<&mut Vec<Value>, Exception> {
+        match self.lookup(array)? {
+            MockObject::Array { elements, .. } => Ok(elements),
+            _ => Err(exception("java/lang/IllegalArgumentException", "not an array".to_string()))
+        }
+    }
+}
+
+impl ConversionEnv for MockJvm {
+    fn class_exists(&mut self, class: &str) -> bool {
+        matches!(class, "java/lang/Object" | "java/lang/String") || class.starts_with('[') || self.classes.contains_key(class)
+    }
+
+    fn class_of(&mut self, object: ObjectRef) -> Result<String, Exception> {
+        Ok(match self.lookup(object)? {
+            MockObject::Instance { class, .. } => class.clone(),
+            MockObject::String(_) => "java/lang/String".to_string(),
+            MockObject::Array { element_signature, .. } => format!("[{}", element_signature),
+        })
+    }
+
+    fn is_assignable(&mut self, class: &str, to: &str) -> bool {
+        let mut class = Some(class);
+        while let Some(current) = class {
+            if current == to {
+                return true;
+            }
+            class = self.classes.get(current).and_then(|class| class.superclass.as_deref());
+        }
+        to == "java/lang/Object"
+    }
+
+    fn get_field(&mut self, object: ObjectRef, name: &str) -> Result<Value, Exception> {
+        match self.lookup(object)? {
+            MockObject::Instance { fields, .. } => fields.iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| *value)
+                .ok_or_else(|| exception("java/lang/NoSuchFieldError", name.to_string())),
+            _ => Err(exception("java/lang/NoSuchFieldError", name.to_string()))
+        }
+    }
+
+    fn new_object(&mut self, class: &str, args: Vec<Value>) -> Result<ObjectRef, Exception> {
+        let Some(layout) = self.classes.get(class) else {
+            return Err(exception("java/lang/NoClassDefFoundError", class.to_string()));
+        };
+        if layout.fields.len() != args.len() {
+            return Err(exception("java/lang/NoSuchMethodError", format!("{} has no constructor taking {} arguments", class, args.len())));
+        }
+
+        let fields = layout.fields.iter().cloned().zip(args).collect();
+        Ok(self.add(MockObject::Instance { class: class.to_string(), fields }))
+    }
+
+    fn new_string(&mut self, value: String) -> Result<ObjectRef, Exception> {
+        Ok(self.add(MockObject::String(value)))
+    }
+
+    fn get_string(&mut self, string: ObjectRef) -> Result<String, Exception> {
+        match self.lookup(string)? {
+            MockObject::String(value) => Ok(value.clone()),
+            _ => Err(exception("java/lang/ClassCastException", "not a java.lang.String".to_string()))
+        }
+    }
+
+    fn new_array(&mut self, element_signature: &str, length: usize) -> Result<ObjectRef, Exception> {
+        let elements = vec![Value::default_for(element_signature); length];
+        Ok(self.add(MockObject::Array { element_signature: element_signature.to_string(), elements }))
+    }
+
+    fn array_length(&mut self, array: ObjectRef) -> Result<usize, Exception> {
+        self.array(array).map(|elements| elements.len())
+    }
+
+    fn get_element(&mut self, array: ObjectRef, index: usize) -> Result<Value, Exception> {
+        let elements = self.array(array)?;
+        let length = elements.len();
+        elements.get(index)
+            .copied()
+            .ok_or_else(|| exception("java/lang/ArrayIndexOutOfBoundsException", format!("Index {} out of bounds for length {}", index, length)))
+    }
+
+    fn set_element(&mut self, array: ObjectRef, index: usize, value: Value) -> Result<(), Exception> {
+        let elements = self.array(array)?;
+        let length = elements.len();
+        let element = elements.get_mut(index)
+            .ok_or_else(|| exception("java/lang/ArrayIndexOutOfBoundsException", format!("Index {} out of bounds for length {}", index, length)))?;
+        *element = value;
+        Ok(())
+    }
+}
+
+/// Run `f` with a [`JNIEnv`] backed by `fake`
+///
+/// Returns the exception pending when `f` returns, if any
+pub fn with_mock_env<R>(fake: &mut dyn ConversionEnv, f: impl for<'local> FnOnce(&mut JNIEnv<'local>) -> R) -> Result<R, Exception> {
+    // SAFETY: The state only outlives the borrow of `fake` as an unused pointer; The env is not usable after `f` returns
+    let fake: *mut (dyn ConversionEnv + 'static) = unsafe { std::mem::transmute(fake as *mut dyn ConversionEnv) };
+    let mut state = MockEnvState { functions: &FUNCTIONS.0, fake, exception: None, pinned: Vec::new() };
+
+    let mut env = unsafe { JNIEnv::from_raw(&mut state as *mut MockEnvState as *mut sys::JNIEnv) }
+        .expect("env pointer is not null");
+    let result = f(&mut env);
+
+    match state.exception.take() {
+        Some(exception) => Err(exception),
+        None => Ok(result)
+    }
+}
+
+/// Per-call state of a mock env; The `JNIEnv` pointer points to this, with the function table first as JNI requires
+#[repr(C)]
+struct MockEnvState {
+    functions: *const JNINativeInterface_,
+    fake: *mut dyn ConversionEnv,
+    exception: Option<Exception>,
+    /// Strings held by `GetStringCritical`
+    pinned: Vec<Box<[jchar]>>,
+}
+
+fn exception(class: &str, msg: String) -> Exception {
+    Exception { class: class.to_string(), msg }
+}
+
+unsafe fn state<'a>(env: *mut sys::JNIEnv) -> &'a mut MockEnvState {
+    &mut *(env as *mut MockEnvState)
+}
+
+/// Run an operation of the fake, throwing its exception and returning `default` on failure
+unsafe fn call<T>(env: *mut sys::JNIEnv, default: T, f: impl FnOnce(&mut dyn ConversionEnv) -> Result<T, Exception>) -> T {
+    let state = state(env);
+    match f(&mut *state.fake) {
+        Ok(value) => value,
+        Err(exception) => {
+            state.exception = Some(exception);
+            default
+        }
+    }
+}
+
+// Handles; Objects are even, classes are odd and shared between all fakes, as classes are cached in statics
+// Neither is ever dereferenced
+
+static CLASS_NAMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static MEMBERS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+fn intern<T: PartialEq>(table: &Mutex<Vec<T>>, value: T) -> usize {
+    let mut table = table.lock().unwrap_or_else(PoisonError::into_inner);
+    match table.iter().position(|entry| *entry == value) {
+        Some(idx) => idx,
+        None => {
+            table.push(value);
+            table.len() - 1
+        }
+    }
+}
+
+fn object_handle(object: Option<ObjectRef>) -> jobject {
+    match object {
+        Some(object) => ((object.0 + 1) << 1) as jobject,
+        None => ptr::null_mut()
+    }
+}
+
+fn class_handle(class: &str) -> jclass {
+    (((intern(&CLASS_NAMES, class.to_string()) + 1) << 1) | 1) as jclass
+}
+
+fn object_ref(handle: jobject) -> Result<Option<ObjectRef>, Exception> {
+    match handle as usize {
+        0 => Ok(None),
+        handle if handle & 1 == 0 => Ok(Some(ObjectRef((handle >> 1) - 1))),
+        _ => Err(exception("java/lang/UnsupportedOperationException", "class objects are not supported by mock envs".to_string()))
+    }
+}
+
+fn non_null_ref(handle: jobject) -> Result<ObjectRef, Exception> {
+    object_ref(handle)?.ok_or_else(|| exception("java/lang/NullPointerException", String::new()))
+}
+
+fn class_name(handle: jclass) -> Result<String, Exception> {
+    match handle as usize {
+        handle if handle & 1 == 1 => Ok(CLASS_NAMES.lock().unwrap_or_else(PoisonError::into_inner)[(handle >> 1) - 1].clone()),
+        _ => Err(exception("java/lang/IllegalArgumentException", "not a class".to_string()))
+    }
+}
+
+/// Name and signature of a field or method ID
+fn member(id: *mut c_void) -> (String, String) {
+    MEMBERS.lock().unwrap_or_else(PoisonError::into_inner)[id as usize - 1].clone()
+}
+
+unsafe fn member_id(name: *const c_char, sig: *const c_char) -> *mut c_void {
+    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+    let sig = CStr::from_ptr(sig).to_string_lossy().into_owned();
+    (intern(&MEMBERS, (name, sig)) + 1) as *mut c_void
+}
+
+unsafe fn decode_string(utf: *const c_char) -> String {
+    Cow::from(JNIStr::from_ptr(utf)).into_owned()
+}
+
+/// JVM type signatures of the parameters of a method signature, such as ["I", "Ljava/lang/String;"] for "(ILjava/lang/String;)V"
+fn parameter_signatures(signature: &str) -> Vec<&str> {
+    let parameters = signature.strip_prefix('(').and_then(|rest| rest.split(')').next()).unwrap_or("");
+    let mut signatures = Vec::new();
+    let mut start = 0;
+    let bytes = parameters.as_bytes();
+    while start < bytes.len() {
+        let mut end = start;
+        while bytes[end] == b'[' {
+            end += 1;
+        }
+        if bytes[end] == b'L' {
+            end += parameters[end..].find(';').unwrap_or(parameters.len() - end - 1);
+        }
+        signatures.push(&parameters[start..=end]);
+        start = end + 1;
+    }
+    signatures
+}
+
+fn element_signature(class: &str) -> String {
+    if class.starts_with('[') {
+        class.to_string()
+    } else {
+        format!("L{};", class)
+    }
+}
+
+fn type_error(value: Value, expected: &str) -> Exception {
+    exception("java/lang/IllegalArgumentException", format!("expected a value of type {}, got {:?}", expected, value))
+}
+
+unsafe extern "system" fn exception_check(env: *mut sys::JNIEnv) -> jboolean {
+    if state(env).exception.is_some() { JNI_TRUE } else { JNI_FALSE }
+}
+
+unsafe extern "system" fn exception_clear(env: *mut sys::JNIEnv) {
+    state(env).exception = None;
+}
+
+unsafe extern "system" fn throw(env: *mut sys::JNIEnv, obj: sys::jthrowable) -> jint {
+    call(env, JNI_OK, |fake| {
+        let class = fake.class_of(non_null_ref(obj)?)?;
+        Err(exception(&class, String::new()))
+    })
+}
+
+unsafe extern "system" fn throw_new(env: *mut sys::JNIEnv, clazz: jclass, msg: *const c_char) -> jint {
+    call(env, JNI_OK, |_| Err(exception(&class_name(clazz)?, decode_string(msg))))
+}
+
+unsafe extern "system" fn find_class(env: *mut sys::JNIEnv, name: *const c_char) -> jclass {
+    let name = decode_string(name);
+    call(env, ptr::null_mut(), |fake| {
+        if fake.class_exists(&name) {
+            Ok(class_handle(&name))
+        } else {
+            Err(exception("java/lang/NoClassDefFoundError", name))
+        }
+    })
+}
+
+unsafe extern "system" fn get_object_class(env: *mut sys::JNIEnv, obj: jobject) -> jclass {
+    call(env, ptr::null_mut(), |fake| Ok(class_handle(&fake.class_of(non_null_ref(obj)?)?)))
+}
+
+unsafe extern "system" fn is_instance_of(env: *mut sys::JNIEnv, obj: jobject, clazz: jclass) -> jboolean {
+    call(env, JNI_FALSE, |fake| match object_ref(obj)? {
+        None => Ok(JNI_TRUE),
+        Some(object) => {
+            let class = fake.class_of(object)?;
+            Ok(fake.is_assignable(&class, &class_name(clazz)?) as jboolean)
+        }
+    })
+}
+
+unsafe extern "system" fn is_assignable_from(env: *mut sys::JNIEnv, sub: jclass, sup: jclass) -> jboolean {
+    call(env, JNI_FALSE, |fake| Ok(fake.is_assignable(&class_name(sub)?, &class_name(sup)?) as jboolean))
+}
+
+unsafe extern "system" fn is_same_object(_env: *mut sys::JNIEnv, obj1: jobject, obj2: jobject) -> jboolean {
+    (obj1 == obj2) as jboolean
+}
+
+unsafe extern "system" fn new_ref(_env: *mut sys::JNIEnv, obj: jobject) -> jobject {
+    obj
+}
+
+unsafe extern "system" fn delete_ref(_env: *mut sys::JNIEnv, _obj: jobject) {}
+
+unsafe extern "system" fn reserve_locals(_env: *mut sys::JNIEnv, _capacity: jint) -> jint {
+    JNI_OK
+}
+
+unsafe extern "system" fn pop_local_frame(_env: *mut sys::JNIEnv, result: jobject) -> jobject {
+    result
+}
+
+unsafe extern "system" fn get_java_vm(_env: *mut sys::JNIEnv, vm: *mut *mut sys::JavaVM) -> jint {
+    *vm = &MOCK_VM.0 as *const *const JNIInvokeInterface_ as *mut sys::JavaVM;
+    JNI_OK
+}
+
+unsafe extern "system" fn get_member_id(_env: *mut sys::JNIEnv, _clazz: jclass, name: *const c_char, sig: *const c_char) -> *mut c_void {
+    member_id(name, sig)
+}
+
+unsafe extern "system" fn get_field_id(env: *mut sys::JNIEnv, clazz: jclass, name: *const c_char, sig: *const c_char) -> jfieldID {
+    get_member_id(env, clazz, name, sig) as jfieldID
+}
+
+unsafe extern "system" fn get_method_id(env: *mut sys::JNIEnv, clazz: jclass, name: *const c_char, sig: *const c_char) -> jmethodID {
+    get_member_id(env, clazz, name, sig) as jmethodID
+}
+
+unsafe extern "system" fn new_object_a(env: *mut sys::JNIEnv, clazz: jclass, method: jmethodID, args: *const jvalue) -> jobject {
+    let (_, signature) = member(method as *mut c_void);
+    call(env, ptr::null_mut(), |fake| {
+        let mut values = Vec::new();
+        for (idx, parameter) in parameter_signatures(&signature).into_iter().enumerate() {
+            let arg = *args.add(idx);
+            values.push(match parameter.as_bytes()[0] {
+                b'Z' => Value::Boolean(arg.z != JNI_FALSE),
+                b'B' => Value::Byte(arg.b),
+                b'C' => Value::Char(arg.c),
+                b'S' => Value::Short(arg.s),
+                b'I' => Value::Int(arg.i),
+                b'J' => Value::Long(arg.j),
+                b'F' => Value::Float(arg.f),
+                b'D' => Value::Double(arg.d),
+                _ => Value::Object(object_ref(arg.l)?),
+            });
+        }
+        fake.new_object(&class_name(clazz)?, values).map(|object| object_handle(Some(object)))
+    })
+}
+
+unsafe extern "system" fn get_object_field(env: *mut sys::JNIEnv, obj: jobject, field: jfieldID) -> jobject {
+    let (name, signature) = member(field as *mut c_void);
+    call(env, ptr::null_mut(), |fake| match fake.get_field(non_null_ref(obj)?, &name)? {
+        Value::Object(object) => Ok(object_handle(object)),
+        value => Err(type_error(value, &signature))
+    })
+}
+
+unsafe extern "system" fn new_string_utf(env: *mut sys::JNIEnv, utf: *const c_char) -> sys::jstring {
+    let value = decode_string(utf);
+    call(env, ptr::null_mut(), |fake| fake.new_string(value).map(|object| object_handle(Some(object))))
+}
+
+unsafe extern "system" fn get_string_utf_chars(env: *mut sys::JNIEnv, string: sys::jstring, is_copy: *mut jboolean) -> *const c_char {
+    call(env, ptr::null(), |fake| {
+        let value = fake.get_string(non_null_ref(string)?)?;
+        if !is_copy.is_null() {
+            *is_copy = JNI_TRUE;
+        }
+        Ok(CString::from(&**JNIString::from(value)).into_raw() as *const c_char)
+    })
+}
+
+unsafe extern "system" fn release_string_utf_chars(_env: *mut sys::JNIEnv, _string: sys::jstring, chars: *const c_char) {
+    drop(CString::from_raw(chars as *mut c_char));
+}
+
+unsafe extern "system" fn get_string_utf_length(env: *mut sys::JNIEnv, string: sys::jstring) -> jsize {
+    call(env, 0, |fake| Ok(JNIString::from(fake.get_string(non_null_ref(string)?)?).to_bytes().len() as jsize))
+}
+
+unsafe extern "system" fn get_string_length(env: *mut sys::JNIEnv, string: sys::jstring) -> jsize {
+    call(env, 0, |fake| Ok(fake.get_string(non_null_ref(string)?)?.encode_utf16().count() as jsize))
+}
+
+unsafe extern "system" fn get_string_critical(env: *mut sys::JNIEnv, string: sys::jstring, is_copy: *mut jboolean) -> *const jchar {
+    let chars = call(env, None, |fake| Ok(Some(fake.get_string(non_null_ref(string)?)?.encode_utf16().collect::<Box<[jchar]>>())));
+    let Some(chars) = chars else {
+        return ptr::null();
+    };
+    if !is_copy.is_null() {
+        *is_copy = JNI_TRUE;
+    }
+    let pinned = &mut state(env).pinned;
+    pinned.push(chars);
+    pinned.last().map_or(ptr::null(), |chars| chars.as_ptr())
+}
+
+unsafe extern "system" fn release_string_critical(env: *mut sys::JNIEnv, _string: sys::jstring, chars: *const jchar) {
+    state(env).pinned.retain(|pinned| pinned.as_ptr() != chars);
+}
+
+unsafe extern "system" fn get_array_length(env: *mut sys::JNIEnv, array: sys::jarray) -> jsize {
+    call(env, 0, |fake| Ok(fake.array_length(non_null_ref(array)?)? as jsize))
+}
+
+unsafe extern "system" fn new_object_array(env: *mut sys::JNIEnv, len: jsize, clazz: jclass, init: jobject) -> sys::jobjectArray {
+    call(env, ptr::null_mut(), |fake| {
+        let array = fake.new_array(&element_signature(&class_name(clazz)?), len as usize)?;
+        if let Some(init) = object_ref(init)? {
+            for idx in 0..len as usize {
+                fake.set_element(array, idx, Value::Object(Some(init)))?;
+            }
+        }
+        Ok(object_handle(Some(array)))
+    })
+}
+
+unsafe extern "system" fn get_object_array_element(env: *mut sys::JNIEnv, array: sys::jobjectArray, index: jsize) -> jobject {
+    call(env, ptr::null_mut(), |fake| match fake.get_element(non_null_ref(array)?, index as usize)? {
+        Value::Object(object) => Ok(object_handle(object)),
+        value => Err(type_error(value, "object"))
+    })
+}
+
+unsafe extern "system" fn set_object_array_element(env: *mut sys::JNIEnv, array: sys::jobjectArray, index: jsize, val: jobject) {
+    call(env, (), |fake| fake.set_element(non_null_ref(array)?, index as usize, Value::Object(object_ref(val)?)))
+}
+
+/// Field and array functions of a primitive type
+macro_rules! primitive_functions {
+    ($($variant:ident($jtype:ty), $signature:literal, $to_value:expr, $from_value:expr, $get_field:ident, $new_array:ident, $get_region:ident, $set_region:ident;)*) => {$(
+        unsafe extern "system" fn $get_field(env: *mut sys::JNIEnv, obj: jobject, field: jfieldID) -> $jtype {
+            let (name, _) = member(field as *mut c_void);
+            call(env, Default::default(), |fake| match fake.get_field(non_null_ref(obj)?, &name)? {
+                Value::$variant(value) => Ok($from_value(value)),
+                value => Err(type_error(value, $signature))
+            })
+        }
+
+        unsafe extern "system" fn $new_array(env: *mut sys::JNIEnv, len: jsize) -> sys::jarray {
+            call(env, ptr::null_mut(), |fake| fake.new_array($signature, len as usize).map(|array| object_handle(Some(array))))
+        }
+
+        unsafe extern "system" fn $get_region(env: *mut sys::JNIEnv, array: sys::jarray, start: jsize, len: jsize, buf: *mut $jtype) {
+            call(env, (), |fake| {
+                let array = non_null_ref(array)?;
+                for idx in 0..len as usize {
+                    match fake.get_element(array, start as usize + idx)? {
+                        Value::$variant(value) => *buf.add(idx) = $from_value(value),
+                        value => Err(type_error(value, $signature))?
+                    }
+                }
+                Ok(())
+            })
+        }
+
+        unsafe extern "system" fn $set_region(env: *mut sys::JNIEnv, array: sys::jarray, start: jsize, len: jsize, buf: *const $jtype) {
+            call(env, (), |fake| {
+                let array = non_null_ref(array)?;
+                for idx in 0..len as usize {
+                    fake.set_element(array, start as usize + idx, Value::$variant($to_value(*buf.add(idx))))?;
+                }
+                Ok(())
+            })
+        }
+    )*};
+}
+
+primitive_functions! {
+    Boolean(jboolean), "Z", |value: jboolean| value != JNI_FALSE, |value: bool| value as jboolean, get_boolean_field, new_boolean_array, get_boolean_array_region, set_boolean_array_region;
+    Byte(jbyte), "B", |value: jbyte| value, |value: i8| value, get_byte_field, new_byte_array, get_byte_array_region, set_byte_array_region;
+    Char(jchar), "C", |value: jchar| value, |value: u16| value, get_char_field, new_char_array, get_char_array_region, set_char_array_region;
+    Short(jshort), "S", |value: jshort| value, |value: i16| value, get_short_field, new_short_array, get_short_array_region, set_short_array_region;
+    Int(jint), "I", |value: jint| value, |value: i32| value, get_int_field, new_int_array, get_int_array_region, set_int_array_region;
+    Long(jlong), "J", |value: jlong| value, |value: i64| value, get_long_field, new_long_array, get_long_array_region, set_long_array_region;
+    Float(jfloat), "F", |value: jfloat| value, |value: f32| value, get_float_field, new_float_array, get_float_array_region, set_float_array_region;
+    Double(jdouble), "D", |value: jdouble| value, |value: f64| value, get_double_field, new_double_array, get_double_array_region, set_double_array_region;
+}
+
+/// Function tables hold raw pointers, and are not Sync by default; They are never mutated
+struct SyncTable<T>(T);
+
+unsafe impl<T> Sync for SyncTable<T> {}
+
+static FUNCTIONS: SyncTable<JNINativeInterface_> = SyncTable(JNINativeInterface_ {
+    FindClass: Some(find_class),
+    IsAssignableFrom: Some(is_assignable_from),
+    Throw: Some(throw),
+    ThrowNew: Some(throw_new),
+    ExceptionClear: Some(exception_clear),
+    ExceptionCheck: Some(exception_check),
+    PushLocalFrame: Some(reserve_locals),
+    PopLocalFrame: Some(pop_local_frame),
+    EnsureLocalCapacity: Some(reserve_locals),
+    NewGlobalRef: Some(new_ref),
+    DeleteGlobalRef: Some(delete_ref),
+    NewLocalRef: Some(new_ref),
+    DeleteLocalRef: Some(delete_ref),
+    IsSameObject: Some(is_same_object),
+    GetObjectClass: Some(get_object_class),
+    IsInstanceOf: Some(is_instance_of),
+    GetJavaVM: Some(get_java_vm),
+    GetFieldID: Some(get_field_id),
+    GetMethodID: Some(get_method_id),
+    NewObjectA: Some(new_object_a),
+    GetObjectField: Some(get_object_field),
+    GetBooleanField: Some(get_boolean_field),
+    GetByteField: Some(get_byte_field),
+    GetCharField: Some(get_char_field),
+    GetShortField: Some(get_short_field),
+    GetIntField: Some(get_int_field),
+    GetLongField: Some(get_long_field),
+    GetFloatField: Some(get_float_field),
+    GetDoubleField: Some(get_double_field),
+    NewStringUTF: Some(new_string_utf),
+    GetStringUTFChars: Some(get_string_utf_chars),
+    ReleaseStringUTFChars: Some(release_string_utf_chars),
+    GetStringUTFLength: Some(get_string_utf_length),
+    GetStringLength: Some(get_string_length),
+    GetStringCritical: Some(get_string_critical),
+    ReleaseStringCritical: Some(release_string_critical),
+    GetArrayLength: Some(get_array_length),
+    NewObjectArray: Some(new_object_array),
+    GetObjectArrayElement: Some(get_object_array_element),
+    SetObjectArrayElement: Some(set_object_array_element),
+    NewBooleanArray: Some(new_boolean_array),
+    NewByteArray: Some(new_byte_array),
+    NewCharArray: Some(new_char_array),
+    NewShortArray: Some(new_short_array),
+    NewIntArray: Some(new_int_array),
+    NewLongArray: Some(new_long_array),
+    NewFloatArray: Some(new_float_array),
+    NewDoubleArray: Some(new_double_array),
+    GetBooleanArrayRegion: Some(get_boolean_array_region),
+    GetByteArrayRegion: Some(get_byte_array_region),
+    GetCharArrayRegion: Some(get_char_array_region),
+    GetShortArrayRegion: Some(get_short_array_region),
+    GetIntArrayRegion: Some(get_int_array_region),
+    GetLongArrayRegion: Some(get_long_array_region),
+    GetFloatArrayRegion: Some(get_float_array_region),
+    GetDoubleArrayRegion: Some(get_double_array_region),
+    SetBooleanArrayRegion: Some(set_boolean_array_region),
+    SetByteArrayRegion: Some(set_byte_array_region),
+    SetCharArrayRegion: Some(set_char_array_region),
+    SetShortArrayRegion: Some(set_short_array_region),
+    SetIntArrayRegion: Some(set_int_array_region),
+    SetLongArrayRegion: Some(set_long_array_region),
+    SetFloatArrayRegion: Some(set_float_array_region),
+    SetDoubleArrayRegion: Some(set_double_array_region),
+    // SAFETY: All other entries are optional function pointers or reserved pointers, for which zero is None/null
+    ..unsafe { std::mem::zeroed() }
+});
+
+/// Threads are never attached to the mock VM; Global references dropped outside [`with_mock_env`] are not deleted, which is harmless as they hold no resources
+unsafe extern "system" fn get_env(_vm: *mut sys::JavaVM, _penv: *mut *mut c_void, _version: jint) -> jint {
+    JNI_EDETACHED
+}
+
+unsafe extern "system" fn attach_current_thread(_vm: *mut sys::JavaVM, _penv: *mut *mut c_void, _args: *mut c_void) -> jint {
+    JNI_ERR
+}
+
+static VM_FUNCTIONS: SyncTable<JNIInvokeInterface_> = SyncTable(JNIInvokeInterface_ {
+    reserved0: ptr::null_mut(),
+    reserved1: ptr::null_mut(),
+    reserved2: ptr::null_mut(),
+    DestroyJavaVM: None,
+    AttachCurrentThread: Some(attach_current_thread),
+    DetachCurrentThread: None,
+    GetEnv: Some(get_env),
+    AttachCurrentThreadAsDaemon: Some(attach_current_thread),
+});
+
+static MOCK_VM: SyncTable<*const JNIInvokeInterface_> = SyncTable(&VM_FUNCTIONS.0);
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Debug;
+    use std::rc::Rc;
+    use crate::JavaType;
+    use crate::proc_macro::{jmodule_methods, jmodule_package, JavaType};
+    use super::*;
+
+    #[derive(JavaType)]
+    #[derive(Clone, PartialEq, Debug)]
+    // Set by `jmodule` on the types of a module
+    #[jmodule_package("com.example")]
+    #[jmodule_methods()]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+        pub label: String,
+    }
+
+    #[derive(JavaType)]
+    #[derive(Clone, PartialEq, Debug)]
+    #[jmodule_package("com.example")]
+    #[jmodule_methods()]
+    pub enum Shape {
+        Circle(i32),
+        Labeled(String, i64),
+    }
+
+    /// Convert `value` to Java and back, on a fresh [`MockJvm`] prepared by `define`
+    fn round_trip<T: JavaType + Clone + PartialEq + Debug>(value: T, define: impl FnOnce(&mut MockJvm)) {
+        let mut jvm = MockJvm::new();
+        define(&mut jvm);
+        let converted = with_mock_env(&mut jvm, |env| {
+            let jni_value = value.clone().into_jni(env)?;
+            T::from_jni(jni_value, env)
+        });
+        match converted {
+            Ok(Ok(converted)) => assert_eq!(converted, value),
+            Ok(Err(exception)) => panic!("conversion failed: {:?}", exception.map(|exception| (exception.class, exception.msg))),
+            Err(exception) => panic!("exception pending: {} {}", exception.class, exception.msg),
+        }
+    }
+
+    #[test]
+    fn primitives() {
+        round_trip(true, |_| {});
+        round_trip(-5i8, |_| {});
+        round_trip(1234i16, |_| {});
+        round_trip(i32::MIN, |_| {});
+        round_trip(i64::MAX, |_| {});
+        round_trip(1.5f32, |_| {});
+        round_trip(-2.25f64, |_| {});
+    }
+
+    #[test]
+    fn strings() {
+        round_trip(String::new(), |_| {});
+        round_trip("instant coffee \u{2615}".to_string(), |_| {});
+    }
+
+    #[test]
+    fn limited_strings() {
+        let limits = crate::jni_util::Limits { max_string_bytes: 16, ..crate::jni_util::Limits::DEFAULT };
+        crate::jni_util::with_limits(limits, || round_trip("measured \u{2615}".to_string(), |_| {}));
+        let mut jvm = MockJvm::new();
+        let converted = crate::jni_util::with_limits(limits, || with_mock_env(&mut jvm, |env| {
+            let jni_value = "x".repeat(17).into_jni(env)?;
+            String::from_jni(jni_value, env)
+        }));
+        assert!(matches!(converted, Ok(Err(Some(Exception { class, .. }))) if class == crate::jni_util::ILLEGAL_ARGUMENT_EXCEPTION));
+    }
+
+    #[test]
+    fn arrays() {
+        round_trip(vec![1i32, 2, 3].into_boxed_slice(), |_| {});
+        round_trip(vec![0.5f64, -0.5].into_boxed_slice(), |_| {});
+        round_trip(vec!["a".to_string(), "bc".to_string()].into_boxed_slice(), |_| {});
+        round_trip(Box::<[i64]>::from([]), |_| {});
+    }
+
+    #[test]
+    fn derived_struct() {
+        let point = Point { x: 1, y: -2, label: "origin".to_string() };
+        round_trip(point, MockJvm::define::<Point>);
+    }
+
+    #[test]
+    fn tuple_variants() {
+        round_trip(Shape::Circle(7), MockJvm::define::<Shape>);
+        round_trip(Shape::Labeled("square".to_string(), -4), MockJvm::define::<Shape>);
+    }
+
+    #[test]
+    fn preserved_identity() {
+        let mut jvm = MockJvm::new();
+        let converted = with_mock_env(&mut jvm, |env| crate::interop::with_preserved_identity(env, |env| {
+            let first = Rc::new("first".to_string());
+            let alias = Rc::clone(&first);
+            let first = first.into_jni(env)?;
+            let again = Rc::clone(&alias).into_jni(env)?;
+            let same = env.is_same_object(&first, &again).map_err(crate::jni_util::map_jni_error)?;
+
+            // Freeing the value must not let another value at the same address take its Java object
+            drop(alias);
+            let second = Rc::new("second".to_string());
+            let second = Rc::clone(&second).into_jni(env)?;
+            Ok((same, String::from_jni(second, env)?))
+        }));
+        assert!(matches!(converted, Ok(Ok((true, second))) if second == "second"));
+    }
+
+    #[test]
+    fn derived_struct_fields() {
+        let mut jvm = MockJvm::new();
+        jvm.define::<Point>();
+        let object = with_mock_env(&mut jvm, |env| {
+            Point { x: 3, y: 4, label: "p".to_string() }.into_jni(env).map(|object| object_ref(object.as_raw()))
+        });
+        let Ok(Ok(Ok(Some(object)))) = object else {
+            panic!("conversion failed");
+        };
+        let Some(MockObject::Instance { fields, .. }) = jvm.object(object) else {
+            panic!("not an instance");
+        };
+        assert_eq!(fields[0], ("x".to_string(), Value::Int(3)));
+        assert_eq!(fields[1], ("y".to_string(), Value::Int(4)));
+    }
+}